@@ -0,0 +1,78 @@
+extern crate jvm_assembler;
+
+use jvm_assembler::*;
+
+fn main() {
+    let mut class = define_class(ACC_PUBLIC, "annotations", "java/lang/Object");
+
+    // @Deprecated(since = "2.0", forRemoval = true)
+    class.annotation(&AnnotationSpec {
+        annotation_type: "java/lang/Deprecated",
+        elements: vec![("since", "2.0".into()), ("forRemoval", true.into())],
+    }, Retention::Runtime);
+
+    // @Inject private String greeting;
+    {
+        let mut field = class.define_field(ACC_PRIVATE, "greeting", &Java::class("java/lang/String"));
+        field.annotation(&AnnotationSpec::marker("javax/inject/Inject"), Retention::Runtime);
+        field.done();
+    }
+
+    {
+        // create constructor
+        let mut method = class.define_method(ACC_PUBLIC, "<init>", &[], &Java::Void);
+        method.aload0();
+        method.invoke_special("java/lang/Object", "<init>", &[], &Java::Void);
+        method.do_return();
+        method.done().unwrap();
+    }
+
+    {
+        // @Test @Timeout(value = 5, unit = TimeUnit.SECONDS) @Tag("fast")
+        // public void greets() {}
+        let mut method = class.define_method(ACC_PUBLIC, "greets", &[], &Java::Void);
+        method.annotation(&AnnotationSpec::marker("org/junit/jupiter/api/Test"), Retention::Runtime);
+        method.annotation(&AnnotationSpec {
+            annotation_type: "org/junit/jupiter/api/Timeout",
+            elements: vec![
+                ("value", 5i64.into()),
+                ("unit", AnnotationValue::Enum("java/util/concurrent/TimeUnit", "SECONDS")),
+            ],
+        }, Retention::Runtime);
+        method.annotation(&AnnotationSpec {
+            annotation_type: "org/junit/jupiter/api/Tag",
+            elements: vec![("value", "fast".into())],
+        }, Retention::Runtime);
+        method.do_return();
+        method.done().unwrap();
+    }
+
+    {
+        // public static void greet(@Named("name") String name), with an annotation that's only kept in the class file
+        let mut method = class.define_method(ACC_PUBLIC | ACC_STATIC, "greet", &[Java::class("java/lang/String")], &Java::Void);
+        method.annotation(&AnnotationSpec::marker("javax/annotation/CheckReturnValue"), Retention::Class);
+        method.parameter_annotation(0, &AnnotationSpec {
+            annotation_type: "javax/inject/Named",
+            elements: vec![("value", "name".into())],
+        }, Retention::Runtime);
+        method.do_return();
+        method.done().unwrap();
+    }
+
+    {
+        // create main method, which prints the class's annotations
+        let mut method = class.define_method(ACC_PUBLIC | ACC_STATIC, "main", &[Java::array(Java::class("java/lang/String"))], &Java::Void);
+        method.get_static("java/lang/System", "out", &Java::class("java/io/PrintStream"));
+        method.load_constant(LoadableConstant::Class("annotations"));
+        method.invoke_virtual("java/lang/Class", "getAnnotations", &[], &Java::array(Java::class("java/lang/annotation/Annotation")));
+        method.invoke_static("java/util/Arrays", "toString", &[Java::array(Java::class("java/lang/Object"))], &Java::class("java/lang/String"));
+        method.invoke_virtual("java/io/PrintStream", "println", &[Java::class("java/lang/String")], &Java::Void);
+        method.do_return();
+
+        // fini!
+        method.done().unwrap();
+    }
+
+    let classfile = class.done().unwrap();
+    write_classfile(classfile, "annotations.class").unwrap();
+}
@@ -0,0 +1,71 @@
+extern crate jvm_assembler;
+
+use jvm_assembler::*;
+
+fn main() {
+    // public class generics<T extends Comparable<? super T>>
+    let mut class = define_class(ACC_PUBLIC, "generics", "java/lang/Object");
+    let comparable = TypeSignature::generic_class("java/lang/Comparable", vec![TypeArgument::Super(TypeSignature::type_variable("T"))]);
+    class.signature(&ClassSignature {
+        type_parameters: vec![TypeParameter {
+            name: "T".to_owned(),
+            class_bound: None,
+            interface_bounds: vec![comparable],
+        }],
+        superclass: ClassTypeSignature::new("java/lang/Object", vec![]),
+        interfaces: vec![],
+    });
+
+    // public static java.util.List<String> names;
+    {
+        let mut field = class.define_field(ACC_PUBLIC | ACC_STATIC, "names", &Java::class("java/util/List"));
+        field.signature(&TypeSignature::generic_class("java/util/List", vec![TypeArgument::Exact(TypeSignature::class("java/lang/String"))]));
+        field.done();
+    }
+
+    {
+        let mut method = class.define_method(ACC_PUBLIC | ACC_STATIC, "main", &[Java::array(Java::class("java/lang/String"))], &Java::Void);
+        method.aload0();
+        method.invoke_static("generics", "print_all", &[Java::array(Java::class("java/lang/Object"))], &Java::Void);
+        method.do_return();
+        method.done().unwrap();
+    }
+
+    {
+        // public static <U> void print_all(U[] items)
+        let mut method = class.define_method(ACC_PUBLIC | ACC_STATIC, "print_all", &[Java::array(Java::class("java/lang/Object"))], &Java::Void);
+        method.signature(&MethodSignature {
+            type_parameters: vec![TypeParameter::new("U", TypeSignature::class("java/lang/Object"))],
+            args: vec![TypeSignature::array(TypeSignature::type_variable("U"))],
+            ret: TypeSignature::Void,
+            throws: vec![],
+        });
+        let condition = method.new_label();
+        let after = method.new_label();
+
+        // for (int i = 0; i < items.length; i++) {
+        //     System.out.println(items[i]);
+        // }
+        method.iconst0();
+        method.istore(1);
+        method.label(condition);
+        method.iload(1);
+        method.aload0();
+        method.array_length();
+        method.if_icmp_ge(after);
+        method.get_static("java/lang/System", "out", &Java::class("java/io/PrintStream"));
+        method.aload0();
+        method.iload(1);
+        method.aaload();
+        method.invoke_virtual("java/io/PrintStream", "println", &[Java::class("java/lang/Object")], &Java::Void);
+        method.iinc(1, 1);
+        method.goto(condition);
+
+        method.label(after);
+        method.do_return();
+        method.done().unwrap();
+    }
+
+    let classfile = class.done().unwrap();
+    write_classfile(classfile, "generics.class").unwrap();
+}
@@ -7,20 +7,20 @@ fn main() {
 
     {
         // create main method
-        let mut method = class.define_method(ACC_PUBLIC | ACC_STATIC, "main", &[Java::Array(Box::new(Java::Class("java/lang/String")))], &Java::Void);
+        let mut method = class.define_method(ACC_PUBLIC | ACC_STATIC, "main", &[Java::array(Java::class("java/lang/String"))], &Java::Void);
 
         // push PrintStream object and string to print onto the stack, and then call println function
-        method.get_static("java/lang/System", "out", &Java::Class("java/io/PrintStream"));
+        method.get_static("java/lang/System", "out", &Java::class("java/io/PrintStream"));
         method.load_constant("Hello, World!");
-        method.invoke_virtual("java/io/PrintStream", "println", &[Java::Class("java/lang/String")], &Java::Void);
+        method.invoke_virtual("java/io/PrintStream", "println", &[Java::class("java/lang/String")], &Java::Void);
 
         // add return statement
         method.do_return();
 
         // fini!
-        method.done();
+        method.done().unwrap();
     }
 
-    let classfile = class.done();
-    write_classfile(classfile, "hello_world.class");
+    let classfile = class.done().unwrap();
+    write_classfile(classfile, "hello_world.class").unwrap();
 }
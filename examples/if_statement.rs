@@ -7,7 +7,9 @@ fn main() {
 
     {
         // create main method
-        let mut method = class.define_method(ACC_PUBLIC | ACC_STATIC, "main", &[Java::Array(Box::new(Java::Class("java/lang/String")))], &Java::Void);
+        let mut method = class.define_method(ACC_PUBLIC | ACC_STATIC, "main", &[Java::array(Java::class("java/lang/String"))], &Java::Void);
+        let false_case = method.new_label();
+        let after = method.new_label();
 
         // if (args.length > 0) {
         //     System.out.println("Hello with args!");
@@ -16,28 +18,28 @@ fn main() {
         // }
         method.aload0();
         method.array_length();
-        method.ifle("false");
+        method.ifle(false_case);
 
         // true case
-        method.get_static("java/lang/System", "out", &Java::Class("java/io/PrintStream"));
+        method.get_static("java/lang/System", "out", &Java::class("java/io/PrintStream"));
         method.load_constant("Hello with args!");
-        method.invoke_virtual("java/io/PrintStream", "println", &[Java::Class("java/lang/String")], &Java::Void);
-        method.goto("after");
+        method.invoke_virtual("java/io/PrintStream", "println", &[Java::class("java/lang/String")], &Java::Void);
+        method.goto(after);
 
         // false case
-        method.label("false");
-        method.get_static("java/lang/System", "out", &Java::Class("java/io/PrintStream"));
+        method.label(false_case);
+        method.get_static("java/lang/System", "out", &Java::class("java/io/PrintStream"));
         method.load_constant("Hello without args!");
-        method.invoke_virtual("java/io/PrintStream", "println", &[Java::Class("java/lang/String")], &Java::Void);
+        method.invoke_virtual("java/io/PrintStream", "println", &[Java::class("java/lang/String")], &Java::Void);
 
         // after
-        method.label("after");
+        method.label(after);
         method.do_return();
 
         // fini!
-        method.done();
+        method.done().unwrap();
     }
 
-    let classfile = class.done();
-    write_classfile(classfile, "if_statement.class");
+    let classfile = class.done().unwrap();
+    write_classfile(classfile, "if_statement.class").unwrap();
 }
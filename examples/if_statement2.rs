@@ -7,7 +7,10 @@ fn main() {
 
     {
         // create main method
-        let mut method = class.define_method(ACC_PUBLIC | ACC_STATIC, "main", &[Java::Array(Box::new(Java::Class("java/lang/String")))], &Java::Void);
+        let mut method = class.define_method(ACC_PUBLIC | ACC_STATIC, "main", &[Java::array(Java::class("java/lang/String"))], &Java::Void);
+        let outer_else = method.new_label();
+        let inner_else = method.new_label();
+        let outer_after = method.new_label();
 
         // if (args.length > 0) {
         //     System.out.println("Hello with args!");
@@ -21,12 +24,12 @@ fn main() {
         // }
         method.aload0();
         method.array_length();
-        method.ifle("outer-else");
+        method.ifle(outer_else);
 
         // outer if: true case
-        method.get_static("java/lang/System", "out", &Java::Class("java/io/PrintStream"));
+        method.get_static("java/lang/System", "out", &Java::class("java/io/PrintStream"));
         method.load_constant("Hello with args!");
-        method.invoke_virtual("java/io/PrintStream", "println", &[Java::Class("java/lang/String")], &Java::Void);
+        method.invoke_virtual("java/io/PrintStream", "println", &[Java::class("java/lang/String")], &Java::Void);
 
         // inner if: load the first arg and calculate string length
         method.aload0();
@@ -36,37 +39,37 @@ fn main() {
 
         // inner if: do comparison against 5
         method.iconst5();
-        method.if_icmp_lt("inner-else");
+        method.if_icmp_lt(inner_else);
 
         // inner if: true case
-        method.get_static("java/lang/System", "out", &Java::Class("java/io/PrintStream"));
+        method.get_static("java/lang/System", "out", &Java::class("java/io/PrintStream"));
         method.load_constant("First arg has at least 5 characters");
-        method.invoke_virtual("java/io/PrintStream", "println", &[Java::Class("java/lang/String")], &Java::Void);
-        method.goto("outer-after");
+        method.invoke_virtual("java/io/PrintStream", "println", &[Java::class("java/lang/String")], &Java::Void);
+        method.goto(outer_after);
 
         // inner if: false case
-        method.label("inner-else");
-        method.get_static("java/lang/System", "out", &Java::Class("java/io/PrintStream"));
+        method.label(inner_else);
+        method.get_static("java/lang/System", "out", &Java::class("java/io/PrintStream"));
         method.load_constant("First arg has less than 5 characters");
-        method.invoke_virtual("java/io/PrintStream", "println", &[Java::Class("java/lang/String")], &Java::Void);
+        method.invoke_virtual("java/io/PrintStream", "println", &[Java::class("java/lang/String")], &Java::Void);
 
         // outer if: done true case
-        method.goto("outer-after");
+        method.goto(outer_after);
 
         // outer if: false case
-        method.label("outer-else");
-        method.get_static("java/lang/System", "out", &Java::Class("java/io/PrintStream"));
+        method.label(outer_else);
+        method.get_static("java/lang/System", "out", &Java::class("java/io/PrintStream"));
         method.load_constant("Hello without args!");
-        method.invoke_virtual("java/io/PrintStream", "println", &[Java::Class("java/lang/String")], &Java::Void);
+        method.invoke_virtual("java/io/PrintStream", "println", &[Java::class("java/lang/String")], &Java::Void);
 
         // after outer if
-        method.label("outer-after");
+        method.label(outer_after);
         method.do_return();
 
         // fini!
-        method.done();
+        method.done().unwrap();
     }
 
-    let classfile = class.done();
-    write_classfile(classfile, "if_statement2.class");
+    let classfile = class.done().unwrap();
+    write_classfile(classfile, "if_statement2.class").unwrap();
 }
@@ -0,0 +1,71 @@
+extern crate jvm_assembler;
+
+use jvm_assembler::*;
+
+fn main() {
+    let mut class = define_class(ACC_PUBLIC, "line_numbers", "java/lang/Object");
+
+    // stack traces show this file name, with the line numbers given to MethodBuilder::line
+    class.source_file("line_numbers.mylang");
+
+    {
+        // create main method
+        let mut method = class.define_method(ACC_PUBLIC | ACC_STATIC, "main", &[Java::array(Java::class("java/lang/String"))], &Java::Void);
+
+        // 1: print "Counting down"
+        method.line(1);
+        method.get_static("java/lang/System", "out", &Java::class("java/io/PrintStream"));
+        method.load_constant("Counting down");
+        method.invoke_virtual("java/io/PrintStream", "println", &[Java::class("java/lang/String")], &Java::Void);
+
+        // 2: countdown(3)
+        method.line(2);
+        method.iconst3();
+        method.invoke_static("line_numbers", "countdown", &[Java::Int], &Java::Void);
+        method.do_return();
+
+        // fini!
+        method.done().unwrap();
+    }
+
+    {
+        // create countdown method, which fails when it gets to 0
+        let mut method = class.define_method(ACC_PUBLIC | ACC_STATIC, "countdown", &[Java::Int], &Java::Void);
+        let n = method.name_argument(0, "n");
+        let fail = method.new_label();
+
+        // 5: if n == 0 then fail
+        method.line(5);
+        method.load(n);
+        method.ifeq(fail);
+
+        // 6: print n
+        method.line(6);
+        method.get_static("java/lang/System", "out", &Java::class("java/io/PrintStream"));
+        method.load(n);
+        method.invoke_virtual("java/io/PrintStream", "println", &[Java::Int], &Java::Void);
+
+        // 7: countdown(n - 1)
+        method.line(7);
+        method.load(n);
+        method.iconst1();
+        method.instruction(Instruction::Isub);
+        method.invoke_static("line_numbers", "countdown", &[Java::Int], &Java::Void);
+        method.do_return();
+
+        // 8: fail "Liftoff!"
+        method.label(fail);
+        method.line(8);
+        method.new_object("java/lang/RuntimeException");
+        method.instruction(Instruction::Dup);
+        method.load_constant("Liftoff!");
+        method.invoke_special("java/lang/RuntimeException", "<init>", &[Java::class("java/lang/String")], &Java::Void);
+        method.athrow();
+
+        // fini!
+        method.done().unwrap();
+    }
+
+    let classfile = class.done().unwrap();
+    write_classfile(classfile, "line_numbers.class").unwrap();
+}
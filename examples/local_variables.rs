@@ -0,0 +1,75 @@
+extern crate jvm_assembler;
+
+use jvm_assembler::*;
+
+fn main() {
+    let mut class = define_class(ACC_PUBLIC, "local_variables", "java/lang/Object");
+
+    {
+        // create main method
+        let mut method = class.define_method(ACC_PUBLIC | ACC_STATIC, "main", &[Java::array(Java::class("java/lang/String"))], &Java::Void);
+        let args = method.name_argument(0, "args");
+        let condition = method.new_label();
+        let after = method.new_label();
+
+        // List<String> list = Arrays.asList(args);
+        // System.out.println(list);
+        let list = method.declare_generic_local("list", &Java::class("java/util/List"), &TypeSignature::generic_class("java/util/List", vec![
+            TypeArgument::Exact(TypeSignature::class("java/lang/String")),
+        ]));
+        method.load(args);
+        method.invoke_static("java/util/Arrays", "asList", &[Java::array(Java::class("java/lang/Object"))], &Java::class("java/util/List"));
+        method.store(list);
+        method.get_static("java/lang/System", "out", &Java::class("java/io/PrintStream"));
+        method.load(list);
+        method.invoke_virtual("java/io/PrintStream", "println", &[Java::class("java/lang/Object")], &Java::Void);
+        method.end_local(list);
+
+        // long total = 0;
+        // for (int i = 0; i < args.length; i++) {
+        //     String arg = args[i];
+        //     total += arg.length();
+        // }
+        // System.out.println(total);
+        let total = method.declare_local("total", &Java::Long);
+        method.load_constant(0i64);
+        method.store(total);
+        let i = method.declare_local("i", &Java::Int);
+        method.iconst0();
+        method.store(i);
+
+        method.label(condition);
+        method.load(i);
+        method.load(args);
+        method.array_length();
+        method.if_icmp_ge(after);
+
+        let arg = method.declare_local("arg", &Java::class("java/lang/String"));
+        method.load(args);
+        method.load(i);
+        method.aaload();
+        method.store(arg);
+        method.load(total);
+        method.load(arg);
+        method.invoke_virtual("java/lang/String", "length", &[], &Java::Int);
+        method.instruction(Instruction::I2l);
+        method.instruction(Instruction::Ladd);
+        method.store(total);
+        method.end_local(arg);
+        method.increment(i, 1);
+        method.goto(condition);
+
+        method.label(after);
+        method.end_local(i);
+        method.get_static("java/lang/System", "out", &Java::class("java/io/PrintStream"));
+        method.load(total);
+        method.invoke_virtual("java/io/PrintStream", "println", &[Java::Long], &Java::Void);
+        method.do_return();
+
+        // fini!
+        method.done().unwrap();
+    }
+
+    let classfile = class.done().unwrap();
+    write_classfile(classfile, "local_variables.class").unwrap();
+}
@@ -7,10 +7,10 @@ fn main() {
 
     {
         // create main method
-        let mut method = class.define_method(ACC_PUBLIC | ACC_STATIC, "main", &[Java::Array(Box::new(Java::Class("java/lang/String")))], &Java::Void);
+        let mut method = class.define_method(ACC_PUBLIC | ACC_STATIC, "main", &[Java::array(Java::class("java/lang/String"))], &Java::Void);
 
         // push PrintStream object onto the stack for later use
-        method.get_static("java/lang/System", "out", &Java::Class("java/io/PrintStream"));
+        method.get_static("java/lang/System", "out", &Java::class("java/io/PrintStream"));
 
         // execute 11 + 37 + 42
         method.bipush(11);
@@ -26,9 +26,9 @@ fn main() {
         method.do_return();
 
         // fini!
-        method.done();
+        method.done().unwrap();
     }
 
-    let classfile = class.done();
-    write_classfile(classfile, "simple_addition.class");
+    let classfile = class.done().unwrap();
+    write_classfile(classfile, "simple_addition.class").unwrap();
 }
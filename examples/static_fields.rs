@@ -0,0 +1,45 @@
+extern crate jvm_assembler;
+
+use jvm_assembler::*;
+
+fn main() {
+    let mut class = define_class(ACC_PUBLIC, "static_fields", "java/lang/Object");
+
+    // public static final String GREETING = "Hello from a constant field!";
+    let greeting = {
+        let mut field = class.define_field(ACC_PUBLIC | ACC_STATIC | ACC_FINAL, "GREETING", &Java::class("java/lang/String"));
+        field.constant_string("Hello from a constant field!");
+        field.done()
+    };
+
+    // private static int counter;
+    let counter = class.define_field(ACC_PRIVATE | ACC_STATIC, "counter", &Java::Int).done();
+
+    {
+        // create main method
+        let mut method = class.define_method(ACC_PUBLIC | ACC_STATIC, "main", &[Java::array(Java::class("java/lang/String"))], &Java::Void);
+
+        // counter = 11 + 31;
+        method.bipush(11);
+        method.bipush(31);
+        method.iadd();
+        method.put_static_ref(&counter);
+
+        // print the greeting and the counter
+        method.get_static("java/lang/System", "out", &Java::class("java/io/PrintStream"));
+        method.get_static_ref(&greeting);
+        method.invoke_virtual("java/io/PrintStream", "println", &[Java::class("java/lang/String")], &Java::Void);
+        method.get_static("java/lang/System", "out", &Java::class("java/io/PrintStream"));
+        method.get_static_ref(&counter);
+        method.invoke_virtual("java/io/PrintStream", "println", &[Java::Int], &Java::Void);
+
+        // add return statement
+        method.do_return();
+
+        // fini!
+        method.done().unwrap();
+    }
+
+    let classfile = class.done().unwrap();
+    write_classfile(classfile, "static_fields.class").unwrap();
+}
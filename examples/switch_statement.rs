@@ -0,0 +1,74 @@
+extern crate jvm_assembler;
+
+use jvm_assembler::*;
+
+fn main() {
+    let mut class = define_class(ACC_PUBLIC, "switch_statement", "java/lang/Object");
+
+    {
+        // create main method
+        let mut method = class.define_method(ACC_PUBLIC | ACC_STATIC, "main", &[Java::array(Java::class("java/lang/String"))], &Java::Void);
+        let no_args = method.new_label();
+        let one_arg = method.new_label();
+        let two_args = method.new_label();
+        let lots_of_args = method.new_label();
+        let round_number = method.new_label();
+        let after = method.new_label();
+
+        // switch (args.length) {
+        //     case 0: System.out.println("No args"); break;
+        //     case 1: System.out.println("One arg"); break;
+        //     case 2: System.out.println("Two args"); break;
+        //     default: System.out.println("Lots of args");
+        // }
+        method.aload0();
+        method.array_length();
+        method.table_switch(0, 2, &[no_args, one_arg, two_args], lots_of_args);
+
+        method.label(no_args);
+        method.get_static("java/lang/System", "out", &Java::class("java/io/PrintStream"));
+        method.load_constant("No args");
+        method.invoke_virtual("java/io/PrintStream", "println", &[Java::class("java/lang/String")], &Java::Void);
+        method.do_return();
+
+        method.label(one_arg);
+        method.get_static("java/lang/System", "out", &Java::class("java/io/PrintStream"));
+        method.load_constant("One arg");
+        method.invoke_virtual("java/io/PrintStream", "println", &[Java::class("java/lang/String")], &Java::Void);
+        method.do_return();
+
+        method.label(two_args);
+        method.get_static("java/lang/System", "out", &Java::class("java/io/PrintStream"));
+        method.load_constant("Two args");
+        method.invoke_virtual("java/io/PrintStream", "println", &[Java::class("java/lang/String")], &Java::Void);
+        method.do_return();
+
+        method.label(lots_of_args);
+        method.get_static("java/lang/System", "out", &Java::class("java/io/PrintStream"));
+        method.load_constant("Lots of args");
+        method.invoke_virtual("java/io/PrintStream", "println", &[Java::class("java/lang/String")], &Java::Void);
+
+        // switch (args.length) {
+        //     case 10: case 100: case 1000: System.out.println("What a round number of args!");
+        // }
+        // (the keys are far apart, so this becomes a lookupswitch)
+        method.aload0();
+        method.array_length();
+        method.lookup_switch(&[(10, round_number), (100, round_number), (1000, round_number)], after);
+
+        method.label(round_number);
+        method.get_static("java/lang/System", "out", &Java::class("java/io/PrintStream"));
+        method.load_constant("What a round number of args!");
+        method.invoke_virtual("java/io/PrintStream", "println", &[Java::class("java/lang/String")], &Java::Void);
+
+        // after
+        method.label(after);
+        method.do_return();
+
+        // fini!
+        method.done().unwrap();
+    }
+
+    let classfile = class.done().unwrap();
+    write_classfile(classfile, "switch_statement.class").unwrap();
+}
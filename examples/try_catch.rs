@@ -0,0 +1,55 @@
+extern crate jvm_assembler;
+
+use jvm_assembler::*;
+
+fn main() {
+    let mut class = define_class(ACC_PUBLIC, "try_catch", "java/lang/Object");
+
+    {
+        // create main method
+        let mut method = class.define_method(ACC_PUBLIC | ACC_STATIC, "main", &[Java::array(Java::class("java/lang/String"))], &Java::Void);
+        let after = method.new_label();
+
+        // try {
+        //     System.out.println(Integer.parseInt(args[0]));
+        // } catch (NumberFormatException e) {
+        //     System.out.println("First arg is not a number");
+        // } catch (Throwable t) {
+        //     System.out.println("Something else went wrong");
+        // }
+        let parse = method.try_start();
+        method.get_static("java/lang/System", "out", &Java::class("java/io/PrintStream"));
+        method.aload0();
+        method.iconst0();
+        method.aaload();
+        method.invoke_static("java/lang/Integer", "parseInt", &[Java::class("java/lang/String")], &Java::Int);
+        method.invoke_virtual("java/io/PrintStream", "println", &[Java::Int], &Java::Void);
+        method.try_end(parse);
+        method.goto(after);
+
+        // number format handler: discard the exception and print a message
+        method.catch_block(parse, "java/lang/NumberFormatException");
+        method.pop();
+        method.get_static("java/lang/System", "out", &Java::class("java/io/PrintStream"));
+        method.load_constant("First arg is not a number");
+        method.invoke_virtual("java/io/PrintStream", "println", &[Java::class("java/lang/String")], &Java::Void);
+        method.goto(after);
+
+        // catch-all handler
+        method.finally_block(parse);
+        method.pop();
+        method.get_static("java/lang/System", "out", &Java::class("java/io/PrintStream"));
+        method.load_constant("Something else went wrong");
+        method.invoke_virtual("java/io/PrintStream", "println", &[Java::class("java/lang/String")], &Java::Void);
+
+        // after
+        method.label(after);
+        method.do_return();
+
+        // fini!
+        method.done().unwrap();
+    }
+
+    let classfile = class.done().unwrap();
+    write_classfile(classfile, "try_catch.class").unwrap();
+}
@@ -0,0 +1,50 @@
+extern crate jvm_assembler;
+
+use jvm_assembler::*;
+
+fn main() {
+    let mut class = define_class(ACC_PUBLIC, "while_loop", "java/lang/Object");
+
+    {
+        // create main method
+        let mut method = class.define_method(ACC_PUBLIC | ACC_STATIC, "main", &[Java::array(Java::class("java/lang/String"))], &Java::Void);
+        let condition = method.new_label();
+        let after = method.new_label();
+
+        // int i = 0;
+        // while (i < args.length) {
+        //     System.out.println(args[i]);
+        //     i++;
+        // }
+        method.iconst0();
+        method.istore(1);
+
+        // loop condition
+        method.label(condition);
+        method.iload(1);
+        method.aload0();
+        method.array_length();
+        method.if_icmp_ge(after);
+
+        // loop body
+        method.get_static("java/lang/System", "out", &Java::class("java/io/PrintStream"));
+        method.aload0();
+        method.iload(1);
+        method.aaload();
+        method.invoke_virtual("java/io/PrintStream", "println", &[Java::class("java/lang/String")], &Java::Void);
+        method.iinc(1, 1);
+
+        // jump back to the condition
+        method.goto(condition);
+
+        // after
+        method.label(after);
+        method.do_return();
+
+        // fini!
+        method.done().unwrap();
+    }
+
+    let classfile = class.done().unwrap();
+    write_classfile(classfile, "while_loop.class").unwrap();
+}
@@ -0,0 +1,1359 @@
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::{slice, vec};
+
+use classfile::*;
+use class_builder::*;
+use errors::*;
+use java_type_signatures::*;
+
+// Assembles a class written in Jasmin syntax, e.g.
+//
+//     .class public hello
+//     .super java/lang/Object
+//
+//     .method public static main([Ljava/lang/String;)V
+//         getstatic java/lang/System/out Ljava/io/PrintStream;
+//         ldc "Hello, World!"
+//         invokevirtual java/io/PrintStream/println(Ljava/lang/String;)V
+//         return
+//     .end method
+//
+// The class is built with ClassBuilder and MethodBuilder, so the stack map frames, max_stack and max_locals are
+// worked out from the code, and .limit directives are only checked for syntax.
+pub fn assemble(source: &str) -> Result<Classfile, AssemblyError> {
+    let mut source = Source::new(tokenize(source)?);
+
+    // JasminXT's .bytecode <major>.<minor> comes before everything else
+    let mut class_line = source.next_line().ok_or_else(|| AssemblyError { line: 1, column: 1, message: "Expected .class or .interface".to_owned() })?;
+    let mut version = None;
+    if class_line.tokens[0].text == ".bytecode" && !class_line.tokens[0].quoted {
+        let mut tokens = Tokens::new(&class_line);
+        tokens.next(".bytecode")?;
+        let token = tokens.next("a version, e.g. 61.0")?;
+        tokens.end()?;
+        version = Some(class_version(token)?);
+        class_line = source.next_line().ok_or_else(|| source.end_error("Expected .class or .interface"))?;
+    }
+    let mut tokens = Tokens::new(&class_line);
+    let directive = tokens.next(".class or .interface")?;
+    let is_interface = match directive.text.as_ref() {
+        ".class" => false,
+        ".interface" => true,
+        _ => return Err(token_error(directive, format!("Expected .class or .interface, found {:?}", directive.text))),
+    };
+    let access_flags = tokens.flags();
+    let this_class = tokens.next("a class name")?;
+    tokens.end()?;
+
+    let super_line = source.next_line().ok_or_else(|| source.end_error("Expected .super"))?;
+    let mut tokens = Tokens::new(&super_line);
+    tokens.directive(".super")?;
+    let super_class = tokens.next("a superclass name")?;
+    tokens.end()?;
+
+    let mut class = if is_interface {
+        ClassBuilder::new(access_flags | ACC_INTERFACE | ACC_ABSTRACT, &this_class.text, &super_class.text)
+    } else {
+        ClassBuilder::new(access_flags | ACC_SUPER, &this_class.text, &super_class.text)
+    };
+    if let Some((major_version, minor_version)) = version {
+        class.version(major_version, minor_version);
+    }
+
+    // every .debug line goes into the one SourceDebugExtension attribute
+    let mut debug_extension: Option<String> = None;
+    while let Some(line) = source.next_line() {
+        let mut tokens = Tokens::new(&line);
+        let directive = tokens.next("a directive")?;
+        match directive.text.as_ref() {
+            ".source" => {
+                let source_file = tokens.next("a source file name")?;
+                tokens.end()?;
+                class.source_file(&source_file.text);
+            },
+            ".debug" => {
+                let text = tokens.string("a quoted string")?;
+                tokens.end()?;
+                debug_extension.get_or_insert_with(String::new).push_str(&text.text);
+            },
+            ".implements" => {
+                let interface = tokens.next("an interface name")?;
+                tokens.end()?;
+                class.add_interface(&interface.text);
+            },
+            ".signature" => {
+                let signature = tokens.string("a quoted class signature")?;
+                tokens.end()?;
+                let signature = ClassSignature::parse(&signature.text).map_err(|e| token_error_at(signature, e.offset, e.to_string()))?;
+                class.signature(&signature);
+            },
+            ".deprecated" => {
+                tokens.end()?;
+                class.deprecated();
+            },
+            ".synthetic" => {
+                tokens.end()?;
+                class.synthetic();
+            },
+            ".inner" => assemble_inner_class(&mut class, &mut tokens)?,
+            // .enclosing method <class>/<name><descriptor>, or just the class outside a method
+            ".enclosing" => {
+                tokens.directive("method")?;
+                let member = tokens.next("a class, or a class, method name and descriptor")?;
+                tokens.end()?;
+                match member.text.find('(') {
+                    Some(paren) => {
+                        let (class_name, name) = split_member(member, &member.text[..paren])?;
+                        let descriptor = MethodDescriptor::parse(&member.text[paren..]).map_err(|e| token_error_at(member, paren + e.offset, e.to_string()))?;
+                        class.enclosing_method(class_name, name, &descriptor.args, &descriptor.ret);
+                    },
+                    None => class.enclosing_class(&member.text),
+                }
+            },
+            ".nesthost" => {
+                let host = tokens.next("a class name")?;
+                tokens.end()?;
+                class.nest_host(&host.text);
+            },
+            ".nestmember" => {
+                let member = tokens.next("a class name")?;
+                tokens.end()?;
+                class.nest_member(&member.text);
+            },
+            ".annotation" => {
+                let retention = retention(tokens.next("visible or invisible")?)?;
+                let annotation_type = class_descriptor(tokens.next("an annotation type")?)?;
+                tokens.end()?;
+                let lines = annotation_lines(&mut source)?;
+                class.annotation(&annotation(annotation_type, &mut AnnotationLines::new(&lines))?, retention);
+            },
+            ".field" => assemble_field(&mut class, &mut tokens, &mut source)?,
+            ".method" => assemble_method(&mut class, &mut tokens, &mut source)?,
+            _ => return Err(token_error(directive, format!("Unexpected {:?}", directive.text))),
+        }
+    }
+    if let Some(debug_extension) = debug_extension {
+        class.source_debug_extension(&debug_extension);
+    }
+    class.done().map_err(|e| token_error(this_class, e.to_string()))
+}
+
+// .inner class|interface <flags> [<name>] inner <class> [outer <class>], leaving out the name of an anonymous class
+// and the outer class of a local or anonymous one
+fn assemble_inner_class(class: &mut ClassBuilder, tokens: &mut Tokens) -> Result<(), AssemblyError> {
+    let kind = tokens.next("class or interface")?;
+    let access_flags = match kind.text.as_ref() {
+        "class" if !kind.quoted => tokens.flags(),
+        "interface" if !kind.quoted => tokens.flags() | ACC_INTERFACE | ACC_ABSTRACT,
+        _ => return Err(token_error(kind, format!("Expected class or interface, found {:?}", kind.text))),
+    };
+    let mut inner_name = None;
+    if !tokens.peek_is("inner") {
+        inner_name = Some(&tokens.next("an inner class name")?.text);
+    }
+    tokens.directive("inner")?;
+    let inner_class = tokens.next("a class name")?;
+    let mut outer_class = None;
+    if tokens.peek_is("outer") {
+        tokens.next("outer")?;
+        outer_class = Some(&tokens.next("a class name")?.text);
+    }
+    tokens.end()?;
+    class.inner_class(access_flags, &inner_class.text, outer_class.map(|class| class.as_ref()), inner_name.map(|name| name.as_ref()));
+    Ok(())
+}
+
+// .field <flags> <name> <descriptor> [signature "<signature>"] [= <value>], then optionally .deprecated, .synthetic
+// and .annotation lines ended by .end field
+fn assemble_field(class: &mut ClassBuilder, tokens: &mut Tokens, source: &mut Source) -> Result<(), AssemblyError> {
+    let access_flags = tokens.flags();
+    let name = tokens.next("a field name")?;
+    let descriptor = tokens.next("a field descriptor")?;
+    let field_type = Java::parse(&descriptor.text).map_err(|e| token_error_at(descriptor, e.offset, e.to_string()))?;
+    let mut field = class.define_field(access_flags, &name.text, &field_type);
+
+    if tokens.peek_is("signature") {
+        tokens.next("signature")?;
+        let signature = tokens.string("a quoted field signature")?;
+        let signature = TypeSignature::parse(&signature.text).map_err(|e| token_error_at(signature, e.offset, e.to_string()))?;
+        field.signature(&signature);
+    }
+
+    if tokens.peek_is("=") {
+        tokens.next("=")?;
+        let value = tokens.next("a constant value")?;
+        match field_type {
+            Java::Boolean | Java::Byte | Java::Char | Java::Short | Java::Int => field.constant_int(integer(value, "an int")?),
+            Java::Long => field.constant_long(long(value)?),
+            Java::Float => field.constant_float(float(value)?),
+            Java::Double => field.constant_double(float(value)?),
+            Java::Class(ref class) if class == "java/lang/String" && value.quoted => field.constant_string(&value.text),
+            Java::Class(ref class) if class == "java/lang/String" => return Err(token_error(value, "Expected a quoted string".to_owned())),
+            _ => return Err(token_error(value, format!("Fields of type {} can't have a constant value", field_type))),
+        }
+    }
+    tokens.end()?;
+
+    // the field's own directives, which the class can't have in between its fields
+    if source.peek_directive().is_some_and(|directive| FIELD_DIRECTIVES.contains(&directive)) {
+        loop {
+            let line = source.next_line().ok_or_else(|| source.end_error("Expected .end field"))?;
+            let mut tokens = Tokens::new(&line);
+            let directive = tokens.next("a directive")?;
+            match directive.text.as_ref() {
+                ".deprecated" => field.deprecated(),
+                ".synthetic" => field.synthetic(),
+                ".annotation" => {
+                    let retention = retention(tokens.next("visible or invisible")?)?;
+                    let annotation_type = class_descriptor(tokens.next("an annotation type")?)?;
+                    tokens.end()?;
+                    let lines = annotation_lines(source)?;
+                    field.annotation(&annotation(annotation_type, &mut AnnotationLines::new(&lines))?, retention);
+                },
+                ".end" => {
+                    tokens.directive("field")?;
+                    tokens.end()?;
+                    break;
+                },
+                _ => return Err(token_error(directive, format!("Expected a field directive or .end field, found {:?}", directive.text))),
+            }
+            tokens.end()?;
+        }
+    }
+    field.done();
+    Ok(())
+}
+
+const FIELD_DIRECTIVES: &[&str] = &[".deprecated", ".synthetic", ".annotation"];
+
+// .method <flags> <name><descriptor>, then the method's code and directives, then .end method
+fn assemble_method(class: &mut ClassBuilder, tokens: &mut Tokens, source: &mut Source) -> Result<(), AssemblyError> {
+    let access_flags = tokens.flags();
+    let name_and_descriptor = tokens.next("a method name and descriptor")?;
+    tokens.end()?;
+    let paren = name_and_descriptor.text.find('(').ok_or_else(|| token_error(name_and_descriptor, "Expected a method name followed by its descriptor".to_owned()))?;
+    let (name, descriptor) = name_and_descriptor.text.split_at(paren);
+    let descriptor = MethodDescriptor::parse(descriptor).map_err(|e| token_error_at(name_and_descriptor, paren + e.offset, e.to_string()))?;
+
+    let mut method = class.define_method(access_flags, name, &descriptor.args, &descriptor.ret);
+    let mut labels = Labels::new();
+    let has_code = access_flags & (ACC_ABSTRACT | ACC_NATIVE) == 0;
+    loop {
+        let line = source.next_line().ok_or_else(|| source.end_error("Expected .end method"))?;
+        let mut tokens = Tokens::new(&line);
+        let is_code = !line.tokens[0].text.starts_with('.') || CODE_DIRECTIVES.contains(&line.tokens[0].text.as_ref());
+        if !has_code && is_code {
+            return Err(token_error(&line.tokens[0], "Abstract and native methods can't have code".to_owned()));
+        }
+
+        // a label can be followed by an instruction on the same line
+        if line.tokens.len() >= 2 && line.tokens[1].text == ":" && !line.tokens[1].quoted {
+            labels.place(&mut method, &line.tokens[0])?;
+            tokens.next("a label")?;
+            tokens.next(":")?;
+            if tokens.peek().is_none() {
+                continue;
+            }
+        }
+
+        let first = tokens.next("an instruction or directive")?;
+        match first.text.as_ref() {
+            ".limit" => {
+                let what = tokens.next("stack or locals")?;
+                if what.text != "stack" && what.text != "locals" {
+                    return Err(token_error(what, format!("Expected stack or locals, found {:?}", what.text)));
+                }
+                integer::<u16>(tokens.next("a limit")?, "a limit between 0 and 65535")?;
+                tokens.end()?;
+            },
+            ".catch" => {
+                let class = tokens.next("an exception class name or all")?;
+                tokens.directive("from")?;
+                let start = labels.jump(&mut method, tokens.next("a label")?);
+                tokens.directive("to")?;
+                let end = labels.jump(&mut method, tokens.next("a label")?);
+                tokens.directive("using")?;
+                let handler = labels.jump(&mut method, tokens.next("a label")?);
+                tokens.end()?;
+                let exception_class = if class.text == "all" { None } else { Some(class.text.as_ref()) };
+                method.add_exception_handler(start, end, handler, exception_class);
+            },
+            ".signature" => {
+                let token = tokens.string("a quoted method signature")?;
+                tokens.end()?;
+                let signature = MethodSignature::parse(&token.text).map_err(|e| token_error_at(token, e.offset, e.to_string()))?;
+                method.signature(&signature);
+            },
+            ".throws" => {
+                let exception_class = tokens.next("an exception class name")?;
+                tokens.end()?;
+                method.throws(&exception_class.text);
+            },
+            ".deprecated" => {
+                tokens.end()?;
+                method.deprecated();
+            },
+            ".synthetic" => {
+                tokens.end()?;
+                method.synthetic();
+            },
+            ".annotation" => assemble_method_annotation(&mut method, &mut tokens, source)?,
+            ".line" => {
+                let line_number = integer::<u16>(tokens.next("a line number")?, "a line number between 0 and 65535")?;
+                tokens.end()?;
+                method.line(line_number);
+            },
+            ".var" => assemble_local_variable(&mut method, &mut labels, &mut tokens)?,
+            ".stack" => {
+                tokens.end()?;
+                assemble_stack_frame(&mut method, &mut labels, source)?;
+            },
+            ".end" => {
+                tokens.directive("method")?;
+                tokens.end()?;
+                break;
+            },
+            directive if directive.starts_with('.') => return Err(token_error(first, format!("Unknown directive {:?}", directive))),
+            _ => {
+                assemble_instruction(&mut method, &mut labels, first, &mut tokens, source)?;
+                tokens.end()?;
+            },
+        }
+    }
+
+    labels.check()?;
+    method.done().map_err(|e| token_error(name_and_descriptor, e.to_string()))
+}
+
+// the directives that only make sense in a method with code
+const CODE_DIRECTIVES: &[&str] = &[".limit", ".catch", ".line", ".var", ".stack"];
+
+// .annotation visible|invisible <type>, .annotation visibleparam|invisibleparam <argument> <type> or .annotation
+// default, then the annotation's elements (or for a default, just its value) and .end annotation
+fn assemble_method_annotation(method: &mut MethodBuilder, tokens: &mut Tokens, source: &mut Source) -> Result<(), AssemblyError> {
+    let kind = tokens.next("visible, invisible, visibleparam, invisibleparam or default")?;
+    match kind.text.as_ref() {
+        "default" if !kind.quoted => {
+            tokens.end()?;
+            let lines = annotation_lines(source)?;
+            let mut lines = AnnotationLines::new(&lines);
+            let line = lines.next()?;
+            let mut tokens = Tokens::new(line);
+            let value = element_value(&mut tokens, &mut lines)?;
+            let line = lines.next()?;
+            let mut tokens = Tokens::new(line);
+            if !end_annotation(&mut tokens)? {
+                return Err(token_error(&line.tokens[0], format!("Expected .end annotation, found {:?}", line.tokens[0].text)));
+            }
+            method.annotation_default(&value);
+        },
+        "visibleparam" | "invisibleparam" if !kind.quoted => {
+            let retention = if kind.text == "visibleparam" { Retention::Runtime } else { Retention::Class };
+            let n = integer::<usize>(tokens.next("an argument index")?, "an argument index")?;
+            let annotation_type = class_descriptor(tokens.next("an annotation type")?)?;
+            tokens.end()?;
+            let lines = annotation_lines(source)?;
+            method.parameter_annotation(n, &annotation(annotation_type, &mut AnnotationLines::new(&lines))?, retention);
+        },
+        _ => {
+            let retention = retention(kind)?;
+            let annotation_type = class_descriptor(tokens.next("an annotation type")?)?;
+            tokens.end()?;
+            let lines = annotation_lines(source)?;
+            method.annotation(&annotation(annotation_type, &mut AnnotationLines::new(&lines))?, retention);
+        },
+    }
+    Ok(())
+}
+
+// visible for an annotation that can be read with reflection, or invisible for one that's only kept in the class file
+fn retention(token: &Token) -> Result<Retention, AssemblyError> {
+    match token.text.as_ref() {
+        "visible" if !token.quoted => Ok(Retention::Runtime),
+        "invisible" if !token.quoted => Ok(Retention::Class),
+        _ => Err(token_error(token, format!("Expected visible or invisible, found {:?}", token.text))),
+    }
+}
+
+// The lines of an annotation up to the .end annotation that closes it, including those of any annotations nested
+// in it, which are read ahead so that the annotation can borrow its names and values from them.
+fn annotation_lines(source: &mut Source) -> Result<Vec<Line>, AssemblyError> {
+    let mut lines = vec![];
+    let mut depth = 0;
+    loop {
+        let line = source.next_line().ok_or_else(|| source.end_error("Expected .end annotation"))?;
+        let first = &line.tokens[0];
+        let is_end = !first.quoted && first.text == ".end";
+        if !first.quoted && first.text == ".annotation" {
+            depth += 1;
+        }
+        lines.push(line);
+        if is_end {
+            if depth == 0 {
+                return Ok(lines);
+            }
+            depth -= 1;
+        }
+    }
+}
+
+// <name> <type> = <value> lines up to .end annotation, e.g. since s = "9" for @Deprecated(since = "9")
+fn annotation<'l>(annotation_type: &'l str, lines: &mut AnnotationLines<'l>) -> Result<AnnotationSpec<'l>, AssemblyError> {
+    let mut elements = vec![];
+    loop {
+        let mut tokens = Tokens::new(lines.next()?);
+        if end_annotation(&mut tokens)? {
+            return Ok(AnnotationSpec { annotation_type, elements });
+        }
+        let name = tokens.next("an element name")?;
+        if !name.quoted && name.text.starts_with('.') {
+            return Err(token_error(name, format!("Expected an element name or .end annotation, found {:?}", name.text)));
+        }
+        elements.push((name.text.as_ref(), element_value(&mut tokens, lines)?));
+    }
+}
+
+// whether the line is .end annotation
+fn end_annotation(tokens: &mut Tokens) -> Result<bool, AssemblyError> {
+    if !tokens.peek_is(".end") {
+        return Ok(false);
+    }
+    tokens.next(".end")?;
+    tokens.directive("annotation")?;
+    tokens.end()?;
+    Ok(true)
+}
+
+// The type and value of an element, e.g. I = 5, e Ljava/lang/annotation/RetentionPolicy; = RUNTIME or [s = "a" "b".
+// The values of an annotation type (@ <type>) are nested .annotation blocks on the lines after it, one for each
+// value of an array.
+fn element_value<'l>(tokens: &mut Tokens<'l>, lines: &mut AnnotationLines<'l>) -> Result<AnnotationValue<'l>, AssemblyError> {
+    let tag_token = tokens.next("an element type, e.g. I or [s")?;
+    let (is_array, tag) = match tag_token.text.strip_prefix('[') {
+        Some(tag) => (true, tag),
+        None => (false, tag_token.text.as_ref()),
+    };
+    if tag_token.quoted || !ELEMENT_TAGS.contains(&tag) {
+        return Err(token_error(tag_token, format!("Expected an element type, e.g. I or [s, found {:?}", tag_token.text)));
+    }
+
+    if tag == "@" {
+        let annotation_type = class_descriptor(tokens.next("an annotation type")?)?;
+        tokens.directive("=")?;
+        tokens.end()?;
+        if !is_array {
+            return Ok(AnnotationValue::Annotation(nested_annotation(annotation_type, lines)?));
+        }
+        let mut values = vec![];
+        while lines.peek_is(".annotation") {
+            values.push(AnnotationValue::Annotation(nested_annotation(annotation_type, lines)?));
+        }
+        return Ok(AnnotationValue::Array(values));
+    }
+
+    let enum_type = if tag == "e" { Some(class_descriptor(tokens.next("an enum type")?)?) } else { None };
+    tokens.directive("=")?;
+    if !is_array {
+        let value = element_constant(tag, enum_type, tokens.next("a value")?)?;
+        tokens.end()?;
+        return Ok(value);
+    }
+    let mut values = vec![];
+    while let Some(token) = tokens.peek() {
+        tokens.next("a value")?;
+        values.push(element_constant(tag, enum_type, token)?);
+    }
+    Ok(AnnotationValue::Array(values))
+}
+
+// a .annotation line with nothing after it, then the elements of the annotation
+fn nested_annotation<'l>(annotation_type: &'l str, lines: &mut AnnotationLines<'l>) -> Result<AnnotationSpec<'l>, AssemblyError> {
+    let mut tokens = Tokens::new(lines.next()?);
+    tokens.directive(".annotation")?;
+    tokens.end()?;
+    annotation(annotation_type, lines)
+}
+
+const ELEMENT_TAGS: &[&str] = &["B", "C", "D", "F", "I", "J", "S", "Z", "s", "e", "c", "@"];
+
+fn element_constant<'l>(tag: &str, enum_type: Option<&'l str>, token: &'l Token) -> Result<AnnotationValue<'l>, AssemblyError> {
+    Ok(match tag {
+        "B" => AnnotationValue::Byte(integer(token, "a value between -128 and 127")?),
+        "C" => {
+            let value = integer::<u16>(token, "a char value between 0 and 65535")?;
+            let c = std::char::from_u32(value as u32).ok_or_else(|| token_error(token, format!("Char {} is a surrogate, which can't be assembled", value)))?;
+            AnnotationValue::Char(c)
+        },
+        "S" => AnnotationValue::Short(integer(token, "a value between -32768 and 32767")?),
+        "I" => AnnotationValue::Int(integer(token, "an int")?),
+        "J" => AnnotationValue::Long(long(token)?),
+        "F" => AnnotationValue::Float(float(token)?),
+        "D" => AnnotationValue::Double(float(token)?),
+        "Z" => match token.text.as_ref() {
+            "true" if !token.quoted => AnnotationValue::Boolean(true),
+            "false" if !token.quoted => AnnotationValue::Boolean(false),
+            _ => return Err(token_error(token, format!("Expected true or false, found {:?}", token.text))),
+        },
+        "s" if token.quoted => AnnotationValue::String(&token.text),
+        "s" => return Err(token_error(token, format!("Expected a quoted string, found {:?}", token.text))),
+        "e" => AnnotationValue::Enum(enum_type.unwrap_or_default(), &token.text),
+        // a return descriptor, e.g. Ljava/lang/String; for String.class or V for void.class
+        _ if token.text == "V" => AnnotationValue::Class(Java::Void),
+        _ => AnnotationValue::Class(Java::parse(&token.text).map_err(|e| token_error_at(token, e.offset, e.to_string()))?),
+    })
+}
+
+// the internal name in a class descriptor, e.g. java/lang/Deprecated for Ljava/lang/Deprecated;
+fn class_descriptor(token: &Token) -> Result<&str, AssemblyError> {
+    match Java::parse(&token.text) {
+        Ok(Java::Class(_)) => Ok(&token.text[1..token.text.len() - 1]),
+        Ok(_) => Err(token_error(token, format!("Expected a class descriptor, e.g. Ljava/lang/Deprecated;, found {:?}", token.text))),
+        Err(e) => Err(token_error_at(token, e.offset, e.to_string())),
+    }
+}
+
+// the lines read by annotation_lines, which end with the .end annotation for the outermost annotation
+struct AnnotationLines<'l> {
+    lines: slice::Iter<'l, Line>,
+    last: &'l Line,
+}
+
+impl<'l> AnnotationLines<'l> {
+    fn new(lines: &'l [Line]) -> AnnotationLines<'l> {
+        AnnotationLines { lines: lines.iter(), last: &lines[lines.len() - 1] }
+    }
+
+    fn peek_is(&self, directive: &str) -> bool {
+        self.lines.as_slice().first().is_some_and(|line| !line.tokens[0].quoted && line.tokens[0].text == directive)
+    }
+
+    fn next(&mut self) -> Result<&'l Line, AssemblyError> {
+        self.lines.next().ok_or_else(|| AssemblyError { line: self.last.number, column: self.last.end_column, message: "Expected .end annotation".to_owned() })
+    }
+}
+
+// .var <slot> is <name> <descriptor> [signature "<signature>"] from <label> to <label>
+fn assemble_local_variable(method: &mut MethodBuilder, labels: &mut Labels, tokens: &mut Tokens) -> Result<(), AssemblyError> {
+    let slot = integer::<u16>(tokens.next("a local variable index")?, "a local variable index between 0 and 65535")?;
+    tokens.directive("is")?;
+    let name = tokens.next("a local variable name")?;
+    let descriptor = tokens.next("a field descriptor")?;
+    let local_type = Java::parse(&descriptor.text).map_err(|e| token_error_at(descriptor, e.offset, e.to_string()))?;
+    let mut signature = None;
+    if tokens.peek_is("signature") {
+        tokens.next("signature")?;
+        let token = tokens.string("a quoted field signature")?;
+        signature = Some(TypeSignature::parse(&token.text).map_err(|e| token_error_at(token, e.offset, e.to_string()))?);
+    }
+    tokens.directive("from")?;
+    let start = labels.jump(method, tokens.next("a label")?);
+    tokens.directive("to")?;
+    let end = labels.jump(method, tokens.next("a label")?);
+    tokens.end()?;
+    method.add_local_variable(slot, &name.text, &local_type, signature.as_ref(), start, end);
+    Ok(())
+}
+
+// .stack, then a line for each local (locals <type>) and stack entry (stack <type>), then .end stack
+fn assemble_stack_frame(method: &mut MethodBuilder, labels: &mut Labels, source: &mut Source) -> Result<(), AssemblyError> {
+    let mut locals = vec![];
+    let mut stack = vec![];
+    loop {
+        let line = source.next_line().ok_or_else(|| source.end_error("Expected .end stack"))?;
+        let mut tokens = Tokens::new(&line);
+        let first = tokens.next("locals, stack or .end stack")?;
+        let entries = match first.text.as_ref() {
+            "locals" => &mut locals,
+            "stack" => &mut stack,
+            ".end" => {
+                tokens.directive("stack")?;
+                tokens.end()?;
+                break;
+            },
+            _ => return Err(token_error(first, format!("Expected locals, stack or .end stack, found {:?}", first.text))),
+        };
+        let verification_type = tokens.next("a verification type")?;
+        entries.push(match verification_type.text.as_ref() {
+            "Top" => FrameType::Top,
+            "Integer" => FrameType::Integer,
+            "Float" => FrameType::Float,
+            "Long" => FrameType::Long,
+            "Double" => FrameType::Double,
+            "Null" => FrameType::Null,
+            "UninitializedThis" => FrameType::UninitializedThis,
+            "Object" => FrameType::Object(tokens.next("a class name")?.text.clone()),
+            "Uninitialized" => FrameType::Uninitialized(labels.jump(method, tokens.next("a label")?)),
+            _ => return Err(token_error(verification_type, format!("Unknown verification type {:?}", verification_type.text))),
+        });
+        tokens.end()?;
+    }
+    method.frame(&locals, &stack);
+    Ok(())
+}
+
+fn assemble_instruction(method: &mut MethodBuilder, labels: &mut Labels, mnemonic: &Token, tokens: &mut Tokens, source: &mut Source) -> Result<(), AssemblyError> {
+    if let Some(instruction) = SIMPLE_INSTRUCTIONS.iter().find(|instruction| instruction.mnemonic() == mnemonic.text) {
+        method.instruction(instruction.clone());
+        return Ok(());
+    }
+
+    // loads and stores of locals past 255 need the wide prefix
+    if let Some(&(_, narrow, wide)) = LOCAL_VARIABLE_INSTRUCTIONS.iter().find(|&&(name, _, _)| name == mnemonic.text) {
+        let index = integer::<u16>(tokens.next("a local variable index")?, "a local variable index between 0 and 65535")?;
+        match u8::try_from(index) {
+            Ok(index) => method.instruction(narrow(index)),
+            Err(_) => method.instruction(Instruction::Wide(wide(index))),
+        }
+        return Ok(());
+    }
+
+    match mnemonic.text.as_ref() {
+        "bipush" => {
+            let value = integer::<i8>(tokens.next("a byte value")?, "a value between -128 and 127")?;
+            method.instruction(Instruction::Bipush(value as u8));
+        },
+        "sipush" => {
+            let value = integer::<i16>(tokens.next("a short value")?, "a value between -32768 and 32767")?;
+            method.instruction(Instruction::Sipush(value as u16));
+        },
+        "iinc" => {
+            let index = integer::<u16>(tokens.next("a local variable index")?, "a local variable index between 0 and 65535")?;
+            let value = integer::<i16>(tokens.next("an increment")?, "an increment between -32768 and 32767")?;
+            match (u8::try_from(index), i8::try_from(value)) {
+                (Ok(index), Ok(value)) => method.instruction(Instruction::Iinc(index, value as u8)),
+                _ => method.instruction(Instruction::Wide(WideInstruction::Iinc(index, value as u16))),
+            }
+        },
+        "newarray" => {
+            let token = tokens.next("an array element type")?;
+            let array_type = match token.text.as_ref() {
+                "boolean" => 4,
+                "char" => 5,
+                "float" => 6,
+                "double" => 7,
+                "byte" => 8,
+                "short" => 9,
+                "int" => 10,
+                "long" => 11,
+                _ => return Err(token_error(token, format!("Expected a primitive type, found {:?}", token.text))),
+            };
+            method.instruction(Instruction::NewArray(array_type));
+        },
+
+        // the builder picks ldc, ldc_w or ldc2_w to suit the constant
+        "ldc" | "ldc_w" | "ldc2_w" => {
+            let wide = mnemonic.text == "ldc2_w";
+            let token = tokens.next("a constant")?;
+            let constant = loadable_constant(token, tokens, wide)?;
+            if wide && !constant.is_wide() {
+                return Err(token_error(token, "ldc2_w can only load longs and doubles".to_owned()));
+            } else if !wide && constant.is_wide() {
+                return Err(token_error(token, format!("{} can't load longs or doubles, which need ldc2_w", mnemonic.text)));
+            }
+            method.load_constant(constant);
+        },
+
+        "ifeq" | "ifne" | "iflt" | "ifge" | "ifgt" | "ifle" | "if_icmpeq" | "if_icmpne" | "if_icmplt" | "if_icmpge" |
+        "if_icmpgt" | "if_icmple" | "if_acmpeq" | "if_acmpne" | "ifnull" | "ifnonnull" | "goto" | "goto_w" => {
+            let label = labels.jump(method, tokens.next("a label")?);
+            match mnemonic.text.as_ref() {
+                "ifeq" => method.ifeq(label),
+                "ifne" => method.ifne(label),
+                "iflt" => method.iflt(label),
+                "ifge" => method.ifge(label),
+                "ifgt" => method.ifgt(label),
+                "ifle" => method.ifle(label),
+                "if_icmpeq" => method.if_icmp_eq(label),
+                "if_icmpne" => method.if_icmp_ne(label),
+                "if_icmplt" => method.if_icmp_lt(label),
+                "if_icmpge" => method.if_icmp_ge(label),
+                "if_icmpgt" => method.if_icmp_gt(label),
+                "if_icmple" => method.if_icmp_le(label),
+                "if_acmpeq" => method.if_acmp_eq(label),
+                "if_acmpne" => method.if_acmp_ne(label),
+                "ifnull" => method.ifnull(label),
+                "ifnonnull" => method.ifnonnull(label),
+                // the builder widens gotos to goto_w as needed
+                _ => method.goto(label),
+            }
+        },
+        "jsr" | "jsr_w" | "ret" => {
+            return Err(token_error(mnemonic, "Subroutines aren't supported, since stack map frames can't describe them".to_owned()));
+        },
+
+        // tableswitch <low> [<high>], then a label per line, then default : <label>
+        "tableswitch" => {
+            let low_token = tokens.next("the lowest key")?;
+            let low = integer::<i32>(low_token, "an int")?;
+            let high = match tokens.peek() {
+                Some(token) => Some((token, integer::<i32>(tokens.next("the highest key")?, "an int")?)),
+                None => None,
+            };
+            tokens.end()?;
+            let mut targets = vec![];
+            let default = loop {
+                let line = source.next_line().ok_or_else(|| source.end_error("Expected default : <label>"))?;
+                let mut tokens = Tokens::new(&line);
+                if tokens.peek_is("default") {
+                    break switch_default(method, labels, &mut tokens)?;
+                }
+                targets.push(labels.jump(method, tokens.next("a label")?));
+                tokens.end()?;
+            };
+            let high = match high {
+                Some((token, high)) if high as i64 - low as i64 + 1 != targets.len() as i64 => {
+                    return Err(token_error(token, BuildErrorCause::SwitchLabelCount(low, high, targets.len()).to_string()));
+                },
+                Some((_, high)) => high,
+                None => i32::try_from(low as i64 + targets.len() as i64 - 1).map_err(|_| {
+                    token_error(low_token, format!("Table switch from {} with {} labels has keys that don't fit in an int", low, targets.len()))
+                })?,
+            };
+            method.table_switch(low, high, &targets, default);
+        },
+
+        // lookupswitch, then <key> : <label> per line, then default : <label>
+        "lookupswitch" => {
+            tokens.end()?;
+            let mut cases = vec![];
+            let mut keys = HashSet::new();
+            let default = loop {
+                let line = source.next_line().ok_or_else(|| source.end_error("Expected default : <label>"))?;
+                let mut tokens = Tokens::new(&line);
+                if tokens.peek_is("default") {
+                    break switch_default(method, labels, &mut tokens)?;
+                }
+                let token = tokens.next("a key")?;
+                let key = integer::<i32>(token, "an int")?;
+                if !keys.insert(key) {
+                    return Err(token_error(token, BuildErrorCause::DuplicateSwitchKey(key).to_string()));
+                }
+                tokens.directive(":")?;
+                cases.push((key, labels.jump(method, tokens.next("a label")?)));
+                tokens.end()?;
+            };
+            method.lookup_switch(&cases, default);
+        },
+
+        // e.g. getstatic java/lang/System/out Ljava/io/PrintStream;
+        "getstatic" | "putstatic" | "getfield" | "putfield" => {
+            let member = tokens.next("a class and field name")?;
+            let (class, name) = split_member(member, &member.text)?;
+            let descriptor = tokens.next("a field descriptor")?;
+            let field_type = Java::parse(&descriptor.text).map_err(|e| token_error_at(descriptor, e.offset, e.to_string()))?;
+            match mnemonic.text.as_ref() {
+                "getstatic" => method.get_static(class, name, &field_type),
+                "putstatic" => method.put_static(class, name, &field_type),
+                "getfield" => method.get_field(class, name, &field_type),
+                _ => method.put_field(class, name, &field_type),
+            }
+        },
+
+        // e.g. invokevirtual java/io/PrintStream/println(Ljava/lang/String;)V, or invokestatic interface <method> for
+        // a method of an interface
+        "invokevirtual" | "invokespecial" | "invokestatic" | "invokeinterface" => {
+            let is_interface = mnemonic.text != "invokevirtual" && mnemonic.text != "invokeinterface" && tokens.peek_is("interface");
+            if is_interface {
+                tokens.next("interface")?;
+            }
+            let member = tokens.next("a class, method name and descriptor")?;
+            let (class, name, descriptor) = split_method(member)?;
+            match mnemonic.text.as_ref() {
+                "invokevirtual" => method.invoke_virtual(class, name, &descriptor.args, &descriptor.ret),
+                "invokespecial" if is_interface => method.invoke_special_interface(class, name, &descriptor.args, &descriptor.ret),
+                "invokespecial" => method.invoke_special(class, name, &descriptor.args, &descriptor.ret),
+                "invokestatic" if is_interface => method.invoke_static_interface(class, name, &descriptor.args, &descriptor.ret),
+                "invokestatic" => method.invoke_static(class, name, &descriptor.args, &descriptor.ret),
+                _ => {
+                    // the count is worked out from the descriptor, so it's optional
+                    if tokens.peek().is_some() {
+                        integer::<u8>(tokens.next("an argument count")?, "an argument count between 0 and 255")?;
+                    }
+                    method.invoke_interface(class, name, &descriptor.args, &descriptor.ret);
+                },
+            }
+        },
+        // invokedynamic <name><descriptor> <bootstrap method handle> { <bootstrap arguments> }
+        "invokedynamic" => {
+            let name_and_descriptor = tokens.next("a name and method descriptor")?;
+            let paren = name_and_descriptor.text.find('(').ok_or_else(|| token_error(name_and_descriptor, "Expected a name followed by a method descriptor".to_owned()))?;
+            let (name, descriptor) = name_and_descriptor.text.split_at(paren);
+            let descriptor = MethodDescriptor::parse(descriptor).map_err(|e| token_error_at(name_and_descriptor, paren + e.offset, e.to_string()))?;
+            let bootstrap_method = handle(tokens)?;
+            let bootstrap_arguments = bootstrap_arguments(tokens)?;
+            method.invoke_dynamic(name, &descriptor.args, &descriptor.ret, &bootstrap_method, &bootstrap_arguments);
+        },
+
+        "new" | "anewarray" | "checkcast" | "instanceof" => {
+            let class = &tokens.next("a class name")?.text;
+            match mnemonic.text.as_ref() {
+                "new" => method.new_object(class),
+                "anewarray" => method.anewarray(class),
+                "checkcast" => method.checkcast(class),
+                _ => method.instance_of(class),
+            }
+        },
+        "multianewarray" => {
+            let descriptor = tokens.next("an array descriptor")?;
+            Java::parse(&descriptor.text).map_err(|e| token_error_at(descriptor, e.offset, e.to_string()))?;
+            let dimensions_token = tokens.next("a number of dimensions")?;
+            let dimensions = integer::<u8>(dimensions_token, "a number of dimensions between 1 and 255")?;
+            if dimensions == 0 {
+                return Err(token_error(dimensions_token, "Expected a number of dimensions between 1 and 255, found \"0\"".to_owned()));
+            }
+            method.multianewarray(&descriptor.text, dimensions);
+        },
+
+        _ => return Err(token_error(mnemonic, format!("Unknown instruction {:?}", mnemonic.text))),
+    }
+    Ok(())
+}
+
+// default : <label>, at the end of a switch
+fn switch_default(method: &mut MethodBuilder, labels: &mut Labels, tokens: &mut Tokens) -> Result<Label, AssemblyError> {
+    tokens.directive("default")?;
+    tokens.directive(":")?;
+    let label = labels.jump(method, tokens.next("a label")?);
+    tokens.end()?;
+    Ok(label)
+}
+
+// A constant for ldc or a bootstrap method: a number, a quoted string, Class <name>, MethodType <descriptor>,
+// MethodHandle <handle> or Dynamic <name> <descriptor> <handle> { <bootstrap arguments> }
+fn loadable_constant<'l>(token: &'l Token, tokens: &mut Tokens<'l>, wide: bool) -> Result<LoadableConstant<'l>, AssemblyError> {
+    if token.quoted {
+        return Ok(LoadableConstant::String(&token.text));
+    }
+    Ok(match token.text.as_ref() {
+        "Class" => LoadableConstant::Class(&tokens.next("a class name")?.text),
+        "MethodType" => {
+            let descriptor = tokens.next("a method descriptor")?;
+            MethodDescriptor::parse(&descriptor.text).map_err(|e| token_error_at(descriptor, e.offset, e.to_string()))?;
+            LoadableConstant::MethodType(&descriptor.text)
+        },
+        "MethodHandle" => LoadableConstant::MethodHandle(handle(tokens)?),
+        "Dynamic" => {
+            let name = tokens.next("a name")?;
+            let descriptor = tokens.next("a field descriptor")?;
+            Java::parse(&descriptor.text).map_err(|e| token_error_at(descriptor, e.offset, e.to_string()))?;
+            let bootstrap_method = handle(tokens)?;
+            LoadableConstant::Dynamic(&name.text, &descriptor.text, bootstrap_method, bootstrap_arguments(tokens)?)
+        },
+        _ => number(token, wide)?,
+    })
+}
+
+// A method handle: its kind, named after the instruction it stands for, then the field or method it refers to,
+// e.g. getstatic java/lang/System/out Ljava/io/PrintStream; or invokestatic interface java/util/List/of()Ljava/util/List;
+fn handle<'l>(tokens: &mut Tokens<'l>) -> Result<Handle<'l>, AssemblyError> {
+    let kind_token = tokens.next("a method handle kind")?;
+    let kind = match HANDLE_KINDS.iter().find(|&&(name, _)| !kind_token.quoted && name == kind_token.text) {
+        Some(&(_, kind)) => kind,
+        None => return Err(token_error(kind_token, format!("Expected a method handle kind, e.g. invokestatic, found {:?}", kind_token.text))),
+    };
+    if kind <= REF_PUT_STATIC {
+        let member = tokens.next("a class and field name")?;
+        let (class, name) = split_member(member, &member.text)?;
+        let descriptor = tokens.next("a field descriptor")?;
+        Java::parse(&descriptor.text).map_err(|e| token_error_at(descriptor, e.offset, e.to_string()))?;
+        return Ok(Handle { kind, class, name, descriptor: &descriptor.text, is_interface: false });
+    }
+    // only a static or special method can be either, and interface methods are always in interfaces
+    let is_interface = kind == REF_INVOKE_INTERFACE || ((kind == REF_INVOKE_STATIC || kind == REF_INVOKE_SPECIAL) && tokens.peek_is("interface"));
+    if is_interface && kind != REF_INVOKE_INTERFACE {
+        tokens.next("interface")?;
+    }
+    let member = tokens.next("a class, method name and descriptor")?;
+    let (class, name, _) = split_method(member)?;
+    let descriptor = &member.text[class.len() + 1 + name.len()..];
+    Ok(Handle { kind, class, name, descriptor, is_interface })
+}
+
+// { <constant> ... }, the arguments passed to a bootstrap method after the name and type
+fn bootstrap_arguments<'l>(tokens: &mut Tokens<'l>) -> Result<Vec<LoadableConstant<'l>>, AssemblyError> {
+    tokens.directive("{")?;
+    let mut arguments = vec![];
+    loop {
+        let token = tokens.next("a constant or }")?;
+        if token.text == "}" && !token.quoted {
+            return Ok(arguments);
+        }
+        arguments.push(loadable_constant(token, tokens, false)?);
+    }
+}
+
+// e.g. 61.0
+fn class_version(token: &Token) -> Result<(u16, u16), AssemblyError> {
+    let invalid = || token_error(token, format!("Expected a version, e.g. 61.0, found {:?}", token.text));
+    let (major, minor) = token.text.split_once('.').ok_or_else(invalid)?;
+    match (major.parse(), minor.parse()) {
+        (Ok(major), Ok(minor)) => Ok((major, minor)),
+        _ => Err(invalid()),
+    }
+}
+
+// splits java/io/PrintStream/println(Ljava/lang/String;)V into the class, method name and descriptor
+fn split_method(member: &Token) -> Result<(&str, &str, MethodDescriptor<'_>), AssemblyError> {
+    let paren = member.text.find('(').ok_or_else(|| token_error(member, "Expected a method descriptor after the method name".to_owned()))?;
+    let (class, name) = split_member(member, &member.text[..paren])?;
+    let descriptor = MethodDescriptor::parse(&member.text[paren..]).map_err(|e| token_error_at(member, paren + e.offset, e.to_string()))?;
+    Ok((class, name, descriptor))
+}
+
+// splits java/lang/System/out into the class and the member name
+fn split_member<'t>(token: &Token, member: &'t str) -> Result<(&'t str, &'t str), AssemblyError> {
+    match member.rfind('/') {
+        Some(slash) if slash > 0 && slash + 1 < member.len() => Ok((&member[..slash], &member[slash + 1..])),
+        _ => Err(token_error(token, "Expected a class name and member name separated by /".to_owned())),
+    }
+}
+
+const FLAGS: &[(&str, u16)] = &[
+    ("public", ACC_PUBLIC), ("private", ACC_PRIVATE), ("protected", ACC_PROTECTED), ("static", ACC_STATIC),
+    ("final", ACC_FINAL), ("super", ACC_SUPER), ("synchronized", ACC_SYNCHRONIZED), ("volatile", ACC_VOLATILE), ("bridge", ACC_BRIDGE),
+    ("transient", ACC_TRANSIENT), ("varargs", ACC_VARARGS), ("native", ACC_NATIVE), ("interface", ACC_INTERFACE),
+    ("abstract", ACC_ABSTRACT), ("strict", ACC_STRICT), ("synthetic", ACC_SYNTHETIC), ("annotation", ACC_ANNOTATION), ("enum", ACC_ENUM),
+];
+
+const SIMPLE_INSTRUCTIONS: &[Instruction] = &[
+    Instruction::Nop, Instruction::AconstNull, Instruction::IconstM1, Instruction::Iconst0, Instruction::Iconst1,
+    Instruction::Iconst2, Instruction::Iconst3, Instruction::Iconst4, Instruction::Iconst5, Instruction::Lconst0,
+    Instruction::Lconst1, Instruction::Fconst0, Instruction::Fconst1, Instruction::Fconst2, Instruction::Dconst0,
+    Instruction::Dconst1, Instruction::Iload0, Instruction::Iload1, Instruction::Iload2, Instruction::Iload3,
+    Instruction::Lload0, Instruction::Lload1, Instruction::Lload2, Instruction::Lload3, Instruction::Fload0,
+    Instruction::Fload1, Instruction::Fload2, Instruction::Fload3, Instruction::Dload0, Instruction::Dload1,
+    Instruction::Dload2, Instruction::Dload3, Instruction::Aload0, Instruction::Aload1, Instruction::Aload2,
+    Instruction::Aload3, Instruction::Iaload, Instruction::Laload, Instruction::Faload, Instruction::Daload,
+    Instruction::Aaload, Instruction::Baload, Instruction::Caload, Instruction::Saload, Instruction::Istore0,
+    Instruction::Istore1, Instruction::Istore2, Instruction::Istore3, Instruction::Lstore0, Instruction::Lstore1,
+    Instruction::Lstore2, Instruction::Lstore3, Instruction::Fstore0, Instruction::Fstore1, Instruction::Fstore2,
+    Instruction::Fstore3, Instruction::Dstore0, Instruction::Dstore1, Instruction::Dstore2, Instruction::Dstore3,
+    Instruction::Astore0, Instruction::Astore1, Instruction::Astore2, Instruction::Astore3, Instruction::Iastore,
+    Instruction::Lastore, Instruction::Fastore, Instruction::Dastore, Instruction::Aastore, Instruction::Bastore,
+    Instruction::Castore, Instruction::Sastore, Instruction::Pop, Instruction::Pop2, Instruction::Dup,
+    Instruction::DupX1, Instruction::DupX2, Instruction::Dup2, Instruction::Dup2X1, Instruction::Dup2X2,
+    Instruction::Swap, Instruction::Iadd, Instruction::Ladd, Instruction::Fadd, Instruction::Dadd, Instruction::Isub,
+    Instruction::Lsub, Instruction::Fsub, Instruction::Dsub, Instruction::Imul, Instruction::Lmul, Instruction::Fmul,
+    Instruction::Dmul, Instruction::Idiv, Instruction::Ldiv, Instruction::Fdiv, Instruction::Ddiv, Instruction::Irem,
+    Instruction::Lrem, Instruction::Frem, Instruction::Drem, Instruction::Ineg, Instruction::Lneg, Instruction::Fneg,
+    Instruction::Dneg, Instruction::Ishl, Instruction::Lshl, Instruction::Ishr, Instruction::Lshr, Instruction::Iushr,
+    Instruction::Lushr, Instruction::Iand, Instruction::Land, Instruction::Ior, Instruction::Lor, Instruction::Ixor,
+    Instruction::Lxor, Instruction::I2l, Instruction::I2f, Instruction::I2d, Instruction::L2i, Instruction::L2f,
+    Instruction::L2d, Instruction::F2i, Instruction::F2l, Instruction::F2d, Instruction::D2i, Instruction::D2l,
+    Instruction::D2f, Instruction::I2b, Instruction::I2c, Instruction::I2s, Instruction::Lcmp, Instruction::Fcmpl,
+    Instruction::Fcmpg, Instruction::Dcmpl, Instruction::Dcmpg, Instruction::Ireturn, Instruction::Lreturn,
+    Instruction::Freturn, Instruction::Dreturn, Instruction::Areturn, Instruction::Return, Instruction::ArrayLength,
+    Instruction::Athrow, Instruction::MonitorEnter, Instruction::MonitorExit,
+];
+
+type LocalVariableInstruction = (&'static str, fn(u8) -> Instruction, fn(u16) -> WideInstruction);
+
+const LOCAL_VARIABLE_INSTRUCTIONS: &[LocalVariableInstruction] = &[
+    ("iload", Instruction::Iload, WideInstruction::Iload),
+    ("lload", Instruction::Lload, WideInstruction::Lload),
+    ("fload", Instruction::Fload, WideInstruction::Fload),
+    ("dload", Instruction::Dload, WideInstruction::Dload),
+    ("aload", Instruction::Aload, WideInstruction::Aload),
+    ("istore", Instruction::Istore, WideInstruction::Istore),
+    ("lstore", Instruction::Lstore, WideInstruction::Lstore),
+    ("fstore", Instruction::Fstore, WideInstruction::Fstore),
+    ("dstore", Instruction::Dstore, WideInstruction::Dstore),
+    ("astore", Instruction::Astore, WideInstruction::Astore),
+];
+
+// The labels of a method by name. Jumps are remembered so that a label that's never placed can be reported where
+// it's first used.
+struct Labels {
+    labels: HashMap<String, Label>,
+    placed: HashSet<String>,
+    jumps: Vec<Token>,
+}
+
+impl Labels {
+    fn new() -> Labels {
+        Labels {
+            labels: HashMap::new(),
+            placed: HashSet::new(),
+            jumps: vec![],
+        }
+    }
+
+    fn get(&mut self, method: &mut MethodBuilder, name: &str) -> Label {
+        *self.labels.entry(name.to_owned()).or_insert_with(|| method.new_label())
+    }
+
+    fn jump(&mut self, method: &mut MethodBuilder, token: &Token) -> Label {
+        self.jumps.push(token.clone());
+        self.get(method, &token.text)
+    }
+
+    fn place(&mut self, method: &mut MethodBuilder, token: &Token) -> Result<(), AssemblyError> {
+        if !self.placed.insert(token.text.clone()) {
+            return Err(token_error(token, format!("Label {:?} is defined more than once", token.text)));
+        }
+        let label = self.get(method, &token.text);
+        method.label(label);
+        Ok(())
+    }
+
+    fn check(&self) -> Result<(), AssemblyError> {
+        match self.jumps.iter().find(|token| !self.placed.contains(&token.text)) {
+            Some(token) => Err(token_error(token, format!("Label {:?} is never defined", token.text))),
+            None => Ok(()),
+        }
+    }
+}
+
+// An int, long (with an L suffix), float (with an f suffix) or double (with a d suffix). Without a suffix, a whole
+// number is an int for ldc and a long for ldc2_w, and any other number is a float for ldc and a double for ldc2_w.
+fn number(token: &Token, wide: bool) -> Result<LoadableConstant<'static>, AssemblyError> {
+    let text = &token.text;
+    if text.ends_with(['L', 'l']) {
+        return long(token).map(LoadableConstant::Long);
+    }
+    if let Some(value) = parse_integer(text) {
+        if wide {
+            return Ok(LoadableConstant::Long(value));
+        }
+        return i32::try_from(value).map(LoadableConstant::Int).map_err(|_| token_error(token, format!("{} is out of range for an int", text)));
+    }
+    let (digits, wide) = match text.chars().last() {
+        Some('f') | Some('F') => (&text[..text.len() - 1], false),
+        Some('d') | Some('D') => (&text[..text.len() - 1], true),
+        _ => (text.as_ref(), wide),
+    };
+    let invalid = || token_error(token, format!("Expected a number, found {:?}", text));
+    if wide {
+        digits.parse::<f64>().map(LoadableConstant::Double).map_err(|_| invalid())
+    } else {
+        digits.parse::<f32>().map(LoadableConstant::Float).map_err(|_| invalid())
+    }
+}
+
+// decimal, or hexadecimal with a 0x prefix, with an optional sign
+fn parse_integer(text: &str) -> Option<i64> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text.strip_prefix('+').unwrap_or(text)),
+    };
+    let (radix, digits) = match digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        Some(digits) => (16, digits),
+        None => (10, digits),
+    };
+    if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+        return None;
+    }
+    let magnitude = i128::from_str_radix(digits, radix).ok()?;
+    i64::try_from(if negative { -magnitude } else { magnitude }).ok()
+}
+
+fn integer<T: TryFrom<i64>>(token: &Token, expected: &str) -> Result<T, AssemblyError> {
+    parse_integer(&token.text).and_then(|value| T::try_from(value).ok()).ok_or_else(|| token_error(token, format!("Expected {}, found {:?}", expected, token.text)))
+}
+
+// with or without an L suffix
+fn long(token: &Token) -> Result<i64, AssemblyError> {
+    let text = token.text.strip_suffix(['L', 'l']).unwrap_or(&token.text);
+    parse_integer(text).ok_or_else(|| token_error(token, format!("Expected a long, found {:?}", token.text)))
+}
+
+// with or without an f or d suffix
+fn float<T: std::str::FromStr>(token: &Token) -> Result<T, AssemblyError> {
+    let text = token.text.trim_end_matches(['f', 'F', 'd', 'D']);
+    text.parse().map_err(|_| token_error(token, format!("Expected a number, found {:?}", token.text)))
+}
+
+#[derive(Clone, Debug)]
+struct Token {
+    text: String, // with the quotes removed and escapes replaced, if it's quoted
+    quoted: bool,
+    line: usize,
+    column: usize,
+}
+
+#[derive(Debug)]
+struct Line {
+    number: usize,
+    end_column: usize, // just past the last character, for errors about missing tokens
+    tokens: Vec<Token>,
+}
+
+// Splits the source into tokens, leaving out blank lines and comments. A comment starts with a ; at the start of a
+// token (since descriptors contain semicolons) and runs to the end of the line. A colon at the end of a token is a
+// token of its own, so that `Loop:` and `default: Loop` both work.
+fn tokenize(source: &str) -> Result<Vec<Line>, AssemblyError> {
+    let mut lines = vec![];
+    for (i, text) in source.lines().enumerate() {
+        let number = i + 1;
+        let chars: Vec<char> = text.chars().collect();
+        let mut tokens = vec![];
+        let mut position = 0;
+        while position < chars.len() {
+            let start = position;
+            if chars[position].is_whitespace() {
+                position += 1;
+            } else if chars[position] == ';' {
+                break;
+            } else if chars[position] == '"' {
+                let mut string = String::new();
+                position += 1;
+                loop {
+                    match chars.get(position) {
+                        None => return Err(AssemblyError { line: number, column: start + 1, message: "Unterminated string".to_owned() }),
+                        Some('"') => break,
+                        Some('\\') => {
+                            let (c, length) = unescape(&chars[position + 1..]).ok_or_else(|| AssemblyError {
+                                line: number,
+                                column: position + 1,
+                                message: "Invalid escape sequence".to_owned(),
+                            })?;
+                            string.push(c);
+                            position += 1 + length;
+                        },
+                        Some(&c) => {
+                            string.push(c);
+                            position += 1;
+                        },
+                    }
+                }
+                position += 1;
+                tokens.push(Token { text: string, quoted: true, line: number, column: start + 1 });
+            } else {
+                while position < chars.len() && !chars[position].is_whitespace() {
+                    position += 1;
+                }
+                let word: String = chars[start..position].iter().collect();
+                match word.strip_suffix(':') {
+                    Some(label) if !label.is_empty() => {
+                        tokens.push(Token { text: label.to_owned(), quoted: false, line: number, column: start + 1 });
+                        tokens.push(Token { text: ":".to_owned(), quoted: false, line: number, column: position });
+                    },
+                    _ => tokens.push(Token { text: word, quoted: false, line: number, column: start + 1 }),
+                }
+            }
+        }
+        if !tokens.is_empty() {
+            lines.push(Line { number, end_column: chars.len() + 1, tokens });
+        }
+    }
+    Ok(lines)
+}
+
+// the character for the escape sequence following a backslash, and the number of characters it takes up
+fn unescape(chars: &[char]) -> Option<(char, usize)> {
+    match *chars.first()? {
+        'n' => Some(('\n', 1)),
+        't' => Some(('\t', 1)),
+        'r' => Some(('\r', 1)),
+        'b' => Some(('\u{8}', 1)),
+        'f' => Some(('\u{c}', 1)),
+        '0' => Some(('\0', 1)),
+        '"' => Some(('"', 1)),
+        '\'' => Some(('\'', 1)),
+        '\\' => Some(('\\', 1)),
+        'u' => {
+            // exactly four hex digits, since from_str_radix would also take a sign
+            let hex = chars.get(1..5)?;
+            if !hex.iter().all(|c| c.is_ascii_hexdigit()) {
+                return None;
+            }
+            let code = u32::from_str_radix(&hex.iter().collect::<String>(), 16).ok()?;
+            Some((std::char::from_u32(code)?, 5))
+        },
+        _ => None,
+    }
+}
+
+// the remaining lines of the source
+struct Source {
+    lines: vec::IntoIter<Line>,
+    last_line: usize,
+    last_column: usize,
+}
+
+impl Source {
+    fn new(lines: Vec<Line>) -> Source {
+        Source {
+            lines: lines.into_iter(),
+            last_line: 1,
+            last_column: 1,
+        }
+    }
+
+    // the directive the next line starts with, if it does
+    fn peek_directive(&self) -> Option<&str> {
+        let token = &self.lines.as_slice().first()?.tokens[0];
+        if token.quoted || !token.text.starts_with('.') {
+            return None;
+        }
+        Some(&token.text)
+    }
+
+    fn next_line(&mut self) -> Option<Line> {
+        let line = self.lines.next()?;
+        self.last_line = line.number;
+        self.last_column = line.end_column;
+        Some(line)
+    }
+
+    // for when the source ends too soon
+    fn end_error(&self, message: &str) -> AssemblyError {
+        AssemblyError { line: self.last_line, column: self.last_column, message: message.to_owned() }
+    }
+}
+
+// the tokens of a line, read from left to right
+struct Tokens<'l> {
+    line: &'l Line,
+    position: usize,
+}
+
+impl<'l> Tokens<'l> {
+    fn new(line: &'l Line) -> Tokens<'l> {
+        Tokens { line, position: 0 }
+    }
+
+    fn peek(&self) -> Option<&'l Token> {
+        self.line.tokens.get(self.position)
+    }
+
+    fn peek_is(&self, text: &str) -> bool {
+        self.peek().is_some_and(|token| !token.quoted && token.text == text)
+    }
+
+    fn next(&mut self, expected: &str) -> Result<&'l Token, AssemblyError> {
+        match self.peek() {
+            Some(token) => {
+                self.position += 1;
+                Ok(token)
+            },
+            None => Err(AssemblyError { line: self.line.number, column: self.line.end_column, message: format!("Expected {}", expected) }),
+        }
+    }
+
+    // a particular keyword, e.g. the `from` in a .catch directive
+    fn directive(&mut self, keyword: &str) -> Result<(), AssemblyError> {
+        let token = self.next(keyword)?;
+        if token.quoted || token.text != keyword {
+            return Err(token_error(token, format!("Expected {}, found {:?}", keyword, token.text)));
+        }
+        Ok(())
+    }
+
+    fn string(&mut self, expected: &str) -> Result<&'l Token, AssemblyError> {
+        let token = self.next(expected)?;
+        if !token.quoted {
+            return Err(token_error(token, format!("Expected {}, found {:?}", expected, token.text)));
+        }
+        Ok(token)
+    }
+
+    // any number of access flags, e.g. public static
+    fn flags(&mut self) -> u16 {
+        let mut access_flags = 0;
+        while let Some(&(_, flag)) = self.peek().and_then(|token| FLAGS.iter().find(|&&(name, _)| !token.quoted && name == token.text)) {
+            access_flags |= flag;
+            self.position += 1;
+        }
+        access_flags
+    }
+
+    fn end(&self) -> Result<(), AssemblyError> {
+        match self.peek() {
+            Some(token) => Err(token_error(token, format!("Unexpected {:?}", token.text))),
+            None => Ok(()),
+        }
+    }
+}
+
+fn token_error(token: &Token, message: String) -> AssemblyError {
+    AssemblyError { line: token.line, column: token.column, message }
+}
+
+// an error at a byte offset into the token's text, e.g. from a descriptor or signature parser
+fn token_error_at(token: &Token, offset: usize, message: String) -> AssemblyError {
+    let quote = if token.quoted { 1 } else { 0 };
+    let offset = token.text.get(..offset).map_or(offset, |prefix| prefix.chars().count());
+    AssemblyError { line: token.line, column: token.column + quote + offset, message }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the line and column of the error from assembling a main method with the given body
+    fn error_position(body: &str) -> (usize, usize) {
+        let source = format!(".class public Test\n.super java/lang/Object\n.method public static main([Ljava/lang/String;)V\n{}\n.end method\n", body);
+        let error = assemble(&source).unwrap_err();
+        (error.line, error.column)
+    }
+
+    #[test]
+    fn assembles_hello_world() {
+        let source = "
+            .class public hello
+            .super java/lang/Object
+
+            .method public static main([Ljava/lang/String;)V
+                getstatic java/lang/System/out Ljava/io/PrintStream;
+                ldc \"Hello, World!\" ; a comment
+                invokevirtual java/io/PrintStream/println(Ljava/lang/String;)V
+                return
+            .end method
+        ";
+        let classfile = assemble(source).unwrap();
+        assert_eq!(classfile.constant_pool.get_class_name(classfile.this_class), Some("hello"));
+        assert_eq!(classfile.methods.len(), 1);
+    }
+
+    #[test]
+    fn reports_unknown_instructions_at_the_mnemonic() {
+        assert_eq!(error_position("    nop\n    frobnicate 1"), (5, 5));
+    }
+
+    #[test]
+    fn reports_missing_operands_at_the_end_of_the_line() {
+        assert_eq!(error_position("    bipush"), (4, 11));
+    }
+
+    #[test]
+    fn reports_descriptor_errors_within_the_token() {
+        // the V can't be an argument type
+        assert_eq!(error_position("    invokestatic Foo/bar(IV)V"), (4, 27));
+        // columns count characters rather than bytes, and skip the opening quote, so this is the > after the é
+        assert_eq!(error_position("    .signature \"<é>()V\""), (4, 19));
+    }
+
+    #[test]
+    fn reports_undefined_labels_where_they_are_first_used() {
+        assert_eq!(error_position("    goto Missing\n    goto Missing\n    return"), (4, 10));
+    }
+
+    #[test]
+    fn reports_duplicate_labels_at_the_second_definition() {
+        assert_eq!(error_position("Here:\n    nop\nHere:\n    return"), (6, 1));
+    }
+
+    #[test]
+    fn reports_a_missing_end_at_the_end_of_the_source() {
+        let error = assemble(".class public Test\n.super java/lang/Object\n.method public static main([Ljava/lang/String;)V\n    return\n").unwrap_err();
+        assert_eq!((error.line, error.column), (4, 11));
+    }
+
+    #[test]
+    fn reports_bad_strings_at_the_quote_or_escape() {
+        assert_eq!(error_position("    ldc \"open"), (4, 9));
+        assert_eq!(error_position("    ldc \"a\\u+123\""), (4, 11));
+    }
+
+    #[test]
+    fn reports_table_switch_keys_that_overflow_at_the_low_key() {
+        assert_eq!(error_position("    iconst_0\n    tableswitch 2147483647\n        A\n        A\n        default : A\nA:\n    return"), (5, 17));
+    }
+
+    #[test]
+    fn reports_zero_dimensions_at_the_count() {
+        assert_eq!(error_position("    iconst_1\n    multianewarray [[I 0\n    pop\n    return"), (5, 24));
+    }
+}
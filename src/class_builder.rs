@@ -1,79 +1,675 @@
 use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::mem;
 
 use classfile::*;
+use constant_pool::*;
+use errors::*;
 use java_type_signatures::*;
+use source_map::*;
+use stack_map_frames::*;
 
 pub const ACC_PUBLIC: u16 = 0x1;
+pub const ACC_PRIVATE: u16 = 0x2;
+pub const ACC_PROTECTED: u16 = 0x4;
 pub const ACC_STATIC: u16 = 0x8;
+pub const ACC_FINAL: u16 = 0x10;
+pub const ACC_SUPER: u16 = 0x20;
+pub const ACC_SYNCHRONIZED: u16 = 0x20;
+pub const ACC_VOLATILE: u16 = 0x40;
+pub const ACC_BRIDGE: u16 = 0x40;
+pub const ACC_TRANSIENT: u16 = 0x80;
+pub const ACC_VARARGS: u16 = 0x80;
+pub const ACC_INTERFACE: u16 = 0x200;
+pub const ACC_NATIVE: u16 = 0x100;
+pub const ACC_ABSTRACT: u16 = 0x400;
+pub const ACC_STRICT: u16 = 0x800;
+pub const ACC_SYNTHETIC: u16 = 0x1000;
+pub const ACC_ANNOTATION: u16 = 0x2000;
+pub const ACC_ENUM: u16 = 0x4000;
+pub const ACC_MODULE: u16 = 0x8000;
 
 pub struct ClassBuilder {
     access_flags: u16,
     this_class_index: u16,
     super_class_index: u16,
-    constants: Vec<Constant>,
+    constants: ConstantPool,
+    interfaces: Vec<u16>,
+    fields: Vec<Field>,
     methods: Vec<Method>,
+    bootstrap_methods: Vec<BootstrapMethod>,
+    inner_classes: Vec<InnerClass>,
+    nest_members: Vec<u16>,
+    annotations: Annotations,
+    attributes: Vec<Attribute>,
+    version: Option<(u16, u16)>, // major and minor, if they're not left to done()
+    common_superclass: Box<CommonSuperclass>,
+}
+
+// A field or method, as referred to by a method handle (e.g. the bootstrap method of a dynamic constant)
+#[derive(Clone, Debug, PartialEq)]
+pub struct Handle<'a> {
+    pub kind: u8, // one of the REF_* constants
+    pub class: &'a str,
+    pub name: &'a str,
+    pub descriptor: &'a str,
+    pub is_interface: bool,
+}
+
+// A value that can be pushed onto the stack with ldc, ldc_w or ldc2_w
+#[derive(Clone, Debug, PartialEq)]
+pub enum LoadableConstant<'a> {
+    Int(i32),
+    Float(f32),
+    Long(i64),
+    Double(f64),
+    String(&'a str),
+    Class(&'a str),      // internal class name, or array descriptor
+    MethodType(&'a str), // method descriptor
+    MethodHandle(Handle<'a>),
+    Dynamic(&'a str, &'a str, Handle<'a>, Vec<LoadableConstant<'a>>), // name, field descriptor, bootstrap method, bootstrap arguments
+}
+
+impl<'a> LoadableConstant<'a> {
+    // Long and Double values (including dynamic ones) need ldc2_w
+    pub fn is_wide(&self) -> bool {
+        match *self {
+            LoadableConstant::Long(_) | LoadableConstant::Double(_) => true,
+            LoadableConstant::Dynamic(_, descriptor, _, _) => descriptor == "J" || descriptor == "D",
+            _ => false,
+        }
+    }
+}
+
+impl<'a> From<i32> for LoadableConstant<'a> {
+    fn from(value: i32) -> LoadableConstant<'a> {
+        LoadableConstant::Int(value)
+    }
+}
+
+impl<'a> From<f32> for LoadableConstant<'a> {
+    fn from(value: f32) -> LoadableConstant<'a> {
+        LoadableConstant::Float(value)
+    }
+}
+
+impl<'a> From<i64> for LoadableConstant<'a> {
+    fn from(value: i64) -> LoadableConstant<'a> {
+        LoadableConstant::Long(value)
+    }
+}
+
+impl<'a> From<f64> for LoadableConstant<'a> {
+    fn from(value: f64) -> LoadableConstant<'a> {
+        LoadableConstant::Double(value)
+    }
+}
+
+impl<'a> From<&'a str> for LoadableConstant<'a> {
+    fn from(value: &'a str) -> LoadableConstant<'a> {
+        LoadableConstant::String(value)
+    }
+}
+
+impl<'a> From<Handle<'a>> for LoadableConstant<'a> {
+    fn from(handle: Handle<'a>) -> LoadableConstant<'a> {
+        LoadableConstant::MethodHandle(handle)
+    }
+}
+
+// Whether an annotation can be read with reflection at runtime (RUNTIME retention in Java), or is only kept in the
+// class file for tools that read it (CLASS retention)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Retention {
+    Runtime,
+    Class,
+}
+
+// An annotation to attach with ClassBuilder::annotation and friends. Elements that are left out get the defaults
+// declared by the annotation type.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AnnotationSpec<'a> {
+    pub annotation_type: &'a str, // internal class name, e.g. org/junit/jupiter/api/Test
+    pub elements: Vec<(&'a str, AnnotationValue<'a>)>,
+}
+
+impl<'a> AnnotationSpec<'a> {
+    // an annotation without any element values, e.g. @Test or @Inject
+    pub fn marker(annotation_type: &'a str) -> AnnotationSpec<'a> {
+        AnnotationSpec { annotation_type, elements: vec![] }
+    }
+}
+
+// The value of an annotation element, which has to be a constant, an enum constant, a class, another annotation or
+// an array of these
+#[derive(Clone, Debug, PartialEq)]
+pub enum AnnotationValue<'a> {
+    Byte(i8),
+    Char(char), // in the Basic Multilingual Plane, since a Java char is a single UTF-16 code unit
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    Boolean(bool),
+    String(&'a str),
+    Enum(&'a str, &'a str), // the enum's internal class name, and the name of the constant
+    Class(Java<'a>),        // e.g. String.class, or Java::Void for void.class
+    Annotation(AnnotationSpec<'a>),
+    Array(Vec<AnnotationValue<'a>>),
+}
+
+impl<'a> From<i32> for AnnotationValue<'a> {
+    fn from(value: i32) -> AnnotationValue<'a> {
+        AnnotationValue::Int(value)
+    }
+}
+
+impl<'a> From<i64> for AnnotationValue<'a> {
+    fn from(value: i64) -> AnnotationValue<'a> {
+        AnnotationValue::Long(value)
+    }
+}
+
+impl<'a> From<f32> for AnnotationValue<'a> {
+    fn from(value: f32) -> AnnotationValue<'a> {
+        AnnotationValue::Float(value)
+    }
+}
+
+impl<'a> From<f64> for AnnotationValue<'a> {
+    fn from(value: f64) -> AnnotationValue<'a> {
+        AnnotationValue::Double(value)
+    }
+}
+
+impl<'a> From<bool> for AnnotationValue<'a> {
+    fn from(value: bool) -> AnnotationValue<'a> {
+        AnnotationValue::Boolean(value)
+    }
+}
+
+impl<'a> From<char> for AnnotationValue<'a> {
+    fn from(value: char) -> AnnotationValue<'a> {
+        AnnotationValue::Char(value)
+    }
+}
+
+impl<'a> From<&'a str> for AnnotationValue<'a> {
+    fn from(value: &'a str) -> AnnotationValue<'a> {
+        AnnotationValue::String(value)
+    }
+}
+
+impl<'a> From<AnnotationSpec<'a>> for AnnotationValue<'a> {
+    fn from(annotation: AnnotationSpec<'a>) -> AnnotationValue<'a> {
+        AnnotationValue::Annotation(annotation)
+    }
+}
+
+// annotations waiting to be written as RuntimeVisibleAnnotations and RuntimeInvisibleAnnotations attributes
+#[derive(Default)]
+struct Annotations {
+    visible: Vec<Annotation>,
+    invisible: Vec<Annotation>,
+}
+
+impl Annotations {
+    fn add(&mut self, annotation: Annotation, retention: Retention) {
+        match retention {
+            Retention::Runtime => self.visible.push(annotation),
+            Retention::Class => self.invisible.push(annotation),
+        }
+    }
 }
 
 impl ClassBuilder {
     pub fn new(access_flags: u16, this_class: &str, super_class: &str) -> ClassBuilder {
         let mut builder = ClassBuilder {
-            access_flags: access_flags,
+            access_flags,
             this_class_index: 0,
             super_class_index: 0,
-            constants: vec![],
+            constants: ConstantPool::new(),
+            interfaces: vec![],
+            fields: vec![],
             methods: vec![],
+            bootstrap_methods: vec![],
+            inner_classes: vec![],
+            nest_members: vec![],
+            annotations: Annotations::default(),
+            attributes: vec![],
+            version: None,
+            common_superclass: Box::new(|_, _| "java/lang/Object".to_owned()),
         };
         builder.this_class_index = builder.define_class(this_class);
         builder.super_class_index = builder.define_class(super_class);
         builder
     }
 
-    pub fn define_method(&mut self, access_flags: u16, name: &str, argument_types: &[Java], return_type: &Java) -> MethodBuilder {
+    pub fn add_interface(&mut self, interface: &str) {
+        let class_index = self.define_class(interface);
+        self.interfaces.push(class_index);
+    }
+
+    pub fn define_field(&mut self, access_flags: u16, name: &str, field_type: &Java) -> FieldBuilder<'_> {
+        FieldBuilder::new(self, access_flags, name, field_type)
+    }
+
+    pub fn define_method(&mut self, access_flags: u16, name: &str, argument_types: &[Java], return_type: &Java) -> MethodBuilder<'_> {
         MethodBuilder::new(self, access_flags, name, argument_types, return_type)
     }
 
-    fn push_constant(&mut self, constant: Constant) -> u16 {
-        // TODO check if this constant is exactly equal to anything already defined in constants. If so, return the existing index instead of re-defining it.
-        self.constants.push(constant);
-        self.constants.len() as u16
+    // abstract methods (including interface methods) have no Code attribute, so there's no MethodBuilder involved
+    pub fn define_abstract_method(&mut self, access_flags: u16, name: &str, argument_types: &[Java], return_type: &Java) {
+        self.define_abstract_method_with_attributes(access_flags, name, argument_types, return_type, vec![]);
+    }
+
+    // an abstract method with generic types, e.g. <T:Ljava/lang/Object;>(TT;)TT; for `<T> T identity(T value)`
+    pub fn define_generic_abstract_method(&mut self, access_flags: u16, name: &str, argument_types: &[Java], return_type: &Java, signature: &MethodSignature) {
+        let attribute = self.signature_attribute(&signature.to_string());
+        self.define_abstract_method_with_attributes(access_flags, name, argument_types, return_type, vec![attribute]);
+    }
+
+    fn define_abstract_method_with_attributes(&mut self, access_flags: u16, name: &str, argument_types: &[Java], return_type: &Java, attributes: Vec<Attribute>) {
+        let name_index = self.define_utf8(name);
+        let descriptor = method_signature(argument_types, return_type);
+        let descriptor_index = self.define_utf8(&descriptor);
+        let method = Method::new(access_flags | ACC_ABSTRACT, name_index, descriptor_index, attributes);
+        self.methods.push(method);
+    }
+
+    // Records the generic superclass and interfaces of the class, and its type parameters. The superclass and
+    // interfaces given to define_class and add_interface should be their erasures.
+    pub fn signature(&mut self, signature: &ClassSignature) {
+        let attribute = self.signature_attribute(&signature.to_string());
+        self.attributes.push(attribute);
+    }
+
+    // Names the source file the class was compiled from, which stack traces show alongside line numbers. Calling it
+    // again replaces the name, as a class can only have one.
+    pub fn source_file(&mut self, source_file: &str) {
+        let name_index = self.define_utf8("SourceFile");
+        let source_file_index = self.define_utf8(source_file);
+        self.replace_attribute(Attribute::SourceFile(name_index, source_file_index));
+    }
+
+    // Maps the class's line numbers back to the files it was generated from, for debuggers.
+    pub fn source_map(&mut self, source_map: &SourceMap) {
+        self.source_debug_extension(&source_map.to_string());
+    }
+
+    // Adds a SourceDebugExtension attribute with any text, which the JVM ignores. Calling it again replaces the text,
+    // as a class can only have one.
+    pub fn source_debug_extension(&mut self, debug_extension: &str) {
+        let name_index = self.define_utf8("SourceDebugExtension");
+        self.replace_attribute(Attribute::SourceDebugExtension(name_index, debug_extension.to_owned()));
+    }
+
+    // adds an attribute that there can only be one of, in place of any earlier one
+    fn replace_attribute(&mut self, attribute: Attribute) {
+        match self.attributes.iter_mut().find(|existing| mem::discriminant(*existing) == mem::discriminant(&attribute)) {
+            Some(existing) => *existing = attribute,
+            None => self.attributes.push(attribute),
+        }
+    }
+
+    // Annotates the class, e.g. with @Deprecated or a framework's @Component.
+    pub fn annotation(&mut self, annotation: &AnnotationSpec, retention: Retention) {
+        let annotation = self.define_annotation(annotation);
+        self.annotations.add(annotation, retention);
+    }
+
+    // The class file version, e.g. 61.0 for Java 17. Without it, the class is Java 8's 52.0, or 55.0 if it uses
+    // something that needs Java 11.
+    pub fn version(&mut self, major_version: u16, minor_version: u16) {
+        self.version = Some((major_version, minor_version));
+    }
+
+    // Finds the closest common superclass of two classes, which is the type a local variable or stack entry gets in
+    // a stack map frame where two paths through a method meet with different classes in it, like ASM's
+    // ClassWriter.getCommonSuperClass. The builder can't load classes to find it, so by default they merge to
+    // java/lang/Object, which only verifies if the value is then used as an Object.
+    pub fn common_superclass<F: Fn(&str, &str) -> String + 'static>(&mut self, common_superclass: F) {
+        self.common_superclass = Box::new(common_superclass);
+    }
+
+    pub fn deprecated(&mut self) {
+        let name_index = self.define_utf8("Deprecated");
+        self.attributes.push(Attribute::Deprecated(name_index));
+    }
+
+    pub fn synthetic(&mut self) {
+        let name_index = self.define_utf8("Synthetic");
+        self.attributes.push(Attribute::Synthetic(name_index));
+    }
+
+    // Records a nested class as the compiler saw it, in the InnerClasses attribute that both the outer and the inner
+    // class need. Local and anonymous classes have no outer class, and anonymous classes have no name.
+    pub fn inner_class(&mut self, access_flags: u16, inner_class: &str, outer_class: Option<&str>, inner_name: Option<&str>) {
+        let inner_class = InnerClass {
+            inner_class_info_index: self.define_class(inner_class),
+            outer_class_info_index: outer_class.map_or(0, |class| self.define_class(class)),
+            inner_name_index: inner_name.map_or(0, |name| self.define_utf8(name)),
+            inner_class_access_flags: access_flags,
+        };
+        self.inner_classes.push(inner_class);
+    }
+
+    // Marks a local or anonymous class as defined outside any method, e.g. in a field initializer
+    pub fn enclosing_class(&mut self, class: &str) {
+        let name_index = self.define_utf8("EnclosingMethod");
+        let class_index = self.define_class(class);
+        self.attributes.push(Attribute::EnclosingMethod(name_index, class_index, 0));
+    }
+
+    // Marks a local or anonymous class as defined in the given method
+    pub fn enclosing_method(&mut self, class: &str, name: &str, argument_types: &[Java], return_type: &Java) {
+        let name_index = self.define_utf8("EnclosingMethod");
+        let class_index = self.define_class(class);
+        let descriptor = method_signature(argument_types, return_type);
+        let method_index = self.constants.add_name_and_type(name, &descriptor);
+        self.attributes.push(Attribute::EnclosingMethod(name_index, class_index, method_index));
+    }
+
+    // The class whose nest this class belongs to, which lets them use each other's private members (Java 11 on)
+    pub fn nest_host(&mut self, class: &str) {
+        let name_index = self.define_utf8("NestHost");
+        let class_index = self.define_class(class);
+        self.attributes.push(Attribute::NestHost(name_index, class_index));
+    }
+
+    // A class in this class's nest, which has to name this class with nest_host
+    pub fn nest_member(&mut self, class: &str) {
+        let class_index = self.define_class(class);
+        self.nest_members.push(class_index);
+    }
+
+    fn signature_attribute(&mut self, signature: &str) -> Attribute {
+        let name_index = self.define_utf8("Signature");
+        let signature_index = self.define_utf8(signature);
+        Attribute::Signature(name_index, signature_index)
     }
 
     fn define_utf8(&mut self, string: &str) -> u16 {
-        self.push_constant(Constant::Utf8(string.to_owned()))
+        self.constants.add_utf8(string)
+    }
+
+    fn define_integer(&mut self, value: i32) -> u16 {
+        self.constants.add_integer(value)
+    }
+
+    fn define_float(&mut self, value: f32) -> u16 {
+        self.constants.add_float(value)
+    }
+
+    fn define_long(&mut self, value: i64) -> u16 {
+        self.constants.add_long(value)
+    }
+
+    fn define_double(&mut self, value: f64) -> u16 {
+        self.constants.add_double(value)
     }
 
     fn define_class(&mut self, class: &str) -> u16 {
-        let name_index = self.define_utf8(class);
-        self.push_constant(Constant::Class(name_index))
+        self.constants.add_class(class)
     }
 
     fn define_string(&mut self, value: &str) -> u16 {
-        let string_index = self.define_utf8(value);
-        self.push_constant(Constant::String(string_index))
+        self.constants.add_string(value)
     }
 
     fn define_fieldref(&mut self, class: &str, name: &str, field_type: &Java) -> u16 {
-        let class_index = self.define_class(class);
         let descriptor = format!("{}", field_type);
-        let name_and_type_index = self.define_name_and_type(name, &descriptor);
-        self.push_constant(Constant::Fieldref(class_index, name_and_type_index))
+        self.constants.add_fieldref(class, name, &descriptor)
+    }
+
+    fn define_own_fieldref(&mut self, name: &str, field_type: &Java) -> u16 {
+        let class_index = self.this_class_index;
+        let descriptor = format!("{}", field_type);
+        let name_and_type_index = self.constants.add_name_and_type(name, &descriptor);
+        self.constants.add(Constant::Fieldref(class_index, name_and_type_index))
     }
 
     fn define_methodref(&mut self, class: &str, name: &str, argument_types: &[Java], return_type: &Java) -> u16 {
-        let class_index = self.define_class(class);
         let descriptor = method_signature(argument_types, return_type);
-        let name_and_type_index = self.define_name_and_type(name, &descriptor);
-        self.push_constant(Constant::Methodref(class_index, name_and_type_index))
+        self.constants.add_methodref(class, name, &descriptor)
     }
 
-    fn define_name_and_type(&mut self, name: &str, descriptor: &str) -> u16 {
-        let name_index = self.define_utf8(name);
-        let descriptor_index = self.define_utf8(&descriptor);
-        self.push_constant(Constant::NameAndType(name_index, descriptor_index))
+    fn define_method_handle(&mut self, handle: &Handle) -> u16 {
+        let reference_index = if handle.kind <= REF_PUT_STATIC {
+            self.constants.add_fieldref(handle.class, handle.name, handle.descriptor)
+        } else if handle.is_interface {
+            self.constants.add_interface_methodref(handle.class, handle.name, handle.descriptor)
+        } else {
+            self.constants.add_methodref(handle.class, handle.name, handle.descriptor)
+        };
+        self.constants.add(Constant::MethodHandle(handle.kind, reference_index))
+    }
+
+    fn define_loadable_constant(&mut self, constant: &LoadableConstant) -> u16 {
+        match *constant {
+            LoadableConstant::Int(value) => self.define_integer(value),
+            LoadableConstant::Float(value) => self.define_float(value),
+            LoadableConstant::Long(value) => self.define_long(value),
+            LoadableConstant::Double(value) => self.define_double(value),
+            LoadableConstant::String(value) => self.define_string(value),
+            LoadableConstant::Class(class) => self.define_class(class),
+            LoadableConstant::MethodType(descriptor) => {
+                let descriptor_index = self.define_utf8(descriptor);
+                self.constants.add(Constant::MethodType(descriptor_index))
+            },
+            LoadableConstant::MethodHandle(ref handle) => self.define_method_handle(handle),
+            LoadableConstant::Dynamic(name, descriptor, ref bootstrap_method, ref bootstrap_arguments) => {
+                let bootstrap_method_index = self.define_bootstrap_method(bootstrap_method, bootstrap_arguments);
+                let name_and_type_index = self.constants.add_name_and_type(name, descriptor);
+                self.constants.add(Constant::Dynamic(bootstrap_method_index, name_and_type_index))
+            },
+        }
+    }
+
+    fn define_annotation(&mut self, annotation: &AnnotationSpec) -> Annotation {
+        let type_index = self.define_utf8(&format!("L{};", annotation.annotation_type));
+        let element_value_pairs = annotation.elements.iter().map(|(name, value)| ElementValuePair {
+            element_name_index: self.define_utf8(name),
+            value: self.define_element_value(value),
+        }).collect();
+        Annotation { type_index, element_value_pairs }
+    }
+
+    fn define_element_value(&mut self, value: &AnnotationValue) -> ElementValue {
+        match *value {
+            AnnotationValue::Byte(value) => ElementValue::Byte(self.define_integer(value as i32)),
+            AnnotationValue::Char(value) => ElementValue::Char(self.define_integer(value as i32)),
+            AnnotationValue::Short(value) => ElementValue::Short(self.define_integer(value as i32)),
+            AnnotationValue::Int(value) => ElementValue::Int(self.define_integer(value)),
+            AnnotationValue::Long(value) => ElementValue::Long(self.define_long(value)),
+            AnnotationValue::Float(value) => ElementValue::Float(self.define_float(value)),
+            AnnotationValue::Double(value) => ElementValue::Double(self.define_double(value)),
+            AnnotationValue::Boolean(value) => ElementValue::Boolean(self.define_integer(value as i32)),
+            AnnotationValue::String(value) => ElementValue::String(self.define_utf8(value)),
+            AnnotationValue::Enum(class, name) => ElementValue::Enum(self.define_utf8(&format!("L{};", class)), self.define_utf8(name)),
+            AnnotationValue::Class(ref class) => ElementValue::Class(self.define_utf8(&class.to_string())),
+            AnnotationValue::Annotation(ref annotation) => ElementValue::Annotation(self.define_annotation(annotation)),
+            AnnotationValue::Array(ref values) => ElementValue::Array(values.iter().map(|value| self.define_element_value(value)).collect()),
+        }
+    }
+
+    // the RuntimeVisibleAnnotations and RuntimeInvisibleAnnotations attributes, for whichever there are annotations
+    fn annotation_attributes(&mut self, annotations: Annotations) -> Vec<Attribute> {
+        let mut attributes = vec![];
+        if !annotations.visible.is_empty() {
+            let name_index = self.define_utf8("RuntimeVisibleAnnotations");
+            attributes.push(Attribute::RuntimeVisibleAnnotations(name_index, annotations.visible));
+        }
+        if !annotations.invisible.is_empty() {
+            let name_index = self.define_utf8("RuntimeInvisibleAnnotations");
+            attributes.push(Attribute::RuntimeInvisibleAnnotations(name_index, annotations.invisible));
+        }
+        attributes
+    }
+
+    // the same for parameter annotations, which have an entry for every parameter if any of them is annotated
+    fn parameter_annotation_attributes(&mut self, parameters: Vec<Annotations>) -> Vec<Attribute> {
+        let (visible, invisible): (Vec<_>, Vec<_>) = parameters.into_iter().map(|annotations| (annotations.visible, annotations.invisible)).unzip();
+        let mut attributes = vec![];
+        if visible.iter().any(|annotations| !annotations.is_empty()) {
+            let name_index = self.define_utf8("RuntimeVisibleParameterAnnotations");
+            attributes.push(Attribute::RuntimeVisibleParameterAnnotations(name_index, visible));
+        }
+        if invisible.iter().any(|annotations| !annotations.is_empty()) {
+            let name_index = self.define_utf8("RuntimeInvisibleParameterAnnotations");
+            attributes.push(Attribute::RuntimeInvisibleParameterAnnotations(name_index, invisible));
+        }
+        attributes
+    }
+
+    // returns the index into the BootstrapMethods attribute, reusing an identical entry if there is one
+    fn define_bootstrap_method(&mut self, handle: &Handle, arguments: &[LoadableConstant]) -> u16 {
+        let bootstrap_method = BootstrapMethod {
+            bootstrap_method_ref: self.define_method_handle(handle),
+            bootstrap_arguments: arguments.iter().map(|argument| self.define_loadable_constant(argument)).collect(),
+        };
+        match self.bootstrap_methods.iter().position(|existing| *existing == bootstrap_method) {
+            Some(index) => index as u16,
+            None => {
+                self.bootstrap_methods.push(bootstrap_method);
+                self.bootstrap_methods.len() as u16 - 1
+            },
+        }
+    }
+
+    pub fn done(mut self) -> Result<Classfile, BuildError> {
+        let mut attributes = mem::take(&mut self.attributes);
+        let annotations = mem::take(&mut self.annotations);
+        attributes.extend(self.annotation_attributes(annotations));
+        let nest_members = mem::take(&mut self.nest_members);
+        if !nest_members.is_empty() {
+            let name_index = self.define_utf8("NestMembers");
+            attributes.push(Attribute::NestMembers(name_index, nest_members));
+        }
+        let inner_classes = mem::take(&mut self.inner_classes);
+        if !inner_classes.is_empty() {
+            let name_index = self.define_utf8("InnerClasses");
+            attributes.push(Attribute::InnerClasses(name_index, inner_classes));
+        }
+        if !self.bootstrap_methods.is_empty() {
+            let name_index = self.define_utf8("BootstrapMethods");
+            attributes.push(Attribute::BootstrapMethods(name_index, self.bootstrap_methods));
+        }
+
+        if self.constants.has_overflowed() {
+            return Err(BuildError { method: String::new(), cause: BuildErrorCause::ConstantPoolFull });
+        }
+
+        // dynamically-computed constants and nests need Java 11 or later
+        let uses_dynamic_constants = self.constants.iter().any(|(_, constant)| matches!(*constant, Constant::Dynamic(_, _)));
+        let uses_nests = attributes.iter().any(|attribute| matches!(*attribute, Attribute::NestHost(_, _) | Attribute::NestMembers(_, _)));
+
+        let mut classfile = Classfile::new(self.constants, self.access_flags, self.this_class_index, self.super_class_index, self.interfaces, self.fields, self.methods);
+        classfile.attributes = attributes;
+        if let Some((major_version, minor_version)) = self.version {
+            classfile.major_version = major_version;
+            classfile.minor_version = minor_version;
+        }
+        if uses_dynamic_constants || uses_nests {
+            classfile.major_version = classfile.major_version.max(55);
+        }
+        Ok(classfile)
+    }
+}
+
+pub struct FieldBuilder<'a> {
+    classfile: &'a mut ClassBuilder,
+    access_flags: u16,
+    name_index: u16,
+    descriptor_index: u16,
+    annotations: Annotations,
+    attributes: Vec<Attribute>,
+    fieldref_index: u16,
+}
+
+// A handle to a field defined on the class being built, for use with MethodBuilder::get_static_ref and friends
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FieldRef {
+    fieldref_index: u16,
+}
+
+impl<'a> FieldBuilder<'a> {
+    fn new(classfile: &'a mut ClassBuilder, access_flags: u16, name: &str, field_type: &Java) -> FieldBuilder<'a> {
+        let name_index = classfile.define_utf8(name);
+        let descriptor = format!("{}", field_type);
+        let descriptor_index = classfile.define_utf8(&descriptor);
+        let fieldref_index = classfile.define_own_fieldref(name, field_type);
+        FieldBuilder {
+            classfile,
+            access_flags,
+            name_index,
+            descriptor_index,
+            annotations: Annotations::default(),
+            attributes: vec![],
+            fieldref_index,
+        }
+    }
+
+    pub fn constant_int(&mut self, value: i32) {
+        let index = self.classfile.define_integer(value);
+        self.constant_value(index);
+    }
+
+    pub fn constant_float(&mut self, value: f32) {
+        let index = self.classfile.define_float(value);
+        self.constant_value(index);
+    }
+
+    pub fn constant_long(&mut self, value: i64) {
+        let index = self.classfile.define_long(value);
+        self.constant_value(index);
+    }
+
+    pub fn constant_double(&mut self, value: f64) {
+        let index = self.classfile.define_double(value);
+        self.constant_value(index);
+    }
+
+    pub fn constant_string(&mut self, value: &str) {
+        let index = self.classfile.define_string(value);
+        self.constant_value(index);
+    }
+
+    // the generic type of the field, e.g. Ljava/util/List<Ljava/lang/String;>; for a List<String>
+    pub fn signature(&mut self, signature: &TypeSignature) {
+        let attribute = self.classfile.signature_attribute(&signature.to_string());
+        self.attributes.push(attribute);
+    }
+
+    pub fn synthetic(&mut self) {
+        let name_index = self.classfile.define_utf8("Synthetic");
+        self.attributes.push(Attribute::Synthetic(name_index));
     }
 
-    pub fn done(self) -> Classfile {
-        Classfile::new(self.constants, self.access_flags, self.this_class_index, self.super_class_index, self.methods)
+    pub fn deprecated(&mut self) {
+        let name_index = self.classfile.define_utf8("Deprecated");
+        self.attributes.push(Attribute::Deprecated(name_index));
+    }
+
+    // Annotates the field, e.g. with @Inject.
+    pub fn annotation(&mut self, annotation: &AnnotationSpec, retention: Retention) {
+        let annotation = self.classfile.define_annotation(annotation);
+        self.annotations.add(annotation, retention);
+    }
+
+    fn constant_value(&mut self, constantvalue_index: u16) {
+        let name_index = self.classfile.define_utf8("ConstantValue");
+        self.attributes.push(Attribute::ConstantValue(name_index, constantvalue_index));
+    }
+
+    pub fn done(mut self) -> FieldRef {
+        let annotations = mem::take(&mut self.annotations);
+        self.attributes.extend(self.classfile.annotation_attributes(annotations));
+        let field = Field::new(self.access_flags, self.name_index, self.descriptor_index, self.attributes);
+        self.classfile.fields.push(field);
+        FieldRef { fieldref_index: self.fieldref_index }
     }
 }
 
@@ -82,19 +678,92 @@ pub struct MethodBuilder<'a> {
     access_flags: u16,
     name_index: u16,
     descriptor_index: u16,
-    instructions: Vec<(u16, IntermediateInstruction<'a>)>,
-    labels: HashMap<String, u16>,
-    stack_index: u16,
-    curr_stack_depth: u16,
-    max_stack_depth: u16,
-    stack_frames: Vec<StackMapFrame>,
-    last_stack_frame_index: Option<u16>,
+    instructions: Vec<IntermediateInstruction>,
+    labels: Vec<Option<usize>>, // positions are indices into instructions, since pcs aren't known until done()
+    errors: Vec<BuildErrorCause>, // problems found while adding code, reported by done()
+    try_blocks: Vec<TryBlock>,
+    exception_handlers: Vec<(Label, Label, Label, u16)>, // start, end, handler and catch type
+    argument_types: Vec<Java<'static>>,
+    locals: Vec<LocalVariable>,
+    next_local_slot: u16, // the first slot that isn't taken by `this`, an argument or a declared local in scope
+    lines: Vec<(usize, u16)>, // positions in instructions where source lines start
+    frames: Vec<(usize, Vec<FrameType>, Vec<FrameType>)>, // positions in instructions with their locals and stack
+    annotations: Annotations,
+    parameter_annotations: Vec<Annotations>,
+    exceptions: Vec<u16>, // the classes in the throws clause
+    attributes: Vec<Attribute>,
 }
 
 #[derive(Debug)]
-pub enum IntermediateInstruction<'a> {
+pub enum IntermediateInstruction {
     Ready(Instruction),
-    Waiting(&'a str, Instruction),
+    Waiting(Label, Instruction),
+    Switch(Label, Vec<Label>, Instruction), // the default label, then the label for each offset in the instruction
+}
+
+// A position in a method's code that branches can jump to. Create one with MethodBuilder::new_label, jump to it
+// with goto and friends, and place it with MethodBuilder::label (before or after the jumps).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Label {
+    id: usize,
+}
+
+impl fmt::Display for Label {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Label #{}", self.id)
+    }
+}
+
+// The type of a local variable or stack entry in a frame given to MethodBuilder::frame
+#[derive(Clone, Debug, PartialEq)]
+pub enum FrameType {
+    Top,
+    Integer,
+    Float,
+    Long,
+    Double,
+    Null,
+    UninitializedThis,
+    Object(String),       // internal class name, or array descriptor
+    Uninitialized(Label), // the result of the `new` instruction at the label, before its constructor has been called
+}
+
+// A region of code protected by exception handlers, from MethodBuilder::try_start. End it with
+// MethodBuilder::try_end, and add handlers with MethodBuilder::catch_block and MethodBuilder::finally_block.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TryBlock {
+    start: Label,
+    end: Label,
+}
+
+impl fmt::Display for TryBlock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Try block starting at {}", self.start)
+    }
+}
+
+// A named local variable (or argument), from MethodBuilder::declare_local or MethodBuilder::name_argument. Read and
+// write it with MethodBuilder::load and MethodBuilder::store, and end its scope with MethodBuilder::end_local.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Local {
+    id: usize,
+}
+
+impl fmt::Display for Local {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Local #{}", self.id)
+    }
+}
+
+// what's needed for a local's LocalVariableTable (and LocalVariableTypeTable) entries
+struct LocalVariable {
+    local_type: Java<'static>,
+    slot: u16,
+    name_index: u16,
+    descriptor_index: u16,
+    signature_index: Option<u16>,
+    start: Option<Label>, // for a declared local, placed after the first store
+    end: Option<Label>,   // the end of the code if the scope is never ended
 }
 
 impl<'a> MethodBuilder<'a> {
@@ -102,164 +771,248 @@ impl<'a> MethodBuilder<'a> {
         let name_index = classfile.define_utf8(name);
         let descriptor = method_signature(argument_types, return_type);
         let descriptor_index = classfile.define_utf8(&descriptor);
+        let this_slots = if access_flags & ACC_STATIC == 0 { 1 } else { 0 };
+        let argument_slots = argument_types.iter().map(|t| t.slot_count()).sum::<u16>();
         MethodBuilder {
-            classfile: classfile,
-            access_flags: access_flags,
-            name_index: name_index,
-            descriptor_index: descriptor_index,
+            classfile,
+            access_flags,
+            name_index,
+            descriptor_index,
             instructions: vec![],
-            labels: HashMap::new(),
-            stack_index: 0,
-            curr_stack_depth: 0,
-            max_stack_depth: 0,
-            stack_frames: vec![],
-            last_stack_frame_index: None,
+            labels: vec![],
+            errors: vec![],
+            try_blocks: vec![],
+            exception_handlers: vec![],
+            argument_types: argument_types.iter().map(|t| t.clone().into_owned()).collect(),
+            locals: vec![],
+            next_local_slot: this_slots + argument_slots,
+            lines: vec![],
+            frames: vec![],
+            annotations: Annotations::default(),
+            parameter_annotations: argument_types.iter().map(|_| Annotations::default()).collect(),
+            exceptions: vec![],
+            attributes: vec![],
         }
     }
 
+    // Adds an instruction as is. Branches and switches need the label-taking methods instead, and instructions that
+    // refer to the constant pool need the methods that add the constants they refer to.
+    pub fn instruction(&mut self, instruction: Instruction) {
+        self.push_instruction(instruction);
+    }
+
     pub fn iconstm1(&mut self) {
         self.push_instruction(Instruction::IconstM1);
-        self.increase_stack_depth();
     }
 
     pub fn iconst0(&mut self) {
         self.push_instruction(Instruction::Iconst0);
-        self.increase_stack_depth();
     }
 
     pub fn iconst1(&mut self) {
         self.push_instruction(Instruction::Iconst1);
-        self.increase_stack_depth();
     }
 
     pub fn iconst2(&mut self) {
         self.push_instruction(Instruction::Iconst2);
-        self.increase_stack_depth();
     }
 
     pub fn iconst3(&mut self) {
         self.push_instruction(Instruction::Iconst3);
-        self.increase_stack_depth();
     }
 
     pub fn iconst4(&mut self) {
         self.push_instruction(Instruction::Iconst4);
-        self.increase_stack_depth();
     }
 
     pub fn iconst5(&mut self) {
         self.push_instruction(Instruction::Iconst5);
-        self.increase_stack_depth();
     }
 
     pub fn bipush(&mut self, value: i8) {
         self.push_instruction(Instruction::Bipush(value as u8));
-        self.increase_stack_depth();
     }
 
-    pub fn load_constant(&mut self, value: &str) {
-        let string_index = self.classfile.define_string(value);
-        if string_index > ::std::u8::MAX as u16 {
-            panic!("Placed a constant in too high of an index: {}", string_index)
+    // Pushes a constant, using ldc2_w for longs and doubles, and ldc_w when the constant's index doesn't fit in a byte.
+    pub fn load_constant<'c, C: Into<LoadableConstant<'c>>>(&mut self, value: C) {
+        let constant = value.into();
+        let index = self.classfile.define_loadable_constant(&constant);
+        if constant.is_wide() {
+            self.push_instruction(Instruction::LoadConstant2Wide(index));
+        } else if index <= u8::MAX as u16 {
+            self.push_instruction(Instruction::LoadConstant(index as u8));
+        } else {
+            self.push_instruction(Instruction::LoadConstantWide(index));
         }
-        self.push_instruction(Instruction::LoadConstant(string_index as u8));
-        self.increase_stack_depth();
     }
 
     pub fn aload0(&mut self) {
         self.push_instruction(Instruction::Aload0);
-        self.increase_stack_depth();
     }
 
     pub fn aload1(&mut self) {
         self.push_instruction(Instruction::Aload1);
-        self.increase_stack_depth();
     }
 
     pub fn aload2(&mut self) {
         self.push_instruction(Instruction::Aload2);
-        self.increase_stack_depth();
     }
 
     pub fn aload3(&mut self) {
         self.push_instruction(Instruction::Aload3);
-        self.increase_stack_depth();
+    }
+
+    pub fn iload(&mut self, index: u8) {
+        self.push_instruction(Instruction::Iload(index));
+    }
+
+    pub fn istore(&mut self, index: u8) {
+        self.push_instruction(Instruction::Istore(index));
+    }
+
+    pub fn iinc(&mut self, index: u8, value: i8) {
+        self.push_instruction(Instruction::Iinc(index, value as u8));
     }
 
     pub fn aaload(&mut self) {
         self.push_instruction(Instruction::Aaload);
-        self.decrease_stack_depth();
+    }
+
+    pub fn pop(&mut self) {
+        self.push_instruction(Instruction::Pop);
     }
 
     pub fn iadd(&mut self) {
         self.push_instruction(Instruction::Iadd);
-        self.decrease_stack_depth();
     }
 
-    pub fn ifeq(&mut self, label: &'a str) {
+    pub fn ifeq(&mut self, label: Label) {
         self.delay_instruction(label, Instruction::IfEq(0));
-        self.decrease_stack_depth();
     }
 
-    pub fn ifne(&mut self, label: &'a str) {
+    pub fn ifne(&mut self, label: Label) {
         self.delay_instruction(label, Instruction::IfNe(0));
-        self.decrease_stack_depth();
     }
 
-    pub fn iflt(&mut self, label: &'a str) {
+    pub fn iflt(&mut self, label: Label) {
         self.delay_instruction(label, Instruction::IfLt(0));
-        self.decrease_stack_depth();
     }
 
-    pub fn ifge(&mut self, label: &'a str) {
+    pub fn ifge(&mut self, label: Label) {
         self.delay_instruction(label, Instruction::IfGe(0));
-        self.decrease_stack_depth();
     }
 
-    pub fn ifgt(&mut self, label: &'a str) {
+    pub fn ifgt(&mut self, label: Label) {
         self.delay_instruction(label, Instruction::IfGt(0));
-        self.decrease_stack_depth();
     }
 
-    pub fn ifle(&mut self, label: &'a str) {
+    pub fn ifle(&mut self, label: Label) {
         self.delay_instruction(label, Instruction::IfLe(0));
-        self.decrease_stack_depth();
     }
 
-    pub fn if_icmp_eq(&mut self, label: &'a str) {
+    pub fn if_icmp_eq(&mut self, label: Label) {
         self.delay_instruction(label, Instruction::IfIcmpEq(0));
-        self.decrease_stack_depth_by(2);
     }
 
-    pub fn if_icmp_ne(&mut self, label: &'a str) {
+    pub fn if_icmp_ne(&mut self, label: Label) {
         self.delay_instruction(label, Instruction::IfIcmpNe(0));
-        self.decrease_stack_depth_by(2);
     }
 
-    pub fn if_icmp_lt(&mut self, label: &'a str) {
+    pub fn if_icmp_lt(&mut self, label: Label) {
         self.delay_instruction(label, Instruction::IfIcmpLt(0));
-        self.decrease_stack_depth_by(2);
     }
 
-    pub fn if_icmp_ge(&mut self, label: &'a str) {
+    pub fn if_icmp_ge(&mut self, label: Label) {
         self.delay_instruction(label, Instruction::IfIcmpGe(0));
-        self.decrease_stack_depth_by(2);
     }
 
-    pub fn if_icmp_gt(&mut self, label: &'a str) {
+    pub fn if_icmp_gt(&mut self, label: Label) {
         self.delay_instruction(label, Instruction::IfIcmpGt(0));
-        self.decrease_stack_depth_by(2);
     }
 
-    pub fn if_icmp_le(&mut self, label: &'a str) {
+    pub fn if_icmp_le(&mut self, label: Label) {
         self.delay_instruction(label, Instruction::IfIcmpLe(0));
-        self.decrease_stack_depth_by(2);
     }
 
-    pub fn goto(&mut self, label: &'a str) {
+    pub fn if_acmp_eq(&mut self, label: Label) {
+        self.delay_instruction(label, Instruction::IfAcmpEq(0));
+    }
+
+    pub fn if_acmp_ne(&mut self, label: Label) {
+        self.delay_instruction(label, Instruction::IfAcmpNe(0));
+    }
+
+    pub fn ifnull(&mut self, label: Label) {
+        self.delay_instruction(label, Instruction::IfNull(0));
+    }
+
+    pub fn ifnonnull(&mut self, label: Label) {
+        self.delay_instruction(label, Instruction::IfNonNull(0));
+    }
+
+    pub fn goto(&mut self, label: Label) {
         self.delay_instruction(label, Instruction::Goto(0));
     }
 
+    // Jumps to labels[value - low] if the int on the stack is between low and high (inclusive), or to default otherwise.
+    pub fn table_switch(&mut self, low: i32, high: i32, labels: &[Label], default: Label) {
+        let count = high as i64 - low as i64 + 1;
+        if count != labels.len() as i64 {
+            self.errors.push(BuildErrorCause::SwitchLabelCount(low, high, labels.len()));
+            return;
+        }
+        let cases = labels.iter().enumerate().map(|(i, &label)| (low.wrapping_add(i as i32), label)).collect();
+        self.switch(cases, default);
+    }
+
+    // Jumps to the label paired with the int on the stack, or to default if there isn't one.
+    pub fn lookup_switch(&mut self, cases: &[(i32, Label)], default: Label) {
+        let mut cases = cases.to_vec();
+        cases.sort_by_key(|&(key, _)| key);
+        if let Some(pair) = cases.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+            self.errors.push(BuildErrorCause::DuplicateSwitchKey(pair[0].0));
+            return;
+        }
+        self.switch(cases, default);
+    }
+
+    // Uses a tableswitch if the keys are dense enough to be worth it, and a lookupswitch otherwise. The costs are the
+    // ones javac uses, weighing the size of the instruction against the time taken to find the key.
+    fn switch(&mut self, cases: Vec<(i32, Label)>, default: Label) {
+        let (low, high) = match (cases.first(), cases.last()) {
+            (Some(&(low, _)), Some(&(high, _))) => (low, high),
+            _ => {
+                self.instructions.push(IntermediateInstruction::Switch(default, vec![], Instruction::LookupSwitch(0, vec![])));
+                return;
+            },
+        };
+        let table_space_cost = 4 + (high as i64 - low as i64 + 1);
+        let table_time_cost = 3;
+        let lookup_space_cost = 3 + 2 * cases.len() as i64;
+        let lookup_time_cost = cases.len() as i64;
+
+        if table_space_cost + 3 * table_time_cost <= lookup_space_cost + 3 * lookup_time_cost {
+            // keys missing from the range go to the default label
+            let mut labels = vec![];
+            let mut cases = cases.into_iter().peekable();
+            for key in low as i64..=high as i64 {
+                match cases.peek() {
+                    Some(&(case_key, label)) if case_key as i64 == key => {
+                        labels.push(label);
+                        cases.next();
+                    },
+                    _ => labels.push(default),
+                }
+            }
+            let offsets = vec![0; labels.len()];
+            self.instructions.push(IntermediateInstruction::Switch(default, labels, Instruction::TableSwitch(0, low, offsets)));
+        } else {
+            let pairs = cases.iter().map(|&(key, _)| (key, 0)).collect();
+            let labels = cases.into_iter().map(|(_, label)| label).collect();
+            self.instructions.push(IntermediateInstruction::Switch(default, labels, Instruction::LookupSwitch(0, pairs)));
+        }
+    }
+
     pub fn do_return(&mut self) {
         self.push_instruction(Instruction::Return);
     }
@@ -267,107 +1020,682 @@ impl<'a> MethodBuilder<'a> {
     pub fn get_static(&mut self, class: &str, name: &str, argument_type: &Java) {
         let fieldref_index = self.classfile.define_fieldref(class, name, argument_type);
         self.push_instruction(Instruction::GetStatic(fieldref_index));
-        self.increase_stack_depth();
+    }
+
+    pub fn put_static(&mut self, class: &str, name: &str, argument_type: &Java) {
+        let fieldref_index = self.classfile.define_fieldref(class, name, argument_type);
+        self.push_instruction(Instruction::PutStatic(fieldref_index));
+    }
+
+    pub fn get_field(&mut self, class: &str, name: &str, argument_type: &Java) {
+        let fieldref_index = self.classfile.define_fieldref(class, name, argument_type);
+        self.push_instruction(Instruction::GetField(fieldref_index));
+    }
+
+    pub fn put_field(&mut self, class: &str, name: &str, argument_type: &Java) {
+        let fieldref_index = self.classfile.define_fieldref(class, name, argument_type);
+        self.push_instruction(Instruction::PutField(fieldref_index));
+    }
+
+    pub fn get_static_ref(&mut self, field: &FieldRef) {
+        self.push_instruction(Instruction::GetStatic(field.fieldref_index));
+    }
+
+    pub fn put_static_ref(&mut self, field: &FieldRef) {
+        self.push_instruction(Instruction::PutStatic(field.fieldref_index));
+    }
+
+    pub fn get_field_ref(&mut self, field: &FieldRef) {
+        self.push_instruction(Instruction::GetField(field.fieldref_index));
+    }
+
+    pub fn put_field_ref(&mut self, field: &FieldRef) {
+        self.push_instruction(Instruction::PutField(field.fieldref_index));
     }
 
     pub fn invoke_virtual(&mut self, class: &str, name: &str, argument_types: &[Java], return_type: &Java) {
         let methodref_index = self.classfile.define_methodref(class, name, argument_types, return_type);
         self.push_instruction(Instruction::InvokeVirtual(methodref_index));
-        self.decrease_stack_depth_by(argument_types.len() as u8 + 1);
-        if *return_type != Java::Void { self.increase_stack_depth(); }
     }
 
     pub fn invoke_special(&mut self, class: &str, name: &str, argument_types: &[Java], return_type: &Java) {
         let methodref_index = self.classfile.define_methodref(class, name, argument_types, return_type);
         self.push_instruction(Instruction::InvokeSpecial(methodref_index));
-        self.decrease_stack_depth_by(argument_types.len() as u8 + 1);
-        if *return_type != Java::Void { self.increase_stack_depth(); }
     }
 
     pub fn invoke_static(&mut self, class: &str, name: &str, argument_types: &[Java], return_type: &Java) {
         let methodref_index = self.classfile.define_methodref(class, name, argument_types, return_type);
         self.push_instruction(Instruction::InvokeStatic(methodref_index));
-        self.decrease_stack_depth_by(argument_types.len() as u8);
-        if *return_type != Java::Void { self.increase_stack_depth(); }
+    }
+
+    // a static method of an interface, which (unlike a class's) is referred to by an InterfaceMethodref
+    pub fn invoke_static_interface(&mut self, class: &str, name: &str, argument_types: &[Java], return_type: &Java) {
+        let descriptor = method_signature(argument_types, return_type);
+        let methodref_index = self.classfile.constants.add_interface_methodref(class, name, &descriptor);
+        self.push_instruction(Instruction::InvokeStatic(methodref_index));
+    }
+
+    // a private or default method of an interface, e.g. InterfaceName.super.method()
+    pub fn invoke_special_interface(&mut self, class: &str, name: &str, argument_types: &[Java], return_type: &Java) {
+        let descriptor = method_signature(argument_types, return_type);
+        let methodref_index = self.classfile.constants.add_interface_methodref(class, name, &descriptor);
+        self.push_instruction(Instruction::InvokeSpecial(methodref_index));
+    }
+
+    // Calls whatever the bootstrap method links the call site to, the way lambdas and string concatenation are
+    // compiled. The name and types are passed to the bootstrap method along with the arguments.
+    pub fn invoke_dynamic(&mut self, name: &str, argument_types: &[Java], return_type: &Java, bootstrap_method: &Handle, bootstrap_arguments: &[LoadableConstant]) {
+        let bootstrap_method_index = self.classfile.define_bootstrap_method(bootstrap_method, bootstrap_arguments);
+        let descriptor = method_signature(argument_types, return_type);
+        let name_and_type_index = self.classfile.constants.add_name_and_type(name, &descriptor);
+        let invoke_dynamic_index = self.classfile.constants.add(Constant::InvokeDynamic(bootstrap_method_index, name_and_type_index));
+        self.push_instruction(Instruction::InvokeDynamic(invoke_dynamic_index));
+    }
+
+    // the count operand is worked out from the argument types
+    pub fn invoke_interface(&mut self, class: &str, name: &str, argument_types: &[Java], return_type: &Java) {
+        let descriptor = method_signature(argument_types, return_type);
+        let methodref_index = self.classfile.constants.add_interface_methodref(class, name, &descriptor);
+        let count = argument_types.iter().map(|t| t.slot_count()).sum::<u16>() + 1;
+        self.push_instruction(Instruction::InvokeInterface(methodref_index, count as u8));
+    }
+
+    // creates an uninitialized object, which has to be initialized by calling one of its <init> methods
+    pub fn new_object(&mut self, class: &str) {
+        let class_index = self.classfile.define_class(class);
+        self.push_instruction(Instruction::New(class_index));
+    }
+
+    // creates an array of references, e.g. java/lang/String for a String[] or [I for an int[][]
+    pub fn anewarray(&mut self, component_class: &str) {
+        let class_index = self.classfile.define_class(component_class);
+        self.push_instruction(Instruction::ANewArray(class_index));
+    }
+
+    // creates a multi-dimensional array of the given array type, e.g. [[I, with the first `dimensions` sizes taken from the stack
+    pub fn multianewarray(&mut self, array_class: &str, dimensions: u8) {
+        let class_index = self.classfile.define_class(array_class);
+        self.push_instruction(Instruction::MultiANewArray(class_index, dimensions));
+    }
+
+    pub fn checkcast(&mut self, class: &str) {
+        let class_index = self.classfile.define_class(class);
+        self.push_instruction(Instruction::CheckCast(class_index));
+    }
+
+    pub fn instance_of(&mut self, class: &str) {
+        let class_index = self.classfile.define_class(class);
+        self.push_instruction(Instruction::InstanceOf(class_index));
     }
 
     pub fn array_length(&mut self) {
         self.push_instruction(Instruction::ArrayLength);
     }
 
-    pub fn label(&mut self, name: &str) {
-        self.labels.insert(name.to_owned(), self.stack_index);
+    pub fn athrow(&mut self) {
+        self.push_instruction(Instruction::Athrow);
+    }
 
-        // create a stack map table entry
-        let offset = match self.last_stack_frame_index {
-            Some(i) => self.stack_index - i - 1,
-            None => self.stack_index
+    // the generic types of the method, e.g. <T:Ljava/lang/Object;>(Ljava/util/List<TT;>;)TT; for `<T> T first(List<T> list)`
+    pub fn signature(&mut self, signature: &MethodSignature) {
+        let attribute = self.classfile.signature_attribute(&signature.to_string());
+        self.attributes.push(attribute);
+    }
+
+    // Adds a class to the method's throws clause, which only the compiler checks.
+    pub fn throws(&mut self, exception_class: &str) {
+        let class_index = self.classfile.define_class(exception_class);
+        self.exceptions.push(class_index);
+    }
+
+    pub fn deprecated(&mut self) {
+        let name_index = self.classfile.define_utf8("Deprecated");
+        self.attributes.push(Attribute::Deprecated(name_index));
+    }
+
+    pub fn synthetic(&mut self) {
+        let name_index = self.classfile.define_utf8("Synthetic");
+        self.attributes.push(Attribute::Synthetic(name_index));
+    }
+
+    // Annotates the method, e.g. with @Test.
+    pub fn annotation(&mut self, annotation: &AnnotationSpec, retention: Retention) {
+        let annotation = self.classfile.define_annotation(annotation);
+        self.annotations.add(annotation, retention);
+    }
+
+    // Annotates the nth argument (not counting `this`), e.g. with @Named("port").
+    pub fn parameter_annotation(&mut self, n: usize, annotation: &AnnotationSpec, retention: Retention) {
+        if n >= self.parameter_annotations.len() {
+            self.errors.push(BuildErrorCause::NoSuchArgument(n));
+            return;
+        }
+        let annotation = self.classfile.define_annotation(annotation);
+        self.parameter_annotations[n].add(annotation, retention);
+    }
+
+    // Sets the default value of an annotation type's element, which is declared as an abstract method of the
+    // annotation type, e.g. the 5 in `long value() default 5;`.
+    pub fn annotation_default(&mut self, value: &AnnotationValue) {
+        let name_index = self.classfile.define_utf8("AnnotationDefault");
+        let value = self.classfile.define_element_value(value);
+        self.attributes.push(Attribute::AnnotationDefault(name_index, value));
+    }
+
+    pub fn new_label(&mut self) -> Label {
+        self.labels.push(None);
+        Label { id: self.labels.len() - 1 }
+    }
+
+    // Places the label at the current position, so that branches to it jump to the next instruction.
+    pub fn label(&mut self, label: Label) {
+        let position = self.instructions.len();
+        match self.labels.get_mut(label.id) {
+            Some(&mut Some(_)) => self.errors.push(BuildErrorCause::DuplicateLabel(label)),
+            Some(slot) => *slot = Some(position),
+            None => {}, // a label from another method, so jumps to it will be reported as undefined
+        }
+    }
+
+    // Marks the start of a region of code protected by the handlers registered with catch_block and finally_block.
+    // Try blocks can be nested or overlap.
+    pub fn try_start(&mut self) -> TryBlock {
+        let start = self.new_label();
+        let end = self.new_label();
+        self.label(start);
+        let block = TryBlock { start, end };
+        self.try_blocks.push(block);
+        block
+    }
+
+    // Marks the (exclusive) end of the protected region started by try_start.
+    pub fn try_end(&mut self, block: TryBlock) {
+        match self.labels.get(block.end.id) {
+            Some(&Some(_)) => self.errors.push(BuildErrorCause::TryBlockEndedTwice(block)),
+            _ => self.label(block.end),
+        }
+    }
+
+    // Starts a handler for exceptions of the given class thrown inside the try block. The exception is on the stack.
+    pub fn catch_block(&mut self, block: TryBlock, exception_class: &str) {
+        let class_index = self.classfile.define_class(exception_class);
+        self.exception_handler(block, class_index);
+    }
+
+    // Starts a handler for any exception thrown inside the try block. The exception is on the stack.
+    pub fn finally_block(&mut self, block: TryBlock) {
+        self.exception_handler(block, 0);
+    }
+
+    // Registers a handler for exceptions of the given class (or any exception) thrown between the start label and the
+    // (exclusive) end label. Unlike try_start and friends, the labels can be placed before or after this is called.
+    pub fn add_exception_handler(&mut self, start: Label, end: Label, handler: Label, exception_class: Option<&str>) {
+        let catch_type = exception_class.map_or(0, |class| self.classfile.define_class(class));
+        self.exception_handlers.push((start, end, handler, catch_type));
+    }
+
+    // Marks the code from here on as coming from the given source line, until the next call.
+    pub fn line(&mut self, line_number: u16) {
+        self.lines.push((self.instructions.len(), line_number));
+    }
+
+    // Declares the types of the locals and stack entries at this point in the code, the way a StackMapTable frame
+    // does (so a long or double local is a single entry). Frames are still worked out from the code, but where paths
+    // through the method meet with different classes in a local or stack entry, the class given here is used instead
+    // of asking ClassBuilder::common_superclass.
+    pub fn frame(&mut self, locals: &[FrameType], stack: &[FrameType]) {
+        self.frames.push((self.instructions.len(), locals.to_vec(), stack.to_vec()));
+    }
+
+    // Declares a local variable with a name for debuggers, in the next free slot. It's in scope from the first store
+    // until end_local is called, or the end of the method. Slots used directly (e.g. with istore) aren't tracked, so
+    // mixing the two needs care.
+    pub fn declare_local(&mut self, name: &str, local_type: &Java) -> Local {
+        self.add_local(name, local_type, None)
+    }
+
+    // a local variable with a generic type, e.g. List<String>, which local_type should be the erasure of
+    pub fn declare_generic_local(&mut self, name: &str, local_type: &Java, signature: &TypeSignature) -> Local {
+        let signature_index = self.classfile.define_utf8(&signature.to_string());
+        self.add_local(name, local_type, Some(signature_index))
+    }
+
+    // Names the nth argument (not counting `this`) for debuggers. It's in scope for the whole method, and can be read
+    // and written with load and store like any other local.
+    pub fn name_argument(&mut self, n: usize, name: &str) -> Local {
+        let this_slots = if self.access_flags & ACC_STATIC == 0 { 1 } else { 0 };
+        let argument_type = match self.argument_types.get(n) {
+            Some(argument_type) => argument_type.clone(),
+            None => {
+                // done() reports the missing argument, so the local it returns is never looked up
+                self.errors.push(BuildErrorCause::NoSuchArgument(n));
+                return Local { id: usize::MAX };
+            },
         };
-        let frame = if offset > ::std::u8::MAX as u16 {
-            StackMapFrame::SameFrameExtended(offset)
-        } else {
-            StackMapFrame::SameFrame(offset as u8)
+        let slot = this_slots + self.argument_types[..n].iter().map(|t| t.slot_count()).sum::<u16>();
+        let local = self.add_local_in_slot(name, argument_type, slot, None);
+        let start = self.new_label();
+        self.labels[start.id] = Some(0);
+        self.locals[local.id].start = Some(start);
+        local
+    }
+
+    // Records a local variable in the given slot between the start label and the (exclusive) end label, for
+    // debuggers, without allocating the slot or tracking its loads and stores. Like add_exception_handler, the labels
+    // can be placed before or after this is called.
+    pub fn add_local_variable(&mut self, slot: u16, name: &str, local_type: &Java, signature: Option<&TypeSignature>, start: Label, end: Label) {
+        let signature_index = signature.map(|signature| self.classfile.define_utf8(&signature.to_string()));
+        let local = self.add_local_in_slot(name, local_type.clone().into_owned(), slot, signature_index);
+        let variable = &mut self.locals[local.id];
+        variable.start = Some(start);
+        variable.end = Some(end);
+    }
+
+    // Ends the scope of a local variable here. If it was the most recently declared local that's still in scope,
+    // its slot is free to be reused by the next one.
+    pub fn end_local(&mut self, local: Local) {
+        let end = self.new_label();
+        self.label(end);
+        let slots = match self.local_variable(local) {
+            Some(variable) => {
+                variable.end = Some(end);
+                (variable.slot, variable.slot + variable.local_type.slot_count())
+            },
+            None => return,
         };
-        self.stack_frames.push(frame);
-        self.last_stack_frame_index = Some(self.stack_index);
+        if slots.1 == self.next_local_slot {
+            self.next_local_slot = slots.0;
+        }
     }
 
-    fn push_instruction(&mut self, instruction: Instruction) {
-        let index = self.stack_index;
-        self.stack_index += instruction.size() as u16;
-        self.instructions.push((index, IntermediateInstruction::Ready(instruction)));
+    // Pushes the value of a local variable, with the load instruction for its type.
+    pub fn load(&mut self, local: Local) {
+        if let Some((slot, local_type)) = self.local_slot(local) {
+            let instruction = match local_type {
+                Java::Long => local_instruction(slot, [Instruction::Lload0, Instruction::Lload1, Instruction::Lload2, Instruction::Lload3], Instruction::Lload, WideInstruction::Lload),
+                Java::Float => local_instruction(slot, [Instruction::Fload0, Instruction::Fload1, Instruction::Fload2, Instruction::Fload3], Instruction::Fload, WideInstruction::Fload),
+                Java::Double => local_instruction(slot, [Instruction::Dload0, Instruction::Dload1, Instruction::Dload2, Instruction::Dload3], Instruction::Dload, WideInstruction::Dload),
+                Java::Class(_) | Java::Array(_) => local_instruction(slot, [Instruction::Aload0, Instruction::Aload1, Instruction::Aload2, Instruction::Aload3], Instruction::Aload, WideInstruction::Aload),
+                _ => local_instruction(slot, [Instruction::Iload0, Instruction::Iload1, Instruction::Iload2, Instruction::Iload3], Instruction::Iload, WideInstruction::Iload),
+            };
+            self.push_instruction(instruction);
+        }
     }
 
-    fn delay_instruction(&mut self, label: &'a str, instruction: Instruction) {
-        let index = self.stack_index;
-        self.stack_index += instruction.size() as u16;
-        self.instructions.push((index, IntermediateInstruction::Waiting(label, instruction)));
+    // Pops a value into a local variable, with the store instruction for its type.
+    pub fn store(&mut self, local: Local) {
+        if let Some((slot, local_type)) = self.local_slot(local) {
+            let instruction = match local_type {
+                Java::Long => local_instruction(slot, [Instruction::Lstore0, Instruction::Lstore1, Instruction::Lstore2, Instruction::Lstore3], Instruction::Lstore, WideInstruction::Lstore),
+                Java::Float => local_instruction(slot, [Instruction::Fstore0, Instruction::Fstore1, Instruction::Fstore2, Instruction::Fstore3], Instruction::Fstore, WideInstruction::Fstore),
+                Java::Double => local_instruction(slot, [Instruction::Dstore0, Instruction::Dstore1, Instruction::Dstore2, Instruction::Dstore3], Instruction::Dstore, WideInstruction::Dstore),
+                Java::Class(_) | Java::Array(_) => local_instruction(slot, [Instruction::Astore0, Instruction::Astore1, Instruction::Astore2, Instruction::Astore3], Instruction::Astore, WideInstruction::Astore),
+                _ => local_instruction(slot, [Instruction::Istore0, Instruction::Istore1, Instruction::Istore2, Instruction::Istore3], Instruction::Istore, WideInstruction::Istore),
+            };
+            self.push_instruction(instruction);
+
+            // the variable has a value from the next instruction on
+            if self.locals[local.id].start.is_none() {
+                let start = self.new_label();
+                self.label(start);
+                self.locals[local.id].start = Some(start);
+            }
+        }
     }
 
-    fn increase_stack_depth(&mut self) {
-        self.curr_stack_depth += 1;
-        if self.curr_stack_depth > self.max_stack_depth {
-            self.max_stack_depth = self.curr_stack_depth;
+    // Adds a constant to an int local variable, with iinc.
+    pub fn increment(&mut self, local: Local, value: i16) {
+        if let Some((slot, _)) = self.local_slot(local) {
+            match (u8::try_from(slot), i8::try_from(value)) {
+                (Ok(slot), Ok(value)) => self.push_instruction(Instruction::Iinc(slot, value as u8)),
+                _ => self.push_instruction(Instruction::Wide(WideInstruction::Iinc(slot, value as u16))),
+            }
         }
     }
 
-    fn decrease_stack_depth(&mut self) {
-        self.curr_stack_depth -= 1;
+    fn add_local(&mut self, name: &str, local_type: &Java, signature_index: Option<u16>) -> Local {
+        if *local_type == Java::Void {
+            self.errors.push(BuildErrorCause::VoidLocal(name.to_owned()));
+        }
+        let slot = self.next_local_slot;
+        self.next_local_slot += local_type.slot_count();
+        self.add_local_in_slot(name, local_type.clone().into_owned(), slot, signature_index)
     }
 
-    fn decrease_stack_depth_by(&mut self, n: u8) {
-        self.curr_stack_depth -= n as u16;
+    fn add_local_in_slot(&mut self, name: &str, local_type: Java<'static>, slot: u16, signature_index: Option<u16>) -> Local {
+        let name_index = self.classfile.define_utf8(name);
+        let descriptor_index = self.classfile.define_utf8(&local_type.to_string());
+        self.locals.push(LocalVariable { local_type, slot, name_index, descriptor_index, signature_index, start: None, end: None });
+        Local { id: self.locals.len() - 1 }
+    }
+
+    fn local_variable(&mut self, local: Local) -> Option<&mut LocalVariable> {
+        if local.id >= self.locals.len() {
+            self.errors.push(BuildErrorCause::UndefinedLocal(local));
+        }
+        self.locals.get_mut(local.id)
     }
 
-    pub fn done(self) {
-        if self.curr_stack_depth != 0 {
-            println!("Warning: stack depth at the end of a method should be 0, but is {} instead", self.curr_stack_depth);
+    // the slot and type of a local that's being read or written, which has to still be in scope
+    fn local_slot(&mut self, local: Local) -> Option<(u16, Java<'static>)> {
+        let (slot, local_type, ended) = {
+            let variable = self.local_variable(local)?;
+            (variable.slot, variable.local_type.clone(), variable.end.is_some())
+        };
+        if ended {
+            self.errors.push(BuildErrorCause::LocalOutOfScope(local));
+            return None;
+        }
+        Some((slot, local_type))
+    }
+
+    fn exception_handler(&mut self, block: TryBlock, catch_type: u16) {
+        let handler = self.new_label();
+        self.label(handler);
+        self.exception_handlers.push((block.start, block.end, handler, catch_type));
+    }
+
+    fn push_instruction(&mut self, instruction: Instruction) {
+        self.instructions.push(IntermediateInstruction::Ready(instruction));
+    }
+
+    fn delay_instruction(&mut self, label: Label, instruction: Instruction) {
+        self.instructions.push(IntermediateInstruction::Waiting(label, instruction));
+    }
+
+    pub fn done(mut self) -> Result<(), BuildError> {
+        let method = format!("{}{}", self.classfile.constants.get_utf8(self.name_index).unwrap(), self.classfile.constants.get_utf8(self.descriptor_index).unwrap());
+        let error = |cause| BuildError { method: method.clone(), cause };
+
+        if let Some(cause) = mem::take(&mut self.errors).into_iter().next() {
+            return Err(error(cause));
+        }
+        if self.classfile.constants.has_overflowed() {
+            return Err(error(BuildErrorCause::ConstantPoolFull));
+        }
+
+        // abstract and native methods have no Code attribute
+        let mut attributes = vec![];
+        if self.access_flags & (ACC_ABSTRACT | ACC_NATIVE) == 0 {
+            attributes.push(self.code().map_err(&error)?);
+        } else if !self.instructions.is_empty() || !self.exception_handlers.is_empty() || !self.locals.is_empty() || !self.lines.is_empty() {
+            return Err(error(BuildErrorCause::CodeInAbstractMethod));
         }
 
         let classfile = self.classfile;
-        let labels = self.labels;
-        let real_instructions = self.instructions.into_iter().map(|(pos, ir)| match ir {
-            IntermediateInstruction::Ready(i) => i,
-            IntermediateInstruction::Waiting(l, i) => {
-                let label_pos = labels.get(l).unwrap();
-                let offset = label_pos - pos;
-                fill_offset(i, offset)
+        if !self.exceptions.is_empty() {
+            let name_index = classfile.define_utf8("Exceptions");
+            attributes.push(Attribute::Exceptions(name_index, self.exceptions));
+        }
+        attributes.extend(self.attributes);
+        attributes.extend(classfile.annotation_attributes(self.annotations));
+        attributes.extend(classfile.parameter_annotation_attributes(self.parameter_annotations));
+        let method = Method::new(self.access_flags, self.name_index, self.descriptor_index, attributes);
+        classfile.methods.push(method);
+        Ok(())
+    }
+
+    // lays out the code and works out its stack map frames and limits
+    fn code(&mut self) -> Result<Attribute, BuildErrorCause> {
+        let classfile = &mut *self.classfile;
+        let labels = &self.labels;
+        if let Some(&block) = self.try_blocks.iter().find(|block| labels[block.end.id].is_none()) {
+            return Err(BuildErrorCause::TryBlockNeverEnded(block));
+        }
+        let (real_instructions, pcs) = lay_out_code(mem::take(&mut self.instructions), labels)?;
+
+        let mut exception_table = vec![];
+        let pc = |label: Label| labels.get(label.id).cloned().and_then(|position| position).map(|position| pcs[position]).ok_or(BuildErrorCause::UndefinedLabel(label));
+        for &(start, end, handler, catch_type) in &self.exception_handlers {
+            exception_table.push(ExceptionTableEntry {
+                start_pc: pc(start)?,
+                end_pc: pc(end)?,
+                handler_pc: pc(handler)?,
+                catch_type,
+            });
+        }
+
+        let mut frame_hints = HashMap::new();
+        for &(position, ref locals, ref stack) in &self.frames {
+            let inferred_type = |t: &FrameType| -> Result<InferredType, BuildErrorCause> {
+                Ok(match *t {
+                    FrameType::Top => InferredType::Top,
+                    FrameType::Integer => InferredType::Integer,
+                    FrameType::Float => InferredType::Float,
+                    FrameType::Long => InferredType::Long,
+                    FrameType::Double => InferredType::Double,
+                    FrameType::Null => InferredType::Null,
+                    FrameType::UninitializedThis => InferredType::UninitializedThis,
+                    FrameType::Object(ref class) => InferredType::Object(class.clone()),
+                    FrameType::Uninitialized(label) => InferredType::Uninitialized(pc(label)?),
+                })
+            };
+            let mut frame = Frame { locals: vec![], stack: vec![] };
+            for local in locals {
+                frame.locals.push(inferred_type(local)?);
+                if matches!(*local, FrameType::Long | FrameType::Double) {
+                    frame.locals.push(InferredType::Top);
+                }
             }
-        }).collect();
+            for entry in stack {
+                frame.stack.push(inferred_type(entry)?);
+            }
+            frame_hints.insert(pcs[position], frame);
+        }
+
+        // work out the types of locals and stack entries at each branch target, for the verifier
+        let analysis = {
+            let constants = &classfile.constants;
+            let context = MethodContext {
+                constants,
+                this_class: constants.get_class_name(classfile.this_class_index).unwrap(),
+                name: constants.get_utf8(self.name_index).unwrap(),
+                descriptor: constants.get_utf8(self.descriptor_index).unwrap(),
+                is_static: self.access_flags & ACC_STATIC != 0,
+                common_superclass: &*classfile.common_superclass,
+                frame_hints: &frame_hints,
+            };
+            analyze(&context, real_instructions, exception_table).map_err(BuildErrorCause::InvalidCode)?
+        };
+        let stack_frames = encode_frames(&analysis.initial_frame, &analysis.frames, |class| classfile.define_class(class));
+        let mut code_attributes = vec![];
+        if let Some(line_numbers) = line_number_table(classfile, &self.lines, &pcs) {
+            code_attributes.push(line_numbers);
+        }
+        code_attributes.extend(local_variable_tables(classfile, self.access_flags, &self.locals, pc, pcs[pcs.len() - 1])?);
+        if !stack_frames.is_empty() {
+            let stack_map_table_index = classfile.define_utf8("StackMapTable");
+            code_attributes.push(Attribute::StackMapTable(stack_map_table_index, stack_frames));
+        }
 
-        let stack_map_table_index = classfile.define_utf8("StackMapTable");
-        let stack_map_table = Attribute::StackMapTable(stack_map_table_index, self.stack_frames);
+        // the arguments (and `this`) take up the first local variable slots, and the code may use more
+        let argument_slots = analysis.initial_frame.locals.len() as u16;
+        let max_locals = analysis.instructions.iter().map(|i| i.locals_used()).fold(argument_slots, u16::max);
+        let max_locals = self.locals.iter().map(|local| local.slot + local.local_type.slot_count()).fold(max_locals, u16::max);
+
+        // the stack in every frame has to fit too, including the frames of unreachable code
+        let max_stack = max_stack(&classfile.constants, &analysis.instructions, &analysis.exception_table).map_err(BuildErrorCause::InvalidCode)?;
+        let max_stack = analysis.frames.iter().map(|(_, frame)| frame.stack_slots()).fold(max_stack, u16::max);
 
-        // TODO track max_locals counts instead of hard-coding to 1
         let code_index = classfile.define_utf8("Code");
-        let code = Attribute::Code(code_index, self.max_stack_depth, 1, real_instructions, vec![], vec![stack_map_table]);
+        Ok(Attribute::Code(code_index, max_stack, max_locals, analysis.instructions, analysis.exception_table, code_attributes))
+    }
+}
 
-        let method = Method::new(self.access_flags, self.name_index, self.descriptor_index, vec![code]);
-        classfile.methods.push(method);
+// The LineNumberTable for the lines marked in the code. A line that starts where the next one does, or at the end of
+// the code, has no instructions, so it's left out.
+fn line_number_table(classfile: &mut ClassBuilder, lines: &[(usize, u16)], pcs: &[u16]) -> Option<Attribute> {
+    let end_of_code = pcs[pcs.len() - 1];
+    let mut entries: Vec<LineNumberTableEntry> = vec![];
+    for &(position, line_number) in lines {
+        let start_pc = pcs[position];
+        if entries.last().is_some_and(|entry| entry.start_pc == start_pc) {
+            entries.pop();
+        }
+        if start_pc < end_of_code {
+            entries.push(LineNumberTableEntry { start_pc, line_number });
+        }
+    }
+    if entries.is_empty() {
+        return None;
+    }
+    Some(Attribute::LineNumberTable(classfile.define_utf8("LineNumberTable"), entries))
+}
+
+// The LocalVariableTable for the locals that were ever stored to (plus `this`, as javac does, unless something else
+// was put in its slot), and the LocalVariableTypeTable for any with generic types.
+fn local_variable_tables<F: Fn(Label) -> Result<u16, BuildErrorCause>>(classfile: &mut ClassBuilder, access_flags: u16, locals: &[LocalVariable], pc: F, end_of_code: u16) -> Result<Vec<Attribute>, BuildErrorCause> {
+    let mut entries = vec![];
+    let mut type_entries = vec![];
+    for local in locals {
+        let start_pc = match local.start {
+            Some(start) => pc(start)?,
+            None => continue,
+        };
+        let end_pc = match local.end {
+            Some(end) => pc(end)?,
+            None => end_of_code,
+        };
+        let name = classfile.constants.get_utf8(local.name_index).unwrap();
+        let length = end_pc.checked_sub(start_pc).ok_or_else(|| BuildErrorCause::InvalidCode(format!("local variable {} ends before it starts", name)))?;
+        entries.push(LocalVariableTableEntry { start_pc, length, name_index: local.name_index, descriptor_index: local.descriptor_index, index: local.slot });
+        if let Some(signature_index) = local.signature_index {
+            type_entries.push(LocalVariableTypeTableEntry { start_pc, length, name_index: local.name_index, signature_index, index: local.slot });
+        }
+    }
+    if entries.is_empty() {
+        return Ok(vec![]);
+    }
+
+    if access_flags & ACC_STATIC == 0 && entries.iter().all(|entry| entry.index != 0) {
+        let this_class = classfile.constants.get_class_name(classfile.this_class_index).unwrap().to_owned();
+        let name_index = classfile.define_utf8("this");
+        let descriptor_index = classfile.define_utf8(&Java::class(this_class).to_string());
+        entries.insert(0, LocalVariableTableEntry { start_pc: 0, length: end_of_code, name_index, descriptor_index, index: 0 });
+    }
+
+    let mut attributes = vec![Attribute::LocalVariableTable(classfile.define_utf8("LocalVariableTable"), entries)];
+    if !type_entries.is_empty() {
+        attributes.push(Attribute::LocalVariableTypeTable(classfile.define_utf8("LocalVariableTypeTable"), type_entries));
+    }
+    Ok(attributes)
+}
+
+// a load or store of a local variable, in its shortest form: there are one-byte instructions for the first four
+// slots, and slots past 255 need wide
+fn local_instruction(slot: u16, short: [Instruction; 4], narrow: fn(u8) -> Instruction, wide: fn(u16) -> WideInstruction) -> Instruction {
+    match u8::try_from(slot) {
+        Ok(slot) if slot < 4 => short[slot as usize].clone(),
+        Ok(slot) => narrow(slot),
+        Err(_) => Instruction::Wide(wide(slot)),
+    }
+}
+
+// Works out the pc of each instruction, and fills in branch offsets. A branch whose target is too far away for a
+// 16-bit offset becomes goto_w (or a conditional branch with the opposite condition that skips over a goto_w), which
+// makes the code longer and may push other branches out of range, so this repeats until nothing else needs widening.
+// Returns the final instructions, and the pc of each of the given instructions plus the end of the code.
+fn lay_out_code(instructions: Vec<IntermediateInstruction>, labels: &[Option<usize>]) -> Result<(Vec<Instruction>, Vec<u16>), BuildErrorCause> {
+    let resolve = |label: Label| labels.get(label.id).cloned().and_then(|position| position).ok_or(BuildErrorCause::UndefinedLabel(label));
+    let mut targets = vec![];
+    for instruction in &instructions {
+        targets.push(match *instruction {
+            IntermediateInstruction::Ready(_) => vec![],
+            IntermediateInstruction::Waiting(label, _) => vec![resolve(label)?],
+            IntermediateInstruction::Switch(default, ref labels, _) => {
+                let mut switch_targets = vec![resolve(default)?];
+                for &label in labels {
+                    switch_targets.push(resolve(label)?);
+                }
+                switch_targets
+            },
+        });
+    }
+
+    let mut wide = vec![false; instructions.len()];
+    let pcs = loop {
+        let mut pcs = vec![0];
+        for (i, instruction) in instructions.iter().enumerate() {
+            let pc = pcs[i];
+            if pc > u16::MAX as usize {
+                return Err(BuildErrorCause::CodeTooLarge(pc));
+            }
+            let size = match *instruction {
+                IntermediateInstruction::Ready(ref instruction) |
+                IntermediateInstruction::Switch(_, _, ref instruction) => instruction.size(pc as u16) as usize,
+                IntermediateInstruction::Waiting(_, Instruction::Goto(_)) if wide[i] => 5,
+                IntermediateInstruction::Waiting(_, _) if wide[i] => 8,
+                IntermediateInstruction::Waiting(_, _) => 3,
+            };
+            pcs.push(pc + size);
+        }
+
+        let mut changed = false;
+        for (i, instruction) in instructions.iter().enumerate() {
+            if let IntermediateInstruction::Waiting(_, _) = *instruction {
+                let offset = pcs[targets[i][0]] as i64 - pcs[i] as i64;
+                if !wide[i] && (offset < i16::MIN as i64 || offset > i16::MAX as i64) {
+                    wide[i] = true;
+                    changed = true;
+                }
+            }
+        }
+        if !changed {
+            break pcs;
+        }
+    };
+    if pcs[instructions.len()] > u16::MAX as usize {
+        return Err(BuildErrorCause::CodeTooLarge(pcs[instructions.len()]));
+    }
+
+    let mut real_instructions = vec![];
+    for (i, instruction) in instructions.into_iter().enumerate() {
+        match instruction {
+            IntermediateInstruction::Ready(instruction) => real_instructions.push(instruction),
+            IntermediateInstruction::Waiting(_, instruction) => {
+                let offset = pcs[targets[i][0]] as i32 - pcs[i] as i32;
+                if !wide[i] {
+                    real_instructions.push(fill_offset(instruction, offset as i16));
+                } else if let Instruction::Goto(_) = instruction {
+                    real_instructions.push(Instruction::GotoW(offset));
+                } else {
+                    // the inverted branch jumps over the 5-byte goto_w that follows it
+                    real_instructions.push(fill_offset(invert_branch(instruction), 3 + 5));
+                    real_instructions.push(Instruction::GotoW(offset - 3));
+                }
+            },
+            IntermediateInstruction::Switch(_, _, instruction) => {
+                let offsets = targets[i].iter().map(|&target| pcs[target] as i32 - pcs[i] as i32).collect();
+                real_instructions.push(fill_switch_offsets(instruction, offsets));
+            },
+        }
+    }
+    Ok((real_instructions, pcs.into_iter().map(|pc| pc as u16).collect()))
+}
+
+fn invert_branch(instruction: Instruction) -> Instruction {
+    match instruction {
+        Instruction::IfEq(offset) => Instruction::IfNe(offset),
+        Instruction::IfNe(offset) => Instruction::IfEq(offset),
+        Instruction::IfLt(offset) => Instruction::IfGe(offset),
+        Instruction::IfGe(offset) => Instruction::IfLt(offset),
+        Instruction::IfGt(offset) => Instruction::IfLe(offset),
+        Instruction::IfLe(offset) => Instruction::IfGt(offset),
+        Instruction::IfIcmpEq(offset) => Instruction::IfIcmpNe(offset),
+        Instruction::IfIcmpNe(offset) => Instruction::IfIcmpEq(offset),
+        Instruction::IfIcmpLt(offset) => Instruction::IfIcmpGe(offset),
+        Instruction::IfIcmpGe(offset) => Instruction::IfIcmpLt(offset),
+        Instruction::IfIcmpGt(offset) => Instruction::IfIcmpLe(offset),
+        Instruction::IfIcmpLe(offset) => Instruction::IfIcmpGt(offset),
+        Instruction::IfAcmpEq(offset) => Instruction::IfAcmpNe(offset),
+        Instruction::IfAcmpNe(offset) => Instruction::IfAcmpEq(offset),
+        Instruction::IfNull(offset) => Instruction::IfNonNull(offset),
+        Instruction::IfNonNull(offset) => Instruction::IfNull(offset),
+        _ => panic!("Instruction isn't a conditional branch: {:?}", instruction)
     }
 }
 
-fn fill_offset(instruction: Instruction, offset: u16) -> Instruction {
+fn fill_offset(instruction: Instruction, offset: i16) -> Instruction {
     match instruction {
         Instruction::IfEq(_) => Instruction::IfEq(offset),
         Instruction::IfNe(_) => Instruction::IfNe(offset),
@@ -381,7 +1709,354 @@ fn fill_offset(instruction: Instruction, offset: u16) -> Instruction {
         Instruction::IfIcmpGe(_) => Instruction::IfIcmpGe(offset),
         Instruction::IfIcmpGt(_) => Instruction::IfIcmpGt(offset),
         Instruction::IfIcmpLe(_) => Instruction::IfIcmpLe(offset),
+        Instruction::IfAcmpEq(_) => Instruction::IfAcmpEq(offset),
+        Instruction::IfAcmpNe(_) => Instruction::IfAcmpNe(offset),
         Instruction::Goto(_) => Instruction::Goto(offset),
+        Instruction::IfNull(_) => Instruction::IfNull(offset),
+        Instruction::IfNonNull(_) => Instruction::IfNonNull(offset),
         _ => panic!("Instruction type doesn't have an offset to fill: {:?}", instruction)
     }
 }
+
+// the first offset is for the default case, and the rest are in the order the instruction lists its cases
+fn fill_switch_offsets(instruction: Instruction, offsets: Vec<i32>) -> Instruction {
+    match instruction {
+        Instruction::TableSwitch(_, low, _) => Instruction::TableSwitch(offsets[0], low, offsets[1..].to_vec()),
+        Instruction::LookupSwitch(_, pairs) => {
+            let pairs = pairs.into_iter().zip(&offsets[1..]).map(|((key, _), &offset)| (key, offset)).collect();
+            Instruction::LookupSwitch(offsets[0], pairs)
+        },
+        _ => panic!("Instruction isn't a switch: {:?}", instruction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a class with a static test()V method, whose code is added by the given function
+    fn class(build: impl FnOnce(&mut MethodBuilder)) -> Classfile {
+        let mut class = ClassBuilder::new(ACC_PUBLIC, "Test", "java/lang/Object");
+        let mut method = class.define_method(ACC_PUBLIC | ACC_STATIC, "test", &[], &Java::Void);
+        build(&mut method);
+        method.do_return();
+        method.done().unwrap();
+        class.done().unwrap()
+    }
+
+    // the code of the class's only method, built by the given function
+    fn code(build: impl FnOnce(&mut MethodBuilder)) -> Vec<Instruction> {
+        match class(build).methods[0].attributes[0] {
+            Attribute::Code(_, _, _, ref instructions, _, _) => instructions.clone(),
+            ref attribute => panic!("expected a Code attribute, got {:?}", attribute),
+        }
+    }
+
+    // the pc of each instruction
+    fn pcs(code: &[Instruction]) -> Vec<u16> {
+        let mut pcs = vec![];
+        let mut pc = 0;
+        for instruction in code {
+            pcs.push(pc);
+            pc += instruction.size(pc);
+        }
+        pcs
+    }
+
+    // the StackMapTable frames of the class's only method
+    fn stack_map(classfile: &Classfile) -> Vec<StackMapFrame> {
+        match classfile.methods[0].attributes[0] {
+            Attribute::Code(_, _, _, _, _, ref attributes) => attributes.iter().filter_map(|attribute| match *attribute {
+                Attribute::StackMapTable(_, ref frames) => Some(frames.clone()),
+                _ => None,
+            }).next().unwrap_or_default(),
+            ref attribute => panic!("expected a Code attribute, got {:?}", attribute),
+        }
+    }
+
+    #[test]
+    fn loads_longs_and_doubles_with_ldc2_w() {
+        let code = code(|method| {
+            method.load_constant(5i64);
+            method.instruction(Instruction::Pop2);
+            method.load_constant(1.5f64);
+            method.instruction(Instruction::Pop2);
+        });
+        assert!(matches!(code[0], Instruction::LoadConstant2Wide(_)));
+        assert!(matches!(code[2], Instruction::LoadConstant2Wide(_)));
+    }
+
+    #[test]
+    fn loads_dynamic_longs_with_ldc2_w() {
+        let bootstrap_method = Handle {
+            kind: REF_INVOKE_STATIC,
+            class: "java/lang/invoke/ConstantBootstraps",
+            name: "nullConstant",
+            descriptor: "(Ljava/lang/invoke/MethodHandles$Lookup;Ljava/lang/String;Ljava/lang/Class;)Ljava/lang/Object;",
+            is_interface: false,
+        };
+        let code = code(|method| {
+            method.load_constant(LoadableConstant::Dynamic("_", "J", bootstrap_method.clone(), vec![]));
+            method.instruction(Instruction::Pop2);
+            method.load_constant(LoadableConstant::Dynamic("_", "Ljava/lang/Object;", bootstrap_method, vec![]));
+            method.pop();
+        });
+        assert!(matches!(code[0], Instruction::LoadConstant2Wide(_)));
+        assert!(matches!(code[2], Instruction::LoadConstant(_)));
+    }
+
+    #[test]
+    fn uses_ldc_w_past_constant_255() {
+        let code = code(|method| {
+            for value in 0..300 {
+                method.load_constant(value * 1000);
+                method.pop();
+            }
+        });
+        let loads: Vec<_> = code.iter().filter(|instruction| !matches!(instruction, Instruction::Pop | Instruction::Return)).collect();
+        assert_eq!(loads.len(), 300);
+        for load in loads {
+            match *load {
+                Instruction::LoadConstant(index) => assert!(index > 0),
+                Instruction::LoadConstantWide(index) => assert!(index > 255),
+                ref instruction => panic!("expected ldc or ldc_w, got {:?}", instruction),
+            }
+        }
+        assert!(code.iter().any(|instruction| matches!(instruction, Instruction::LoadConstantWide(_))));
+    }
+
+    #[test]
+    fn reuses_constants() {
+        let code = code(|method| {
+            method.load_constant("hello");
+            method.load_constant("hello");
+            method.instruction(Instruction::Pop2);
+        });
+        assert_eq!(code[0], code[1]);
+    }
+
+    #[test]
+    fn merges_classes_to_their_common_superclass() {
+        // Number n = b ? Integer.valueOf(1) : Long.valueOf(2); return n.intValue();
+        let mut class = ClassBuilder::new(ACC_PUBLIC, "Test", "java/lang/Object");
+        class.common_superclass(|a, b| match (a, b) {
+            ("java/lang/Integer", "java/lang/Long") | ("java/lang/Long", "java/lang/Integer") => "java/lang/Number".to_owned(),
+            _ => "java/lang/Object".to_owned(),
+        });
+        {
+            let mut method = class.define_method(ACC_PUBLIC | ACC_STATIC, "test", &[Java::Boolean], &Java::Int);
+            let long = method.new_label();
+            let join = method.new_label();
+            method.iload(0);
+            method.ifeq(long);
+            method.iconst1();
+            method.invoke_static("java/lang/Integer", "valueOf", &[Java::Int], &Java::Class("java/lang/Integer".into()));
+            method.goto(join);
+            method.label(long);
+            method.load_constant(2i64);
+            method.invoke_static("java/lang/Long", "valueOf", &[Java::Long], &Java::Class("java/lang/Long".into()));
+            method.label(join);
+            method.invoke_virtual("java/lang/Number", "intValue", &[], &Java::Int);
+            method.do_return();
+            method.done().unwrap();
+        }
+        let classfile = class.done().unwrap();
+        let frames = stack_map(&classfile);
+        assert_eq!(frames.len(), 2);
+        match frames[1] {
+            StackMapFrame::SameLocals1StackItemFrame(_, VerificationType::Object(index)) => {
+                assert_eq!(classfile.constant_pool.get_class_name(index), Some("java/lang/Number"));
+            },
+            ref frame => panic!("expected a frame with one stack item, got {:?}", frame),
+        }
+    }
+
+    #[test]
+    fn replaces_the_source_file_and_debug_extension() {
+        let mut class = ClassBuilder::new(ACC_PUBLIC, "Test", "java/lang/Object");
+        class.source_file("Old.java");
+        class.source_debug_extension("old");
+        class.source_file("Test.java");
+        class.source_debug_extension("new");
+        let classfile = class.done().unwrap();
+        let source_files: Vec<_> = classfile.attributes.iter().filter_map(|attribute| match *attribute {
+            Attribute::SourceFile(_, index) => classfile.constant_pool.get_utf8(index),
+            _ => None,
+        }).collect();
+        assert_eq!(source_files, vec!["Test.java"]);
+        let debug_extensions: Vec<_> = classfile.attributes.iter().filter_map(|attribute| match *attribute {
+            Attribute::SourceDebugExtension(_, ref text) => Some(text.as_str()),
+            _ => None,
+        }).collect();
+        assert_eq!(debug_extensions, vec!["new"]);
+    }
+
+    #[test]
+    fn branches_backwards_in_loops() {
+        let code = code(|method| {
+            let top = method.new_label();
+            method.iconst0();
+            method.istore(0);
+            method.label(top);
+            method.iinc(0, 1);
+            method.iload(0);
+            method.bipush(10);
+            method.if_icmp_lt(top);
+        });
+        let pcs = pcs(&code);
+        let top = code.iter().position(|instruction| matches!(instruction, Instruction::Iinc(..))).unwrap();
+        let branch = code.iter().position(|instruction| matches!(instruction, Instruction::IfIcmpLt(_))).unwrap();
+        assert_eq!(code[branch], Instruction::IfIcmpLt(pcs[top] as i16 - pcs[branch] as i16));
+    }
+
+    #[test]
+    fn inverts_conditional_branches_around_goto_w_when_they_are_too_far() {
+        let code = code(|method| {
+            let end = method.new_label();
+            method.iconst0();
+            method.ifeq(end);
+            for _ in 0..33000 {
+                method.instruction(Instruction::Nop);
+            }
+            method.label(end);
+        });
+        // ifne jumps over the goto_w to the nops, and the goto_w (at pc 4) jumps to the return
+        assert_eq!(code[1], Instruction::IfNe(3 + 5));
+        assert_eq!(code[2], Instruction::GotoW(1 + 3 + 5 + 33000 - 4));
+        assert_eq!(code.last(), Some(&Instruction::Return));
+    }
+
+    #[test]
+    fn pads_table_switches_to_a_multiple_of_four() {
+        for padding in 0..4 {
+            let classfile = class(|method| {
+                let (case, default) = (method.new_label(), method.new_label());
+                for _ in 0..padding {
+                    method.instruction(Instruction::Nop);
+                }
+                method.iconst0();
+                method.table_switch(0, 3, &[case; 4], default);
+                method.label(case);
+                method.label(default);
+            });
+            let code = match classfile.methods[0].attributes[0] {
+                Attribute::Code(_, _, _, ref instructions, _, _) => instructions.clone(),
+                ref attribute => panic!("expected a Code attribute, got {:?}", attribute),
+            };
+            let switch_pc = padding + 1;
+            let return_pc = pcs(&code)[padding + 2] as usize;
+            let offset = (return_pc - switch_pc) as i32;
+            assert_eq!(code[padding + 1], Instruction::TableSwitch(offset, 0, vec![offset; 4]));
+            assert_eq!(return_pc, switch_pc + 1 + (3 - switch_pc % 4) + 12 + 16);
+
+            // in the written code, the operands start at the next multiple of 4 after the opcode
+            let mut bytes = vec![];
+            classfile.serialize(&mut bytes).unwrap();
+            let mut code_bytes = (return_pc as u32 + 1).to_be_bytes().to_vec(); // code_length
+            code_bytes.extend(vec![0; padding]);
+            code_bytes.extend(&[0x03, 0xAA]);
+            let code_start = bytes.windows(code_bytes.len()).position(|window| window == &code_bytes[..]).unwrap() + 4;
+            let operands = code_start + (switch_pc + 4) / 4 * 4;
+            assert!(bytes[code_start + switch_pc + 1..operands].iter().all(|&b| b == 0));
+            assert_eq!(&bytes[operands..operands + 12], &[offset.to_be_bytes(), 0i32.to_be_bytes(), 3i32.to_be_bytes()].concat()[..]);
+        }
+    }
+
+    #[test]
+    fn uses_table_switches_for_dense_keys_and_lookup_switches_for_sparse_ones() {
+        let switch = |keys: &'static [i32]| {
+            let code = code(|method| {
+                let (case, default) = (method.new_label(), method.new_label());
+                method.iconst0();
+                method.lookup_switch(&keys.iter().map(|&key| (key, case)).collect::<Vec<_>>(), default);
+                method.label(case);
+                method.label(default);
+            });
+            code[1].clone()
+        };
+        assert!(matches!(switch(&[1, 2, 3, 5]), Instruction::TableSwitch(_, 1, ref offsets) if offsets.len() == 5));
+        assert!(matches!(switch(&[1, 1000, 1000000]), Instruction::LookupSwitch(_, ref pairs) if pairs.len() == 3));
+        assert!(matches!(switch(&[]), Instruction::LookupSwitch(_, ref pairs) if pairs.is_empty()));
+    }
+
+    #[test]
+    fn compresses_frames() {
+        let classfile = class(|method| {
+            let labels: Vec<Label> = (0..7).map(|_| method.new_label()).collect();
+            // same
+            method.iconst0();
+            method.ifeq(labels[0]);
+            method.label(labels[0]);
+            // same_locals_1_stack_item
+            method.iconst0();
+            method.iconst0();
+            method.ifeq(labels[1]);
+            method.label(labels[1]);
+            method.pop();
+            // append
+            method.iconst0();
+            method.istore(0);
+            method.iconst0();
+            method.ifeq(labels[2]);
+            method.label(labels[2]);
+            // chop, as local 0 is an int on one path and null on the other
+            method.iconst0();
+            method.ifeq(labels[3]);
+            method.instruction(Instruction::AconstNull);
+            method.instruction(Instruction::Astore0);
+            method.label(labels[3]);
+            // full, with a new local and something on the stack
+            method.iconst0();
+            method.istore(0);
+            method.iconst0();
+            method.iconst0();
+            method.ifeq(labels[4]);
+            method.label(labels[4]);
+            method.pop();
+            // same_frame_extended and same_locals_1_stack_item_frame_extended, more than 63 bytes after the last frame
+            method.iconst0();
+            method.ifeq(labels[5]);
+            for _ in 0..64 {
+                method.instruction(Instruction::Nop);
+            }
+            method.label(labels[5]);
+            method.iconst0();
+            method.iconst0();
+            method.ifeq(labels[6]);
+            for _ in 0..64 {
+                method.instruction(Instruction::Nop);
+            }
+            method.label(labels[6]);
+            method.pop();
+        });
+        assert_eq!(stack_map(&classfile), vec![
+            StackMapFrame::SameFrame(4),
+            StackMapFrame::SameLocals1StackItemFrame(4, VerificationType::Integer),
+            StackMapFrame::AppendFrame(1, 7, vec![VerificationType::Integer]),
+            StackMapFrame::ChopFrame(1, 5),
+            StackMapFrame::FullFrame(7, vec![VerificationType::Integer], vec![VerificationType::Integer]),
+            StackMapFrame::SameFrameExtended(68),
+            StackMapFrame::SameLocals1StackItemFrameExtended(68, VerificationType::Integer),
+        ]);
+    }
+
+    #[test]
+    fn counts_long_and_double_arguments_as_two_stack_slots() {
+        let max_stack = |build: fn(&mut MethodBuilder)| match class(build).methods[0].attributes[0] {
+            Attribute::Code(_, max_stack, _, _, _, _) => max_stack,
+            ref attribute => panic!("expected a Code attribute, got {:?}", attribute),
+        };
+        assert_eq!(max_stack(|method| {
+            method.load_constant(1i64);
+            method.load_constant(2.0f64);
+            method.invoke_static("Test", "f", &[Java::Long, Java::Double], &Java::Long);
+            method.instruction(Instruction::Pop2);
+        }), 4);
+        assert_eq!(max_stack(|method| {
+            method.instruction(Instruction::AconstNull);
+            method.load_constant(1i64);
+            method.invoke_virtual("Test", "g", &[Java::Long], &Java::Double);
+            method.instruction(Instruction::Pop2);
+        }), 3);
+    }
+}
@@ -1,3 +1,11 @@
+use std::any::Any;
+use std::collections::HashMap;
+use std::fmt;
+
+use constant_pool::*;
+use java_type_signatures::*;
+use serialization::{decode_modified_utf8_units, encode_modified_utf8};
+
 const CAFEBABE: u32 = 0xCAFEBABE;
 const MAJOR_VERSION: u16 = 52;
 const MINOR_VERSION: u16 = 0;
@@ -7,11 +15,11 @@ pub struct Classfile {
     pub magic: u32,
     pub minor_version: u16,
     pub major_version: u16,
-    pub constant_pool: Vec<Constant>,
+    pub constant_pool: ConstantPool,
     pub access_flags: u16,
     pub this_class: u16,
     pub super_class: u16,
-    pub interfaces: Vec<Interface>,
+    pub interfaces: Vec<u16>,
     pub fields: Vec<Field>,
     pub methods: Vec<Method>,
     pub attributes: Vec<Attribute>,
@@ -19,19 +27,102 @@ pub struct Classfile {
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum Constant {
-    Utf8(String),          //  1
-    Class(u16),            //  7
-    String(u16),           //  8
-    Fieldref(u16, u16),    //  9
-    Methodref(u16, u16),   // 10
-    NameAndType(u16, u16), // 12
+    Utf8(JavaString),              //  1
+    Integer(i32),                  //  3
+    Float(f32),                    //  4
+    Long(i64),                     //  5
+    Double(f64),                   //  6
+    Class(u16),                    //  7
+    String(u16),                   //  8
+    Fieldref(u16, u16),            //  9
+    Methodref(u16, u16),           // 10
+    InterfaceMethodref(u16, u16),  // 11
+    NameAndType(u16, u16),         // 12
+    MethodHandle(u8, u16),         // 15
+    MethodType(u16),               // 16
+    Dynamic(u16, u16),             // 17
+    InvokeDynamic(u16, u16),       // 18
+    Module(u16),                   // 19
+    Package(u16),                  // 20
+    Unusable,                      // second slot taken up by a Long or Double
 }
 
-#[derive(Clone, Debug, PartialEq)]
-pub struct Interface;
+// The text of a Utf8 constant. Java strings are UTF-16, and can hold unpaired surrogates that a Rust String can't, so
+// for those the text has U+FFFD in their place and the bytes that were read are kept to be written back out.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct JavaString {
+    text: String,
+    bytes: Option<Vec<u8>>, // the modified UTF-8 read from a class file, if the text doesn't encode back to it
+}
+
+impl JavaString {
+    pub fn new(text: &str) -> JavaString {
+        JavaString { text: text.to_owned(), bytes: None }
+    }
+
+    // Decodes modified UTF-8, keeping the bytes unless the text encodes back to exactly them. Returns None for
+    // malformed input.
+    pub fn from_modified_utf8(bytes: &[u8]) -> Option<JavaString> {
+        let text = String::from_utf16_lossy(&decode_modified_utf8_units(bytes)?);
+        let bytes = if encode_modified_utf8(&text) == bytes { None } else { Some(bytes.to_vec()) };
+        Some(JavaString { text, bytes })
+    }
+
+    pub fn to_modified_utf8(&self) -> Vec<u8> {
+        match self.bytes {
+            Some(ref bytes) => bytes.clone(),
+            None => encode_modified_utf8(&self.text),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    // Whether the bytes read from the class file were kept because the text can't stand in for them, e.g. when they
+    // encode an unpaired surrogate
+    pub fn keeps_original_bytes(&self) -> bool {
+        self.bytes.is_some()
+    }
+}
+
+impl<'a> From<&'a str> for JavaString {
+    fn from(text: &'a str) -> JavaString {
+        JavaString::new(text)
+    }
+}
+
+impl fmt::Display for JavaString {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+// reference kinds for Constant::MethodHandle
+pub const REF_GET_FIELD: u8 = 1;
+pub const REF_GET_STATIC: u8 = 2;
+pub const REF_PUT_FIELD: u8 = 3;
+pub const REF_PUT_STATIC: u8 = 4;
+pub const REF_INVOKE_VIRTUAL: u8 = 5;
+pub const REF_INVOKE_STATIC: u8 = 6;
+pub const REF_INVOKE_SPECIAL: u8 = 7;
+pub const REF_NEW_INVOKE_SPECIAL: u8 = 8;
+pub const REF_INVOKE_INTERFACE: u8 = 9;
+
+// the reference kinds by name, as the assembler and disassembler write them, e.g. invokestatic for REF_INVOKE_STATIC
+pub const HANDLE_KINDS: &[(&str, u8)] = &[
+    ("getfield", REF_GET_FIELD), ("getstatic", REF_GET_STATIC), ("putfield", REF_PUT_FIELD), ("putstatic", REF_PUT_STATIC),
+    ("invokevirtual", REF_INVOKE_VIRTUAL), ("invokestatic", REF_INVOKE_STATIC), ("invokespecial", REF_INVOKE_SPECIAL),
+    ("newinvokespecial", REF_NEW_INVOKE_SPECIAL), ("invokeinterface", REF_INVOKE_INTERFACE),
+];
 
 #[derive(Clone, Debug, PartialEq)]
-pub struct Field;
+pub struct Field {
+    pub access_flags: u16,
+    pub name_index: u16,
+    pub descriptor_index: u16,
+    pub attributes: Vec<Attribute>,
+}
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Method {
@@ -44,13 +135,104 @@ pub struct Method {
 #[derive(Clone, Debug, PartialEq)]
 pub enum Attribute {
     Code(u16, u16, u16, Vec<Instruction>, Vec<ExceptionTableEntry>, Vec<Attribute>),
+    AnnotationDefault(u16, ElementValue),
+    BootstrapMethods(u16, Vec<BootstrapMethod>),
+    ConstantValue(u16, u16),
+    Deprecated(u16),
+    EnclosingMethod(u16, u16, u16), // the class, and the method's NameAndType (0 outside a method)
+    Exceptions(u16, Vec<u16>),      // the classes in the method's throws clause
+    InnerClasses(u16, Vec<InnerClass>),
     LineNumberTable(u16, Vec<LineNumberTableEntry>),
+    LocalVariableTable(u16, Vec<LocalVariableTableEntry>),
+    LocalVariableTypeTable(u16, Vec<LocalVariableTypeTableEntry>),
+    NestHost(u16, u16),
+    NestMembers(u16, Vec<u16>),
+    RuntimeVisibleAnnotations(u16, Vec<Annotation>),
+    RuntimeInvisibleAnnotations(u16, Vec<Annotation>),
+    RuntimeVisibleParameterAnnotations(u16, Vec<Vec<Annotation>>), // the annotations of each parameter
+    RuntimeInvisibleParameterAnnotations(u16, Vec<Vec<Annotation>>),
+    RuntimeVisibleTypeAnnotations(u16, Vec<TypeAnnotation>),
+    RuntimeInvisibleTypeAnnotations(u16, Vec<TypeAnnotation>),
+    Signature(u16, u16),
+    SourceDebugExtension(u16, String), // e.g. a JSR-45 SMAP, see SourceMap
     SourceFile(u16, u16),
     StackMapTable(u16, Vec<StackMapFrame>),
+    Synthetic(u16),
+    Custom { name_index: u16, value: Box<dyn CustomAttribute> }, // decoded by a decoder in AttributeDecoders
+    Unknown { name_index: u16, bytes: Vec<u8> },                  // kept as is, so it's written back unchanged
+}
+
+// An attribute that this crate doesn't understand, decoded by a decoder registered with AttributeDecoders (e.g.
+// Scala's ScalaSig, or Kotlin's metadata).
+pub trait CustomAttribute: fmt::Debug {
+    // the attribute's body, as written to the class file
+    fn encode(&self) -> Vec<u8>;
+
+    fn clone_box(&self) -> Box<dyn CustomAttribute>;
+
+    // for getting back to the decoder's own type, with downcast_ref
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl Clone for Box<dyn CustomAttribute> {
+    fn clone(&self) -> Box<dyn CustomAttribute> {
+        self.clone_box()
+    }
+}
+
+// custom attributes are the same if they would be written the same
+impl PartialEq for Box<dyn CustomAttribute> {
+    fn eq(&self, other: &Box<dyn CustomAttribute>) -> bool {
+        self.encode() == other.encode()
+    }
+}
+
+// Decodes the body of an attribute, given the constant pool of the class file it's in.
+pub type AttributeDecoder = fn(&[u8], &ConstantPool) -> Result<Box<dyn CustomAttribute>, String>;
+
+// Decoders for attributes that this crate doesn't understand, by attribute name. Attributes without a decoder are
+// read as Attribute::Unknown.
+#[derive(Clone, Debug, Default)]
+pub struct AttributeDecoders {
+    decoders: HashMap<String, AttributeDecoder>,
+}
+
+impl AttributeDecoders {
+    pub fn new() -> AttributeDecoders {
+        AttributeDecoders { decoders: HashMap::new() }
+    }
+
+    pub fn register(&mut self, name: &str, decoder: AttributeDecoder) {
+        self.decoders.insert(name.to_owned(), decoder);
+    }
+
+    pub fn get(&self, name: &str) -> Option<AttributeDecoder> {
+        self.decoders.get(name).cloned()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExceptionTableEntry {
+    pub start_pc: u16,
+    pub end_pc: u16,
+    pub handler_pc: u16,
+    pub catch_type: u16, // 0 catches everything (used for finally blocks)
 }
 
 #[derive(Clone, Debug, PartialEq)]
-pub struct ExceptionTableEntry;
+pub struct BootstrapMethod {
+    pub bootstrap_method_ref: u16,     // index of a MethodHandle constant
+    pub bootstrap_arguments: Vec<u16>, // indices of loadable constants
+}
+
+// a class that's a member of another, or local or anonymous, as the compiler saw it
+#[derive(Clone, Debug, PartialEq)]
+pub struct InnerClass {
+    pub inner_class_info_index: u16,
+    pub outer_class_info_index: u16, // 0 for local and anonymous classes
+    pub inner_name_index: u16,       // 0 for anonymous classes
+    pub inner_class_access_flags: u16,
+}
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct LineNumberTableEntry {
@@ -58,6 +240,93 @@ pub struct LineNumberTableEntry {
     pub line_number: u16,
 }
 
+// a named local variable, live from start_pc for length bytes of code, for debuggers
+#[derive(Clone, Debug, PartialEq)]
+pub struct LocalVariableTableEntry {
+    pub start_pc: u16,
+    pub length: u16,
+    pub name_index: u16,
+    pub descriptor_index: u16,
+    pub index: u16, // the local variable slot
+}
+
+// the generic type of a local variable that has one, alongside its LocalVariableTableEntry
+#[derive(Clone, Debug, PartialEq)]
+pub struct LocalVariableTypeTableEntry {
+    pub start_pc: u16,
+    pub length: u16,
+    pub name_index: u16,
+    pub signature_index: u16,
+    pub index: u16,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Annotation {
+    pub type_index: u16, // a field descriptor, e.g. Lorg/junit/Test;
+    pub element_value_pairs: Vec<ElementValuePair>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ElementValuePair {
+    pub element_name_index: u16,
+    pub value: ElementValue,
+}
+
+// The value of an annotation element. Booleans, bytes, chars and shorts are Integer constants, and strings are Utf8
+// constants rather than String constants.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ElementValue {
+    Byte(u16),                // B
+    Char(u16),                // C
+    Double(u16),              // D
+    Float(u16),               // F
+    Int(u16),                 // I
+    Long(u16),                // J
+    Short(u16),               // S
+    Boolean(u16),             // Z
+    String(u16),              // s
+    Enum(u16, u16),           // e, the enum's field descriptor and the name of the constant
+    Class(u16),               // c, a return descriptor, e.g. Ljava/lang/String; or V
+    Annotation(Annotation),   // @
+    Array(Vec<ElementValue>), // [
+}
+
+// An annotation on a use of a type, e.g. the @NonNull in List<@NonNull String>
+#[derive(Clone, Debug, PartialEq)]
+pub struct TypeAnnotation {
+    pub target_type: u8, // what kind of type use it's on, which decides the kind of target_info
+    pub target_info: TargetInfo,
+    pub type_path: Vec<TypePathEntry>, // where in the type it is, e.g. the first type argument
+    pub annotation: Annotation,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TargetInfo {
+    TypeParameter(u8),                       // 0x00 and 0x01
+    Supertype(u16),                          // 0x10, an index into the interfaces, or 65535 for the superclass
+    TypeParameterBound(u8, u8),              // 0x11 and 0x12, the type parameter and bound indices
+    Empty,                                   // 0x13 to 0x15, a field type, return type or receiver type
+    FormalParameter(u8),                     // 0x16
+    Throws(u16),                             // 0x17, an index into the Exceptions attribute
+    LocalVariable(Vec<LocalVariableTarget>), // 0x40 and 0x41
+    Catch(u16),                              // 0x42, an index into the exception table
+    Offset(u16),                             // 0x43 to 0x46, the pc of an instanceof, new or method reference
+    TypeArgument(u16, u8),                   // 0x47 to 0x4B, the pc of a cast or call, and the type argument index
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LocalVariableTarget {
+    pub start_pc: u16,
+    pub length: u16,
+    pub index: u16,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TypePathEntry {
+    pub type_path_kind: u8, // 0 for an array's component type, 1 for a nested type, 2 for a wildcard's bound and 3 for a type argument
+    pub type_argument_index: u8,
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum StackMapFrame {
     SameFrame(u8),
@@ -74,8 +343,8 @@ pub enum VerificationType {
     Top,                // 0
     Integer,            // 1
     Float,              // 2
-    Long,               // 3
-    Double,             // 4
+    Double,             // 3
+    Long,               // 4
     Null,               // 5
     UninitializedThis,  // 6
     Object(u16),        // 7
@@ -84,86 +353,325 @@ pub enum VerificationType {
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum Instruction {
-    IconstM1,           // 0x02
-    Iconst0,            // 0x03
-    Iconst1,            // 0x04
-    Iconst2,            // 0x05
-    Iconst3,            // 0x06
-    Iconst4,            // 0x07
-    Iconst5,            // 0x08
-    Bipush(u8),         // 0x10
-    LoadConstant(u8),   // 0x12
-    Aload0,             // 0x2A
-    Aload1,             // 0x2B
-    Aload2,             // 0x2C
-    Aload3,             // 0x2D
-    Aaload,             // 0x32
-    Iadd,               // 0x60
-    IfEq(u16),          // 0x99
-    IfNe(u16),          // 0x9A
-    IfLt(u16),          // 0x9B
-    IfGe(u16),          // 0x9C
-    IfGt(u16),          // 0x9D
-    IfLe(u16),          // 0x9E
-    IfIcmpEq(u16),      // 0x9F
-    IfIcmpNe(u16),      // 0xA0
-    IfIcmpLt(u16),      // 0xA1
-    IfIcmpGe(u16),      // 0xA2
-    IfIcmpGt(u16),      // 0xA3
-    IfIcmpLe(u16),      // 0xA4
-    Goto(u16),          // 0xA7
-    Return,             // 0xB1
-    GetStatic(u16),     // 0xB2
-    InvokeVirtual(u16), // 0xB6
-    InvokeSpecial(u16), // 0xB7
-    InvokeStatic(u16),  // 0xB8
-    ArrayLength,        // 0xBE
+    Nop,                                 // 0x00
+    AconstNull,                          // 0x01
+    IconstM1,                            // 0x02
+    Iconst0,                             // 0x03
+    Iconst1,                             // 0x04
+    Iconst2,                             // 0x05
+    Iconst3,                             // 0x06
+    Iconst4,                             // 0x07
+    Iconst5,                             // 0x08
+    Lconst0,                             // 0x09
+    Lconst1,                             // 0x0A
+    Fconst0,                             // 0x0B
+    Fconst1,                             // 0x0C
+    Fconst2,                             // 0x0D
+    Dconst0,                             // 0x0E
+    Dconst1,                             // 0x0F
+    Bipush(u8),                          // 0x10
+    Sipush(u16),                         // 0x11
+    LoadConstant(u8),                    // 0x12
+    LoadConstantWide(u16),               // 0x13
+    LoadConstant2Wide(u16),              // 0x14
+    Iload(u8),                           // 0x15
+    Lload(u8),                           // 0x16
+    Fload(u8),                           // 0x17
+    Dload(u8),                           // 0x18
+    Aload(u8),                           // 0x19
+    Iload0,                              // 0x1A
+    Iload1,                              // 0x1B
+    Iload2,                              // 0x1C
+    Iload3,                              // 0x1D
+    Lload0,                              // 0x1E
+    Lload1,                              // 0x1F
+    Lload2,                              // 0x20
+    Lload3,                              // 0x21
+    Fload0,                              // 0x22
+    Fload1,                              // 0x23
+    Fload2,                              // 0x24
+    Fload3,                              // 0x25
+    Dload0,                              // 0x26
+    Dload1,                              // 0x27
+    Dload2,                              // 0x28
+    Dload3,                              // 0x29
+    Aload0,                              // 0x2A
+    Aload1,                              // 0x2B
+    Aload2,                              // 0x2C
+    Aload3,                              // 0x2D
+    Iaload,                              // 0x2E
+    Laload,                              // 0x2F
+    Faload,                              // 0x30
+    Daload,                              // 0x31
+    Aaload,                              // 0x32
+    Baload,                              // 0x33
+    Caload,                              // 0x34
+    Saload,                              // 0x35
+    Istore(u8),                          // 0x36
+    Lstore(u8),                          // 0x37
+    Fstore(u8),                          // 0x38
+    Dstore(u8),                          // 0x39
+    Astore(u8),                          // 0x3A
+    Istore0,                             // 0x3B
+    Istore1,                             // 0x3C
+    Istore2,                             // 0x3D
+    Istore3,                             // 0x3E
+    Lstore0,                             // 0x3F
+    Lstore1,                             // 0x40
+    Lstore2,                             // 0x41
+    Lstore3,                             // 0x42
+    Fstore0,                             // 0x43
+    Fstore1,                             // 0x44
+    Fstore2,                             // 0x45
+    Fstore3,                             // 0x46
+    Dstore0,                             // 0x47
+    Dstore1,                             // 0x48
+    Dstore2,                             // 0x49
+    Dstore3,                             // 0x4A
+    Astore0,                             // 0x4B
+    Astore1,                             // 0x4C
+    Astore2,                             // 0x4D
+    Astore3,                             // 0x4E
+    Iastore,                             // 0x4F
+    Lastore,                             // 0x50
+    Fastore,                             // 0x51
+    Dastore,                             // 0x52
+    Aastore,                             // 0x53
+    Bastore,                             // 0x54
+    Castore,                             // 0x55
+    Sastore,                             // 0x56
+    Pop,                                 // 0x57
+    Pop2,                                // 0x58
+    Dup,                                 // 0x59
+    DupX1,                               // 0x5A
+    DupX2,                               // 0x5B
+    Dup2,                                // 0x5C
+    Dup2X1,                              // 0x5D
+    Dup2X2,                              // 0x5E
+    Swap,                                // 0x5F
+    Iadd,                                // 0x60
+    Ladd,                                // 0x61
+    Fadd,                                // 0x62
+    Dadd,                                // 0x63
+    Isub,                                // 0x64
+    Lsub,                                // 0x65
+    Fsub,                                // 0x66
+    Dsub,                                // 0x67
+    Imul,                                // 0x68
+    Lmul,                                // 0x69
+    Fmul,                                // 0x6A
+    Dmul,                                // 0x6B
+    Idiv,                                // 0x6C
+    Ldiv,                                // 0x6D
+    Fdiv,                                // 0x6E
+    Ddiv,                                // 0x6F
+    Irem,                                // 0x70
+    Lrem,                                // 0x71
+    Frem,                                // 0x72
+    Drem,                                // 0x73
+    Ineg,                                // 0x74
+    Lneg,                                // 0x75
+    Fneg,                                // 0x76
+    Dneg,                                // 0x77
+    Ishl,                                // 0x78
+    Lshl,                                // 0x79
+    Ishr,                                // 0x7A
+    Lshr,                                // 0x7B
+    Iushr,                               // 0x7C
+    Lushr,                               // 0x7D
+    Iand,                                // 0x7E
+    Land,                                // 0x7F
+    Ior,                                 // 0x80
+    Lor,                                 // 0x81
+    Ixor,                                // 0x82
+    Lxor,                                // 0x83
+    Iinc(u8, u8),                        // 0x84 (index, const)
+    I2l,                                 // 0x85
+    I2f,                                 // 0x86
+    I2d,                                 // 0x87
+    L2i,                                 // 0x88
+    L2f,                                 // 0x89
+    L2d,                                 // 0x8A
+    F2i,                                 // 0x8B
+    F2l,                                 // 0x8C
+    F2d,                                 // 0x8D
+    D2i,                                 // 0x8E
+    D2l,                                 // 0x8F
+    D2f,                                 // 0x90
+    I2b,                                 // 0x91
+    I2c,                                 // 0x92
+    I2s,                                 // 0x93
+    Lcmp,                                // 0x94
+    Fcmpl,                               // 0x95
+    Fcmpg,                               // 0x96
+    Dcmpl,                               // 0x97
+    Dcmpg,                               // 0x98
+    IfEq(i16),                           // 0x99
+    IfNe(i16),                           // 0x9A
+    IfLt(i16),                           // 0x9B
+    IfGe(i16),                           // 0x9C
+    IfGt(i16),                           // 0x9D
+    IfLe(i16),                           // 0x9E
+    IfIcmpEq(i16),                       // 0x9F
+    IfIcmpNe(i16),                       // 0xA0
+    IfIcmpLt(i16),                       // 0xA1
+    IfIcmpGe(i16),                       // 0xA2
+    IfIcmpGt(i16),                       // 0xA3
+    IfIcmpLe(i16),                       // 0xA4
+    IfAcmpEq(i16),                       // 0xA5
+    IfAcmpNe(i16),                       // 0xA6
+    Goto(i16),                           // 0xA7
+    Jsr(i16),                            // 0xA8
+    Ret(u8),                             // 0xA9
+    TableSwitch(i32, i32, Vec<i32>),     // 0xAA (default, low, offsets)
+    LookupSwitch(i32, Vec<(i32, i32)>),  // 0xAB (default, match/offset pairs)
+    Ireturn,                             // 0xAC
+    Lreturn,                             // 0xAD
+    Freturn,                             // 0xAE
+    Dreturn,                             // 0xAF
+    Areturn,                             // 0xB0
+    Return,                              // 0xB1
+    GetStatic(u16),                      // 0xB2
+    PutStatic(u16),                      // 0xB3
+    GetField(u16),                       // 0xB4
+    PutField(u16),                       // 0xB5
+    InvokeVirtual(u16),                  // 0xB6
+    InvokeSpecial(u16),                  // 0xB7
+    InvokeStatic(u16),                   // 0xB8
+    InvokeInterface(u16, u8),            // 0xB9 (index, count)
+    InvokeDynamic(u16),                  // 0xBA
+    New(u16),                            // 0xBB
+    NewArray(u8),                        // 0xBC
+    ANewArray(u16),                      // 0xBD
+    ArrayLength,                         // 0xBE
+    Athrow,                              // 0xBF
+    CheckCast(u16),                      // 0xC0
+    InstanceOf(u16),                     // 0xC1
+    MonitorEnter,                        // 0xC2
+    MonitorExit,                         // 0xC3
+    Wide(WideInstruction),               // 0xC4
+    MultiANewArray(u16, u8),             // 0xC5 (index, dimensions)
+    IfNull(i16),                         // 0xC6
+    IfNonNull(i16),                      // 0xC7
+    GotoW(i32),                          // 0xC8
+    JsrW(i32),                           // 0xC9
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum WideInstruction {
+    Iload(u16),       // 0x15
+    Lload(u16),       // 0x16
+    Fload(u16),       // 0x17
+    Dload(u16),       // 0x18
+    Aload(u16),       // 0x19
+    Istore(u16),      // 0x36
+    Lstore(u16),      // 0x37
+    Fstore(u16),      // 0x38
+    Dstore(u16),      // 0x39
+    Astore(u16),      // 0x3A
+    Iinc(u16, u16),   // 0x84 (index, const)
+    Ret(u16),         // 0xA9
 }
 
 impl Classfile {
-    pub fn new(constants: Vec<Constant>, access_flags: u16, this_class: u16, super_class: u16, methods: Vec<Method>) -> Classfile {
+    pub fn new(constant_pool: ConstantPool, access_flags: u16, this_class: u16, super_class: u16, interfaces: Vec<u16>, fields: Vec<Field>, methods: Vec<Method>) -> Classfile {
         Classfile {
             magic: CAFEBABE,
             minor_version: MINOR_VERSION,
             major_version: MAJOR_VERSION,
-            constant_pool: constants,
-            access_flags: access_flags,
-            this_class: this_class,
-            super_class: super_class,
-            interfaces: vec![],
-            fields: vec![],
-            methods: methods,
+            constant_pool,
+            access_flags,
+            this_class,
+            super_class,
+            interfaces,
+            fields,
+            methods,
             attributes: vec![],
         }
     }
 
     pub fn lookup_constant(&self, index: u16) -> &Constant {
-        &self.constant_pool[index as usize - 1]
+        self.constant_pool.get(index).unwrap_or_else(|| panic!("Invalid constant pool index {}", index))
     }
 
     pub fn lookup_string(&self, index: u16) -> &str {
         let val = self.lookup_constant(index);
         match *val {
-            Constant::Utf8(ref str) => str,
+            Constant::Utf8(ref string) => string.as_str(),
             _ => panic!("Wanted string, found {:?}", val)
         }
     }
 }
 
+impl Constant {
+    // Long and Double constants take up two entries in the constant pool
+    pub fn is_wide(&self) -> bool {
+        matches!(*self, Constant::Long(_) | Constant::Double(_))
+    }
+}
+
+impl Attribute {
+    pub fn name_index(&self) -> u16 {
+        match *self {
+            Attribute::Code(name_index, _, _, _, _, _) => name_index,
+            Attribute::AnnotationDefault(name_index, _) => name_index,
+            Attribute::BootstrapMethods(name_index, _) => name_index,
+            Attribute::ConstantValue(name_index, _) => name_index,
+            Attribute::Deprecated(name_index) => name_index,
+            Attribute::EnclosingMethod(name_index, _, _) => name_index,
+            Attribute::Exceptions(name_index, _) => name_index,
+            Attribute::InnerClasses(name_index, _) => name_index,
+            Attribute::LineNumberTable(name_index, _) => name_index,
+            Attribute::LocalVariableTable(name_index, _) => name_index,
+            Attribute::LocalVariableTypeTable(name_index, _) => name_index,
+            Attribute::NestHost(name_index, _) => name_index,
+            Attribute::NestMembers(name_index, _) => name_index,
+            Attribute::RuntimeVisibleAnnotations(name_index, _) => name_index,
+            Attribute::RuntimeInvisibleAnnotations(name_index, _) => name_index,
+            Attribute::RuntimeVisibleParameterAnnotations(name_index, _) => name_index,
+            Attribute::RuntimeInvisibleParameterAnnotations(name_index, _) => name_index,
+            Attribute::RuntimeVisibleTypeAnnotations(name_index, _) => name_index,
+            Attribute::RuntimeInvisibleTypeAnnotations(name_index, _) => name_index,
+            Attribute::Signature(name_index, _) => name_index,
+            Attribute::SourceDebugExtension(name_index, _) => name_index,
+            Attribute::SourceFile(name_index, _) => name_index,
+            Attribute::StackMapTable(name_index, _) => name_index,
+            Attribute::Synthetic(name_index) => name_index,
+            Attribute::Custom { name_index, .. } => name_index,
+            Attribute::Unknown { name_index, .. } => name_index,
+        }
+    }
+}
+
+impl Field {
+    pub fn new(access_flags: u16, name_index: u16, descriptor_index: u16, attributes: Vec<Attribute>) -> Field {
+        Field {
+            access_flags,
+            name_index,
+            descriptor_index,
+            attributes,
+        }
+    }
+}
+
 impl Method {
     pub fn new(access_flags: u16, name_index: u16, descriptor_index: u16, attributes: Vec<Attribute>) -> Method {
         Method {
-            access_flags: access_flags,
-            name_index: name_index,
-            descriptor_index: descriptor_index,
-            attributes: attributes,
+            access_flags,
+            name_index,
+            descriptor_index,
+            attributes,
         }
     }
 }
 
 impl Instruction {
-    pub fn size(&self) -> u8 {
+    // The size in bytes of this instruction when placed at the given offset in the method's code.
+    // Only tableswitch and lookupswitch depend on the offset, because of their alignment padding.
+    pub fn size(&self, pc: u16) -> u16 {
         match *self {
+            Instruction::Nop => 1,
+            Instruction::AconstNull => 1,
             Instruction::IconstM1 => 1,
             Instruction::Iconst0 => 1,
             Instruction::Iconst1 => 1,
@@ -171,14 +679,150 @@ impl Instruction {
             Instruction::Iconst3 => 1,
             Instruction::Iconst4 => 1,
             Instruction::Iconst5 => 1,
+            Instruction::Lconst0 => 1,
+            Instruction::Lconst1 => 1,
+            Instruction::Fconst0 => 1,
+            Instruction::Fconst1 => 1,
+            Instruction::Fconst2 => 1,
+            Instruction::Dconst0 => 1,
+            Instruction::Dconst1 => 1,
             Instruction::Bipush(_) => 2,
+            Instruction::Sipush(_) => 3,
             Instruction::LoadConstant(_) => 2,
+            Instruction::LoadConstantWide(_) => 3,
+            Instruction::LoadConstant2Wide(_) => 3,
+            Instruction::Iload(_) => 2,
+            Instruction::Lload(_) => 2,
+            Instruction::Fload(_) => 2,
+            Instruction::Dload(_) => 2,
+            Instruction::Aload(_) => 2,
+            Instruction::Iload0 => 1,
+            Instruction::Iload1 => 1,
+            Instruction::Iload2 => 1,
+            Instruction::Iload3 => 1,
+            Instruction::Lload0 => 1,
+            Instruction::Lload1 => 1,
+            Instruction::Lload2 => 1,
+            Instruction::Lload3 => 1,
+            Instruction::Fload0 => 1,
+            Instruction::Fload1 => 1,
+            Instruction::Fload2 => 1,
+            Instruction::Fload3 => 1,
+            Instruction::Dload0 => 1,
+            Instruction::Dload1 => 1,
+            Instruction::Dload2 => 1,
+            Instruction::Dload3 => 1,
             Instruction::Aload0 => 1,
             Instruction::Aload1 => 1,
             Instruction::Aload2 => 1,
             Instruction::Aload3 => 1,
+            Instruction::Iaload => 1,
+            Instruction::Laload => 1,
+            Instruction::Faload => 1,
+            Instruction::Daload => 1,
             Instruction::Aaload => 1,
+            Instruction::Baload => 1,
+            Instruction::Caload => 1,
+            Instruction::Saload => 1,
+            Instruction::Istore(_) => 2,
+            Instruction::Lstore(_) => 2,
+            Instruction::Fstore(_) => 2,
+            Instruction::Dstore(_) => 2,
+            Instruction::Astore(_) => 2,
+            Instruction::Istore0 => 1,
+            Instruction::Istore1 => 1,
+            Instruction::Istore2 => 1,
+            Instruction::Istore3 => 1,
+            Instruction::Lstore0 => 1,
+            Instruction::Lstore1 => 1,
+            Instruction::Lstore2 => 1,
+            Instruction::Lstore3 => 1,
+            Instruction::Fstore0 => 1,
+            Instruction::Fstore1 => 1,
+            Instruction::Fstore2 => 1,
+            Instruction::Fstore3 => 1,
+            Instruction::Dstore0 => 1,
+            Instruction::Dstore1 => 1,
+            Instruction::Dstore2 => 1,
+            Instruction::Dstore3 => 1,
+            Instruction::Astore0 => 1,
+            Instruction::Astore1 => 1,
+            Instruction::Astore2 => 1,
+            Instruction::Astore3 => 1,
+            Instruction::Iastore => 1,
+            Instruction::Lastore => 1,
+            Instruction::Fastore => 1,
+            Instruction::Dastore => 1,
+            Instruction::Aastore => 1,
+            Instruction::Bastore => 1,
+            Instruction::Castore => 1,
+            Instruction::Sastore => 1,
+            Instruction::Pop => 1,
+            Instruction::Pop2 => 1,
+            Instruction::Dup => 1,
+            Instruction::DupX1 => 1,
+            Instruction::DupX2 => 1,
+            Instruction::Dup2 => 1,
+            Instruction::Dup2X1 => 1,
+            Instruction::Dup2X2 => 1,
+            Instruction::Swap => 1,
             Instruction::Iadd => 1,
+            Instruction::Ladd => 1,
+            Instruction::Fadd => 1,
+            Instruction::Dadd => 1,
+            Instruction::Isub => 1,
+            Instruction::Lsub => 1,
+            Instruction::Fsub => 1,
+            Instruction::Dsub => 1,
+            Instruction::Imul => 1,
+            Instruction::Lmul => 1,
+            Instruction::Fmul => 1,
+            Instruction::Dmul => 1,
+            Instruction::Idiv => 1,
+            Instruction::Ldiv => 1,
+            Instruction::Fdiv => 1,
+            Instruction::Ddiv => 1,
+            Instruction::Irem => 1,
+            Instruction::Lrem => 1,
+            Instruction::Frem => 1,
+            Instruction::Drem => 1,
+            Instruction::Ineg => 1,
+            Instruction::Lneg => 1,
+            Instruction::Fneg => 1,
+            Instruction::Dneg => 1,
+            Instruction::Ishl => 1,
+            Instruction::Lshl => 1,
+            Instruction::Ishr => 1,
+            Instruction::Lshr => 1,
+            Instruction::Iushr => 1,
+            Instruction::Lushr => 1,
+            Instruction::Iand => 1,
+            Instruction::Land => 1,
+            Instruction::Ior => 1,
+            Instruction::Lor => 1,
+            Instruction::Ixor => 1,
+            Instruction::Lxor => 1,
+            Instruction::Iinc(_, _) => 3,
+            Instruction::I2l => 1,
+            Instruction::I2f => 1,
+            Instruction::I2d => 1,
+            Instruction::L2i => 1,
+            Instruction::L2f => 1,
+            Instruction::L2d => 1,
+            Instruction::F2i => 1,
+            Instruction::F2l => 1,
+            Instruction::F2d => 1,
+            Instruction::D2i => 1,
+            Instruction::D2l => 1,
+            Instruction::D2f => 1,
+            Instruction::I2b => 1,
+            Instruction::I2c => 1,
+            Instruction::I2s => 1,
+            Instruction::Lcmp => 1,
+            Instruction::Fcmpl => 1,
+            Instruction::Fcmpg => 1,
+            Instruction::Dcmpl => 1,
+            Instruction::Dcmpg => 1,
             Instruction::IfEq(_) => 3,
             Instruction::IfNe(_) => 3,
             Instruction::IfLt(_) => 3,
@@ -191,13 +835,433 @@ impl Instruction {
             Instruction::IfIcmpGe(_) => 3,
             Instruction::IfIcmpGt(_) => 3,
             Instruction::IfIcmpLe(_) => 3,
+            Instruction::IfAcmpEq(_) => 3,
+            Instruction::IfAcmpNe(_) => 3,
             Instruction::Goto(_) => 3,
+            Instruction::Jsr(_) => 3,
+            Instruction::Ret(_) => 2,
+            Instruction::TableSwitch(_, _, ref offsets) => 1 + switch_padding(pc) + 12 + 4 * offsets.len() as u16,
+            Instruction::LookupSwitch(_, ref pairs) => 1 + switch_padding(pc) + 8 + 8 * pairs.len() as u16,
+            Instruction::Ireturn => 1,
+            Instruction::Lreturn => 1,
+            Instruction::Freturn => 1,
+            Instruction::Dreturn => 1,
+            Instruction::Areturn => 1,
             Instruction::Return => 1,
             Instruction::GetStatic(_) => 3,
+            Instruction::PutStatic(_) => 3,
+            Instruction::GetField(_) => 3,
+            Instruction::PutField(_) => 3,
             Instruction::InvokeVirtual(_) => 3,
             Instruction::InvokeSpecial(_) => 3,
             Instruction::InvokeStatic(_) => 3,
+            Instruction::InvokeInterface(_, _) => 5,
+            Instruction::InvokeDynamic(_) => 5,
+            Instruction::New(_) => 3,
+            Instruction::NewArray(_) => 2,
+            Instruction::ANewArray(_) => 3,
             Instruction::ArrayLength => 1,
+            Instruction::Athrow => 1,
+            Instruction::CheckCast(_) => 3,
+            Instruction::InstanceOf(_) => 3,
+            Instruction::MonitorEnter => 1,
+            Instruction::MonitorExit => 1,
+            Instruction::Wide(ref instruction) => 1 + instruction.size(),
+            Instruction::MultiANewArray(_, _) => 4,
+            Instruction::IfNull(_) => 3,
+            Instruction::IfNonNull(_) => 3,
+            Instruction::GotoW(_) => 5,
+            Instruction::JsrW(_) => 5,
+        }
+    }
+
+    // the name of this instruction's opcode, as used by javap and Jasmin (e.g. invokevirtual, iconst_m1)
+    pub fn mnemonic(&self) -> &'static str {
+        match *self {
+            Instruction::Nop => "nop",
+            Instruction::AconstNull => "aconst_null",
+            Instruction::IconstM1 => "iconst_m1",
+            Instruction::Iconst0 => "iconst_0",
+            Instruction::Iconst1 => "iconst_1",
+            Instruction::Iconst2 => "iconst_2",
+            Instruction::Iconst3 => "iconst_3",
+            Instruction::Iconst4 => "iconst_4",
+            Instruction::Iconst5 => "iconst_5",
+            Instruction::Lconst0 => "lconst_0",
+            Instruction::Lconst1 => "lconst_1",
+            Instruction::Fconst0 => "fconst_0",
+            Instruction::Fconst1 => "fconst_1",
+            Instruction::Fconst2 => "fconst_2",
+            Instruction::Dconst0 => "dconst_0",
+            Instruction::Dconst1 => "dconst_1",
+            Instruction::Bipush(_) => "bipush",
+            Instruction::Sipush(_) => "sipush",
+            Instruction::LoadConstant(_) => "ldc",
+            Instruction::LoadConstantWide(_) => "ldc_w",
+            Instruction::LoadConstant2Wide(_) => "ldc2_w",
+            Instruction::Iload(_) => "iload",
+            Instruction::Lload(_) => "lload",
+            Instruction::Fload(_) => "fload",
+            Instruction::Dload(_) => "dload",
+            Instruction::Aload(_) => "aload",
+            Instruction::Iload0 => "iload_0",
+            Instruction::Iload1 => "iload_1",
+            Instruction::Iload2 => "iload_2",
+            Instruction::Iload3 => "iload_3",
+            Instruction::Lload0 => "lload_0",
+            Instruction::Lload1 => "lload_1",
+            Instruction::Lload2 => "lload_2",
+            Instruction::Lload3 => "lload_3",
+            Instruction::Fload0 => "fload_0",
+            Instruction::Fload1 => "fload_1",
+            Instruction::Fload2 => "fload_2",
+            Instruction::Fload3 => "fload_3",
+            Instruction::Dload0 => "dload_0",
+            Instruction::Dload1 => "dload_1",
+            Instruction::Dload2 => "dload_2",
+            Instruction::Dload3 => "dload_3",
+            Instruction::Aload0 => "aload_0",
+            Instruction::Aload1 => "aload_1",
+            Instruction::Aload2 => "aload_2",
+            Instruction::Aload3 => "aload_3",
+            Instruction::Iaload => "iaload",
+            Instruction::Laload => "laload",
+            Instruction::Faload => "faload",
+            Instruction::Daload => "daload",
+            Instruction::Aaload => "aaload",
+            Instruction::Baload => "baload",
+            Instruction::Caload => "caload",
+            Instruction::Saload => "saload",
+            Instruction::Istore(_) => "istore",
+            Instruction::Lstore(_) => "lstore",
+            Instruction::Fstore(_) => "fstore",
+            Instruction::Dstore(_) => "dstore",
+            Instruction::Astore(_) => "astore",
+            Instruction::Istore0 => "istore_0",
+            Instruction::Istore1 => "istore_1",
+            Instruction::Istore2 => "istore_2",
+            Instruction::Istore3 => "istore_3",
+            Instruction::Lstore0 => "lstore_0",
+            Instruction::Lstore1 => "lstore_1",
+            Instruction::Lstore2 => "lstore_2",
+            Instruction::Lstore3 => "lstore_3",
+            Instruction::Fstore0 => "fstore_0",
+            Instruction::Fstore1 => "fstore_1",
+            Instruction::Fstore2 => "fstore_2",
+            Instruction::Fstore3 => "fstore_3",
+            Instruction::Dstore0 => "dstore_0",
+            Instruction::Dstore1 => "dstore_1",
+            Instruction::Dstore2 => "dstore_2",
+            Instruction::Dstore3 => "dstore_3",
+            Instruction::Astore0 => "astore_0",
+            Instruction::Astore1 => "astore_1",
+            Instruction::Astore2 => "astore_2",
+            Instruction::Astore3 => "astore_3",
+            Instruction::Iastore => "iastore",
+            Instruction::Lastore => "lastore",
+            Instruction::Fastore => "fastore",
+            Instruction::Dastore => "dastore",
+            Instruction::Aastore => "aastore",
+            Instruction::Bastore => "bastore",
+            Instruction::Castore => "castore",
+            Instruction::Sastore => "sastore",
+            Instruction::Pop => "pop",
+            Instruction::Pop2 => "pop2",
+            Instruction::Dup => "dup",
+            Instruction::DupX1 => "dup_x1",
+            Instruction::DupX2 => "dup_x2",
+            Instruction::Dup2 => "dup2",
+            Instruction::Dup2X1 => "dup2_x1",
+            Instruction::Dup2X2 => "dup2_x2",
+            Instruction::Swap => "swap",
+            Instruction::Iadd => "iadd",
+            Instruction::Ladd => "ladd",
+            Instruction::Fadd => "fadd",
+            Instruction::Dadd => "dadd",
+            Instruction::Isub => "isub",
+            Instruction::Lsub => "lsub",
+            Instruction::Fsub => "fsub",
+            Instruction::Dsub => "dsub",
+            Instruction::Imul => "imul",
+            Instruction::Lmul => "lmul",
+            Instruction::Fmul => "fmul",
+            Instruction::Dmul => "dmul",
+            Instruction::Idiv => "idiv",
+            Instruction::Ldiv => "ldiv",
+            Instruction::Fdiv => "fdiv",
+            Instruction::Ddiv => "ddiv",
+            Instruction::Irem => "irem",
+            Instruction::Lrem => "lrem",
+            Instruction::Frem => "frem",
+            Instruction::Drem => "drem",
+            Instruction::Ineg => "ineg",
+            Instruction::Lneg => "lneg",
+            Instruction::Fneg => "fneg",
+            Instruction::Dneg => "dneg",
+            Instruction::Ishl => "ishl",
+            Instruction::Lshl => "lshl",
+            Instruction::Ishr => "ishr",
+            Instruction::Lshr => "lshr",
+            Instruction::Iushr => "iushr",
+            Instruction::Lushr => "lushr",
+            Instruction::Iand => "iand",
+            Instruction::Land => "land",
+            Instruction::Ior => "ior",
+            Instruction::Lor => "lor",
+            Instruction::Ixor => "ixor",
+            Instruction::Lxor => "lxor",
+            Instruction::Iinc(_, _) => "iinc",
+            Instruction::I2l => "i2l",
+            Instruction::I2f => "i2f",
+            Instruction::I2d => "i2d",
+            Instruction::L2i => "l2i",
+            Instruction::L2f => "l2f",
+            Instruction::L2d => "l2d",
+            Instruction::F2i => "f2i",
+            Instruction::F2l => "f2l",
+            Instruction::F2d => "f2d",
+            Instruction::D2i => "d2i",
+            Instruction::D2l => "d2l",
+            Instruction::D2f => "d2f",
+            Instruction::I2b => "i2b",
+            Instruction::I2c => "i2c",
+            Instruction::I2s => "i2s",
+            Instruction::Lcmp => "lcmp",
+            Instruction::Fcmpl => "fcmpl",
+            Instruction::Fcmpg => "fcmpg",
+            Instruction::Dcmpl => "dcmpl",
+            Instruction::Dcmpg => "dcmpg",
+            Instruction::IfEq(_) => "ifeq",
+            Instruction::IfNe(_) => "ifne",
+            Instruction::IfLt(_) => "iflt",
+            Instruction::IfGe(_) => "ifge",
+            Instruction::IfGt(_) => "ifgt",
+            Instruction::IfLe(_) => "ifle",
+            Instruction::IfIcmpEq(_) => "if_icmpeq",
+            Instruction::IfIcmpNe(_) => "if_icmpne",
+            Instruction::IfIcmpLt(_) => "if_icmplt",
+            Instruction::IfIcmpGe(_) => "if_icmpge",
+            Instruction::IfIcmpGt(_) => "if_icmpgt",
+            Instruction::IfIcmpLe(_) => "if_icmple",
+            Instruction::IfAcmpEq(_) => "if_acmpeq",
+            Instruction::IfAcmpNe(_) => "if_acmpne",
+            Instruction::Goto(_) => "goto",
+            Instruction::Jsr(_) => "jsr",
+            Instruction::Ret(_) => "ret",
+            Instruction::TableSwitch(_, _, _) => "tableswitch",
+            Instruction::LookupSwitch(_, _) => "lookupswitch",
+            Instruction::Ireturn => "ireturn",
+            Instruction::Lreturn => "lreturn",
+            Instruction::Freturn => "freturn",
+            Instruction::Dreturn => "dreturn",
+            Instruction::Areturn => "areturn",
+            Instruction::Return => "return",
+            Instruction::GetStatic(_) => "getstatic",
+            Instruction::PutStatic(_) => "putstatic",
+            Instruction::GetField(_) => "getfield",
+            Instruction::PutField(_) => "putfield",
+            Instruction::InvokeVirtual(_) => "invokevirtual",
+            Instruction::InvokeSpecial(_) => "invokespecial",
+            Instruction::InvokeStatic(_) => "invokestatic",
+            Instruction::InvokeInterface(_, _) => "invokeinterface",
+            Instruction::InvokeDynamic(_) => "invokedynamic",
+            Instruction::New(_) => "new",
+            Instruction::NewArray(_) => "newarray",
+            Instruction::ANewArray(_) => "anewarray",
+            Instruction::ArrayLength => "arraylength",
+            Instruction::Athrow => "athrow",
+            Instruction::CheckCast(_) => "checkcast",
+            Instruction::InstanceOf(_) => "instanceof",
+            Instruction::MonitorEnter => "monitorenter",
+            Instruction::MonitorExit => "monitorexit",
+            Instruction::Wide(_) => "wide",
+            Instruction::MultiANewArray(_, _) => "multianewarray",
+            Instruction::IfNull(_) => "ifnull",
+            Instruction::IfNonNull(_) => "ifnonnull",
+            Instruction::GotoW(_) => "goto_w",
+            Instruction::JsrW(_) => "jsr_w",
+        }
+    }
+
+    // The number of local variable slots a method needs for this instruction to be valid, e.g. 4 for lstore_2
+    // (which writes slots 2 and 3). 0 for instructions that don't touch local variables.
+    pub fn locals_used(&self) -> u16 {
+        match *self {
+            Instruction::Iload(index) | Instruction::Fload(index) | Instruction::Aload(index) |
+            Instruction::Istore(index) | Instruction::Fstore(index) | Instruction::Astore(index) |
+            Instruction::Iinc(index, _) | Instruction::Ret(index) => index as u16 + 1,
+            Instruction::Lload(index) | Instruction::Dload(index) |
+            Instruction::Lstore(index) | Instruction::Dstore(index) => index as u16 + 2,
+            Instruction::Iload0 | Instruction::Fload0 | Instruction::Aload0 |
+            Instruction::Istore0 | Instruction::Fstore0 | Instruction::Astore0 => 1,
+            Instruction::Iload1 | Instruction::Fload1 | Instruction::Aload1 |
+            Instruction::Istore1 | Instruction::Fstore1 | Instruction::Astore1 |
+            Instruction::Lload0 | Instruction::Dload0 | Instruction::Lstore0 | Instruction::Dstore0 => 2,
+            Instruction::Iload2 | Instruction::Fload2 | Instruction::Aload2 |
+            Instruction::Istore2 | Instruction::Fstore2 | Instruction::Astore2 |
+            Instruction::Lload1 | Instruction::Dload1 | Instruction::Lstore1 | Instruction::Dstore1 => 3,
+            Instruction::Iload3 | Instruction::Fload3 | Instruction::Aload3 |
+            Instruction::Istore3 | Instruction::Fstore3 | Instruction::Astore3 |
+            Instruction::Lload2 | Instruction::Dload2 | Instruction::Lstore2 | Instruction::Dstore2 => 4,
+            Instruction::Lload3 | Instruction::Dload3 | Instruction::Lstore3 | Instruction::Dstore3 => 5,
+            Instruction::Wide(ref instruction) => instruction.locals_used(),
+            _ => 0,
+        }
+    }
+
+    // The number of operand stack slots this instruction pops and then pushes, as (pops, pushes). Longs and doubles
+    // take two slots. Field and method instructions look up their descriptor in the given constant pool.
+    pub fn stack_effect(&self, constants: &ConstantPool) -> Result<(u16, u16), String> {
+        let effect = match *self {
+            Instruction::Nop | Instruction::Iinc(_, _) | Instruction::Goto(_) | Instruction::GotoW(_) |
+            Instruction::Ret(_) | Instruction::Return | Instruction::Wide(WideInstruction::Iinc(_, _)) |
+            Instruction::Wide(WideInstruction::Ret(_)) => (0, 0),
+            Instruction::AconstNull | Instruction::IconstM1 | Instruction::Iconst0 | Instruction::Iconst1 |
+            Instruction::Iconst2 | Instruction::Iconst3 | Instruction::Iconst4 | Instruction::Iconst5 |
+            Instruction::Fconst0 | Instruction::Fconst1 | Instruction::Fconst2 |
+            Instruction::Bipush(_) | Instruction::Sipush(_) | Instruction::LoadConstant(_) | Instruction::LoadConstantWide(_) |
+            Instruction::Iload(_) | Instruction::Fload(_) | Instruction::Aload(_) |
+            Instruction::Iload0 | Instruction::Iload1 | Instruction::Iload2 | Instruction::Iload3 |
+            Instruction::Fload0 | Instruction::Fload1 | Instruction::Fload2 | Instruction::Fload3 |
+            Instruction::Aload0 | Instruction::Aload1 | Instruction::Aload2 | Instruction::Aload3 |
+            Instruction::Wide(WideInstruction::Iload(_)) | Instruction::Wide(WideInstruction::Fload(_)) |
+            Instruction::Wide(WideInstruction::Aload(_)) |
+            Instruction::Jsr(_) | Instruction::JsrW(_) | Instruction::New(_) => (0, 1),
+            Instruction::Lconst0 | Instruction::Lconst1 | Instruction::Dconst0 | Instruction::Dconst1 |
+            Instruction::LoadConstant2Wide(_) | Instruction::Lload(_) | Instruction::Dload(_) |
+            Instruction::Lload0 | Instruction::Lload1 | Instruction::Lload2 | Instruction::Lload3 |
+            Instruction::Dload0 | Instruction::Dload1 | Instruction::Dload2 | Instruction::Dload3 |
+            Instruction::Wide(WideInstruction::Lload(_)) | Instruction::Wide(WideInstruction::Dload(_)) => (0, 2),
+            Instruction::Istore(_) | Instruction::Fstore(_) | Instruction::Astore(_) |
+            Instruction::Istore0 | Instruction::Istore1 | Instruction::Istore2 | Instruction::Istore3 |
+            Instruction::Fstore0 | Instruction::Fstore1 | Instruction::Fstore2 | Instruction::Fstore3 |
+            Instruction::Astore0 | Instruction::Astore1 | Instruction::Astore2 | Instruction::Astore3 |
+            Instruction::Wide(WideInstruction::Istore(_)) | Instruction::Wide(WideInstruction::Fstore(_)) |
+            Instruction::Wide(WideInstruction::Astore(_)) |
+            Instruction::Pop | Instruction::IfEq(_) | Instruction::IfNe(_) | Instruction::IfLt(_) |
+            Instruction::IfGe(_) | Instruction::IfGt(_) | Instruction::IfLe(_) | Instruction::IfNull(_) |
+            Instruction::IfNonNull(_) | Instruction::TableSwitch(_, _, _) | Instruction::LookupSwitch(_, _) |
+            Instruction::Ireturn | Instruction::Freturn | Instruction::Areturn | Instruction::Athrow |
+            Instruction::MonitorEnter | Instruction::MonitorExit => (1, 0),
+            Instruction::Lstore(_) | Instruction::Dstore(_) |
+            Instruction::Lstore0 | Instruction::Lstore1 | Instruction::Lstore2 | Instruction::Lstore3 |
+            Instruction::Dstore0 | Instruction::Dstore1 | Instruction::Dstore2 | Instruction::Dstore3 |
+            Instruction::Wide(WideInstruction::Lstore(_)) | Instruction::Wide(WideInstruction::Dstore(_)) |
+            Instruction::Pop2 | Instruction::IfIcmpEq(_) | Instruction::IfIcmpNe(_) | Instruction::IfIcmpLt(_) |
+            Instruction::IfIcmpGe(_) | Instruction::IfIcmpGt(_) | Instruction::IfIcmpLe(_) |
+            Instruction::IfAcmpEq(_) | Instruction::IfAcmpNe(_) | Instruction::Lreturn | Instruction::Dreturn => (2, 0),
+            Instruction::Iaload | Instruction::Faload | Instruction::Aaload | Instruction::Baload |
+            Instruction::Caload | Instruction::Saload |
+            Instruction::Iadd | Instruction::Fadd | Instruction::Isub | Instruction::Fsub |
+            Instruction::Imul | Instruction::Fmul | Instruction::Idiv | Instruction::Fdiv |
+            Instruction::Irem | Instruction::Frem | Instruction::Ishl | Instruction::Ishr | Instruction::Iushr |
+            Instruction::Iand | Instruction::Ior | Instruction::Ixor | Instruction::Fcmpl | Instruction::Fcmpg => (2, 1),
+            Instruction::Laload | Instruction::Daload => (2, 2),
+            Instruction::Iastore | Instruction::Fastore | Instruction::Aastore | Instruction::Bastore |
+            Instruction::Castore | Instruction::Sastore => (3, 0),
+            Instruction::Lastore | Instruction::Dastore => (4, 0),
+            Instruction::Dup => (1, 2),
+            Instruction::DupX1 => (2, 3),
+            Instruction::DupX2 => (3, 4),
+            Instruction::Dup2 => (2, 4),
+            Instruction::Dup2X1 => (3, 5),
+            Instruction::Dup2X2 => (4, 6),
+            Instruction::Swap => (2, 2),
+            Instruction::Ladd | Instruction::Dadd | Instruction::Lsub | Instruction::Dsub |
+            Instruction::Lmul | Instruction::Dmul | Instruction::Ldiv | Instruction::Ddiv |
+            Instruction::Lrem | Instruction::Drem | Instruction::Land | Instruction::Lor | Instruction::Lxor => (4, 2),
+            Instruction::Lshl | Instruction::Lshr | Instruction::Lushr => (3, 2),
+            Instruction::Lcmp | Instruction::Dcmpl | Instruction::Dcmpg => (4, 1),
+            Instruction::Ineg | Instruction::Fneg | Instruction::I2f | Instruction::F2i |
+            Instruction::I2b | Instruction::I2c | Instruction::I2s |
+            Instruction::NewArray(_) | Instruction::ANewArray(_) | Instruction::ArrayLength |
+            Instruction::CheckCast(_) | Instruction::InstanceOf(_) => (1, 1),
+            Instruction::Lneg | Instruction::Dneg | Instruction::L2d | Instruction::D2l => (2, 2),
+            Instruction::I2l | Instruction::I2d | Instruction::F2l | Instruction::F2d => (1, 2),
+            Instruction::L2i | Instruction::L2f | Instruction::D2i | Instruction::D2f => (2, 1),
+            Instruction::MultiANewArray(_, dimensions) => (dimensions as u16, 1),
+            Instruction::GetStatic(index) => (0, field_slots(constants, index)?),
+            Instruction::PutStatic(index) => (field_slots(constants, index)?, 0),
+            Instruction::GetField(index) => (1, field_slots(constants, index)?),
+            Instruction::PutField(index) => (1 + field_slots(constants, index)?, 0),
+            Instruction::InvokeVirtual(index) | Instruction::InvokeSpecial(index) |
+            Instruction::InvokeInterface(index, _) => {
+                let (arguments, result) = method_slots(constants, index)?;
+                (1 + arguments, result)
+            },
+            Instruction::InvokeStatic(index) | Instruction::InvokeDynamic(index) => method_slots(constants, index)?,
+        };
+        Ok(effect)
+    }
+}
+
+impl WideInstruction {
+    // the name of the widened instruction's opcode, without the wide prefix
+    pub fn mnemonic(&self) -> &'static str {
+        match *self {
+            WideInstruction::Iload(_) => "iload",
+            WideInstruction::Lload(_) => "lload",
+            WideInstruction::Fload(_) => "fload",
+            WideInstruction::Dload(_) => "dload",
+            WideInstruction::Aload(_) => "aload",
+            WideInstruction::Istore(_) => "istore",
+            WideInstruction::Lstore(_) => "lstore",
+            WideInstruction::Fstore(_) => "fstore",
+            WideInstruction::Dstore(_) => "dstore",
+            WideInstruction::Astore(_) => "astore",
+            WideInstruction::Iinc(_, _) => "iinc",
+            WideInstruction::Ret(_) => "ret",
         }
     }
+
+    // The size in bytes of this instruction, not including the wide opcode itself.
+    pub fn size(&self) -> u16 {
+        match *self {
+            WideInstruction::Iinc(_, _) => 5,
+            _ => 3,
+        }
+    }
+
+    pub fn locals_used(&self) -> u16 {
+        match *self {
+            WideInstruction::Lload(index) | WideInstruction::Dload(index) |
+            WideInstruction::Lstore(index) | WideInstruction::Dstore(index) => index.saturating_add(2),
+            WideInstruction::Iload(index) | WideInstruction::Fload(index) | WideInstruction::Aload(index) |
+            WideInstruction::Istore(index) | WideInstruction::Fstore(index) | WideInstruction::Astore(index) |
+            WideInstruction::Iinc(index, _) | WideInstruction::Ret(index) => index.saturating_add(1),
+        }
+    }
+}
+
+// tableswitch and lookupswitch operands start at the next multiple of 4 bytes after the opcode
+pub fn switch_padding(pc: u16) -> u16 {
+    3 - pc % 4
+}
+
+// the number of stack slots taken by a value of the field referred to by a Fieldref constant
+fn field_slots(constants: &ConstantPool, index: u16) -> Result<u16, String> {
+    let field_type = Java::parse(member_descriptor(constants, index)?).map_err(|e| e.to_string())?;
+    Ok(field_type.slot_count())
+}
+
+// the number of stack slots taken by the arguments and the result of the method referred to by a Methodref,
+// InterfaceMethodref or InvokeDynamic constant
+fn method_slots(constants: &ConstantPool, index: u16) -> Result<(u16, u16), String> {
+    let descriptor = MethodDescriptor::parse(member_descriptor(constants, index)?).map_err(|e| e.to_string())?;
+    Ok((descriptor.slot_count(), descriptor.ret.slot_count()))
+}
+
+fn member_descriptor(constants: &ConstantPool, index: u16) -> Result<&str, String> {
+    let name_and_type_index = match constants.get(index) {
+        Some(&Constant::Fieldref(_, name_and_type_index)) | Some(&Constant::Methodref(_, name_and_type_index)) |
+        Some(&Constant::InterfaceMethodref(_, name_and_type_index)) |
+        Some(&Constant::InvokeDynamic(_, name_and_type_index)) => name_and_type_index,
+        other => return Err(format!("Expected a member reference at index {}, found {:?}", index, other)),
+    };
+    match constants.get(name_and_type_index) {
+        Some(&Constant::NameAndType(_, descriptor_index)) => {
+            constants.get_utf8(descriptor_index).ok_or_else(|| format!("Expected a Utf8 constant at index {}", descriptor_index))
+        },
+        other => Err(format!("Expected a NameAndType constant at index {}, found {:?}", name_and_type_index, other)),
+    }
 }
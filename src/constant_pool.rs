@@ -0,0 +1,190 @@
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::mem;
+
+use classfile::*;
+
+// The largest constant_pool_count a class file can have; indices run from 1 to one less than the count.
+pub const MAX_CONSTANT_POOL_COUNT: usize = u16::MAX as usize;
+
+// A class's constant pool. Indices are 1-based, and Long and Double constants take up two slots (the second
+// holding Constant::Unusable). Constants added with `add` and the `add_*` helpers are interned, so defining the
+// same string or method reference twice returns the original index.
+#[derive(Clone, Debug, Default)]
+pub struct ConstantPool {
+    constants: Vec<Constant>,
+    indices: HashMap<ConstantKey, u16>,
+    overflowed: bool, // a constant didn't fit, and was given index 0
+}
+
+// Constants compared by value, except that floats are compared by their bits, so NaN can be interned and
+// 0.0 and -0.0 stay distinct.
+#[derive(Clone, Debug)]
+struct ConstantKey(Constant);
+
+impl ConstantPool {
+    pub fn new() -> ConstantPool {
+        ConstantPool::default()
+    }
+
+    // Appends a constant even if an equal one already exists, e.g. to keep the exact layout of a class file being read.
+    pub fn push(&mut self, constant: Constant) -> u16 {
+        let wide = constant.is_wide();
+        let slots = if wide { 2 } else { 1 };
+        if self.constants.len() + slots >= MAX_CONSTANT_POOL_COUNT {
+            // the builders check has_overflowed when they're done, rather than every index they get back
+            self.overflowed = true;
+            return 0;
+        }
+
+        let index = self.constants.len() as u16 + 1;
+        self.indices.entry(ConstantKey(constant.clone())).or_insert(index);
+        self.constants.push(constant);
+        if wide {
+            self.constants.push(Constant::Unusable); // Long and Double take up two slots
+        }
+        index
+    }
+
+    // Returns the index of an equal constant if there is one, or appends the constant.
+    pub fn add(&mut self, constant: Constant) -> u16 {
+        match self.find(&constant) {
+            Some(index) => index,
+            None => self.push(constant),
+        }
+    }
+
+    pub fn add_utf8(&mut self, string: &str) -> u16 {
+        self.add(Constant::Utf8(JavaString::new(string)))
+    }
+
+    pub fn add_integer(&mut self, value: i32) -> u16 {
+        self.add(Constant::Integer(value))
+    }
+
+    pub fn add_float(&mut self, value: f32) -> u16 {
+        self.add(Constant::Float(value))
+    }
+
+    pub fn add_long(&mut self, value: i64) -> u16 {
+        self.add(Constant::Long(value))
+    }
+
+    pub fn add_double(&mut self, value: f64) -> u16 {
+        self.add(Constant::Double(value))
+    }
+
+    pub fn add_class(&mut self, class: &str) -> u16 {
+        let name_index = self.add_utf8(class);
+        self.add(Constant::Class(name_index))
+    }
+
+    pub fn add_string(&mut self, value: &str) -> u16 {
+        let string_index = self.add_utf8(value);
+        self.add(Constant::String(string_index))
+    }
+
+    pub fn add_name_and_type(&mut self, name: &str, descriptor: &str) -> u16 {
+        let name_index = self.add_utf8(name);
+        let descriptor_index = self.add_utf8(descriptor);
+        self.add(Constant::NameAndType(name_index, descriptor_index))
+    }
+
+    pub fn add_fieldref(&mut self, class: &str, name: &str, descriptor: &str) -> u16 {
+        let class_index = self.add_class(class);
+        let name_and_type_index = self.add_name_and_type(name, descriptor);
+        self.add(Constant::Fieldref(class_index, name_and_type_index))
+    }
+
+    pub fn add_methodref(&mut self, class: &str, name: &str, descriptor: &str) -> u16 {
+        let class_index = self.add_class(class);
+        let name_and_type_index = self.add_name_and_type(name, descriptor);
+        self.add(Constant::Methodref(class_index, name_and_type_index))
+    }
+
+    pub fn add_interface_methodref(&mut self, class: &str, name: &str, descriptor: &str) -> u16 {
+        let class_index = self.add_class(class);
+        let name_and_type_index = self.add_name_and_type(name, descriptor);
+        self.add(Constant::InterfaceMethodref(class_index, name_and_type_index))
+    }
+
+    // The index of a constant equal to the given one, without adding it.
+    pub fn find(&self, constant: &Constant) -> Option<u16> {
+        self.indices.get(&ConstantKey(constant.clone())).cloned()
+    }
+
+    pub fn get(&self, index: u16) -> Option<&Constant> {
+        self.constants.get((index as usize).wrapping_sub(1))
+    }
+
+    pub fn get_utf8(&self, index: u16) -> Option<&str> {
+        match self.get(index) {
+            Some(Constant::Utf8(string)) => Some(string.as_str()),
+            _ => None,
+        }
+    }
+
+    // the name of the class referred to by a Class constant
+    pub fn get_class_name(&self, index: u16) -> Option<&str> {
+        match self.get(index) {
+            Some(&Constant::Class(name_index)) => self.get_utf8(name_index),
+            _ => None,
+        }
+    }
+
+    // Whether a constant was added that didn't fit, in which case it was given the invalid index 0.
+    pub fn has_overflowed(&self) -> bool {
+        self.overflowed
+    }
+
+    // The number of slots used, counting Long and Double constants twice. The class file's constant_pool_count is one more.
+    pub fn len(&self) -> usize {
+        self.constants.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.constants.is_empty()
+    }
+
+    // the constants and their indices, skipping the unusable second slots of Long and Double constants
+    pub fn iter(&self) -> impl Iterator<Item = (u16, &Constant)> {
+        self.constants.iter().enumerate().map(|(i, constant)| (i as u16 + 1, constant)).filter(|&(_, constant)| *constant != Constant::Unusable)
+    }
+}
+
+impl PartialEq for ConstantPool {
+    fn eq(&self, other: &ConstantPool) -> bool {
+        self.constants == other.constants
+    }
+}
+
+impl PartialEq for ConstantKey {
+    fn eq(&self, other: &ConstantKey) -> bool {
+        match (&self.0, &other.0) {
+            (Constant::Float(a), Constant::Float(b)) => a.to_bits() == b.to_bits(),
+            (Constant::Double(a), Constant::Double(b)) => a.to_bits() == b.to_bits(),
+            (a, b) => a == b,
+        }
+    }
+}
+
+impl Eq for ConstantKey {}
+
+impl Hash for ConstantKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        mem::discriminant(&self.0).hash(state);
+        match self.0 {
+            Constant::Utf8(ref string) => string.hash(state),
+            Constant::Integer(value) => value.hash(state),
+            Constant::Float(value) => value.to_bits().hash(state),
+            Constant::Long(value) => value.hash(state),
+            Constant::Double(value) => value.to_bits().hash(state),
+            Constant::Class(index) | Constant::String(index) | Constant::MethodType(index) |
+            Constant::Module(index) | Constant::Package(index) => index.hash(state),
+            Constant::Fieldref(a, b) | Constant::Methodref(a, b) | Constant::InterfaceMethodref(a, b) |
+            Constant::NameAndType(a, b) | Constant::Dynamic(a, b) | Constant::InvokeDynamic(a, b) => (a, b).hash(state),
+            Constant::MethodHandle(kind, index) => (kind, index).hash(state),
+            Constant::Unusable => {},
+        }
+    }
+}
@@ -0,0 +1,886 @@
+use std::collections::BTreeSet;
+use std::convert::TryFrom;
+use std::fmt::Write;
+
+use classfile::*;
+use class_builder::*;
+use errors::*;
+use java_type_signatures::*;
+
+// Writes a class file out in the Jasmin syntax read by `assemble`, with labels for branch targets and constants
+// written out in full rather than as constant pool indices, so that it can be edited and assembled again. Stack
+// map frames are written out in full as .stack directives, which the assembler recomputes but takes the classes
+// from where paths meet. Anything the assembler can't reproduce, e.g. an attribute it doesn't know, is an error
+// rather than being left out.
+pub fn disassemble(classfile: &Classfile) -> Result<String, DisassemblyError> {
+    let error = |message: String| DisassemblyError { context: String::new(), message };
+    let mut out = String::new();
+
+    writeln!(out, ".bytecode {}.{}", classfile.major_version, classfile.minor_version).unwrap();
+    let this_class = class_name(classfile, classfile.this_class).map_err(&error)?;
+    let super_class = class_name(classfile, classfile.super_class).map_err(&error)?;
+    if classfile.access_flags & ACC_INTERFACE != 0 {
+        // the assembler adds the interface and abstract flags itself
+        let access_flags = classfile.access_flags & !(ACC_INTERFACE | ACC_ABSTRACT);
+        writeln!(out, ".interface {}{}", flags(access_flags, CLASS_FLAGS), this_class).unwrap();
+    } else {
+        // and ACC_SUPER, which every class compiled since Java 1.0.2 has anyway
+        let access_flags = classfile.access_flags & !ACC_SUPER;
+        writeln!(out, ".class {}{}", flags(access_flags, CLASS_FLAGS), this_class).unwrap();
+    }
+    writeln!(out, ".super {}", super_class).unwrap();
+    for &interface in &classfile.interfaces {
+        writeln!(out, ".implements {}", class_name(classfile, interface).map_err(&error)?).unwrap();
+    }
+    // in the order the assembler adds them, so that assembling the output and disassembling it again gives the same text
+    let mut debug_extension = None;
+    let mut nest_members = vec![];
+    let mut inner_classes = vec![];
+    let mut annotations = Annotations::default();
+    for attribute in &classfile.attributes {
+        match *attribute {
+            Attribute::Signature(_, index) => writeln!(out, ".signature {}", quote(utf8(classfile, index).map_err(&error)?)).unwrap(),
+            Attribute::SourceFile(_, index) => writeln!(out, ".source {}", token(utf8(classfile, index).map_err(&error)?)).unwrap(),
+            Attribute::SourceDebugExtension(_, ref text) => debug_extension = Some(text),
+            Attribute::Deprecated(_) => writeln!(out, ".deprecated").unwrap(),
+            Attribute::Synthetic(_) => writeln!(out, ".synthetic").unwrap(),
+            Attribute::EnclosingMethod(_, class_index, method_index) => {
+                let class = class_name(classfile, class_index).map_err(&error)?;
+                if method_index == 0 {
+                    writeln!(out, ".enclosing method {}", class).unwrap();
+                } else {
+                    let (name, descriptor) = name_and_type(classfile, method_index).map_err(&error)?;
+                    writeln!(out, ".enclosing method {}/{}{}", class, name, descriptor).unwrap();
+                }
+            },
+            Attribute::NestHost(_, index) => writeln!(out, ".nesthost {}", class_name(classfile, index).map_err(&error)?).unwrap(),
+            Attribute::NestMembers(_, ref classes) => nest_members.extend(classes),
+            Attribute::InnerClasses(_, ref classes) => inner_classes.extend(classes),
+            Attribute::RuntimeVisibleAnnotations(_, ref visible) => annotations.visible.extend(visible),
+            Attribute::RuntimeInvisibleAnnotations(_, ref invisible) => annotations.invisible.extend(invisible),
+            // rebuilt from the invokedynamic instructions and dynamic constants that use it
+            Attribute::BootstrapMethods(_, _) => {},
+            ref attribute => return Err(error(unsupported(classfile, attribute))),
+        }
+    }
+    if let Some(debug_extension) = debug_extension {
+        writeln!(out, ".debug {}", quote(debug_extension)).unwrap();
+    }
+    annotations.write(&mut out, classfile, "").map_err(&error)?;
+    for &index in nest_members {
+        writeln!(out, ".nestmember {}", class_name(classfile, index).map_err(&error)?).unwrap();
+    }
+    for inner_class in inner_classes {
+        writeln!(out, "{}", inner_class_directive(classfile, inner_class).map_err(&error)?).unwrap();
+    }
+
+    for field in &classfile.fields {
+        let name = utf8(classfile, field.name_index).map_err(&error)?;
+        let descriptor = utf8(classfile, field.descriptor_index).map_err(&error)?;
+        let error = |message: String| DisassemblyError { context: format!("field {}", name), message };
+
+        // the signature and constant value go on the .field line, and anything else in a block after it
+        let mut line = format!(".field {}{} {}", flags(field.access_flags, FIELD_FLAGS), name_token(name), descriptor);
+        let mut block = String::new();
+        let mut annotations = Annotations::default();
+        for attribute in &field.attributes {
+            match *attribute {
+                Attribute::Signature(_, index) => write!(line, " signature {}", quote(utf8(classfile, index).map_err(&error)?)).unwrap(),
+                Attribute::ConstantValue(_, index) => write!(line, " = {}", constant_value(classfile, index).map_err(&error)?).unwrap(),
+                Attribute::Deprecated(_) => writeln!(block, "    .deprecated").unwrap(),
+                Attribute::Synthetic(_) => writeln!(block, "    .synthetic").unwrap(),
+                Attribute::RuntimeVisibleAnnotations(_, ref visible) => annotations.visible.extend(visible),
+                Attribute::RuntimeInvisibleAnnotations(_, ref invisible) => annotations.invisible.extend(invisible),
+                ref attribute => return Err(error(unsupported(classfile, attribute))),
+            }
+        }
+        annotations.write(&mut block, classfile, "    ").map_err(&error)?;
+        writeln!(out).unwrap();
+        writeln!(out, "{}", line).unwrap();
+        if !block.is_empty() {
+            out.push_str(&block);
+            writeln!(out, ".end field").unwrap();
+        }
+    }
+
+    for method in &classfile.methods {
+        let name = utf8(classfile, method.name_index).map_err(&error)?;
+        let descriptor = utf8(classfile, method.descriptor_index).map_err(&error)?;
+        let error = |message: String| DisassemblyError { context: format!("method {}{}", name, descriptor), message };
+
+        writeln!(out).unwrap();
+        writeln!(out, ".method {}{}{}", flags(method.access_flags, METHOD_FLAGS), name, descriptor).unwrap();
+        // the throws clause comes first, as the assembler adds it straight after the code
+        for attribute in &method.attributes {
+            if let Attribute::Exceptions(_, ref exception_index_table) = *attribute {
+                for &index in exception_index_table {
+                    writeln!(out, "    .throws {}", class_name(classfile, index).map_err(&error)?).unwrap();
+                }
+            }
+        }
+        let mut code = None;
+        let mut annotations = Annotations::default();
+        let mut parameter_annotations = vec![];
+        for attribute in &method.attributes {
+            match *attribute {
+                Attribute::Code(..) => code = Some(attribute),
+                Attribute::Exceptions(_, _) => {},
+                Attribute::Signature(_, index) => writeln!(out, "    .signature {}", quote(utf8(classfile, index).map_err(&error)?)).unwrap(),
+                Attribute::Deprecated(_) => writeln!(out, "    .deprecated").unwrap(),
+                Attribute::Synthetic(_) => writeln!(out, "    .synthetic").unwrap(),
+                Attribute::AnnotationDefault(_, ref value) => {
+                    writeln!(out, "    .annotation default").unwrap();
+                    write_element_value(&mut out, classfile, "        ", value, "        ").map_err(&error)?;
+                    writeln!(out, "    .end annotation").unwrap();
+                },
+                Attribute::RuntimeVisibleAnnotations(_, ref visible) => annotations.visible.extend(visible),
+                Attribute::RuntimeInvisibleAnnotations(_, ref invisible) => annotations.invisible.extend(invisible),
+                Attribute::RuntimeVisibleParameterAnnotations(_, ref parameters) |
+                Attribute::RuntimeInvisibleParameterAnnotations(_, ref parameters) => {
+                    // the assembler has an entry for every argument, where javac sometimes leaves out synthetic ones
+                    let argument_count = MethodDescriptor::parse(descriptor).map_err(|e| error(e.to_string()))?.args.len();
+                    if parameters.len() != argument_count {
+                        let message = format!("{} for {} of the {} arguments can't be assembled", utf8(classfile, attribute.name_index()).unwrap_or("Unnamed"), parameters.len(), argument_count);
+                        return Err(error(message));
+                    }
+                    parameter_annotations.push(attribute);
+                },
+                ref attribute => return Err(error(unsupported(classfile, attribute))),
+            }
+        }
+        annotations.write(&mut out, classfile, "    ").map_err(&error)?;
+        // the builder writes the visible parameter annotations first
+        parameter_annotations.sort_by_key(|attribute| matches!(attribute, Attribute::RuntimeInvisibleParameterAnnotations(_, _)));
+        for attribute in parameter_annotations {
+            let (kind, parameters) = match *attribute {
+                Attribute::RuntimeVisibleParameterAnnotations(_, ref parameters) => ("visibleparam", parameters),
+                Attribute::RuntimeInvisibleParameterAnnotations(_, ref parameters) => ("invisibleparam", parameters),
+                _ => continue,
+            };
+            for (n, annotations) in parameters.iter().enumerate() {
+                for annotation in annotations {
+                    let header = format!("    .annotation {} {} {}", kind, n, class_descriptor(classfile, annotation.type_index).map_err(&error)?);
+                    write_annotation(&mut out, classfile, &header, annotation, "    ").map_err(&error)?;
+                }
+            }
+        }
+        if let Some(code) = code {
+            let locals = initial_locals(classfile, method.access_flags, name, descriptor).map_err(&error)?;
+            write_code(&mut out, classfile, code, locals).map_err(&error)?;
+        }
+        writeln!(out, ".end method").unwrap();
+    }
+    Ok(out)
+}
+
+// the initial locals are those of the implicit frame before the first instruction, for the frames that build on it
+fn write_code(out: &mut String, classfile: &Classfile, code_attribute: &Attribute, initial_locals: Vec<String>) -> Result<(), String> {
+    let (max_stack, max_locals, code, exception_table, attributes) = match *code_attribute {
+        Attribute::Code(_, max_stack, max_locals, ref code, ref exception_table, ref attributes) => (max_stack, max_locals, code, exception_table, attributes),
+        _ => return Ok(()),
+    };
+    // every pc that's jumped to gets a label
+    let mut pcs = vec![];
+    let mut pc = 0;
+    for instruction in code {
+        pcs.push(pc);
+        pc += instruction.size(pc);
+    }
+    let end_pc = pc;
+    let mut targets = BTreeSet::new();
+    for (instruction, &pc) in code.iter().zip(&pcs) {
+        targets.extend(branch_targets(instruction, pc));
+    }
+    for entry in exception_table {
+        targets.insert(entry.start_pc);
+        targets.insert(entry.end_pc);
+        targets.insert(entry.handler_pc);
+    }
+
+    // the stack map frames in full, by pc, and the local variables, whose scopes need labels too
+    let mut frames = vec![];
+    let mut lines = vec![];
+    let mut variables = vec![];
+    let mut variable_signatures = vec![];
+    for attribute in attributes {
+        match *attribute {
+            Attribute::LocalVariableTable(_, ref entries) => {
+                for entry in entries {
+                    targets.insert(entry.start_pc);
+                    targets.insert(entry.start_pc + entry.length);
+                }
+                variables.extend(entries);
+            },
+            Attribute::LocalVariableTypeTable(_, ref entries) => variable_signatures.extend(entries),
+            Attribute::LineNumberTable(_, ref entries) => lines.extend(entries),
+            Attribute::StackMapTable(_, ref stack_map_frames) => {
+                let mut pc: i32 = -1;
+                let mut locals = initial_locals.clone();
+                for frame in stack_map_frames {
+                    pc += frame_offset_delta(frame) as i32 + 1;
+                    let types = |types: &[VerificationType]| types.iter().map(|t| verification_type(classfile, t)).collect::<Result<Vec<_>, _>>();
+                    let stack = match *frame {
+                        StackMapFrame::SameFrame(_) | StackMapFrame::SameFrameExtended(_) => vec![],
+                        StackMapFrame::SameLocals1StackItemFrame(_, ref stack_item) |
+                        StackMapFrame::SameLocals1StackItemFrameExtended(_, ref stack_item) => types(std::slice::from_ref(stack_item))?,
+                        StackMapFrame::ChopFrame(chopped, _) => {
+                            let length = locals.len().checked_sub(chopped as usize).ok_or_else(|| format!("Stack map frame at pc {} chops more locals than there are", pc))?;
+                            locals.truncate(length);
+                            vec![]
+                        },
+                        StackMapFrame::AppendFrame(_, _, ref appended) => {
+                            locals.extend(types(appended)?);
+                            vec![]
+                        },
+                        StackMapFrame::FullFrame(_, ref full_locals, ref stack) => {
+                            locals = types(full_locals)?;
+                            types(stack)?
+                        },
+                    };
+                    frames.push((pc as u16, locals.clone(), stack));
+                }
+                // an uninitialized object is referred to by the pc of its new instruction
+                for frame in stack_map_frames {
+                    let types: &[VerificationType] = match *frame {
+                        StackMapFrame::SameLocals1StackItemFrame(_, ref stack_item) |
+                        StackMapFrame::SameLocals1StackItemFrameExtended(_, ref stack_item) => std::slice::from_ref(stack_item),
+                        StackMapFrame::AppendFrame(_, _, ref locals) => locals,
+                        StackMapFrame::FullFrame(_, ref locals, ref stack) => {
+                            targets.extend(locals.iter().filter_map(|t| if let VerificationType::Uninitialized(pc) = *t { Some(pc) } else { None }));
+                            stack
+                        },
+                        _ => &[],
+                    };
+                    targets.extend(types.iter().filter_map(|t| if let VerificationType::Uninitialized(pc) = *t { Some(pc) } else { None }));
+                }
+            },
+            ref attribute => return Err(unsupported(classfile, attribute)),
+        }
+    }
+
+    writeln!(out, "    .limit stack {}", max_stack).unwrap();
+    writeln!(out, "    .limit locals {}", max_locals).unwrap();
+    for entry in exception_table {
+        let catch_type = if entry.catch_type == 0 { "all" } else { class_name(classfile, entry.catch_type)? };
+        writeln!(out, "    .catch {} from L{} to L{} using L{}", catch_type, entry.start_pc, entry.end_pc, entry.handler_pc).unwrap();
+    }
+    for variable in variables {
+        let mut line = format!("    .var {} is {} {}", variable.index, utf8(classfile, variable.name_index)?, utf8(classfile, variable.descriptor_index)?);
+        let signature = variable_signatures.iter().find(|entry| {
+            entry.index == variable.index && entry.start_pc == variable.start_pc && entry.length == variable.length && entry.name_index == variable.name_index
+        });
+        if let Some(signature) = signature {
+            write!(line, " signature {}", quote(utf8(classfile, signature.signature_index)?)).unwrap();
+        }
+        writeln!(out, "{} from L{} to L{}", line, variable.start_pc, variable.start_pc + variable.length).unwrap();
+    }
+
+    for (instruction, &pc) in code.iter().zip(&pcs) {
+        if targets.contains(&pc) {
+            writeln!(out, "L{}:", pc).unwrap();
+        }
+        for (_, locals, stack) in frames.iter().filter(|&&(frame_pc, _, _)| frame_pc == pc) {
+            writeln!(out, "    .stack").unwrap();
+            for local in locals {
+                writeln!(out, "        locals {}", local).unwrap();
+            }
+            for entry in stack {
+                writeln!(out, "        stack {}", entry).unwrap();
+            }
+            writeln!(out, "    .end stack").unwrap();
+        }
+        for line in lines.iter().filter(|line| line.start_pc == pc) {
+            writeln!(out, "    .line {}", line.line_number).unwrap();
+        }
+        write_instruction(out, classfile, instruction, pc).map_err(|message| format!("{} at pc {}", message, pc))?;
+    }
+    if targets.contains(&end_pc) {
+        writeln!(out, "L{}:", end_pc).unwrap();
+    }
+    Ok(())
+}
+
+fn write_instruction(out: &mut String, classfile: &Classfile, instruction: &Instruction, pc: u16) -> Result<(), String> {
+    let label = |offset: i32| format!("L{}", pc as i32 + offset);
+    let mnemonic = instruction.mnemonic();
+    let operands = match *instruction {
+        Instruction::Bipush(value) => (value as i8).to_string(),
+        Instruction::Sipush(value) => (value as i16).to_string(),
+        Instruction::LoadConstant(index) => loadable_constant(classfile, index as u16)?,
+        Instruction::LoadConstantWide(index) | Instruction::LoadConstant2Wide(index) => loadable_constant(classfile, index)?,
+        Instruction::Iload(index) | Instruction::Lload(index) | Instruction::Fload(index) | Instruction::Dload(index) |
+        Instruction::Aload(index) | Instruction::Istore(index) | Instruction::Lstore(index) | Instruction::Fstore(index) |
+        Instruction::Dstore(index) | Instruction::Astore(index) => index.to_string(),
+        Instruction::Iinc(index, value) => format!("{} {}", index, value as i8),
+        Instruction::IfEq(offset) | Instruction::IfNe(offset) | Instruction::IfLt(offset) | Instruction::IfGe(offset) |
+        Instruction::IfGt(offset) | Instruction::IfLe(offset) | Instruction::IfIcmpEq(offset) | Instruction::IfIcmpNe(offset) |
+        Instruction::IfIcmpLt(offset) | Instruction::IfIcmpGe(offset) | Instruction::IfIcmpGt(offset) |
+        Instruction::IfIcmpLe(offset) | Instruction::IfAcmpEq(offset) | Instruction::IfAcmpNe(offset) |
+        Instruction::IfNull(offset) | Instruction::IfNonNull(offset) | Instruction::Goto(offset) => label(offset as i32),
+        // the assembler uses goto_w when it needs to
+        Instruction::GotoW(offset) => {
+            writeln!(out, "    goto {}", label(offset)).unwrap();
+            return Ok(());
+        },
+        Instruction::TableSwitch(default, low, ref offsets) => {
+            let high = low as i64 + offsets.len() as i64 - 1;
+            writeln!(out, "    tableswitch {} {}", low, high).unwrap();
+            for &offset in offsets {
+                writeln!(out, "        {}", label(offset)).unwrap();
+            }
+            writeln!(out, "        default : {}", label(default)).unwrap();
+            return Ok(());
+        },
+        Instruction::LookupSwitch(default, ref pairs) => {
+            writeln!(out, "    lookupswitch").unwrap();
+            for &(key, offset) in pairs {
+                writeln!(out, "        {} : {}", key, label(offset)).unwrap();
+            }
+            writeln!(out, "        default : {}", label(default)).unwrap();
+            return Ok(());
+        },
+        Instruction::GetStatic(index) | Instruction::PutStatic(index) | Instruction::GetField(index) |
+        Instruction::PutField(index) => match classfile.constant_pool.get(index) {
+            Some(&Constant::Fieldref(class_index, name_and_type_index)) => {
+                let (name, descriptor) = name_and_type(classfile, name_and_type_index)?;
+                format!("{}/{} {}", class_name(classfile, class_index)?, name, descriptor)
+            },
+            _ => return Err(format!("Constant #{} isn't a Fieldref", index)),
+        },
+        Instruction::InvokeVirtual(index) => match classfile.constant_pool.get(index) {
+            Some(&Constant::Methodref(class_index, name_and_type_index)) => method(classfile, class_index, name_and_type_index)?,
+            _ => return Err(format!("Constant #{} isn't a Methodref", index)),
+        },
+        // static and private methods can belong to interfaces too
+        Instruction::InvokeSpecial(index) | Instruction::InvokeStatic(index) => match classfile.constant_pool.get(index) {
+            Some(&Constant::Methodref(class_index, name_and_type_index)) => method(classfile, class_index, name_and_type_index)?,
+            Some(&Constant::InterfaceMethodref(class_index, name_and_type_index)) => {
+                format!("interface {}", method(classfile, class_index, name_and_type_index)?)
+            },
+            _ => return Err(format!("Constant #{} isn't a Methodref or InterfaceMethodref", index)),
+        },
+        Instruction::InvokeInterface(index, count) => match classfile.constant_pool.get(index) {
+            Some(&Constant::InterfaceMethodref(class_index, name_and_type_index)) => {
+                format!("{} {}", method(classfile, class_index, name_and_type_index)?, count)
+            },
+            _ => return Err(format!("Constant #{} isn't an InterfaceMethodref", index)),
+        },
+        Instruction::New(index) | Instruction::ANewArray(index) | Instruction::CheckCast(index) |
+        Instruction::InstanceOf(index) => class_name(classfile, index)?.to_owned(),
+        Instruction::MultiANewArray(index, dimensions) => format!("{} {}", class_name(classfile, index)?, dimensions),
+        Instruction::NewArray(array_type) => match array_type {
+            4 => "boolean",
+            5 => "char",
+            6 => "float",
+            7 => "double",
+            8 => "byte",
+            9 => "short",
+            10 => "int",
+            11 => "long",
+            _ => return Err(format!("Invalid newarray type {}", array_type)),
+        }.to_owned(),
+        Instruction::Wide(ref wide) => {
+            let operands = match *wide {
+                WideInstruction::Iinc(index, value) => format!("{} {}", index, value as i16),
+                WideInstruction::Iload(index) | WideInstruction::Lload(index) | WideInstruction::Fload(index) |
+                WideInstruction::Dload(index) | WideInstruction::Aload(index) | WideInstruction::Istore(index) |
+                WideInstruction::Lstore(index) | WideInstruction::Fstore(index) | WideInstruction::Dstore(index) |
+                WideInstruction::Astore(index) => index.to_string(),
+                WideInstruction::Ret(_) => return Err("Subroutines can't be assembled".to_owned()),
+            };
+            // the assembler adds the wide prefix for indices that need it
+            writeln!(out, "    {} {}", wide.mnemonic(), operands).unwrap();
+            return Ok(());
+        },
+        Instruction::Jsr(_) | Instruction::JsrW(_) | Instruction::Ret(_) => return Err("Subroutines can't be assembled".to_owned()),
+        Instruction::InvokeDynamic(index) => match classfile.constant_pool.get(index) {
+            Some(&Constant::InvokeDynamic(bootstrap_method_index, name_and_type_index)) => {
+                let (name, descriptor) = name_and_type(classfile, name_and_type_index)?;
+                format!("{}{} {}", name, descriptor, bootstrap_method(classfile, bootstrap_method_index)?)
+            },
+            _ => return Err(format!("Constant #{} isn't an InvokeDynamic", index)),
+        },
+        _ => String::new(),
+    };
+    if operands.is_empty() {
+        writeln!(out, "    {}", mnemonic).unwrap();
+    } else {
+        writeln!(out, "    {} {}", mnemonic, operands).unwrap();
+    }
+    Ok(())
+}
+
+fn branch_targets(instruction: &Instruction, pc: u16) -> Vec<u16> {
+    let target = |offset: i32| (pc as i32 + offset) as u16;
+    match *instruction {
+        Instruction::IfEq(offset) | Instruction::IfNe(offset) | Instruction::IfLt(offset) | Instruction::IfGe(offset) |
+        Instruction::IfGt(offset) | Instruction::IfLe(offset) | Instruction::IfIcmpEq(offset) | Instruction::IfIcmpNe(offset) |
+        Instruction::IfIcmpLt(offset) | Instruction::IfIcmpGe(offset) | Instruction::IfIcmpGt(offset) |
+        Instruction::IfIcmpLe(offset) | Instruction::IfAcmpEq(offset) | Instruction::IfAcmpNe(offset) |
+        Instruction::IfNull(offset) | Instruction::IfNonNull(offset) | Instruction::Goto(offset) |
+        Instruction::Jsr(offset) => vec![target(offset as i32)],
+        Instruction::GotoW(offset) | Instruction::JsrW(offset) => vec![target(offset)],
+        Instruction::TableSwitch(default, _, ref offsets) => {
+            let mut targets = vec![target(default)];
+            targets.extend(offsets.iter().map(|&offset| target(offset)));
+            targets
+        },
+        Instruction::LookupSwitch(default, ref pairs) => {
+            let mut targets = vec![target(default)];
+            targets.extend(pairs.iter().map(|&(_, offset)| target(offset)));
+            targets
+        },
+        _ => vec![],
+    }
+}
+
+fn frame_offset_delta(frame: &StackMapFrame) -> u16 {
+    match *frame {
+        StackMapFrame::SameFrame(offset_delta) | StackMapFrame::SameLocals1StackItemFrame(offset_delta, _) => offset_delta as u16,
+        StackMapFrame::SameLocals1StackItemFrameExtended(offset_delta, _) | StackMapFrame::ChopFrame(_, offset_delta) |
+        StackMapFrame::SameFrameExtended(offset_delta) | StackMapFrame::AppendFrame(_, offset_delta, _) |
+        StackMapFrame::FullFrame(offset_delta, _, _) => offset_delta,
+    }
+}
+
+// e.g. Object java/lang/String, as written in a .stack directive
+fn verification_type(classfile: &Classfile, t: &VerificationType) -> Result<String, String> {
+    Ok(match *t {
+        VerificationType::Top => "Top".to_owned(),
+        VerificationType::Integer => "Integer".to_owned(),
+        VerificationType::Float => "Float".to_owned(),
+        VerificationType::Double => "Double".to_owned(),
+        VerificationType::Long => "Long".to_owned(),
+        VerificationType::Null => "Null".to_owned(),
+        VerificationType::UninitializedThis => "UninitializedThis".to_owned(),
+        VerificationType::Object(index) => format!("Object {}", token(class_name(classfile, index)?)),
+        VerificationType::Uninitialized(pc) => format!("Uninitialized L{}", pc),
+    })
+}
+
+// the locals of the implicit frame at the start of a method: `this` (uninitialized in a constructor), then the arguments
+fn initial_locals(classfile: &Classfile, access_flags: u16, name: &str, descriptor: &str) -> Result<Vec<String>, String> {
+    let mut locals = vec![];
+    if access_flags & ACC_STATIC == 0 {
+        let this_class = class_name(classfile, classfile.this_class)?;
+        if name == "<init>" && this_class != "java/lang/Object" {
+            locals.push("UninitializedThis".to_owned());
+        } else {
+            locals.push(format!("Object {}", token(this_class)));
+        }
+    }
+    for argument_type in MethodDescriptor::parse(descriptor).map_err(|e| e.to_string())?.args {
+        locals.push(match argument_type {
+            Java::Boolean | Java::Byte | Java::Char | Java::Short | Java::Int => "Integer".to_owned(),
+            Java::Float => "Float".to_owned(),
+            Java::Long => "Long".to_owned(),
+            Java::Double => "Double".to_owned(),
+            Java::Class(ref class) => format!("Object {}", token(class)),
+            Java::Array(_) => format!("Object {}", token(&argument_type.to_string())),
+            Java::Void => return Err("Method descriptor has a void argument".to_owned()),
+        });
+    }
+    Ok(locals)
+}
+
+// e.g. java/io/PrintStream/println(Ljava/lang/String;)V
+fn method(classfile: &Classfile, class_index: u16, name_and_type_index: u16) -> Result<String, String> {
+    let (name, descriptor) = name_and_type(classfile, name_and_type_index)?;
+    Ok(format!("{}/{}{}", class_name(classfile, class_index)?, name, descriptor))
+}
+
+// the operand of ldc, ldc_w or ldc2_w
+fn loadable_constant(classfile: &Classfile, index: u16) -> Result<String, String> {
+    match classfile.constant_pool.get(index) {
+        Some(&Constant::Class(_)) => Ok(format!("Class {}", class_name(classfile, index)?)),
+        Some(&Constant::MethodType(descriptor_index)) => Ok(format!("MethodType {}", utf8(classfile, descriptor_index)?)),
+        Some(&Constant::MethodHandle(kind, reference_index)) => Ok(format!("MethodHandle {}", handle(classfile, kind, reference_index)?)),
+        Some(&Constant::Dynamic(bootstrap_method_index, name_and_type_index)) => {
+            let (name, descriptor) = name_and_type(classfile, name_and_type_index)?;
+            Ok(format!("Dynamic {} {} {}", token(name), descriptor, bootstrap_method(classfile, bootstrap_method_index)?))
+        },
+        _ => constant_value(classfile, index),
+    }
+}
+
+// a bootstrap method argument, which (unlike the operand of ldc2_w) needs a suffix to be read back as a double
+fn bootstrap_argument(classfile: &Classfile, index: u16) -> Result<String, String> {
+    match classfile.constant_pool.get(index) {
+        Some(&Constant::Float(_)) => Ok(format!("{}f", constant_value(classfile, index)?)),
+        Some(&Constant::Double(_)) => Ok(format!("{}d", constant_value(classfile, index)?)),
+        _ => loadable_constant(classfile, index),
+    }
+}
+
+// the handle and arguments of an entry in the BootstrapMethods attribute, e.g.
+// invokestatic java/lang/invoke/StringConcatFactory/makeConcatWithConstants(...)Ljava/lang/invoke/CallSite; { "\u0001!" }
+fn bootstrap_method(classfile: &Classfile, bootstrap_method_index: u16) -> Result<String, String> {
+    let bootstrap_method = classfile.attributes.iter().filter_map(|attribute| match *attribute {
+        Attribute::BootstrapMethods(_, ref bootstrap_methods) => bootstrap_methods.get(bootstrap_method_index as usize),
+        _ => None,
+    }).next().ok_or_else(|| format!("Bootstrap method {} doesn't exist", bootstrap_method_index))?;
+    let handle = match classfile.constant_pool.get(bootstrap_method.bootstrap_method_ref) {
+        Some(&Constant::MethodHandle(kind, reference_index)) => handle(classfile, kind, reference_index)?,
+        _ => return Err(format!("Constant #{} isn't a MethodHandle", bootstrap_method.bootstrap_method_ref)),
+    };
+    let mut text = format!("{} {{", handle);
+    for &argument in &bootstrap_method.bootstrap_arguments {
+        write!(text, " {}", bootstrap_argument(classfile, argument)?).unwrap();
+    }
+    text.push_str(" }");
+    Ok(text)
+}
+
+// e.g. getstatic java/lang/System/out Ljava/io/PrintStream; or invokestatic interface java/util/List/of()Ljava/util/List;
+fn handle(classfile: &Classfile, kind: u8, reference_index: u16) -> Result<String, String> {
+    let kind_name = match HANDLE_KINDS.iter().find(|&&(_, k)| k == kind) {
+        Some(&(name, _)) => name,
+        None => return Err(format!("Invalid method handle kind {}", kind)),
+    };
+    match (kind, classfile.constant_pool.get(reference_index)) {
+        (REF_GET_FIELD..=REF_PUT_STATIC, Some(&Constant::Fieldref(class_index, name_and_type_index))) => {
+            let (name, descriptor) = name_and_type(classfile, name_and_type_index)?;
+            Ok(format!("{} {}/{} {}", kind_name, class_name(classfile, class_index)?, name, descriptor))
+        },
+        (REF_INVOKE_VIRTUAL..=REF_NEW_INVOKE_SPECIAL, Some(&Constant::Methodref(class_index, name_and_type_index))) => {
+            Ok(format!("{} {}", kind_name, method(classfile, class_index, name_and_type_index)?))
+        },
+        (REF_INVOKE_STATIC | REF_INVOKE_SPECIAL, Some(&Constant::InterfaceMethodref(class_index, name_and_type_index))) => {
+            Ok(format!("{} interface {}", kind_name, method(classfile, class_index, name_and_type_index)?))
+        },
+        (REF_INVOKE_INTERFACE, Some(&Constant::InterfaceMethodref(class_index, name_and_type_index))) => {
+            Ok(format!("{} {}", kind_name, method(classfile, class_index, name_and_type_index)?))
+        },
+        _ => Err(format!("Constant #{} doesn't fit a {} method handle", reference_index, kind_name)),
+    }
+}
+
+// the annotations of a class, field or method, which the builder writes with the visible ones first
+#[derive(Default)]
+struct Annotations<'a> {
+    visible: Vec<&'a Annotation>,
+    invisible: Vec<&'a Annotation>,
+}
+
+impl<'a> Annotations<'a> {
+    fn write(&self, out: &mut String, classfile: &Classfile, indent: &str) -> Result<(), String> {
+        for &(kind, annotations) in &[("visible", &self.visible), ("invisible", &self.invisible)] {
+            for annotation in annotations.iter() {
+                let header = format!("{}.annotation {} {}", indent, kind, class_descriptor(classfile, annotation.type_index)?);
+                write_annotation(out, classfile, &header, annotation, indent)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// the .annotation line, then a line for each element, then .end annotation
+fn write_annotation(out: &mut String, classfile: &Classfile, header: &str, annotation: &Annotation, indent: &str) -> Result<(), String> {
+    writeln!(out, "{}", header).unwrap();
+    let element_indent = format!("{}    ", indent);
+    for pair in &annotation.element_value_pairs {
+        let name = utf8(classfile, pair.element_name_index)?;
+        // a name at the start of a line is quoted if it could be taken for a directive
+        let name = if name.starts_with('.') { quote(name) } else { token(name) };
+        write_element_value(out, classfile, &format!("{}{} ", element_indent, name), &pair.value, &element_indent)?;
+    }
+    writeln!(out, "{}.end annotation", indent).unwrap();
+    Ok(())
+}
+
+// <type> = <value> after the prefix, e.g. I = 5 or [s = "a" "b", then a nested .annotation block for each value
+// that's an annotation
+fn write_element_value(out: &mut String, classfile: &Classfile, prefix: &str, value: &ElementValue, indent: &str) -> Result<(), String> {
+    let (is_array, values) = match *value {
+        ElementValue::Array(ref values) => (true, values.iter().collect()),
+        _ => (false, vec![value]),
+    };
+    let mut line = String::new();
+    let mut element_type = None;
+    let mut annotations = vec![];
+    for value in values {
+        let (value_type, constant) = element_constant(classfile, value)?;
+        // the type is only written once, so every element of an array needs the same one
+        if element_type.as_ref().is_some_and(|element_type| *element_type != value_type) {
+            return Err(format!("Arrays of both {} and {} elements can't be assembled", element_type.unwrap(), value_type));
+        }
+        element_type = Some(value_type);
+        match *value {
+            ElementValue::Annotation(ref annotation) => annotations.push(annotation),
+            _ => write!(line, " {}", constant).unwrap(),
+        }
+    }
+    // an empty array could have any type
+    let element_type = element_type.unwrap_or_else(|| "I".to_owned());
+    writeln!(out, "{}{}{} ={}", prefix, if is_array { "[" } else { "" }, element_type, line).unwrap();
+    for annotation in annotations {
+        write_annotation(out, classfile, &format!("{}    .annotation", indent), annotation, &format!("{}    ", indent))?;
+    }
+    Ok(())
+}
+
+// the type and value of an element that isn't an array, e.g. ("Z", "true"), or ("@ Ljava/lang/Deprecated;", "") for
+// an annotation, which is written as a block of its own
+fn element_constant(classfile: &Classfile, value: &ElementValue) -> Result<(String, String), String> {
+    let int = |index: u16| match classfile.constant_pool.get(index) {
+        Some(&Constant::Integer(value)) => Ok(value),
+        _ => Err(format!("Constant #{} isn't an Integer", index)),
+    };
+    let out_of_range = |value: i32, element_type: &str| format!("{} element value {} is out of range", element_type, value);
+    Ok(match *value {
+        ElementValue::Byte(index) => {
+            let value = int(index)?;
+            i8::try_from(value).map_err(|_| out_of_range(value, "Byte"))?;
+            ("B".to_owned(), value.to_string())
+        },
+        // chars are read back as Rust chars, which can't be surrogates
+        ElementValue::Char(index) => {
+            let value = int(index)?;
+            if !(0..=0xffff).contains(&value) || (0xd800..=0xdfff).contains(&value) {
+                return Err(out_of_range(value, "Char"));
+            }
+            ("C".to_owned(), value.to_string())
+        },
+        ElementValue::Short(index) => {
+            let value = int(index)?;
+            i16::try_from(value).map_err(|_| out_of_range(value, "Short"))?;
+            ("S".to_owned(), value.to_string())
+        },
+        ElementValue::Int(index) => ("I".to_owned(), int(index)?.to_string()),
+        ElementValue::Boolean(index) => match int(index)? {
+            0 => ("Z".to_owned(), "false".to_owned()),
+            1 => ("Z".to_owned(), "true".to_owned()),
+            value => return Err(out_of_range(value, "Boolean")),
+        },
+        ElementValue::Long(index) => match classfile.constant_pool.get(index) {
+            Some(&Constant::Long(value)) => ("J".to_owned(), value.to_string()),
+            _ => return Err(format!("Constant #{} isn't a Long", index)),
+        },
+        ElementValue::Float(index) => match classfile.constant_pool.get(index) {
+            Some(&Constant::Float(value)) => ("F".to_owned(), float_literal(value as f64, format!("{:?}", value))),
+            _ => return Err(format!("Constant #{} isn't a Float", index)),
+        },
+        ElementValue::Double(index) => match classfile.constant_pool.get(index) {
+            Some(&Constant::Double(value)) => ("D".to_owned(), float_literal(value, format!("{:?}", value))),
+            _ => return Err(format!("Constant #{} isn't a Double", index)),
+        },
+        ElementValue::String(index) => ("s".to_owned(), quote(utf8(classfile, index)?)),
+        ElementValue::Enum(type_index, name_index) => (format!("e {}", class_descriptor(classfile, type_index)?), token(utf8(classfile, name_index)?)),
+        ElementValue::Class(index) => ("c".to_owned(), utf8(classfile, index)?.to_owned()),
+        ElementValue::Annotation(ref annotation) => (format!("@ {}", class_descriptor(classfile, annotation.type_index)?), String::new()),
+        ElementValue::Array(_) => return Err("Arrays of arrays can't be assembled".to_owned()),
+    })
+}
+
+// e.g. Ljava/lang/Deprecated; for the type of an annotation or enum
+fn class_descriptor(classfile: &Classfile, index: u16) -> Result<&str, String> {
+    let descriptor = utf8(classfile, index)?;
+    match Java::parse(descriptor) {
+        Ok(Java::Class(_)) => Ok(descriptor),
+        _ => Err(format!("{:?} isn't a class descriptor", descriptor)),
+    }
+}
+
+// .inner class|interface <flags> [<name>] inner <class> [outer <class>]
+fn inner_class_directive(classfile: &Classfile, inner_class: &InnerClass) -> Result<String, String> {
+    let access_flags = inner_class.inner_class_access_flags;
+    let mut line = if access_flags & (ACC_INTERFACE | ACC_ABSTRACT) == ACC_INTERFACE | ACC_ABSTRACT {
+        format!(".inner interface {}", flags(access_flags & !(ACC_INTERFACE | ACC_ABSTRACT), INNER_CLASS_FLAGS))
+    } else {
+        format!(".inner class {}", flags(access_flags, INNER_CLASS_FLAGS))
+    };
+    if inner_class.inner_name_index != 0 {
+        let name = utf8(classfile, inner_class.inner_name_index)?;
+        let name = if name == "inner" || name == "outer" { quote(name) } else { name_token(name) };
+        write!(line, "{} ", name).unwrap();
+    }
+    write!(line, "inner {}", class_name(classfile, inner_class.inner_class_info_index)?).unwrap();
+    if inner_class.outer_class_info_index != 0 {
+        write!(line, " outer {}", class_name(classfile, inner_class.outer_class_info_index)?).unwrap();
+    }
+    Ok(line)
+}
+
+// a number or quoted string, as used by ldc and ConstantValue attributes
+fn constant_value(classfile: &Classfile, index: u16) -> Result<String, String> {
+    match classfile.constant_pool.get(index) {
+        Some(&Constant::Integer(value)) => Ok(value.to_string()),
+        Some(&Constant::Long(value)) => Ok(format!("{}L", value)),
+        Some(&Constant::Float(value)) => Ok(float_literal(value as f64, format!("{:?}", value))),
+        Some(&Constant::Double(value)) => Ok(float_literal(value, format!("{:?}", value))),
+        Some(&Constant::String(string_index)) => Ok(quote(utf8(classfile, string_index)?)),
+        _ => Err(format!("Constant #{} isn't a number or string", index)),
+    }
+}
+
+// Rust's shortest representation reads back as the same value, apart from the spelling of infinity
+fn float_literal(value: f64, shortest: String) -> String {
+    if value == f64::INFINITY {
+        "Infinity".to_owned()
+    } else if value == f64::NEG_INFINITY {
+        "-Infinity".to_owned()
+    } else {
+        shortest
+    }
+}
+
+// a name as a single token, quoted only if it has to be
+fn token(string: &str) -> String {
+    if string.is_empty() || string.contains(|c: char| c.is_whitespace() || c == '"' || c == ';' || c == ':') {
+        quote(string)
+    } else {
+        string.to_owned()
+    }
+}
+
+// a name that follows access flags, quoted if it could be taken for one, e.g. a field called strict
+fn name_token(name: &str) -> String {
+    if [CLASS_FLAGS, FIELD_FLAGS, METHOD_FLAGS, INNER_CLASS_FLAGS].iter().any(|names| names.iter().any(|&(_, flag)| flag == name)) {
+        quote(name)
+    } else {
+        token(name)
+    }
+}
+
+fn quote(string: &str) -> String {
+    let mut quoted = "\"".to_owned();
+    for c in string.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '\r' => quoted.push_str("\\r"),
+            c if c.is_control() => write!(quoted, "\\u{:04x}", c as u32).unwrap(),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn utf8(classfile: &Classfile, index: u16) -> Result<&str, String> {
+    match classfile.constant_pool.get(index) {
+        // the assembler only deals in Rust strings, which would lose them
+        Some(Constant::Utf8(string)) if string.keeps_original_bytes() => Err(format!("Constant #{} has an unpaired surrogate or other text that can't be assembled", index)),
+        Some(Constant::Utf8(string)) => Ok(string.as_str()),
+        _ => Err(format!("Constant #{} isn't a Utf8", index)),
+    }
+}
+
+fn class_name(classfile: &Classfile, index: u16) -> Result<&str, String> {
+    match classfile.constant_pool.get(index) {
+        Some(&Constant::Class(name_index)) => utf8(classfile, name_index),
+        _ => Err(format!("Constant #{} isn't a Class", index)),
+    }
+}
+
+fn name_and_type(classfile: &Classfile, index: u16) -> Result<(&str, &str), String> {
+    match classfile.constant_pool.get(index) {
+        Some(&Constant::NameAndType(name_index, descriptor_index)) => Ok((utf8(classfile, name_index)?, utf8(classfile, descriptor_index)?)),
+        _ => Err(format!("Constant #{} isn't a NameAndType", index)),
+    }
+}
+
+// e.g. "RuntimeVisibleTypeAnnotations attributes can't be assembled"
+fn unsupported(classfile: &Classfile, attribute: &Attribute) -> String {
+    let name = utf8(classfile, attribute.name_index()).unwrap_or("Unnamed");
+    format!("{} attributes can't be assembled", name)
+}
+
+const CLASS_FLAGS: &[(u16, &str)] = &[
+    (ACC_PUBLIC, "public"), (ACC_FINAL, "final"), (ACC_SUPER, "super"), (ACC_INTERFACE, "interface"),
+    (ACC_ABSTRACT, "abstract"), (ACC_SYNTHETIC, "synthetic"), (ACC_ANNOTATION, "annotation"), (ACC_ENUM, "enum"),
+];
+
+const FIELD_FLAGS: &[(u16, &str)] = &[
+    (ACC_PUBLIC, "public"), (ACC_PRIVATE, "private"), (ACC_PROTECTED, "protected"), (ACC_STATIC, "static"),
+    (ACC_FINAL, "final"), (ACC_VOLATILE, "volatile"), (ACC_TRANSIENT, "transient"), (ACC_SYNTHETIC, "synthetic"),
+    (ACC_ENUM, "enum"),
+];
+
+const INNER_CLASS_FLAGS: &[(u16, &str)] = &[
+    (ACC_PUBLIC, "public"), (ACC_PRIVATE, "private"), (ACC_PROTECTED, "protected"), (ACC_STATIC, "static"),
+    (ACC_FINAL, "final"), (ACC_INTERFACE, "interface"), (ACC_ABSTRACT, "abstract"), (ACC_SYNTHETIC, "synthetic"),
+    (ACC_ANNOTATION, "annotation"), (ACC_ENUM, "enum"),
+];
+
+const METHOD_FLAGS: &[(u16, &str)] = &[
+    (ACC_PUBLIC, "public"), (ACC_PRIVATE, "private"), (ACC_PROTECTED, "protected"), (ACC_STATIC, "static"),
+    (ACC_FINAL, "final"), (ACC_SYNCHRONIZED, "synchronized"), (ACC_BRIDGE, "bridge"), (ACC_VARARGS, "varargs"), (ACC_NATIVE, "native"),
+    (ACC_ABSTRACT, "abstract"), (ACC_STRICT, "strict"), (ACC_SYNTHETIC, "synthetic"),
+];
+
+// e.g. "public static ", ready to be followed by a name
+fn flags(access_flags: u16, names: &[(u16, &str)]) -> String {
+    names.iter().filter(|&&(flag, _)| access_flags & flag != 0).map(|&(_, name)| format!("{} ", name)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::*;
+    use assembler::*;
+
+    // the classes in the stack map frames of the named method
+    fn frame_classes<'a>(classfile: &'a Classfile, method_name: &str) -> Vec<&'a str> {
+        let method = classfile.methods.iter().find(|method| classfile.constant_pool.get_utf8(method.name_index) == Some(method_name)).unwrap();
+        let mut classes = vec![];
+        for attribute in &method.attributes {
+            if let Attribute::Code(_, _, _, _, _, ref attributes) = *attribute {
+                for attribute in attributes {
+                    if let Attribute::StackMapTable(_, ref frames) = *attribute {
+                        for frame in frames {
+                            let types: Vec<&VerificationType> = match *frame {
+                                StackMapFrame::SameLocals1StackItemFrame(_, ref t) | StackMapFrame::SameLocals1StackItemFrameExtended(_, ref t) => vec![t],
+                                StackMapFrame::AppendFrame(_, _, ref locals) => locals.iter().collect(),
+                                StackMapFrame::FullFrame(_, ref locals, ref stack) => locals.iter().chain(stack).collect(),
+                                _ => vec![],
+                            };
+                            classes.extend(types.into_iter().filter_map(|t| match *t {
+                                VerificationType::Object(index) => classfile.constant_pool.get_class_name(index),
+                                _ => None,
+                            }));
+                        }
+                    }
+                }
+            }
+        }
+        classes
+    }
+
+    #[test]
+    fn reassembles_javac_frames_with_their_merged_classes() {
+        // see tests/data/Merge.java
+        let original = Classfile::deserialize(Box::new(&include_bytes!("../tests/data/Merge.class")[..])).unwrap();
+        let source = disassemble(&original).unwrap();
+        assert!(source.contains("        locals Object java/lang/Number\n"));
+        let reassembled = assemble(&source).unwrap();
+        assert_eq!(frame_classes(&reassembled, "number"), frame_classes(&original, "number"));
+        assert!(frame_classes(&reassembled, "number").contains(&"java/lang/Number"));
+        assert!(frame_classes(&reassembled, "exception").contains(&"java/lang/Exception"));
+    }
+
+    #[test]
+    fn refuses_strings_with_unpaired_surrogates() {
+        let mut class = ClassBuilder::new(ACC_PUBLIC, "Test", "java/lang/Object");
+        {
+            let mut method = class.define_method(ACC_PUBLIC | ACC_STATIC, "test", &[], &Java::Class("java/lang/String".into()));
+            method.load_constant("SURROGATEx");
+            method.do_return();
+            method.done().unwrap();
+        }
+        let mut bytes = vec![];
+        class.done().unwrap().serialize(&mut bytes).unwrap();
+
+        // swap the placeholder for "\uD800x", which modified UTF-8 writes as the three bytes of the lone surrogate
+        let placeholder = b"\x01\x00\x0aSURROGATEx";
+        let position = bytes.windows(placeholder.len()).position(|window| window == placeholder).unwrap();
+        bytes.splice(position..position + placeholder.len(), b"\x01\x00\x04\xed\xa0\x80x".iter().cloned());
+
+        let classfile = Classfile::deserialize(Box::new(io::Cursor::new(bytes))).unwrap();
+        let error = disassemble(&classfile).unwrap_err();
+        assert!(error.message.contains("unpaired surrogate"), "{}", error);
+    }
+}
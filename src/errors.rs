@@ -0,0 +1,228 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use class_builder::{Label, Local, TryBlock};
+
+#[derive(Debug)]
+pub struct ClassfileError {
+    pub offset: usize,   // byte offset into the class file where the problem was found
+    pub context: String, // the structure being read or written, e.g. "method #3 attribute Code"
+    pub cause: ClassfileErrorCause,
+}
+
+#[derive(Debug)]
+pub enum ClassfileErrorCause {
+    Io(io::Error),
+    UnexpectedEof,
+    TrailingBytes(usize),
+    InvalidMagic(u32),
+    InvalidConstantTag(u8),
+    InvalidConstantIndex(u16),
+    InvalidModifiedUtf8,
+    StringTooLong(usize),
+    InvalidOpcode(u8),
+    InvalidWideOpcode(u8),
+    InvalidStackMapFrameType(u8),
+    InvalidVerificationType(u8),
+    InvalidElementValueTag(u8),
+    InvalidTargetType(u8),
+    AttributeDecoderFailed(String),
+    TooManyEntries(usize),
+    TooManyEntriesForByteCount(usize),
+    CodeTooLarge(usize),
+}
+
+impl ClassfileError {
+    pub fn new(offset: usize, cause: ClassfileErrorCause) -> ClassfileError {
+        ClassfileError {
+            offset,
+            context: String::new(),
+            cause,
+        }
+    }
+
+    // Errors are created where the bad bytes are found, and pick up the names of the enclosing structures on the way out.
+    pub fn within(mut self, context: &str) -> ClassfileError {
+        if self.context.is_empty() {
+            self.context = context.to_owned();
+        } else {
+            self.context = format!("{} {}", context, self.context);
+        }
+        self
+    }
+}
+
+impl From<io::Error> for ClassfileError {
+    fn from(error: io::Error) -> ClassfileError {
+        ClassfileError::new(0, ClassfileErrorCause::Io(error))
+    }
+}
+
+impl fmt::Display for ClassfileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.cause)?;
+        if !self.context.is_empty() {
+            write!(f, " in {}", self.context)?;
+        }
+        write!(f, " at byte offset {}", self.offset)
+    }
+}
+
+impl Error for ClassfileError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self.cause {
+            ClassfileErrorCause::Io(ref error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ClassfileErrorCause {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ClassfileErrorCause::Io(ref error) => write!(f, "I/O error: {}", error),
+            ClassfileErrorCause::UnexpectedEof => write!(f, "Unexpected end of data"),
+            ClassfileErrorCause::TrailingBytes(n) => write!(f, "{} unexpected trailing bytes", n),
+            ClassfileErrorCause::InvalidMagic(magic) => write!(f, "Invalid magic number 0x{:X}", magic),
+            ClassfileErrorCause::InvalidConstantTag(tag) => write!(f, "Invalid constant tag {}", tag),
+            ClassfileErrorCause::InvalidConstantIndex(index) => write!(f, "Invalid constant pool index {}", index),
+            ClassfileErrorCause::InvalidModifiedUtf8 => write!(f, "Invalid modified UTF-8 string"),
+            ClassfileErrorCause::StringTooLong(n) => write!(f, "String is too long ({} bytes when encoded, the limit is 65535)", n),
+            ClassfileErrorCause::InvalidOpcode(code) => write!(f, "Invalid opcode 0x{:X}", code),
+            ClassfileErrorCause::InvalidWideOpcode(code) => write!(f, "Invalid opcode 0x{:X} after wide", code),
+            ClassfileErrorCause::InvalidStackMapFrameType(frame_type) => write!(f, "Invalid stack map frame type {}", frame_type),
+            ClassfileErrorCause::InvalidVerificationType(tag) => write!(f, "Invalid verification type {}", tag),
+            ClassfileErrorCause::InvalidElementValueTag(tag) => write!(f, "Invalid annotation element value tag {:?}", tag as char),
+            ClassfileErrorCause::InvalidTargetType(target_type) => write!(f, "Invalid type annotation target type 0x{:X}", target_type),
+            ClassfileErrorCause::AttributeDecoderFailed(ref message) => write!(f, "Attribute decoder failed: {}", message),
+            ClassfileErrorCause::TooManyEntries(n) => write!(f, "Too many entries ({}) for a 2-byte count", n),
+            ClassfileErrorCause::TooManyEntriesForByteCount(n) => write!(f, "Too many entries ({}) for a 1-byte count", n),
+            ClassfileErrorCause::CodeTooLarge(n) => write!(f, "Method code is too large ({} bytes)", n),
+        }
+    }
+}
+
+// A malformed field or method descriptor
+#[derive(Debug, PartialEq)]
+pub struct DescriptorError {
+    pub descriptor: String,
+    pub offset: usize, // byte offset into the descriptor where parsing failed
+}
+
+impl fmt::Display for DescriptorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid descriptor {:?} at offset {}", self.descriptor, self.offset)
+    }
+}
+
+impl Error for DescriptorError {}
+
+// A malformed generic signature
+#[derive(Debug, PartialEq)]
+pub struct SignatureError {
+    pub signature: String,
+    pub offset: usize, // byte offset into the signature where parsing failed
+}
+
+impl fmt::Display for SignatureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid signature {:?} at offset {}", self.signature, self.offset)
+    }
+}
+
+impl Error for SignatureError {}
+
+// An error in the code given to a MethodBuilder, found when the method is finished, or in a class being finished by
+// ClassBuilder::done
+#[derive(Debug)]
+pub struct BuildError {
+    pub method: String, // empty for an error in the class as a whole
+    pub cause: BuildErrorCause,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum BuildErrorCause {
+    UndefinedLabel(Label),
+    DuplicateLabel(Label),
+    SwitchLabelCount(i32, i32, usize), // low, high and the number of labels given to table_switch
+    DuplicateSwitchKey(i32),
+    TryBlockNeverEnded(TryBlock),
+    TryBlockEndedTwice(TryBlock),
+    UndefinedLocal(Local),
+    LocalOutOfScope(Local),
+    VoidLocal(String),
+    NoSuchArgument(usize), // an index passed to name_argument or parameter_annotation
+    CodeTooLarge(usize),
+    CodeInAbstractMethod,
+    ConstantPoolFull,
+    InvalidCode(String), // found while working out the stack map frames, e.g. popping from an empty stack
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.method.is_empty() {
+            write!(f, "{}", self.cause)
+        } else {
+            write!(f, "{} in method {}", self.cause, self.method)
+        }
+    }
+}
+
+impl Error for BuildError {}
+
+impl fmt::Display for BuildErrorCause {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BuildErrorCause::UndefinedLabel(label) => write!(f, "{} is jumped to but never placed", label),
+            BuildErrorCause::DuplicateLabel(label) => write!(f, "{} is placed more than once", label),
+            BuildErrorCause::SwitchLabelCount(low, high, n) => write!(f, "Table switch from {} to {} needs {} labels, but was given {}", low, high, high as i64 - low as i64 + 1, n),
+            BuildErrorCause::DuplicateSwitchKey(key) => write!(f, "Switch has more than one case for key {}", key),
+            BuildErrorCause::TryBlockNeverEnded(block) => write!(f, "{} was never ended", block),
+            BuildErrorCause::TryBlockEndedTwice(block) => write!(f, "{} is ended more than once", block),
+            BuildErrorCause::UndefinedLocal(local) => write!(f, "{} was declared in another method", local),
+            BuildErrorCause::LocalOutOfScope(local) => write!(f, "{} is used after end_local", local),
+            BuildErrorCause::VoidLocal(ref name) => write!(f, "Local variable {:?} can't be void", name),
+            BuildErrorCause::NoSuchArgument(n) => write!(f, "Method has no argument {}", n),
+            BuildErrorCause::CodeTooLarge(n) => write!(f, "Method code is too large ({} bytes)", n),
+            BuildErrorCause::CodeInAbstractMethod => write!(f, "Abstract and native methods can't have code"),
+            BuildErrorCause::ConstantPoolFull => write!(f, "Class has more constants than fit in the constant pool"),
+            BuildErrorCause::InvalidCode(ref message) => write!(f, "Invalid code: {}", message),
+        }
+    }
+}
+
+// A problem in assembly source, at a 1-based line and column
+#[derive(Debug, PartialEq)]
+pub struct AssemblyError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AssemblyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}, column {}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl Error for AssemblyError {}
+
+// Something in a class file that can't be written as assembly source
+#[derive(Debug, PartialEq)]
+pub struct DisassemblyError {
+    pub context: String, // e.g. "method main([Ljava/lang/String;)V"
+    pub message: String,
+}
+
+impl fmt::Display for DisassemblyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.context.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{} in {}", self.message, self.context)
+        }
+    }
+}
+
+impl Error for DisassemblyError {}
@@ -1,5 +1,8 @@
+use std::borrow::Cow;
 use std::fmt;
 
+use errors::*;
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum Java<'a> {
     Boolean, // Z
@@ -11,10 +14,147 @@ pub enum Java<'a> {
     Float,   // F
     Double,  // D
     Void,    // V
-    Class(&'a str), // Lclassname;
+    Class(Cow<'a, str>), // Lclassname; (the internal name, like java/lang/String)
     Array(Box<Java<'a>>), // [type
 }
 
+// A method's argument and return types, as described by a method descriptor like (I[Ljava/lang/String;)V
+#[derive(Clone, Debug, PartialEq)]
+pub struct MethodDescriptor<'a> {
+    pub args: Vec<Java<'a>>,
+    pub ret: Java<'a>,
+}
+
+impl<'a> Java<'a> {
+    // A class type, from either a borrowed name or an owned one (e.g. a name built at runtime).
+    pub fn class<S: Into<Cow<'a, str>>>(name: S) -> Java<'a> {
+        Java::Class(name.into())
+    }
+
+    pub fn array(component_type: Java<'a>) -> Java<'a> {
+        Java::Array(Box::new(component_type))
+    }
+
+    // A copy of this type that doesn't borrow anything.
+    pub fn into_owned(self) -> Java<'static> {
+        match self {
+            Java::Boolean => Java::Boolean,
+            Java::Byte => Java::Byte,
+            Java::Char => Java::Char,
+            Java::Short => Java::Short,
+            Java::Int => Java::Int,
+            Java::Long => Java::Long,
+            Java::Float => Java::Float,
+            Java::Double => Java::Double,
+            Java::Void => Java::Void,
+            Java::Class(name) => Java::Class(Cow::Owned(name.into_owned())),
+            Java::Array(component_type) => Java::Array(Box::new(component_type.into_owned())),
+        }
+    }
+
+    // The number of local variable or operand stack slots taken by a value of this type: 2 for long and double,
+    // 0 for void and 1 for everything else.
+    pub fn slot_count(&self) -> u16 {
+        match *self {
+            Java::Long | Java::Double => 2,
+            Java::Void => 0,
+            _ => 1,
+        }
+    }
+
+    // Parses a field descriptor, like I or [Ljava/lang/String;. V isn't a field descriptor, so it's rejected.
+    pub fn parse(descriptor: &'a str) -> Result<Java<'a>, DescriptorError> {
+        let invalid = |offset| DescriptorError { descriptor: descriptor.to_owned(), offset };
+        let (t, end) = parse_field_type(descriptor, 0).map_err(invalid)?;
+        if end != descriptor.len() {
+            return Err(invalid(end));
+        }
+        Ok(t)
+    }
+}
+
+impl<'a> MethodDescriptor<'a> {
+    pub fn new(args: Vec<Java<'a>>, ret: Java<'a>) -> MethodDescriptor<'a> {
+        MethodDescriptor { args, ret }
+    }
+
+    pub fn into_owned(self) -> MethodDescriptor<'static> {
+        MethodDescriptor {
+            args: self.args.into_iter().map(Java::into_owned).collect(),
+            ret: self.ret.into_owned(),
+        }
+    }
+
+    // The number of local variable slots taken by the arguments, not counting `this` for instance methods.
+    pub fn slot_count(&self) -> u16 {
+        self.args.iter().map(Java::slot_count).sum()
+    }
+
+    pub fn parse(descriptor: &'a str) -> Result<MethodDescriptor<'a>, DescriptorError> {
+        let invalid = |offset| DescriptorError { descriptor: descriptor.to_owned(), offset };
+        if !descriptor.starts_with('(') {
+            return Err(invalid(0));
+        }
+
+        let mut args = vec![];
+        let mut offset = 1;
+        while !descriptor[offset..].starts_with(')') {
+            let (t, end) = parse_field_type(descriptor, offset).map_err(invalid)?;
+            args.push(t);
+            offset = end;
+        }
+        offset += 1;
+
+        let (ret, end) = if descriptor[offset..].starts_with('V') {
+            (Java::Void, offset + 1)
+        } else {
+            parse_field_type(descriptor, offset).map_err(invalid)?
+        };
+        if end != descriptor.len() {
+            return Err(invalid(end));
+        }
+        Ok(MethodDescriptor { args, ret })
+    }
+}
+
+// Parses the field type starting at the given byte offset, returning it and the offset just after it, or the
+// offset where it went wrong.
+fn parse_field_type(descriptor: &str, start: usize) -> Result<(Java<'_>, usize), usize> {
+    let rest = &descriptor[start..];
+    let t = match rest.chars().next() {
+        Some('Z') => Java::Boolean,
+        Some('B') => Java::Byte,
+        Some('C') => Java::Char,
+        Some('S') => Java::Short,
+        Some('I') => Java::Int,
+        Some('J') => Java::Long,
+        Some('F') => Java::Float,
+        Some('D') => Java::Double,
+        Some('L') => {
+            let end = rest.find(';').ok_or(descriptor.len())?;
+            let class = &rest[1..end];
+            // internal names are /-separated, and neither they nor their parts can be empty
+            if let Some(i) = class.find(['.', '[']) {
+                return Err(start + 1 + i);
+            }
+            if class.is_empty() || class.split('/').any(|part| part.is_empty()) {
+                return Err(start + 1);
+            }
+            return Ok((Java::Class(Cow::Borrowed(class)), start + end + 1));
+        },
+        Some('[') => {
+            let dimensions = rest.len() - rest.trim_start_matches('[').len();
+            if dimensions > 255 {
+                return Err(start + 255);
+            }
+            let (component, end) = parse_field_type(descriptor, start + 1)?;
+            return Ok((Java::Array(Box::new(component)), end));
+        },
+        _ => return Err(start),
+    };
+    Ok((t, start + 1))
+}
+
 impl<'a> fmt::Display for Java<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
@@ -33,6 +173,12 @@ impl<'a> fmt::Display for Java<'a> {
     }
 }
 
+impl<'a> fmt::Display for MethodDescriptor<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", method_signature(&self.args, &self.ret))
+    }
+}
+
 pub fn method_signature(argument_types: &[Java], return_type: &Java) -> String {
     let mut args = "".to_owned();
     for t in argument_types {
@@ -40,3 +186,510 @@ pub fn method_signature(argument_types: &[Java], return_type: &Java) -> String {
     }
     format!("({}){}", args, return_type)
 }
+
+// Converts a binary class name as used in Java source and reflection (java.lang.String, or java.util.Map$Entry)
+// to the internal form used in class files (java/lang/String).
+pub fn binary_name_to_internal(name: &str) -> String {
+    name.replace('.', "/")
+}
+
+// Converts an internal class name (java/lang/String) to its binary name (java.lang.String).
+pub fn internal_name_to_binary(name: &str) -> String {
+    name.replace('/', ".")
+}
+
+// Generic type information, as stored in Signature attributes (JVMS 4.7.9.1). Unlike descriptors, these keep type
+// arguments and type variables, e.g. Ljava/util/List<TT;>; rather than Ljava/util/List;.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TypeSignature {
+    Boolean, // Z
+    Byte,    // B
+    Char,    // C
+    Short,   // S
+    Int,     // I
+    Long,    // J
+    Float,   // F
+    Double,  // D
+    Void,    // V (only as a method's return type)
+    Class(ClassTypeSignature),
+    TypeVariable(String),       // Tname;
+    Array(Box<TypeSignature>), // [type
+}
+
+// A class type like Ljava/util/Map<TK;TV;>.Entry<TK;TV;>;
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClassTypeSignature {
+    pub name: String,                                    // internal name of the outermost class, like java/util/Map
+    pub type_arguments: Vec<TypeArgument>,
+    pub inner_classes: Vec<(String, Vec<TypeArgument>)>, // simple names of nested classes, with their own type arguments
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TypeArgument {
+    Any,                   // *
+    Exact(TypeSignature),  // type
+    Extends(TypeSignature), // +type (? extends type)
+    Super(TypeSignature),   // -type (? super type)
+}
+
+// A type parameter like T:Ljava/lang/Object; or T::Ljava/lang/Comparable<TT;>;
+#[derive(Clone, Debug, PartialEq)]
+pub struct TypeParameter {
+    pub name: String,
+    pub class_bound: Option<TypeSignature>, // None when the only bounds are interfaces
+    pub interface_bounds: Vec<TypeSignature>,
+}
+
+// The signature of a generic class or interface: its type parameters, superclass and interfaces.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClassSignature {
+    pub type_parameters: Vec<TypeParameter>,
+    pub superclass: ClassTypeSignature,
+    pub interfaces: Vec<ClassTypeSignature>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MethodSignature {
+    pub type_parameters: Vec<TypeParameter>,
+    pub args: Vec<TypeSignature>,
+    pub ret: TypeSignature,
+    pub throws: Vec<TypeSignature>, // class types or type variables
+}
+
+impl TypeSignature {
+    // A class type with no type arguments.
+    pub fn class(name: &str) -> TypeSignature {
+        TypeSignature::Class(ClassTypeSignature::new(name, vec![]))
+    }
+
+    // A class type with type arguments, like List<String>.
+    pub fn generic_class(name: &str, type_arguments: Vec<TypeArgument>) -> TypeSignature {
+        TypeSignature::Class(ClassTypeSignature::new(name, type_arguments))
+    }
+
+    pub fn type_variable(name: &str) -> TypeSignature {
+        TypeSignature::TypeVariable(name.to_owned())
+    }
+
+    pub fn array(component_type: TypeSignature) -> TypeSignature {
+        TypeSignature::Array(Box::new(component_type))
+    }
+
+    // Parses a field signature. Base types like I are accepted too, although javac only writes Signature attributes
+    // for fields with class, array or type variable types.
+    pub fn parse(signature: &str) -> Result<TypeSignature, SignatureError> {
+        let mut parser = SignatureParser::new(signature);
+        let t = parser.type_signature(false)?;
+        parser.end()?;
+        Ok(t)
+    }
+}
+
+impl ClassTypeSignature {
+    pub fn new(name: &str, type_arguments: Vec<TypeArgument>) -> ClassTypeSignature {
+        ClassTypeSignature {
+            name: name.to_owned(),
+            type_arguments,
+            inner_classes: vec![],
+        }
+    }
+}
+
+impl TypeParameter {
+    // A type parameter bounded by a class, like T extends Number (or just T, with java/lang/Object).
+    pub fn new(name: &str, class_bound: TypeSignature) -> TypeParameter {
+        TypeParameter {
+            name: name.to_owned(),
+            class_bound: Some(class_bound),
+            interface_bounds: vec![],
+        }
+    }
+}
+
+impl ClassSignature {
+    pub fn parse(signature: &str) -> Result<ClassSignature, SignatureError> {
+        let mut parser = SignatureParser::new(signature);
+        let type_parameters = parser.type_parameters()?;
+        let superclass = parser.class_type_signature()?;
+        let mut interfaces = vec![];
+        while !parser.at_end() {
+            interfaces.push(parser.class_type_signature()?);
+        }
+        Ok(ClassSignature { type_parameters, superclass, interfaces })
+    }
+}
+
+impl MethodSignature {
+    pub fn parse(signature: &str) -> Result<MethodSignature, SignatureError> {
+        let mut parser = SignatureParser::new(signature);
+        let type_parameters = parser.type_parameters()?;
+        parser.expect('(')?;
+        let mut args = vec![];
+        while !parser.eat(')') {
+            args.push(parser.type_signature(false)?);
+        }
+        let ret = parser.type_signature(true)?;
+        let mut throws = vec![];
+        while parser.eat('^') {
+            throws.push(match parser.peek() {
+                Some('T') => parser.type_signature(false)?,
+                _ => TypeSignature::Class(parser.class_type_signature()?),
+            });
+        }
+        parser.end()?;
+        Ok(MethodSignature { type_parameters, args, ret, throws })
+    }
+}
+
+struct SignatureParser<'a> {
+    signature: &'a str,
+    offset: usize,
+}
+
+impl<'a> SignatureParser<'a> {
+    fn new(signature: &'a str) -> SignatureParser<'a> {
+        SignatureParser { signature, offset: 0 }
+    }
+
+    fn error(&self) -> SignatureError {
+        SignatureError { signature: self.signature.to_owned(), offset: self.offset }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.signature[self.offset..].chars().next()
+    }
+
+    fn at_end(&self) -> bool {
+        self.offset == self.signature.len()
+    }
+
+    fn end(&self) -> Result<(), SignatureError> {
+        if self.at_end() { Ok(()) } else { Err(self.error()) }
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.offset += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), SignatureError> {
+        if self.eat(c) { Ok(()) } else { Err(self.error()) }
+    }
+
+    // identifiers can contain anything except the characters that separate the parts of a signature
+    fn identifier(&mut self) -> Result<String, SignatureError> {
+        let rest = &self.signature[self.offset..];
+        let end = rest.find(['.', ';', '[', '/', '<', '>', ':']).unwrap_or(rest.len());
+        if end == 0 {
+            return Err(self.error());
+        }
+        self.offset += end;
+        Ok(rest[..end].to_owned())
+    }
+
+    fn type_parameters(&mut self) -> Result<Vec<TypeParameter>, SignatureError> {
+        let mut type_parameters = vec![];
+        if self.eat('<') {
+            // there has to be at least one
+            if self.peek() == Some('>') {
+                return Err(self.error());
+            }
+            while !self.eat('>') {
+                let name = self.identifier()?;
+                self.expect(':')?;
+                let class_bound = match self.peek() {
+                    Some(':') | Some('>') => None,
+                    _ => Some(self.reference_type_signature()?),
+                };
+                let mut interface_bounds = vec![];
+                while self.eat(':') {
+                    interface_bounds.push(self.reference_type_signature()?);
+                }
+                type_parameters.push(TypeParameter { name, class_bound, interface_bounds });
+            }
+        }
+        Ok(type_parameters)
+    }
+
+    fn type_signature(&mut self, allow_void: bool) -> Result<TypeSignature, SignatureError> {
+        let t = match self.peek() {
+            Some('Z') => TypeSignature::Boolean,
+            Some('B') => TypeSignature::Byte,
+            Some('C') => TypeSignature::Char,
+            Some('S') => TypeSignature::Short,
+            Some('I') => TypeSignature::Int,
+            Some('J') => TypeSignature::Long,
+            Some('F') => TypeSignature::Float,
+            Some('D') => TypeSignature::Double,
+            Some('V') if allow_void => TypeSignature::Void,
+            _ => return self.reference_type_signature(),
+        };
+        self.offset += 1;
+        Ok(t)
+    }
+
+    fn reference_type_signature(&mut self) -> Result<TypeSignature, SignatureError> {
+        match self.peek() {
+            Some('L') => Ok(TypeSignature::Class(self.class_type_signature()?)),
+            Some('T') => {
+                self.offset += 1;
+                let name = self.identifier()?;
+                self.expect(';')?;
+                Ok(TypeSignature::TypeVariable(name))
+            },
+            Some('[') => {
+                self.offset += 1;
+                Ok(TypeSignature::Array(Box::new(self.type_signature(false)?)))
+            },
+            _ => Err(self.error()),
+        }
+    }
+
+    fn class_type_signature(&mut self) -> Result<ClassTypeSignature, SignatureError> {
+        self.expect('L')?;
+        let mut name = self.identifier()?;
+        while self.eat('/') {
+            name.push('/');
+            name.push_str(&self.identifier()?);
+        }
+        let type_arguments = self.type_arguments()?;
+        let mut inner_classes = vec![];
+        while self.eat('.') {
+            let inner_name = self.identifier()?;
+            inner_classes.push((inner_name, self.type_arguments()?));
+        }
+        self.expect(';')?;
+        Ok(ClassTypeSignature { name, type_arguments, inner_classes })
+    }
+
+    fn type_arguments(&mut self) -> Result<Vec<TypeArgument>, SignatureError> {
+        let mut type_arguments = vec![];
+        if self.eat('<') {
+            if self.peek() == Some('>') {
+                return Err(self.error());
+            }
+            while !self.eat('>') {
+                type_arguments.push(if self.eat('*') {
+                    TypeArgument::Any
+                } else if self.eat('+') {
+                    TypeArgument::Extends(self.reference_type_signature()?)
+                } else if self.eat('-') {
+                    TypeArgument::Super(self.reference_type_signature()?)
+                } else {
+                    TypeArgument::Exact(self.reference_type_signature()?)
+                });
+            }
+        }
+        Ok(type_arguments)
+    }
+}
+
+impl fmt::Display for TypeSignature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TypeSignature::Boolean             => write!(f, "Z"),
+            TypeSignature::Byte                => write!(f, "B"),
+            TypeSignature::Char                => write!(f, "C"),
+            TypeSignature::Short               => write!(f, "S"),
+            TypeSignature::Int                 => write!(f, "I"),
+            TypeSignature::Long                => write!(f, "J"),
+            TypeSignature::Float               => write!(f, "F"),
+            TypeSignature::Double              => write!(f, "D"),
+            TypeSignature::Void                => write!(f, "V"),
+            TypeSignature::Class(ref c)        => write!(f, "{}", c),
+            TypeSignature::TypeVariable(ref s) => write!(f, "T{};", s),
+            TypeSignature::Array(ref t)        => write!(f, "[{}", t),
+        }
+    }
+}
+
+impl fmt::Display for ClassTypeSignature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "L{}", self.name)?;
+        write_type_arguments(f, &self.type_arguments)?;
+        for (name, type_arguments) in &self.inner_classes {
+            write!(f, ".{}", name)?;
+            write_type_arguments(f, type_arguments)?;
+        }
+        write!(f, ";")
+    }
+}
+
+impl fmt::Display for TypeArgument {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TypeArgument::Any            => write!(f, "*"),
+            TypeArgument::Exact(ref t)   => write!(f, "{}", t),
+            TypeArgument::Extends(ref t) => write!(f, "+{}", t),
+            TypeArgument::Super(ref t)   => write!(f, "-{}", t),
+        }
+    }
+}
+
+impl fmt::Display for TypeParameter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:", self.name)?;
+        if let Some(ref class_bound) = self.class_bound {
+            write!(f, "{}", class_bound)?;
+        }
+        for bound in &self.interface_bounds {
+            write!(f, ":{}", bound)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for ClassSignature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_type_parameters(f, &self.type_parameters)?;
+        write!(f, "{}", self.superclass)?;
+        for interface in &self.interfaces {
+            write!(f, "{}", interface)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for MethodSignature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_type_parameters(f, &self.type_parameters)?;
+        write!(f, "(")?;
+        for arg in &self.args {
+            write!(f, "{}", arg)?;
+        }
+        write!(f, "){}", self.ret)?;
+        for t in &self.throws {
+            write!(f, "^{}", t)?;
+        }
+        Ok(())
+    }
+}
+
+fn write_type_arguments(f: &mut fmt::Formatter, type_arguments: &[TypeArgument]) -> fmt::Result {
+    if !type_arguments.is_empty() {
+        write!(f, "<")?;
+        for type_argument in type_arguments {
+            write!(f, "{}", type_argument)?;
+        }
+        write!(f, ">")?;
+    }
+    Ok(())
+}
+
+fn write_type_parameters(f: &mut fmt::Formatter, type_parameters: &[TypeParameter]) -> fmt::Result {
+    if !type_parameters.is_empty() {
+        write!(f, "<")?;
+        for type_parameter in type_parameters {
+            write!(f, "{}", type_parameter)?;
+        }
+        write!(f, ">")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_field_descriptors() {
+        assert_eq!(Java::parse("I"), Ok(Java::Int));
+        assert_eq!(Java::parse("Ljava/lang/String;"), Ok(Java::class("java/lang/String")));
+        assert_eq!(Java::parse("[[J"), Ok(Java::array(Java::array(Java::Long))));
+    }
+
+    #[test]
+    fn rejects_malformed_field_descriptors() {
+        let offset = |descriptor| Java::parse(descriptor).unwrap_err().offset;
+        assert_eq!(offset(""), 0);
+        assert_eq!(offset("V"), 0);
+        assert_eq!(offset("II"), 1);
+        assert_eq!(offset("Ljava/lang/String"), 17);
+        assert_eq!(offset("Ljava.lang.String;"), 5);
+        assert_eq!(offset("Ljava//String;"), 1);
+        assert_eq!(offset("[V"), 1);
+    }
+
+    #[test]
+    fn limits_arrays_to_255_dimensions() {
+        assert!(Java::parse(&format!("{}I", "[".repeat(255))).is_ok());
+        assert_eq!(Java::parse(&format!("{}I", "[".repeat(256))).unwrap_err().offset, 255);
+    }
+
+    #[test]
+    fn parses_method_descriptors() {
+        let descriptor = MethodDescriptor::parse("(I[Ljava/lang/String;J)V").unwrap();
+        assert_eq!(descriptor.args, vec![Java::Int, Java::array(Java::class("java/lang/String")), Java::Long]);
+        assert_eq!(descriptor.ret, Java::Void);
+        assert_eq!(descriptor.slot_count(), 4);
+        assert_eq!(descriptor.to_string(), "(I[Ljava/lang/String;J)V");
+    }
+
+    #[test]
+    fn rejects_malformed_method_descriptors() {
+        let offset = |descriptor| MethodDescriptor::parse(descriptor).unwrap_err().offset;
+        assert_eq!(offset("I)V"), 0);
+        assert_eq!(offset("(V)V"), 1);
+        assert_eq!(offset("(I)"), 3);
+        assert_eq!(offset("(I)VI"), 4);
+    }
+
+    #[test]
+    fn parses_nested_generic_class_types() {
+        let signature = "Ljava/util/Map<TK;TV;>.Entry<+Ljava/lang/Number;*>;";
+        let t = TypeSignature::parse(signature).unwrap();
+        let expected = ClassTypeSignature {
+            name: "java/util/Map".to_owned(),
+            type_arguments: vec![TypeArgument::Exact(TypeSignature::type_variable("K")), TypeArgument::Exact(TypeSignature::type_variable("V"))],
+            inner_classes: vec![("Entry".to_owned(), vec![TypeArgument::Extends(TypeSignature::class("java/lang/Number")), TypeArgument::Any])],
+        };
+        assert_eq!(t, TypeSignature::Class(expected));
+        assert_eq!(t.to_string(), signature);
+    }
+
+    #[test]
+    fn parses_class_signatures() {
+        // class Sorted<T extends Comparable<? super T>> extends AbstractList<T> implements RandomAccess
+        let signature = "<T::Ljava/lang/Comparable<-TT;>;>Ljava/util/AbstractList<TT;>;Ljava/util/RandomAccess;";
+        let class = ClassSignature::parse(signature).unwrap();
+        assert_eq!(class.type_parameters.len(), 1);
+        assert_eq!(class.type_parameters[0].class_bound, None);
+        assert_eq!(class.type_parameters[0].interface_bounds, vec![
+            TypeSignature::generic_class("java/lang/Comparable", vec![TypeArgument::Super(TypeSignature::type_variable("T"))]),
+        ]);
+        assert_eq!(class.superclass, ClassTypeSignature::new("java/util/AbstractList", vec![TypeArgument::Exact(TypeSignature::type_variable("T"))]));
+        assert_eq!(class.interfaces, vec![ClassTypeSignature::new("java/util/RandomAccess", vec![])]);
+        assert_eq!(class.to_string(), signature);
+    }
+
+    #[test]
+    fn parses_method_signatures() {
+        let signature = "<T:Ljava/lang/Object;E:Ljava/lang/Exception;>(TT;[I)V^TE;^Ljava/io/IOException;";
+        let method = MethodSignature::parse(signature).unwrap();
+        assert_eq!(method.type_parameters, vec![
+            TypeParameter::new("T", TypeSignature::class("java/lang/Object")),
+            TypeParameter::new("E", TypeSignature::class("java/lang/Exception")),
+        ]);
+        assert_eq!(method.args, vec![TypeSignature::type_variable("T"), TypeSignature::array(TypeSignature::Int)]);
+        assert_eq!(method.ret, TypeSignature::Void);
+        assert_eq!(method.throws, vec![TypeSignature::type_variable("E"), TypeSignature::class("java/io/IOException")]);
+        assert_eq!(method.to_string(), signature);
+    }
+
+    #[test]
+    fn rejects_malformed_signatures() {
+        let offset = |signature| TypeSignature::parse(signature).unwrap_err().offset;
+        assert_eq!(offset("V"), 0);
+        assert_eq!(offset("Ljava/util/List<>;"), 16);
+        assert_eq!(offset("Ljava/util/List<I>;"), 16);
+        assert_eq!(offset("TT"), 2);
+        assert_eq!(offset("Ljava/lang/String;I"), 18);
+        assert_eq!(ClassSignature::parse("<>Ljava/lang/Object;").unwrap_err().offset, 1);
+        assert_eq!(MethodSignature::parse("(V)V").unwrap_err().offset, 1);
+        assert_eq!(MethodSignature::parse("()V^I").unwrap_err().offset, 4);
+    }
+}
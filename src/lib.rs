@@ -1,29 +1,50 @@
+mod assembler;
 mod classfile;
+mod constant_pool;
+mod disassembler;
+mod errors;
 mod class_builder;
 mod java_type_signatures;
 mod pretty_printing;
 mod serialization;
+mod source_map;
+mod stack_map_frames;
 
 use std::fs::File;
 use std::io::Write;
 
+pub use assembler::*;
 pub use classfile::*;
+pub use constant_pool::*;
+pub use disassembler::*;
+pub use errors::*;
 pub use class_builder::*;
 pub use java_type_signatures::*;
+pub use source_map::*;
 
-pub fn write_classfile(classfile: Classfile, filename: &str) {
+pub fn write_classfile(classfile: Classfile, filename: &str) -> Result<(), ClassfileError> {
     let mut bytes = vec![];
-    classfile.serialize(&mut bytes);
+    classfile.serialize(&mut bytes)?;
 
-    let mut f = File::create(filename).unwrap();
-    f.write_all(&bytes).unwrap();
+    let mut f = File::create(filename)?;
+    f.write_all(&bytes)?;
+    Ok(())
 }
 
-pub fn read_classfile(filename: &str) -> Classfile {
-    let f = File::open(filename).unwrap();
+pub fn read_classfile(filename: &str) -> Result<Classfile, ClassfileError> {
+    let f = File::open(filename)?;
     Classfile::deserialize(Box::new(f))
 }
 
+pub fn read_classfile_with_decoders(filename: &str, decoders: &AttributeDecoders) -> Result<Classfile, ClassfileError> {
+    let f = File::open(filename)?;
+    Classfile::deserialize_with_decoders(Box::new(f), decoders)
+}
+
 pub fn define_class(access_flags: u16, this_class: &str, super_class: &str) -> ClassBuilder {
     ClassBuilder::new(access_flags, this_class, super_class)
 }
+
+pub fn define_interface(access_flags: u16, this_class: &str) -> ClassBuilder {
+    ClassBuilder::new(access_flags | ACC_INTERFACE | ACC_ABSTRACT, this_class, "java/lang/Object")
+}
@@ -1,6 +1,9 @@
 extern crate jvm_assembler;
 
 use std::env;
+use std::fs;
+use std::path::Path;
+use std::process;
 
 use jvm_assembler::*;
 
@@ -14,7 +17,58 @@ fn main() {
     let filename = &args[2];
 
     match command.as_ref() {
-        "read" => print!("{}", read_classfile(filename)),
+        "read" => match read_classfile(filename) {
+            Ok(classfile) => print!("{}", classfile),
+            Err(err) => {
+                eprintln!("Error reading {}: {}", filename, err);
+                process::exit(1);
+            }
+        },
+        "disassemble" => {
+            let classfile = match read_classfile(filename) {
+                Ok(classfile) => classfile,
+                Err(err) => {
+                    eprintln!("Error reading {}: {}", filename, err);
+                    process::exit(1);
+                }
+            };
+            match disassemble(&classfile) {
+                Ok(source) => print!("{}", source),
+                Err(err) => {
+                    eprintln!("Error disassembling {}: {}", filename, err);
+                    process::exit(1);
+                }
+            }
+        },
+        "assemble" => {
+            let source = match fs::read_to_string(filename) {
+                Ok(source) => source,
+                Err(err) => {
+                    eprintln!("Error reading {}: {}", filename, err);
+                    process::exit(1);
+                }
+            };
+            let classfile = match assemble(&source) {
+                Ok(classfile) => classfile,
+                Err(err) => {
+                    eprintln!("Error assembling {}: {}", filename, err);
+                    process::exit(1);
+                }
+            };
+
+            // like javac, a class in a package goes in the package's directory, e.g. com/example/Foo.class
+            let output = format!("{}.class", classfile.constant_pool.get_class_name(classfile.this_class).unwrap());
+            if let Some(directory) = Path::new(&output).parent() {
+                if let Err(err) = fs::create_dir_all(directory) {
+                    eprintln!("Error creating {}: {}", directory.display(), err);
+                    process::exit(1);
+                }
+            }
+            if let Err(err) = write_classfile(classfile, &output) {
+                eprintln!("Error writing {}: {}", output, err);
+                process::exit(1);
+            }
+        },
         _ => panic!("Unknown command: {}", command)
     }
 }
@@ -1,189 +1,972 @@
 use std::fmt;
 
 use classfile::*;
+use class_builder::*;
+use java_type_signatures::*;
 
+// A listing in the style of `javap -v -p`: the constant pool with resolved references, then each field and method
+// with decoded flags and descriptors, and the code of each method with its pcs, branch targets and stack map frames.
 impl fmt::Display for Classfile {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        try!(writeln!(f, "Magic: 0x{:X}", self.magic));
-        try!(writeln!(f, "Minor version: {}", self.minor_version));
-        try!(writeln!(f, "Major version: {}", self.major_version));
-        try!(writeln!(f, "Constant pool:"));
-        let constant_pool_with_indices: Vec<(u16, &Constant)> = self.constant_pool.iter().enumerate().map(|(i, v)| (i as u16 + 1, v)).collect();
-        try!(constant_pool_with_indices.pretty_println(f, 2));
-        try!(writeln!(f, "Access flags: 0x{:X}", self.access_flags));
-        try!(writeln!(f, "This class: {}", self.this_class));
-        try!(writeln!(f, "Super class: {}", self.super_class));
-        try!(writeln!(f, "Interfaces:"));
-        try!(self.interfaces.pretty_println(f, 2));
-        try!(writeln!(f, "Fields:"));
-        try!(self.fields.pretty_println(f, 2));
-        try!(writeln!(f, "Methods:"));
-        try!(self.methods.pretty_println(f, 2));
-        try!(writeln!(f, "Attributes:"));
+        write_class_header(f, self)?;
+        writeln!(f, "  minor version: {}", self.minor_version)?;
+        writeln!(f, "  major version: {}", self.major_version)?;
+        writeln!(f, "  flags: {}", flags(self.access_flags, CLASS_FLAGS))?;
+        writeln!(f, "{:<42}// {}", format!("  this_class: #{}", self.this_class), quoted_class_name(self, self.this_class))?;
+        writeln!(f, "{:<42}// {}", format!("  super_class: #{}", self.super_class), quoted_class_name(self, self.super_class))?;
+        writeln!(f, "  interfaces: {}, fields: {}, methods: {}, attributes: {}", self.interfaces.len(), self.fields.len(), self.methods.len(), self.attributes.len())?;
+
+        writeln!(f, "Constant pool:")?;
+        let width = format!("#{}", self.constant_pool.len()).len() + 2;
+        for (index, constant) in self.constant_pool.iter() {
+            let (listing, comment) = constant_listing(self, constant);
+            let line = format!("{:>1$} = {2}", format!("#{}", index), width, listing);
+            match comment {
+                // javap lines the comments up in one column
+                Some(comment) => writeln!(f, "{}", format!("{:<41} // {}", line, comment).trim_end())?,
+                None => writeln!(f, "{}", line.trim_end())?,
+            }
+        }
+
+        writeln!(f, "{{")?;
+        let mut first = true;
+        for field in &self.fields {
+            if !first {
+                writeln!(f)?;
+            }
+            first = false;
+            write_field(f, self, field)?;
+        }
+        for method in &self.methods {
+            if !first {
+                writeln!(f)?;
+            }
+            first = false;
+            write_method(f, self, method)?;
+        }
+        writeln!(f, "}}")?;
+        for attribute in &self.attributes {
+            write_attribute(f, self, attribute, 0)?;
+        }
         Ok(())
     }
 }
 
-trait PrettyPrint {
-    fn pretty_print(&self, f: &mut fmt::Formatter, indent: usize) -> fmt::Result;
+const CLASS_FLAGS: &[(u16, &str)] = &[
+    (ACC_PUBLIC, "ACC_PUBLIC"), (ACC_FINAL, "ACC_FINAL"), (ACC_SUPER, "ACC_SUPER"), (ACC_INTERFACE, "ACC_INTERFACE"),
+    (ACC_ABSTRACT, "ACC_ABSTRACT"), (ACC_SYNTHETIC, "ACC_SYNTHETIC"), (ACC_ANNOTATION, "ACC_ANNOTATION"), (ACC_ENUM, "ACC_ENUM"),
+    (ACC_MODULE, "ACC_MODULE"),
+];
+
+const FIELD_FLAGS: &[(u16, &str)] = &[
+    (ACC_PUBLIC, "ACC_PUBLIC"), (ACC_PRIVATE, "ACC_PRIVATE"), (ACC_PROTECTED, "ACC_PROTECTED"), (ACC_STATIC, "ACC_STATIC"),
+    (ACC_FINAL, "ACC_FINAL"), (ACC_VOLATILE, "ACC_VOLATILE"), (ACC_TRANSIENT, "ACC_TRANSIENT"),
+    (ACC_SYNTHETIC, "ACC_SYNTHETIC"), (ACC_ENUM, "ACC_ENUM"),
+];
 
-    fn pretty_println(&self, f: &mut fmt::Formatter, indent: usize) -> fmt::Result {
-        try!(self.pretty_print(f, indent));
-        write!(f, "\n")
+const METHOD_FLAGS: &[(u16, &str)] = &[
+    (ACC_PUBLIC, "ACC_PUBLIC"), (ACC_PRIVATE, "ACC_PRIVATE"), (ACC_PROTECTED, "ACC_PROTECTED"), (ACC_STATIC, "ACC_STATIC"),
+    (ACC_FINAL, "ACC_FINAL"), (ACC_SYNCHRONIZED, "ACC_SYNCHRONIZED"), (ACC_BRIDGE, "ACC_BRIDGE"), (ACC_VARARGS, "ACC_VARARGS"),
+    (ACC_NATIVE, "ACC_NATIVE"), (ACC_ABSTRACT, "ACC_ABSTRACT"), (ACC_STRICT, "ACC_STRICT"), (ACC_SYNTHETIC, "ACC_SYNTHETIC"),
+];
+
+// e.g. (0x0021) ACC_PUBLIC, ACC_SUPER
+fn flags(access_flags: u16, names: &[(u16, &str)]) -> String {
+    let names: Vec<&str> = names.iter().filter(|&&(flag, _)| access_flags & flag != 0).map(|&(_, name)| name).collect();
+    if names.is_empty() {
+        format!("(0x{:04x})", access_flags)
+    } else {
+        format!("(0x{:04x}) {}", access_flags, names.join(", "))
     }
+}
 
-    fn pretty_print_preln(&self, f: &mut fmt::Formatter, indent: usize) -> fmt::Result {
-        try!(write!(f, "\n"));
-        self.pretty_print(f, indent)
+// the Java modifiers for the given flags, in the order javap lists them, each followed by a space
+fn modifiers(access_flags: u16, names: &[(u16, &str)]) -> String {
+    names.iter().filter(|&&(flag, _)| access_flags & flag != 0).map(|&(_, name)| format!("{} ", name)).collect()
+}
+
+fn write_class_header(f: &mut fmt::Formatter, classfile: &Classfile) -> fmt::Result {
+    for attribute in &classfile.attributes {
+        if let Attribute::SourceFile(_, index) = *attribute {
+            writeln!(f, "  Compiled from \"{}\"", utf8(classfile, index))?;
+        }
     }
+
+    let is_interface = classfile.access_flags & ACC_INTERFACE != 0;
+    let mut header = if is_interface {
+        modifiers(classfile.access_flags, &[(ACC_PUBLIC, "public")]) + "interface "
+    } else {
+        modifiers(classfile.access_flags, &[(ACC_PUBLIC, "public"), (ACC_FINAL, "final"), (ACC_ABSTRACT, "abstract")]) + "class "
+    };
+    header.push_str(&binary_class_name(classfile, classfile.this_class));
+
+    let signature = signature(classfile, &classfile.attributes).and_then(|signature| ClassSignature::parse(signature).ok());
+    // javap only leaves out `extends java.lang.Object` when there's no generic signature
+    let (superclass, interfaces) = match signature {
+        Some(signature) => {
+            header.push_str(&source_type_parameters(&signature.type_parameters));
+            let interfaces = signature.interfaces.iter().map(source_class_type).collect();
+            (Some(source_class_type(&signature.superclass)), interfaces)
+        },
+        None => {
+            let interfaces = classfile.interfaces.iter().map(|&index| binary_class_name(classfile, index)).collect();
+            let superclass = binary_class_name(classfile, classfile.super_class);
+            (if superclass == "java.lang.Object" { None } else { Some(superclass) }, interfaces)
+        },
+    };
+    let interfaces: Vec<String> = interfaces;
+
+    if is_interface {
+        if !interfaces.is_empty() {
+            header.push_str(&format!(" extends {}", interfaces.join(", ")));
+        }
+    } else {
+        if let Some(superclass) = superclass {
+            header.push_str(&format!(" extends {}", superclass));
+        }
+        if !interfaces.is_empty() {
+            header.push_str(&format!(" implements {}", interfaces.join(", ")));
+        }
+    }
+    writeln!(f, "{}", header)
 }
 
-impl<T: PrettyPrint> PrettyPrint for Vec<T> {
-    fn pretty_print(&self, f: &mut fmt::Formatter, indent: usize) -> fmt::Result {
-        let mut count = 0;
-        for item in self {
-            try!(write!(f, "{0:1$}", "", indent));
-            try!(item.pretty_print(f, indent + 2));
-            count += 1;
-            if count < self.len() {
-                try!(write!(f, "\n"));
+fn write_field(f: &mut fmt::Formatter, classfile: &Classfile, field: &Field) -> fmt::Result {
+    let descriptor = utf8(classfile, field.descriptor_index);
+    let field_type = match signature(classfile, &field.attributes).and_then(|signature| TypeSignature::parse(signature).ok()) {
+        Some(signature) => source_type(&signature),
+        None => Java::parse(descriptor).map(|t| source_java_type(&t)).unwrap_or_else(|_| descriptor.to_owned()),
+    };
+    let modifiers = modifiers(field.access_flags, &[
+        (ACC_PUBLIC, "public"), (ACC_PRIVATE, "private"), (ACC_PROTECTED, "protected"), (ACC_STATIC, "static"),
+        (ACC_FINAL, "final"), (ACC_VOLATILE, "volatile"), (ACC_TRANSIENT, "transient"),
+    ]);
+    writeln!(f, "  {}{} {};", modifiers, field_type, utf8(classfile, field.name_index))?;
+    writeln!(f, "    descriptor: {}", descriptor)?;
+    writeln!(f, "    flags: {}", flags(field.access_flags, FIELD_FLAGS))?;
+    for attribute in &field.attributes {
+        write_attribute(f, classfile, attribute, 4)?;
+    }
+    Ok(())
+}
+
+fn write_method(f: &mut fmt::Formatter, classfile: &Classfile, method: &Method) -> fmt::Result {
+    let name = utf8(classfile, method.name_index);
+    let descriptor = utf8(classfile, method.descriptor_index);
+    let is_interface = classfile.access_flags & ACC_INTERFACE != 0;
+
+    let mut header = modifiers(method.access_flags, &[
+        (ACC_PUBLIC, "public"), (ACC_PRIVATE, "private"), (ACC_PROTECTED, "protected"), (ACC_STATIC, "static"),
+        (ACC_FINAL, "final"), (ACC_SYNCHRONIZED, "synchronized"), (ACC_NATIVE, "native"), (ACC_ABSTRACT, "abstract"), (ACC_STRICT, "strictfp"),
+    ]);
+    if is_interface && method.access_flags & (ACC_STATIC | ACC_ABSTRACT | ACC_PRIVATE) == 0 {
+        header.push_str("default ");
+    }
+
+    // the types come from the generic signature if there is one, and the descriptor otherwise
+    let signature = signature(classfile, &method.attributes).and_then(|signature| MethodSignature::parse(signature).ok());
+    let (type_parameters, mut args, ret, mut throws) = match signature {
+        Some(signature) => {
+            let args = signature.args.iter().map(source_type).collect();
+            let throws = signature.throws.iter().map(source_type).collect();
+            (source_type_parameters(&signature.type_parameters), args, source_type(&signature.ret), throws)
+        },
+        None => match MethodDescriptor::parse(descriptor) {
+            Ok(parsed) => (String::new(), parsed.args.iter().map(source_java_type).collect(), source_java_type(&parsed.ret), vec![]),
+            Err(_) => (String::new(), vec![descriptor.to_owned()], String::new(), vec![]),
+        },
+    };
+    // like javap, the exceptions come from the Exceptions attribute unless the signature lists them
+    if throws.is_empty() {
+        for attribute in &method.attributes {
+            if let Attribute::Exceptions(_, ref exception_index_table) = *attribute {
+                throws.extend(exception_index_table.iter().map(|&index| binary_class_name(classfile, index)));
+            }
+        }
+    }
+    let args: &mut Vec<String> = &mut args;
+    if method.access_flags & ACC_VARARGS != 0 {
+        if let Some(last) = args.last_mut() {
+            if last.ends_with("[]") {
+                let length = last.len() - 2;
+                last.truncate(length);
+                last.push_str("...");
             }
         }
-        Ok(())
     }
 
-    fn pretty_println(&self, f: &mut fmt::Formatter, indent: usize) -> fmt::Result {
-        if self.len() > 0 {
-            try!(self.pretty_print(f, indent));
-            write!(f, "\n")
+    if name == "<clinit>" {
+        header.push_str("{}");
+    } else {
+        if !type_parameters.is_empty() {
+            header.push_str(&type_parameters);
+            header.push(' ');
+        }
+        if name == "<init>" {
+            header.push_str(&binary_class_name(classfile, classfile.this_class));
         } else {
-            Ok(())
+            header.push_str(&format!("{} {}", ret, name));
+        }
+        header.push_str(&format!("({})", args.join(", ")));
+        if !throws.is_empty() {
+            header.push_str(&format!(" throws {}", throws.join(", ")));
+        }
+    }
+    writeln!(f, "  {};", header)?;
+    writeln!(f, "    descriptor: {}", descriptor)?;
+    writeln!(f, "    flags: {}", flags(method.access_flags, METHOD_FLAGS))?;
+
+    for attribute in &method.attributes {
+        match *attribute {
+            Attribute::Code(_, max_stack, max_locals, ref code, ref exception_table, ref attributes) => {
+                let this_arg = if method.access_flags & ACC_STATIC == 0 { 1 } else { 0 };
+                let args_size = MethodDescriptor::parse(descriptor).map(|parsed| parsed.args.len()).unwrap_or(0) + this_arg;
+                writeln!(f, "    Code:")?;
+                writeln!(f, "      stack={}, locals={}, args_size={}", max_stack, max_locals, args_size)?;
+                write_code(f, classfile, code)?;
+                if !exception_table.is_empty() {
+                    writeln!(f, "      Exception table:")?;
+                    writeln!(f, "         from    to  target type")?;
+                    for entry in exception_table {
+                        let catch_type = if entry.catch_type == 0 {
+                            "any".to_owned()
+                        } else {
+                            format!("Class {}", class_name(classfile, entry.catch_type))
+                        };
+                        writeln!(f, "{:>14}{:>6}{:>6}   {}", entry.start_pc, entry.end_pc, entry.handler_pc, catch_type)?;
+                    }
+                }
+                for attribute in attributes {
+                    write_attribute(f, classfile, attribute, 6)?;
+                }
+            },
+            ref attribute => write_attribute(f, classfile, attribute, 4)?,
         }
     }
+    Ok(())
+}
 
+fn write_code(f: &mut fmt::Formatter, classfile: &Classfile, code: &[Instruction]) -> fmt::Result {
+    let mut pc: u16 = 0;
+    for instruction in code {
+        let target = |offset: i32| (pc as i32 + offset).to_string();
+        let (operands, comment) = match *instruction {
+            Instruction::Bipush(value) => ((value as i8).to_string(), None),
+            Instruction::Sipush(value) => ((value as i16).to_string(), None),
+            Instruction::LoadConstant(index) => (format!("#{}", index), Some(loadable_constant(classfile, index as u16))),
+            Instruction::LoadConstantWide(index) | Instruction::LoadConstant2Wide(index) => {
+                (format!("#{}", index), Some(loadable_constant(classfile, index)))
+            },
+            Instruction::Iload(index) | Instruction::Lload(index) | Instruction::Fload(index) |
+            Instruction::Dload(index) | Instruction::Aload(index) | Instruction::Istore(index) |
+            Instruction::Lstore(index) | Instruction::Fstore(index) | Instruction::Dstore(index) |
+            Instruction::Astore(index) | Instruction::Ret(index) => (index.to_string(), None),
+            Instruction::Iinc(index, value) => (format!("{}, {}", index, value as i8), None),
+            Instruction::IfEq(offset) | Instruction::IfNe(offset) | Instruction::IfLt(offset) |
+            Instruction::IfGe(offset) | Instruction::IfGt(offset) | Instruction::IfLe(offset) |
+            Instruction::IfIcmpEq(offset) | Instruction::IfIcmpNe(offset) | Instruction::IfIcmpLt(offset) |
+            Instruction::IfIcmpGe(offset) | Instruction::IfIcmpGt(offset) | Instruction::IfIcmpLe(offset) |
+            Instruction::IfAcmpEq(offset) | Instruction::IfAcmpNe(offset) | Instruction::Goto(offset) |
+            Instruction::Jsr(offset) | Instruction::IfNull(offset) | Instruction::IfNonNull(offset) => (target(offset as i32), None),
+            Instruction::GotoW(offset) | Instruction::JsrW(offset) => (target(offset), None),
+            Instruction::TableSwitch(default, low, ref offsets) => {
+                writeln!(f, "{:>10}: {:<13} {{ // {} to {}", pc, instruction.mnemonic(), low, low as i64 + offsets.len() as i64 - 1)?;
+                for (i, &offset) in offsets.iter().enumerate() {
+                    writeln!(f, "{:>24}: {}", low as i64 + i as i64, target(offset))?;
+                }
+                writeln!(f, "{:>24}: {}", "default", target(default))?;
+                writeln!(f, "{:>13}", "}")?;
+                pc += instruction.size(pc);
+                continue;
+            },
+            Instruction::LookupSwitch(default, ref pairs) => {
+                writeln!(f, "{:>10}: {:<13} {{ // {}", pc, instruction.mnemonic(), pairs.len())?;
+                for &(key, offset) in pairs {
+                    writeln!(f, "{:>24}: {}", key, target(offset))?;
+                }
+                writeln!(f, "{:>24}: {}", "default", target(default))?;
+                writeln!(f, "{:>13}", "}")?;
+                pc += instruction.size(pc);
+                continue;
+            },
+            Instruction::GetStatic(index) | Instruction::PutStatic(index) | Instruction::GetField(index) |
+            Instruction::PutField(index) | Instruction::InvokeVirtual(index) | Instruction::InvokeSpecial(index) |
+            Instruction::InvokeStatic(index) => (format!("#{}", index), Some(member_reference(classfile, index, true))),
+            Instruction::InvokeInterface(index, count) => (format!("#{},  {}", index, count), Some(member_reference(classfile, index, true))),
+            Instruction::InvokeDynamic(index) => (format!("#{},  0", index), Some(member_reference(classfile, index, true))),
+            Instruction::New(index) | Instruction::ANewArray(index) | Instruction::CheckCast(index) |
+            Instruction::InstanceOf(index) => (format!("#{}", index), Some(format!("class {}", quoted_class_name(classfile, index)))),
+            Instruction::MultiANewArray(index, dimensions) => {
+                (format!("#{},  {}", index, dimensions), Some(format!("class {}", quoted_class_name(classfile, index))))
+            },
+            // javap lines the element type up one column further along
+            Instruction::NewArray(array_type) => (format!(" {}", array_type_name(array_type)), None),
+            Instruction::Wide(ref wide) => {
+                let operands = match *wide {
+                    WideInstruction::Iinc(index, value) => format!("{}, {}", index, value as i16),
+                    WideInstruction::Iload(index) | WideInstruction::Lload(index) | WideInstruction::Fload(index) |
+                    WideInstruction::Dload(index) | WideInstruction::Aload(index) | WideInstruction::Istore(index) |
+                    WideInstruction::Lstore(index) | WideInstruction::Fstore(index) | WideInstruction::Dstore(index) |
+                    WideInstruction::Astore(index) | WideInstruction::Ret(index) => index.to_string(),
+                };
+                writeln!(f, "{:>10}: {:<13} {}", pc, format!("{}_w", wide.mnemonic()), operands)?;
+                pc += instruction.size(pc);
+                continue;
+            },
+            _ => (String::new(), None),
+        };
 
-    fn pretty_print_preln(&self, f: &mut fmt::Formatter, indent: usize) -> fmt::Result {
-        if self.len() > 0 {
-            try!(write!(f, "\n"));
-            self.pretty_print(f, indent)
+        let text = if operands.is_empty() {
+            instruction.mnemonic().to_owned()
         } else {
-            Ok(())
+            format!("{:<13} {}", instruction.mnemonic(), operands)
+        };
+        match comment {
+            Some(comment) => writeln!(f, "{}", format!("{:>10}: {:<34}// {}", pc, text, comment).trim_end())?,
+            None => writeln!(f, "{:>10}: {}", pc, text)?,
         }
+        pc += instruction.size(pc);
     }
+    Ok(())
 }
 
-impl<T: PrettyPrint, U: PrettyPrint> PrettyPrint for (T, U) {
-    fn pretty_print(&self, f: &mut fmt::Formatter, indent: usize) -> fmt::Result {
-        let (ref t, ref u) = *self;
-        try!(t.pretty_print(f, indent));
-        try!(write!(f, ": "));
-        try!(u.pretty_print(f, indent));
-        Ok(())
+fn write_attribute(f: &mut fmt::Formatter, classfile: &Classfile, attribute: &Attribute, indent: usize) -> fmt::Result {
+    let pad = " ".repeat(indent);
+    match *attribute {
+        Attribute::Code(..) => {
+            writeln!(f, "{}Code: (not in a method)", pad)?;
+        },
+        Attribute::AnnotationDefault(_, ref default_value) => {
+            writeln!(f, "{}AnnotationDefault:", pad)?;
+            writeln!(f, "{}  default_value: {}", pad, element_value(default_value))?;
+            let value_pad = format!("{}    ", pad);
+            writeln!(f, "{}{}", value_pad, resolved_element_value(classfile, default_value, &value_pad))?;
+        },
+        Attribute::BootstrapMethods(_, ref bootstrap_methods) => {
+            writeln!(f, "{}BootstrapMethods:", pad)?;
+            for (i, bootstrap_method) in bootstrap_methods.iter().enumerate() {
+                let handle = match classfile.constant_pool.get(bootstrap_method.bootstrap_method_ref) {
+                    Some(&Constant::MethodHandle(kind, index)) => format!("{} {}", reference_kind_name(kind), member_reference(classfile, index, false)),
+                    _ => "<invalid method handle>".to_owned(),
+                };
+                writeln!(f, "{}  {}: #{} {}", pad, i, bootstrap_method.bootstrap_method_ref, handle)?;
+                writeln!(f, "{}    Method arguments:", pad)?;
+                for &argument in &bootstrap_method.bootstrap_arguments {
+                    writeln!(f, "{}      #{} {}", pad, argument, constant_value(classfile, argument))?;
+                }
+            }
+        },
+        Attribute::ConstantValue(_, index) => {
+            writeln!(f, "{}ConstantValue: {}", pad, loadable_constant(classfile, index))?;
+        },
+        Attribute::Deprecated(_) => {
+            writeln!(f, "{}Deprecated: true", pad)?;
+        },
+        Attribute::EnclosingMethod(_, class_index, method_index) => {
+            let mut enclosing = binary_class_name(classfile, class_index);
+            if let Some(&Constant::NameAndType(name_index, _)) = classfile.constant_pool.get(method_index) {
+                enclosing.push('.');
+                enclosing.push_str(utf8(classfile, name_index));
+            }
+            writeln!(f, "{}{:<40}// {}", pad, format!("EnclosingMethod: #{}.#{}", class_index, method_index), enclosing)?;
+        },
+        Attribute::Exceptions(_, ref exception_index_table) => {
+            let exceptions: Vec<String> = exception_index_table.iter().map(|&index| binary_class_name(classfile, index)).collect();
+            writeln!(f, "{}Exceptions:", pad)?;
+            writeln!(f, "{}  throws {}", pad, exceptions.join(", "))?;
+        },
+        Attribute::InnerClasses(_, ref classes) => {
+            writeln!(f, "{}InnerClasses:", pad)?;
+            for class in classes {
+                write_inner_class(f, classfile, class, &pad)?;
+            }
+        },
+        Attribute::LineNumberTable(_, ref entries) => {
+            writeln!(f, "{}LineNumberTable:", pad)?;
+            for entry in entries {
+                writeln!(f, "{}  line {}: {}", pad, entry.line_number, entry.start_pc)?;
+            }
+        },
+        Attribute::LocalVariableTable(_, ref entries) => {
+            writeln!(f, "{}LocalVariableTable:", pad)?;
+            writeln!(f, "{}  Start  Length  Slot  Name   Signature", pad)?;
+            for entry in entries {
+                writeln!(f, "{}  {:>5} {:>7} {:>5} {:>5}   {}", pad, entry.start_pc, entry.length, entry.index, utf8(classfile, entry.name_index), utf8(classfile, entry.descriptor_index))?;
+            }
+        },
+        Attribute::LocalVariableTypeTable(_, ref entries) => {
+            writeln!(f, "{}LocalVariableTypeTable:", pad)?;
+            writeln!(f, "{}  Start  Length  Slot  Name   Signature", pad)?;
+            for entry in entries {
+                writeln!(f, "{}  {:>5} {:>7} {:>5} {:>5}   {}", pad, entry.start_pc, entry.length, entry.index, utf8(classfile, entry.name_index), utf8(classfile, entry.signature_index))?;
+            }
+        },
+        Attribute::NestHost(_, index) => {
+            writeln!(f, "{}NestHost: class {}", pad, class_name(classfile, index))?;
+        },
+        Attribute::NestMembers(_, ref classes) => {
+            writeln!(f, "{}NestMembers:", pad)?;
+            for &index in classes {
+                writeln!(f, "{}  {}", pad, class_name(classfile, index))?;
+            }
+        },
+        Attribute::RuntimeVisibleAnnotations(_, ref annotations) => {
+            writeln!(f, "{}RuntimeVisibleAnnotations:", pad)?;
+            write_annotations(f, classfile, annotations, &format!("{}  ", pad))?;
+        },
+        Attribute::RuntimeInvisibleAnnotations(_, ref annotations) => {
+            writeln!(f, "{}RuntimeInvisibleAnnotations:", pad)?;
+            write_annotations(f, classfile, annotations, &format!("{}  ", pad))?;
+        },
+        Attribute::RuntimeVisibleParameterAnnotations(_, ref parameter_annotations) => {
+            writeln!(f, "{}RuntimeVisibleParameterAnnotations:", pad)?;
+            write_parameter_annotations(f, classfile, parameter_annotations, &pad)?;
+        },
+        Attribute::RuntimeInvisibleParameterAnnotations(_, ref parameter_annotations) => {
+            writeln!(f, "{}RuntimeInvisibleParameterAnnotations:", pad)?;
+            write_parameter_annotations(f, classfile, parameter_annotations, &pad)?;
+        },
+        Attribute::RuntimeVisibleTypeAnnotations(_, ref annotations) => {
+            writeln!(f, "{}RuntimeVisibleTypeAnnotations:", pad)?;
+            write_type_annotations(f, classfile, annotations, &format!("{}  ", pad))?;
+        },
+        Attribute::RuntimeInvisibleTypeAnnotations(_, ref annotations) => {
+            writeln!(f, "{}RuntimeInvisibleTypeAnnotations:", pad)?;
+            write_type_annotations(f, classfile, annotations, &format!("{}  ", pad))?;
+        },
+        Attribute::Signature(_, index) => {
+            writeln!(f, "{}{:<40}// {}", pad, format!("Signature: #{}", index), utf8(classfile, index))?;
+        },
+        Attribute::SourceDebugExtension(_, ref debug_extension) => {
+            writeln!(f, "{}SourceDebugExtension:", pad)?;
+            for line in debug_extension.split(['\r', '\n']).filter(|line| !line.is_empty()) {
+                writeln!(f, "{}  {}", pad, line)?;
+            }
+        },
+        Attribute::SourceFile(_, index) => {
+            writeln!(f, "{}SourceFile: \"{}\"", pad, utf8(classfile, index))?;
+        },
+        Attribute::StackMapTable(_, ref frames) => {
+            writeln!(f, "{}StackMapTable: number_of_entries = {}", pad, frames.len())?;
+            for frame in frames {
+                write_stack_map_frame(f, classfile, frame, &pad)?;
+            }
+        },
+        Attribute::Synthetic(_) => {
+            writeln!(f, "{}Synthetic: true", pad)?;
+        },
+        Attribute::Custom { name_index, ref value } => {
+            writeln!(f, "{}{}: {:?}", pad, utf8(classfile, name_index), value)?;
+        },
+        Attribute::Unknown { name_index, ref bytes } => {
+            writeln!(f, "{}{}: length = 0x{:x} (unknown attribute)", pad, utf8(classfile, name_index), bytes.len())?;
+            for chunk in bytes.chunks(16) {
+                let hex: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+                writeln!(f, "{}   {}", pad, hex.join(" "))?;
+            }
+        },
     }
+    Ok(())
 }
 
-impl PrettyPrint for u8 {
-    fn pretty_print(&self, f: &mut fmt::Formatter, _indent: usize) -> fmt::Result {
-        write!(f, "{:2}", self)
+// e.g. `static final #12= #7 of #2;` with the names resolved in a comment, leaving out whatever is 0
+fn write_inner_class(f: &mut fmt::Formatter, classfile: &Classfile, class: &InnerClass, pad: &str) -> fmt::Result {
+    let mut names = vec![(ACC_PUBLIC, "public"), (ACC_PRIVATE, "private"), (ACC_PROTECTED, "protected"), (ACC_STATIC, "static"), (ACC_FINAL, "final")];
+    if class.inner_class_access_flags & ACC_INTERFACE == 0 {
+        names.push((ACC_ABSTRACT, "abstract"));
+    }
+    let mut entry = modifiers(class.inner_class_access_flags, &names);
+    let mut comment = String::new();
+    if class.inner_name_index != 0 {
+        entry.push_str(&format!("#{}= ", class.inner_name_index));
+        comment.push_str(&format!("{}=", utf8(classfile, class.inner_name_index)));
     }
+    entry.push_str(&format!("#{}", class.inner_class_info_index));
+    comment.push_str(&format!("class {}", class_name(classfile, class.inner_class_info_index)));
+    if class.outer_class_info_index != 0 {
+        entry.push_str(&format!(" of #{}", class.outer_class_info_index));
+        comment.push_str(&format!(" of class {}", class_name(classfile, class.outer_class_info_index)));
+    }
+    writeln!(f, "{}  {:<39} // {}", pad, format!("{};", entry), comment)
 }
 
-impl PrettyPrint for u16 {
-    fn pretty_print(&self, f: &mut fmt::Formatter, _indent: usize) -> fmt::Result {
-        write!(f, "{:2}", self)
+// each annotation as javap shows it: with constant pool indices, then resolved on the following lines
+fn write_annotations(f: &mut fmt::Formatter, classfile: &Classfile, annotations: &[Annotation], pad: &str) -> fmt::Result {
+    let resolved_pad = format!("{}  ", pad);
+    for (i, annotation) in annotations.iter().enumerate() {
+        writeln!(f, "{}{}: {}", pad, i, raw_annotation(annotation))?;
+        writeln!(f, "{}{}", resolved_pad, resolved_annotation(classfile, annotation, &resolved_pad))?;
     }
+    Ok(())
 }
 
-impl PrettyPrint for u32 {
-    fn pretty_print(&self, f: &mut fmt::Formatter, _indent: usize) -> fmt::Result {
-        write!(f, "{:2}", self)
+fn write_parameter_annotations(f: &mut fmt::Formatter, classfile: &Classfile, parameter_annotations: &[Vec<Annotation>], pad: &str) -> fmt::Result {
+    for (i, annotations) in parameter_annotations.iter().enumerate() {
+        writeln!(f, "{}  parameter {}:", pad, i)?;
+        write_annotations(f, classfile, annotations, &format!("{}    ", pad))?;
     }
+    Ok(())
 }
 
-impl<'a> PrettyPrint for &'a Constant {
-    fn pretty_print(&self, f: &mut fmt::Formatter, _indent: usize) -> fmt::Result {
-        write!(f, "{:?}", self)
+fn write_type_annotations(f: &mut fmt::Formatter, classfile: &Classfile, annotations: &[TypeAnnotation], pad: &str) -> fmt::Result {
+    let resolved_pad = format!("{}  ", pad);
+    for (i, annotation) in annotations.iter().enumerate() {
+        writeln!(f, "{}{}: {}: {}", pad, i, raw_annotation(&annotation.annotation), type_annotation_position(annotation))?;
+        writeln!(f, "{}{}", resolved_pad, resolved_annotation(classfile, &annotation.annotation, &resolved_pad))?;
     }
+    Ok(())
 }
 
-impl PrettyPrint for Interface {
-    fn pretty_print(&self, f: &mut fmt::Formatter, _indent: usize) -> fmt::Result {
-        write!(f, "{:?}", self)
+// e.g. #27(#28=I#29,#30=[I#31])
+fn raw_annotation(annotation: &Annotation) -> String {
+    let pairs: Vec<String> = annotation.element_value_pairs.iter().map(|pair| format!("#{}={}", pair.element_name_index, element_value(&pair.value))).collect();
+    format!("#{}({})", annotation.type_index, pairs.join(","))
+}
+
+fn element_value(value: &ElementValue) -> String {
+    match *value {
+        ElementValue::Byte(index) => format!("B#{}", index),
+        ElementValue::Char(index) => format!("C#{}", index),
+        ElementValue::Double(index) => format!("D#{}", index),
+        ElementValue::Float(index) => format!("F#{}", index),
+        ElementValue::Int(index) => format!("I#{}", index),
+        ElementValue::Long(index) => format!("J#{}", index),
+        ElementValue::Short(index) => format!("S#{}", index),
+        ElementValue::Boolean(index) => format!("Z#{}", index),
+        ElementValue::String(index) => format!("s#{}", index),
+        ElementValue::Enum(type_name_index, const_name_index) => format!("e#{}.#{}", type_name_index, const_name_index),
+        ElementValue::Class(index) => format!("c#{}", index),
+        ElementValue::Annotation(ref annotation) => format!("@{}", raw_annotation(annotation)),
+        ElementValue::Array(ref values) => format!("[{}]", values.iter().map(element_value).collect::<Vec<_>>().join(",")),
     }
 }
 
-impl PrettyPrint for Field {
-    fn pretty_print(&self, f: &mut fmt::Formatter, _indent: usize) -> fmt::Result {
-        write!(f, "{:?}", self)
+// e.g. Info(, then each element on a line of its own, then ) on a line at the given indentation
+fn resolved_annotation(classfile: &Classfile, annotation: &Annotation, pad: &str) -> String {
+    let descriptor = utf8(classfile, annotation.type_index);
+    let mut resolved = Java::parse(descriptor).map_or_else(|_| descriptor.to_owned(), |t| source_java_type(&t));
+    if !annotation.element_value_pairs.is_empty() {
+        let element_pad = format!("{}  ", pad);
+        resolved.push_str("(\n");
+        for pair in &annotation.element_value_pairs {
+            let value = resolved_element_value(classfile, &pair.value, &element_pad);
+            resolved.push_str(&format!("{}{}={}\n", element_pad, utf8(classfile, pair.element_name_index), value));
+        }
+        resolved.push_str(&format!("{})", pad));
     }
+    resolved
 }
 
-impl PrettyPrint for Method {
-    fn pretty_print(&self, f: &mut fmt::Formatter, indent: usize) -> fmt::Result {
-        try!(write!(f, "Method(access_flags: 0x{:X}, name_index: {}, descriptor_index: {})\n", self.access_flags, self.name_index, self.descriptor_index));
-        try!(write!(f, "{0:1$}Attributes:", "", indent));
-        try!(self.attributes.pretty_print_preln(f, indent + 2));
-        Ok(())
+fn resolved_element_value(classfile: &Classfile, value: &ElementValue, pad: &str) -> String {
+    let integer = |index: u16| match classfile.constant_pool.get(index) {
+        Some(&Constant::Integer(value)) => value,
+        _ => 0,
+    };
+    match *value {
+        ElementValue::Byte(index) => format!("(byte) {}", integer(index)),
+        // javap doesn't escape chars, even newlines, which then start an indented line like any other
+        ElementValue::Char(index) => match std::char::from_u32(integer(index) as u32).unwrap_or(std::char::REPLACEMENT_CHARACTER) {
+            '\n' => format!("'\n{}'", pad),
+            c => format!("'{}'", c),
+        },
+        ElementValue::Short(index) => format!("(short) {}", integer(index)),
+        ElementValue::Boolean(index) => (integer(index) != 0).to_string(),
+        ElementValue::Double(index) | ElementValue::Float(index) | ElementValue::Int(index) |
+        ElementValue::Long(index) => constant_value(classfile, index),
+        ElementValue::String(index) => format!("\"{}\"", escape(utf8(classfile, index))),
+        ElementValue::Enum(type_name_index, const_name_index) => format!("{}.{}", utf8(classfile, type_name_index), utf8(classfile, const_name_index)),
+        ElementValue::Class(index) => format!("class {}", utf8(classfile, index)),
+        ElementValue::Annotation(ref annotation) => format!("@{}", resolved_annotation(classfile, annotation, pad)),
+        ElementValue::Array(ref values) => {
+            let values: Vec<String> = values.iter().map(|value| resolved_element_value(classfile, value, pad)).collect();
+            format!("[{}]", values.join(","))
+        },
     }
 }
 
-impl PrettyPrint for Attribute {
-    fn pretty_print(&self, f: &mut fmt::Formatter, indent: usize) -> fmt::Result {
-        match *self {
-            Attribute::Code(_, max_stack, max_locals, ref code, ref exception_table, ref attributes) => {
-                try!(write!(f, "Code(max_stack: {}, max_locals: {})\n", max_stack, max_locals));
-                try!(write!(f, "{0:1$}Instructions:\n", "", indent));
-                try!(code.pretty_println(f, indent + 2));
-                try!(write!(f, "{0:1$}Exception table:\n", "", indent));
-                try!(exception_table.pretty_println(f, indent + 2));
-                try!(write!(f, "{0:1$}Attributes:", "", indent));
-                try!(attributes.pretty_print_preln(f, indent + 2));
-                Ok(())
-            },
-            Attribute::LineNumberTable(_, ref entries) => {
-                try!(write!(f, "LineNumberTable:"));
-                try!(entries.pretty_print_preln(f, indent));
-                Ok(())
-            },
-            Attribute::SourceFile(_, index) => {
-                try!(write!(f, "SourceFile(index: {}):", index));
-                Ok(())
-            }
-            Attribute::StackMapTable(_, ref entries) => {
-                try!(write!(f, "StackMapTable:"));
-                try!(entries.pretty_print_preln(f, indent));
-                Ok(())
-            },
+// e.g. LOCAL_VARIABLE, {start_pc=6, length=15, index=5}, location=[TYPE_ARGUMENT(0)]
+fn type_annotation_position(annotation: &TypeAnnotation) -> String {
+    let mut position = target_type_name(annotation.target_type).to_owned();
+    match annotation.target_info {
+        TargetInfo::TypeParameter(index) | TargetInfo::FormalParameter(index) => position.push_str(&format!(", param_index={}", index)),
+        TargetInfo::Supertype(index) | TargetInfo::Throws(index) => position.push_str(&format!(", type_index={}", index)),
+        TargetInfo::TypeParameterBound(index, bound_index) => position.push_str(&format!(", param_index={}, bound_index={}", index, bound_index)),
+        TargetInfo::Empty => {},
+        TargetInfo::LocalVariable(ref table) => {
+            let entries: Vec<String> = table.iter().map(|entry| format!("start_pc={}, length={}, index={}", entry.start_pc, entry.length, entry.index)).collect();
+            position.push_str(&format!(", {{{}}}", entries.join("; ")));
+        },
+        TargetInfo::Catch(index) => position.push_str(&format!(", exception_index={}", index)),
+        TargetInfo::Offset(offset) => position.push_str(&format!(", offset={}", offset)),
+        TargetInfo::TypeArgument(offset, index) => position.push_str(&format!(", offset={}, type_index={}", offset, index)),
+    }
+    if !annotation.type_path.is_empty() {
+        let entries: Vec<String> = annotation.type_path.iter().map(|entry| match entry.type_path_kind {
+            0 => "ARRAY".to_owned(),
+            1 => "INNER_TYPE".to_owned(),
+            2 => "WILDCARD".to_owned(),
+            _ => format!("TYPE_ARGUMENT({})", entry.type_argument_index),
+        }).collect();
+        position.push_str(&format!(", location=[{}]", entries.join(", ")));
+    }
+    position
+}
+
+fn target_type_name(target_type: u8) -> &'static str {
+    match target_type {
+        0x00 => "CLASS_TYPE_PARAMETER",
+        0x01 => "METHOD_TYPE_PARAMETER",
+        0x10 => "CLASS_EXTENDS",
+        0x11 => "CLASS_TYPE_PARAMETER_BOUND",
+        0x12 => "METHOD_TYPE_PARAMETER_BOUND",
+        0x13 => "FIELD",
+        0x14 => "METHOD_RETURN",
+        0x15 => "METHOD_RECEIVER",
+        0x16 => "METHOD_FORMAL_PARAMETER",
+        0x17 => "THROWS",
+        0x40 => "LOCAL_VARIABLE",
+        0x41 => "RESOURCE_VARIABLE",
+        0x42 => "EXCEPTION_PARAMETER",
+        0x43 => "INSTANCEOF",
+        0x44 => "NEW",
+        0x45 => "CONSTRUCTOR_REFERENCE",
+        0x46 => "METHOD_REFERENCE",
+        0x47 => "CAST",
+        0x48 => "CONSTRUCTOR_INVOCATION_TYPE_ARGUMENT",
+        0x49 => "METHOD_INVOCATION_TYPE_ARGUMENT",
+        0x4A => "CONSTRUCTOR_REFERENCE_TYPE_ARGUMENT",
+        0x4B => "METHOD_REFERENCE_TYPE_ARGUMENT",
+        _ => "UNKNOWN",
+    }
+}
+
+fn write_stack_map_frame(f: &mut fmt::Formatter, classfile: &Classfile, frame: &StackMapFrame, pad: &str) -> fmt::Result {
+    let types = |types: &[VerificationType]| -> String {
+        let names: Vec<String> = types.iter().map(|t| verification_type(classfile, t)).collect();
+        if names.is_empty() { "[]".to_owned() } else { format!("[ {} ]", names.join(", ")) }
+    };
+    match *frame {
+        StackMapFrame::SameFrame(offset_delta) => {
+            writeln!(f, "{}  frame_type = {} /* same */", pad, offset_delta)?;
+        },
+        StackMapFrame::SameLocals1StackItemFrame(offset_delta, ref stack_item) => {
+            writeln!(f, "{}  frame_type = {} /* same_locals_1_stack_item */", pad, 64 + offset_delta as u16)?;
+            writeln!(f, "{}    stack = {}", pad, types(std::slice::from_ref(stack_item)))?;
+        },
+        StackMapFrame::SameLocals1StackItemFrameExtended(offset_delta, ref stack_item) => {
+            writeln!(f, "{}  frame_type = 247 /* same_locals_1_stack_item_frame_extended */", pad)?;
+            writeln!(f, "{}    offset_delta = {}", pad, offset_delta)?;
+            writeln!(f, "{}    stack = {}", pad, types(std::slice::from_ref(stack_item)))?;
+        },
+        StackMapFrame::ChopFrame(chopped, offset_delta) => {
+            writeln!(f, "{}  frame_type = {} /* chop */", pad, 251 - chopped as u16)?;
+            writeln!(f, "{}    offset_delta = {}", pad, offset_delta)?;
+        },
+        StackMapFrame::SameFrameExtended(offset_delta) => {
+            writeln!(f, "{}  frame_type = 251 /* same_frame_extended */", pad)?;
+            writeln!(f, "{}    offset_delta = {}", pad, offset_delta)?;
+        },
+        StackMapFrame::AppendFrame(appended, offset_delta, ref locals) => {
+            writeln!(f, "{}  frame_type = {} /* append */", pad, 251 + appended as u16)?;
+            writeln!(f, "{}    offset_delta = {}", pad, offset_delta)?;
+            writeln!(f, "{}    locals = {}", pad, types(locals))?;
+        },
+        StackMapFrame::FullFrame(offset_delta, ref locals, ref stack) => {
+            writeln!(f, "{}  frame_type = 255 /* full_frame */", pad)?;
+            writeln!(f, "{}    offset_delta = {}", pad, offset_delta)?;
+            writeln!(f, "{}    locals = {}", pad, types(locals))?;
+            writeln!(f, "{}    stack = {}", pad, types(stack))?;
+        },
+    }
+    Ok(())
+}
+
+fn verification_type(classfile: &Classfile, t: &VerificationType) -> String {
+    match *t {
+        VerificationType::Top => "top".to_owned(),
+        VerificationType::Integer => "int".to_owned(),
+        VerificationType::Float => "float".to_owned(),
+        VerificationType::Double => "double".to_owned(),
+        VerificationType::Long => "long".to_owned(),
+        VerificationType::Null => "null".to_owned(),
+        VerificationType::UninitializedThis => "this".to_owned(),
+        VerificationType::Object(index) => format!("class {}", quoted_class_name(classfile, index)),
+        VerificationType::Uninitialized(pc) => format!("uninitialized {}", pc),
+    }
+}
+
+// the tag and value of a constant pool entry, and the resolved reference if it refers to other constants
+fn constant_listing(classfile: &Classfile, constant: &Constant) -> (String, Option<String>) {
+    let with_comment = |tag: &str, operands: String, comment: String| (format!("{:<18} {}", tag, operands), Some(comment));
+    match *constant {
+        Constant::Utf8(ref string) => (format!("{:<18} {}", "Utf8", escape(string.as_str())), None),
+        Constant::Integer(value) => (format!("{:<18} {}", "Integer", value), None),
+        Constant::Float(value) => (format!("{:<18} {}f", "Float", java_float(value as f64, true)), None),
+        Constant::Long(value) => (format!("{:<18} {}l", "Long", value), None),
+        Constant::Double(value) => (format!("{:<18} {}d", "Double", java_float(value, false)), None),
+        Constant::Class(name_index) => with_comment("Class", format!("#{}", name_index), quote_if_needed(utf8(classfile, name_index))),
+        Constant::String(string_index) => with_comment("String", format!("#{}", string_index), escape(utf8(classfile, string_index))),
+        Constant::Fieldref(class_index, name_and_type_index) => {
+            with_comment("Fieldref", format!("#{}.#{}", class_index, name_and_type_index), member(classfile, class_index, name_and_type_index, false))
+        },
+        Constant::Methodref(class_index, name_and_type_index) => {
+            with_comment("Methodref", format!("#{}.#{}", class_index, name_and_type_index), member(classfile, class_index, name_and_type_index, false))
+        },
+        Constant::InterfaceMethodref(class_index, name_and_type_index) => {
+            with_comment("InterfaceMethodref", format!("#{}.#{}", class_index, name_and_type_index), member(classfile, class_index, name_and_type_index, false))
+        },
+        Constant::NameAndType(name_index, descriptor_index) => {
+            with_comment("NameAndType", format!("#{}:#{}", name_index, descriptor_index), name_and_type(classfile, (name_index, descriptor_index)))
+        },
+        Constant::MethodHandle(kind, index) => {
+            with_comment("MethodHandle", format!("{}:#{}", kind, index), format!("{} {}", reference_kind_name(kind), member_reference(classfile, index, false)))
+        },
+        Constant::MethodType(descriptor_index) => with_comment("MethodType", format!("#{}", descriptor_index), utf8(classfile, descriptor_index).to_owned()),
+        Constant::Dynamic(bootstrap_index, name_and_type_index) => {
+            with_comment("Dynamic", format!("#{}:#{}", bootstrap_index, name_and_type_index), format!("#{}:{}", bootstrap_index, name_and_type_at(classfile, name_and_type_index)))
+        },
+        Constant::InvokeDynamic(bootstrap_index, name_and_type_index) => {
+            with_comment("InvokeDynamic", format!("#{}:#{}", bootstrap_index, name_and_type_index), format!("#{}:{}", bootstrap_index, name_and_type_at(classfile, name_and_type_index)))
+        },
+        Constant::Module(name_index) => with_comment("Module", format!("#{}", name_index), utf8(classfile, name_index).to_owned()),
+        Constant::Package(name_index) => with_comment("Package", format!("#{}", name_index), utf8(classfile, name_index).to_owned()),
+        Constant::Unusable => (String::new(), None),
+    }
+}
+
+fn name_and_type(classfile: &Classfile, (name_index, descriptor_index): (u16, u16)) -> String {
+    format!("{}:{}", quote_if_needed(utf8(classfile, name_index)), utf8(classfile, descriptor_index))
+}
+
+fn name_and_type_at(classfile: &Classfile, index: u16) -> String {
+    match classfile.constant_pool.get(index) {
+        Some(&Constant::NameAndType(name_index, descriptor_index)) => name_and_type(classfile, (name_index, descriptor_index)),
+        _ => format!("<invalid NameAndType #{}>", index),
+    }
+}
+
+// e.g. java/io/PrintStream.println:(Ljava/lang/String;)V, leaving out the class if `in_code` and it's this class
+fn member(classfile: &Classfile, class_index: u16, name_and_type_index: u16, in_code: bool) -> String {
+    let name_and_type = name_and_type_at(classfile, name_and_type_index);
+    if in_code && class_index == classfile.this_class {
+        name_and_type
+    } else {
+        format!("{}.{}", quoted_class_name(classfile, class_index), name_and_type)
+    }
+}
+
+// a Fieldref, Methodref, InterfaceMethodref or InvokeDynamic constant, prefixed with its kind
+fn member_reference(classfile: &Classfile, index: u16, in_code: bool) -> String {
+    let (kind, text) = match classfile.constant_pool.get(index) {
+        Some(&Constant::Fieldref(class_index, name_and_type_index)) => ("Field", member(classfile, class_index, name_and_type_index, in_code)),
+        Some(&Constant::Methodref(class_index, name_and_type_index)) => ("Method", member(classfile, class_index, name_and_type_index, in_code)),
+        Some(&Constant::InterfaceMethodref(class_index, name_and_type_index)) => ("InterfaceMethod", member(classfile, class_index, name_and_type_index, in_code)),
+        Some(&Constant::InvokeDynamic(bootstrap_index, name_and_type_index)) => {
+            ("InvokeDynamic", format!("#{}:{}", bootstrap_index, name_and_type_at(classfile, name_and_type_index)))
+        },
+        _ => return format!("<invalid member reference #{}>", index),
+    };
+    if in_code {
+        format!("{} {}", kind, text)
+    } else {
+        text
+    }
+}
+
+// a constant loaded by ldc or used as a ConstantValue, e.g. `String Hello` or `long 5l`
+fn loadable_constant(classfile: &Classfile, index: u16) -> String {
+    match classfile.constant_pool.get(index) {
+        Some(&Constant::Integer(value)) => format!("int {}", value),
+        Some(&Constant::Float(value)) => format!("float {}f", java_float(value as f64, true)),
+        Some(&Constant::Long(value)) => format!("long {}l", value),
+        Some(&Constant::Double(value)) => format!("double {}d", java_float(value, false)),
+        Some(&Constant::String(string_index)) => format!("String {}", escape(utf8(classfile, string_index))),
+        Some(&Constant::Class(_)) => format!("class {}", quoted_class_name(classfile, index)),
+        Some(&Constant::MethodType(descriptor_index)) => format!("MethodType {}", utf8(classfile, descriptor_index)),
+        Some(&Constant::MethodHandle(kind, reference_index)) => {
+            format!("MethodHandle {} {}", reference_kind_name(kind), member_reference(classfile, reference_index, false))
+        },
+        Some(&Constant::Dynamic(bootstrap_index, name_and_type_index)) => {
+            format!("Dynamic #{}:{}", bootstrap_index, name_and_type_at(classfile, name_and_type_index))
+        },
+        _ => format!("<invalid constant #{}>", index),
+    }
+}
+
+// a bootstrap method argument, the way javap shows it
+fn constant_value(classfile: &Classfile, index: u16) -> String {
+    match classfile.constant_pool.get(index) {
+        Some(&Constant::String(string_index)) => escape(utf8(classfile, string_index)),
+        Some(&Constant::Integer(value)) => value.to_string(),
+        Some(&Constant::Float(value)) => format!("{}f", java_float(value as f64, true)),
+        Some(&Constant::Long(value)) => format!("{}l", value),
+        Some(&Constant::Double(value)) => format!("{}d", java_float(value, false)),
+        Some(&Constant::MethodType(descriptor_index)) => utf8(classfile, descriptor_index).to_owned(),
+        _ => loadable_constant(classfile, index),
+    }
+}
+
+fn reference_kind_name(kind: u8) -> &'static str {
+    match kind {
+        REF_GET_FIELD => "REF_getField",
+        REF_GET_STATIC => "REF_getStatic",
+        REF_PUT_FIELD => "REF_putField",
+        REF_PUT_STATIC => "REF_putStatic",
+        REF_INVOKE_VIRTUAL => "REF_invokeVirtual",
+        REF_INVOKE_STATIC => "REF_invokeStatic",
+        REF_INVOKE_SPECIAL => "REF_invokeSpecial",
+        REF_NEW_INVOKE_SPECIAL => "REF_newInvokeSpecial",
+        REF_INVOKE_INTERFACE => "REF_invokeInterface",
+        _ => "REF_invalid",
+    }
+}
+
+// the element type names used by newarray
+fn array_type_name(array_type: u8) -> &'static str {
+    match array_type {
+        4 => "boolean",
+        5 => "char",
+        6 => "float",
+        7 => "double",
+        8 => "byte",
+        9 => "short",
+        10 => "int",
+        11 => "long",
+        _ => "invalid",
+    }
+}
+
+fn utf8(classfile: &Classfile, index: u16) -> &str {
+    classfile.constant_pool.get_utf8(index).unwrap_or("<invalid Utf8>")
+}
+
+fn class_name(classfile: &Classfile, index: u16) -> &str {
+    classfile.constant_pool.get_class_name(index).unwrap_or("<invalid Class>")
+}
+
+// array class names and special method names are quoted, as javap does
+fn quoted_class_name(classfile: &Classfile, index: u16) -> String {
+    quote_if_needed(class_name(classfile, index))
+}
+
+fn quote_if_needed(name: &str) -> String {
+    if name.starts_with('[') || name.starts_with('<') {
+        format!("\"{}\"", name)
+    } else {
+        name.to_owned()
+    }
+}
+
+fn binary_class_name(classfile: &Classfile, index: u16) -> String {
+    internal_name_to_binary(class_name(classfile, index))
+}
+
+// control characters are escaped, so that every constant fits on one line, and so are quotes and backslashes
+fn escape(string: &str) -> String {
+    string.chars().map(|c| match c {
+        '\n' => "\\n".to_owned(),
+        '\t' => "\\t".to_owned(),
+        '\r' => "\\r".to_owned(),
+        '\u{8}' => "\\b".to_owned(),
+        '\u{c}' => "\\f".to_owned(),
+        '"' | '\'' | '\\' => format!("\\{}", c),
+        c if c.is_control() => format!("\\u{:04x}", c as u32),
+        c => c.to_string(),
+    }).collect()
+}
+
+// formats a float or double the way Java's toString does, e.g. 1.0E10 rather than 10000000000
+fn java_float(value: f64, single_precision: bool) -> String {
+    if value.is_nan() {
+        return "NaN".to_owned();
+    }
+    if value.is_infinite() {
+        return if value > 0.0 { "Infinity".to_owned() } else { "-Infinity".to_owned() };
+    }
+    let magnitude = value.abs();
+    if magnitude == 0.0 || (1e-3..1e7).contains(&magnitude) {
+        if single_precision { format!("{:?}", value as f32) } else { format!("{:?}", value) }
+    } else {
+        let scientific = if single_precision { format!("{:e}", value as f32) } else { format!("{:e}", value) };
+        let (mantissa, exponent) = scientific.split_at(scientific.find('e').unwrap());
+        if mantissa.contains('.') {
+            format!("{}E{}", mantissa, &exponent[1..])
+        } else {
+            format!("{}.0E{}", mantissa, &exponent[1..])
         }
     }
 }
 
-impl PrettyPrint for ExceptionTableEntry {
-    fn pretty_print(&self, f: &mut fmt::Formatter, _indent: usize) -> fmt::Result {
-        write!(f, "{:?}", self)
+// the attribute's string if it's a Signature attribute
+fn signature<'a>(classfile: &'a Classfile, attributes: &[Attribute]) -> Option<&'a str> {
+    attributes.iter().filter_map(|attribute| match *attribute {
+        Attribute::Signature(_, index) => classfile.constant_pool.get_utf8(index),
+        _ => None,
+    }).next()
+}
+
+// Java source syntax for types, e.g. java.lang.String[] or java.util.List<? extends T>
+fn source_java_type(t: &Java) -> String {
+    match *t {
+        Java::Boolean => "boolean".to_owned(),
+        Java::Byte => "byte".to_owned(),
+        Java::Char => "char".to_owned(),
+        Java::Short => "short".to_owned(),
+        Java::Int => "int".to_owned(),
+        Java::Long => "long".to_owned(),
+        Java::Float => "float".to_owned(),
+        Java::Double => "double".to_owned(),
+        Java::Void => "void".to_owned(),
+        Java::Class(ref name) => internal_name_to_binary(name),
+        Java::Array(ref component_type) => format!("{}[]", source_java_type(component_type)),
     }
 }
 
-impl PrettyPrint for LineNumberTableEntry {
-    fn pretty_print(&self, f: &mut fmt::Formatter, _indent: usize) -> fmt::Result {
-        write!(f, "start_pc: {:2}, line_number: {:2}", self.start_pc, self.line_number)
+fn source_type(t: &TypeSignature) -> String {
+    match *t {
+        TypeSignature::Boolean => "boolean".to_owned(),
+        TypeSignature::Byte => "byte".to_owned(),
+        TypeSignature::Char => "char".to_owned(),
+        TypeSignature::Short => "short".to_owned(),
+        TypeSignature::Int => "int".to_owned(),
+        TypeSignature::Long => "long".to_owned(),
+        TypeSignature::Float => "float".to_owned(),
+        TypeSignature::Double => "double".to_owned(),
+        TypeSignature::Void => "void".to_owned(),
+        TypeSignature::Class(ref class) => source_class_type(class),
+        TypeSignature::TypeVariable(ref name) => name.clone(),
+        TypeSignature::Array(ref component_type) => format!("{}[]", source_type(component_type)),
     }
 }
 
-impl PrettyPrint for StackMapFrame {
-    fn pretty_print(&self, f: &mut fmt::Formatter, _indent: usize) -> fmt::Result {
-        write!(f, "{:?}", self)
+fn source_class_type(class: &ClassTypeSignature) -> String {
+    let mut source = internal_name_to_binary(&class.name) + &source_type_arguments(&class.type_arguments);
+    for (name, type_arguments) in &class.inner_classes {
+        source.push_str(&format!(".{}{}", name, source_type_arguments(type_arguments)));
     }
+    source
 }
 
-impl PrettyPrint for VerificationType {
-    fn pretty_print(&self, f: &mut fmt::Formatter, _indent: usize) -> fmt::Result {
-        write!(f, "{:?}", self)
+fn source_type_arguments(type_arguments: &[TypeArgument]) -> String {
+    if type_arguments.is_empty() {
+        return String::new();
     }
+    let arguments: Vec<String> = type_arguments.iter().map(|type_argument| match *type_argument {
+        TypeArgument::Any => "?".to_owned(),
+        TypeArgument::Exact(ref t) => source_type(t),
+        TypeArgument::Extends(ref t) => format!("? extends {}", source_type(t)),
+        TypeArgument::Super(ref t) => format!("? super {}", source_type(t)),
+    }).collect();
+    format!("<{}>", arguments.join(", "))
 }
 
-impl PrettyPrint for Instruction {
-    fn pretty_print(&self, f: &mut fmt::Formatter, _indent: usize) -> fmt::Result {
-        write!(f, "{:?}", self)
+fn source_type_parameters(type_parameters: &[TypeParameter]) -> String {
+    if type_parameters.is_empty() {
+        return String::new();
     }
+    let parameters: Vec<String> = type_parameters.iter().map(|type_parameter| {
+        let bounds: Vec<String> = type_parameter.class_bound.iter().chain(&type_parameter.interface_bounds).map(source_type).collect();
+        format!("{} extends {}", type_parameter.name, bounds.join(" & "))
+    }).collect();
+    format!("<{}>", parameters.join(", "))
 }
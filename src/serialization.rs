@@ -1,29 +1,40 @@
 use std::io::Read;
 
 use classfile::*;
+use constant_pool::*;
+use errors::*;
 
 impl Classfile {
-    pub fn serialize(self, buf: &mut Vec<u8>) {
-        self.magic.serialize(buf);
-        self.minor_version.serialize(buf);
-        self.major_version.serialize(buf);
-        self.constant_pool.serialize(buf);
-        self.access_flags.serialize(buf);
-        self.this_class.serialize(buf);
-        self.super_class.serialize(buf);
-        self.interfaces.serialize(buf);
-        self.fields.serialize(buf);
-        self.methods.serialize(buf);
-        self.attributes.serialize(buf);
-    }
-
-    pub fn deserialize(stream: Box<Read>) -> Classfile {
-        let mut buf = &mut Deserializer::new(Box::new(stream.bytes().map(|r| r.unwrap())));
+    pub fn serialize(self, buf: &mut Vec<u8>) -> Result<(), ClassfileError> {
+        self.magic.serialize(buf)?;
+        self.minor_version.serialize(buf)?;
+        self.major_version.serialize(buf)?;
+        self.constant_pool.serialize(buf)?;
+        self.access_flags.serialize(buf)?;
+        self.this_class.serialize(buf)?;
+        self.super_class.serialize(buf)?;
+        self.interfaces.serialize(buf)?;
+        self.fields.serialize(buf)?;
+        self.methods.serialize(buf)?;
+        self.attributes.serialize(buf)?;
+        Ok(())
+    }
+
+    pub fn deserialize(stream: Box<dyn Read>) -> Result<Classfile, ClassfileError> {
+        Classfile::deserialize_with_decoders(stream, &AttributeDecoders::new())
+    }
+
+    // Like deserialize, but attributes with a registered decoder become Attribute::Custom rather than Attribute::Unknown.
+    pub fn deserialize_with_decoders(mut stream: Box<dyn Read>, decoders: &AttributeDecoders) -> Result<Classfile, ClassfileError> {
+        let mut bytes = vec![];
+        stream.read_to_end(&mut bytes)?;
+
+        let buf = &mut Deserializer::new(bytes, 0, decoders);
         let mut c = Classfile {
             magic: 0,
             minor_version: 0,
             major_version: 0,
-            constant_pool: vec![],
+            constant_pool: ConstantPool::new(),
             access_flags: 0,
             this_class: 0,
             super_class: 0,
@@ -32,567 +43,1320 @@ impl Classfile {
             methods: vec![],
             attributes: vec![],
         };
-        c.magic = u32::deserialize(buf, &c);
-        c.minor_version = u16::deserialize(buf, &c);
-        c.major_version = u16::deserialize(buf, &c);
-        c.constant_pool = Vec::deserialize(buf, &c);
-        c.access_flags = u16::deserialize(buf, &c);
-        c.this_class = u16::deserialize(buf, &c);
-        c.super_class = u16::deserialize(buf, &c);
-        c.interfaces = Vec::deserialize(buf, &c);
-        c.fields = Vec::deserialize(buf, &c);
-        c.methods = Vec::deserialize(buf, &c);
-        c.attributes = Vec::deserialize(buf, &c);
-        c
+        c.magic = u32::deserialize(buf, &c).map_err(|e| e.within("magic"))?;
+        if c.magic != 0xCAFEBABE {
+            return Err(ClassfileError::new(0, ClassfileErrorCause::InvalidMagic(c.magic)));
+        }
+        c.minor_version = u16::deserialize(buf, &c).map_err(|e| e.within("minor_version"))?;
+        c.major_version = u16::deserialize(buf, &c).map_err(|e| e.within("major_version"))?;
+        c.constant_pool = ConstantPool::deserialize(buf, &c)?;
+        c.access_flags = u16::deserialize(buf, &c).map_err(|e| e.within("access_flags"))?;
+        c.this_class = u16::deserialize(buf, &c).map_err(|e| e.within("this_class"))?;
+        c.super_class = u16::deserialize(buf, &c).map_err(|e| e.within("super_class"))?;
+        c.interfaces = Vec::deserialize(buf, &c)?;
+        c.fields = Vec::deserialize(buf, &c)?;
+        c.methods = Vec::deserialize(buf, &c)?;
+        c.attributes = Vec::deserialize(buf, &c)?;
+        buf.finish()?;
+        Ok(c)
     }
 }
 
-struct Deserializer {
-    stream: Box<Iterator<Item=u8>>,
-    bytes_taken: u32,
+struct Deserializer<'a> {
+    bytes: Vec<u8>,
+    bytes_taken: usize,
+    base_offset: usize, // where these bytes start in the class file, for error reporting
+    decoders: &'a AttributeDecoders,
 }
 
-impl Deserializer {
-    fn new(stream: Box<Iterator<Item=u8>>) -> Deserializer {
-        Deserializer { stream: stream, bytes_taken: 0 }
+impl<'a> Deserializer<'a> {
+    fn new(bytes: Vec<u8>, base_offset: usize, decoders: &'a AttributeDecoders) -> Deserializer<'a> {
+        Deserializer { bytes, bytes_taken: 0, base_offset, decoders }
+    }
+
+    // a deserializer for a chunk of bytes that was just taken from this one (an attribute body, or a method's code)
+    fn sub_deserializer(&self, bytes: Vec<u8>) -> Deserializer<'a> {
+        let base_offset = self.offset() - bytes.len();
+        Deserializer::new(bytes, base_offset, self.decoders)
     }
 
-    fn take_byte(&mut self) -> u8 {
-        let v = self.take_bytes(1);
-        v[0]
+    fn offset(&self) -> usize {
+        self.base_offset + self.bytes_taken
     }
 
-    fn take_bytes(&mut self, n: u32) -> Vec<u8> {
+    fn remaining(&self) -> usize {
+        self.bytes.len() - self.bytes_taken
+    }
+
+    fn error(&self, cause: ClassfileErrorCause) -> ClassfileError {
+        ClassfileError::new(self.offset(), cause)
+    }
+
+    fn take_byte(&mut self) -> Result<u8, ClassfileError> {
+        let v = self.take_bytes(1)?;
+        Ok(v[0])
+    }
+
+    fn take_bytes(&mut self, n: usize) -> Result<Vec<u8>, ClassfileError> {
+        if n > self.remaining() {
+            return Err(self.error(ClassfileErrorCause::UnexpectedEof));
+        }
+        let v = self.bytes[self.bytes_taken..self.bytes_taken + n].to_vec();
         self.bytes_taken += n;
-        (&mut self.stream).take(n as usize).collect()
+        Ok(v)
+    }
+
+    fn finish(&self) -> Result<(), ClassfileError> {
+        if self.remaining() > 0 {
+            return Err(self.error(ClassfileErrorCause::TrailingBytes(self.remaining())));
+        }
+        Ok(())
     }
 }
 
-trait Serializable {
-    fn serialize(self, &mut Vec<u8>);
-    fn deserialize(&mut Deserializer, &Classfile) -> Self;
+trait Serializable: Sized {
+    fn serialize(self, buf: &mut Vec<u8>) -> Result<(), ClassfileError>;
+    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> Result<Self, ClassfileError>;
 }
 
 impl Serializable for u8 {
-    fn serialize(self, buf: &mut Vec<u8>) {
-        buf.push(self)
+    fn serialize(self, buf: &mut Vec<u8>) -> Result<(), ClassfileError> {
+        buf.push(self);
+        Ok(())
     }
 
-    fn deserialize(buf: &mut Deserializer, _classfile: &Classfile) -> u8 {
+    fn deserialize(buf: &mut Deserializer, _classfile: &Classfile) -> Result<u8, ClassfileError> {
         buf.take_byte()
     }
 }
 
 impl Serializable for u16 {
-    fn serialize(self, buf: &mut Vec<u8>) {
+    fn serialize(self, buf: &mut Vec<u8>) -> Result<(), ClassfileError> {
         buf.push((self >> 8) as u8);
         buf.push(self as u8);
+        Ok(())
     }
 
-    fn deserialize(buf: &mut Deserializer, _classfile: &Classfile) -> u16 {
-        let v = buf.take_bytes(2);
-        ((v[0] as u16) << 8) + (v[1] as u16)
+    fn deserialize(buf: &mut Deserializer, _classfile: &Classfile) -> Result<u16, ClassfileError> {
+        let v = buf.take_bytes(2)?;
+        Ok(((v[0] as u16) << 8) + (v[1] as u16))
     }
 }
 
 impl Serializable for u32 {
-    fn serialize(self, buf: &mut Vec<u8>) {
+    fn serialize(self, buf: &mut Vec<u8>) -> Result<(), ClassfileError> {
         buf.push((self >> 24) as u8);
         buf.push((self >> 16) as u8);
         buf.push((self >> 8) as u8);
         buf.push(self as u8);
+        Ok(())
     }
 
-    fn deserialize(buf: &mut Deserializer, _classfile: &Classfile) -> u32 {
-        let v = buf.take_bytes(4);
-        ((v[0] as u32) << 24) + ((v[1] as u32) << 16) + ((v[2] as u32) << 8) + (v[3] as u32)
+    fn deserialize(buf: &mut Deserializer, _classfile: &Classfile) -> Result<u32, ClassfileError> {
+        let v = buf.take_bytes(4)?;
+        Ok(((v[0] as u32) << 24) + ((v[1] as u32) << 16) + ((v[2] as u32) << 8) + (v[3] as u32))
     }
 }
 
-impl Serializable for String {
-    fn serialize(self, buf: &mut Vec<u8>) {
-        (self.len() as u16).serialize(buf);
-        for b in self.as_bytes() {
-            b.serialize(buf);
+impl Serializable for JavaString {
+    fn serialize(self, buf: &mut Vec<u8>) -> Result<(), ClassfileError> {
+        let bytes = self.to_modified_utf8();
+        if bytes.len() > u16::MAX as usize {
+            return Err(ClassfileError::new(buf.len(), ClassfileErrorCause::StringTooLong(bytes.len())));
         }
+        (bytes.len() as u16).serialize(buf)?;
+        buf.extend(bytes);
+        Ok(())
     }
 
-    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> String {
-        let len = u16::deserialize(buf, classfile);
-        let v = buf.take_bytes(len as u32);
-        String::from_utf8(v).unwrap()
+    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> Result<JavaString, ClassfileError> {
+        let len = u16::deserialize(buf, classfile)?;
+        let offset = buf.offset();
+        let v = buf.take_bytes(len as usize)?;
+        JavaString::from_modified_utf8(&v).ok_or_else(|| ClassfileError::new(offset, ClassfileErrorCause::InvalidModifiedUtf8))
     }
 }
 
-impl Serializable for Vec<u8> {
-    fn serialize(self, buf: &mut Vec<u8>) {
-        (self.len() as u32).serialize(buf); // byte vectors use a 4-byte length prefix, not 2-byte
-        for b in self.into_iter() {
-            b.serialize(buf);
+// Class files store strings in "modified UTF-8": NUL is written as the two bytes 0xC0 0x80, and characters outside the
+// Basic Multilingual Plane are written as a UTF-16 surrogate pair, with each surrogate encoded as its own 3-byte sequence.
+pub fn encode_modified_utf8(string: &str) -> Vec<u8> {
+    let mut bytes = vec![];
+    for unit in string.encode_utf16() {
+        match unit {
+            0x0001..=0x007F => {
+                bytes.push(unit as u8);
+            },
+            0x0000 | 0x0080..=0x07FF => {
+                bytes.push(0xC0 | (unit >> 6) as u8);
+                bytes.push(0x80 | (unit & 0x3F) as u8);
+            },
+            _ => {
+                bytes.push(0xE0 | (unit >> 12) as u8);
+                bytes.push(0x80 | ((unit >> 6) & 0x3F) as u8);
+                bytes.push(0x80 | (unit & 0x3F) as u8);
+            },
         }
     }
+    bytes
+}
 
-    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> Vec<u8> {
-        let len = u32::deserialize(buf, classfile); // byte vectors use a 4-byte length prefix, not 2-byte
-        buf.take_bytes(len)
-    }
+// Returns None for malformed input, and for unpaired surrogates, which Rust strings can't represent.
+pub fn decode_modified_utf8(bytes: &[u8]) -> Option<String> {
+    String::from_utf16(&decode_modified_utf8_units(bytes)?).ok()
 }
 
-impl Serializable for Vec<Constant> {
-    fn serialize(self, buf: &mut Vec<u8>) {
-        ((self.len() + 1) as u16).serialize(buf); // IMPORTANT: constant_pool_length is len + 1
-        for constant in self.into_iter() {
-            constant.serialize(buf);
+// the UTF-16 code units, which can include unpaired surrogates, or None for malformed input
+pub fn decode_modified_utf8_units(bytes: &[u8]) -> Option<Vec<u16>> {
+    let continuation = |i: usize| -> Option<u16> {
+        match bytes.get(i) {
+            Some(&b) if b & 0xC0 == 0x80 => Some((b & 0x3F) as u16),
+            _ => None,
         }
+    };
+
+    let mut units = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        let b = bytes[i] as u16;
+        if b == 0 {
+            return None; // NUL is always written in its 2-byte form
+        } else if b & 0x80 == 0 {
+            units.push(b);
+            i += 1;
+        } else if b & 0xE0 == 0xC0 {
+            units.push(((b & 0x1F) << 6) | continuation(i + 1)?);
+            i += 2;
+        } else if b & 0xF0 == 0xE0 {
+            units.push(((b & 0x0F) << 12) | (continuation(i + 1)? << 6) | continuation(i + 2)?);
+            i += 3;
+        } else {
+            return None;
+        }
+    }
+    Some(units)
+}
+
+impl Serializable for Vec<u8> {
+    fn serialize(self, buf: &mut Vec<u8>) -> Result<(), ClassfileError> {
+        (self.len() as u32).serialize(buf)?; // byte vectors use a 4-byte length prefix, not 2-byte
+        buf.extend(self);
+        Ok(())
     }
 
-    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> Vec<Constant> {
-        let len = u16::deserialize(buf, classfile) - 1; // IMPORTANT: constant_pool_length is len + 1
-        (0..len).into_iter().map(|_| Constant::deserialize(buf, classfile)).collect()
+    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> Result<Vec<u8>, ClassfileError> {
+        let len = u32::deserialize(buf, classfile)?; // byte vectors use a 4-byte length prefix, not 2-byte
+        buf.take_bytes(len as usize)
     }
 }
 
-impl Serializable for Vec<Interface> {
-    fn serialize(self, buf: &mut Vec<u8>) {
-        (self.len() as u16).serialize(buf);
-        for constant in self.into_iter() {
-            constant.serialize(buf);
+impl Serializable for ConstantPool {
+    fn serialize(self, buf: &mut Vec<u8>) -> Result<(), ClassfileError> {
+        serialize_count(self.len() + 1, buf)?; // IMPORTANT: constant_pool_length is len + 1
+        for (index, constant) in self.iter() {
+            constant.clone().serialize(buf).map_err(|e| e.within(&format!("constant #{}", index)))?;
         }
+        Ok(())
     }
 
-    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> Vec<Interface> {
-        let len = u16::deserialize(buf, classfile);
-        (0..len).into_iter().map(|_| Interface::deserialize(buf, classfile)).collect()
+    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> Result<ConstantPool, ClassfileError> {
+        let len = u16::deserialize(buf, classfile)?.saturating_sub(1); // IMPORTANT: constant_pool_length is len + 1
+        let mut constants = ConstantPool::new();
+        while constants.len() < len as usize {
+            let index = constants.len() + 1;
+            let offset = buf.offset();
+            let constant = Constant::deserialize(buf, classfile).map_err(|e| e.within(&format!("constant #{}", index)))?;
+            if constant.is_wide() && index == len as usize {
+                // a Long or Double in the last slot would spill past constant_pool_count
+                return Err(ClassfileError::new(offset, ClassfileErrorCause::TooManyEntries(index + 1)).within(&format!("constant #{}", index)));
+            }
+            constants.push(constant); // keep duplicates, so the indices used by the rest of the class stay valid
+        }
+        Ok(constants)
+    }
+}
+
+impl Serializable for Vec<u16> {
+    fn serialize(self, buf: &mut Vec<u8>) -> Result<(), ClassfileError> {
+        serialize_list(self, buf, "interface")
+    }
+
+    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> Result<Vec<u16>, ClassfileError> {
+        deserialize_list(buf, classfile, "interface")
     }
 }
 
 impl Serializable for Vec<Field> {
-    fn serialize(self, buf: &mut Vec<u8>) {
-        (self.len() as u16).serialize(buf);
-        for constant in self.into_iter() {
-            constant.serialize(buf);
-        }
+    fn serialize(self, buf: &mut Vec<u8>) -> Result<(), ClassfileError> {
+        serialize_list(self, buf, "field")
     }
 
-    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> Vec<Field> {
-        let len = u16::deserialize(buf, classfile);
-        (0..len).into_iter().map(|_| Field::deserialize(buf, classfile)).collect()
+    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> Result<Vec<Field>, ClassfileError> {
+        deserialize_list(buf, classfile, "field")
     }
 }
 
 impl Serializable for Vec<Method> {
-    fn serialize(self, buf: &mut Vec<u8>) {
-        (self.len() as u16).serialize(buf);
-        for constant in self.into_iter() {
-            constant.serialize(buf);
-        }
+    fn serialize(self, buf: &mut Vec<u8>) -> Result<(), ClassfileError> {
+        serialize_list(self, buf, "method")
     }
 
-    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> Vec<Method> {
-        let len = u16::deserialize(buf, classfile);
-        (0..len).into_iter().map(|_| Method::deserialize(buf, classfile)).collect()
+    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> Result<Vec<Method>, ClassfileError> {
+        deserialize_list(buf, classfile, "method")
     }
 }
 
 impl Serializable for Vec<Attribute> {
-    fn serialize(self, buf: &mut Vec<u8>) {
-        (self.len() as u16).serialize(buf);
-        for constant in self.into_iter() {
-            constant.serialize(buf);
+    // attributes describe themselves by name in errors, so they aren't numbered like the other lists
+    fn serialize(self, buf: &mut Vec<u8>) -> Result<(), ClassfileError> {
+        serialize_count(self.len(), buf)?;
+        for attribute in self.into_iter() {
+            attribute.serialize(buf)?;
         }
+        Ok(())
     }
 
-    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> Vec<Attribute> {
-        let len = u16::deserialize(buf, classfile);
-        (0..len).into_iter().map(|_| Attribute::deserialize(buf, classfile)).collect()
+    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> Result<Vec<Attribute>, ClassfileError> {
+        let len = u16::deserialize(buf, classfile)?;
+        (0..len).map(|_| Attribute::deserialize(buf, classfile)).collect()
     }
 }
 
 impl Serializable for Vec<ExceptionTableEntry> {
-    fn serialize(self, buf: &mut Vec<u8>) {
-        (self.len() as u16).serialize(buf);
-        for constant in self.into_iter() {
-            constant.serialize(buf);
-        }
+    fn serialize(self, buf: &mut Vec<u8>) -> Result<(), ClassfileError> {
+        serialize_list(self, buf, "exception table entry")
+    }
+
+    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> Result<Vec<ExceptionTableEntry>, ClassfileError> {
+        deserialize_list(buf, classfile, "exception table entry")
+    }
+}
+
+impl Serializable for Vec<BootstrapMethod> {
+    fn serialize(self, buf: &mut Vec<u8>) -> Result<(), ClassfileError> {
+        serialize_list(self, buf, "bootstrap method")
     }
 
-    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> Vec<ExceptionTableEntry> {
-        let len = u16::deserialize(buf, classfile);
-        (0..len).into_iter().map(|_| ExceptionTableEntry::deserialize(buf, classfile)).collect()
+    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> Result<Vec<BootstrapMethod>, ClassfileError> {
+        deserialize_list(buf, classfile, "bootstrap method")
+    }
+}
+
+impl Serializable for Vec<InnerClass> {
+    fn serialize(self, buf: &mut Vec<u8>) -> Result<(), ClassfileError> {
+        serialize_list(self, buf, "inner class")
+    }
+
+    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> Result<Vec<InnerClass>, ClassfileError> {
+        deserialize_list(buf, classfile, "inner class")
     }
 }
 
 impl Serializable for Vec<LineNumberTableEntry> {
-    fn serialize(self, buf: &mut Vec<u8>) {
-        (self.len() as u16).serialize(buf);
-        for constant in self.into_iter() {
-            constant.serialize(buf);
-        }
+    fn serialize(self, buf: &mut Vec<u8>) -> Result<(), ClassfileError> {
+        serialize_list(self, buf, "line number table entry")
+    }
+
+    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> Result<Vec<LineNumberTableEntry>, ClassfileError> {
+        deserialize_list(buf, classfile, "line number table entry")
+    }
+}
+
+impl Serializable for Vec<LocalVariableTableEntry> {
+    fn serialize(self, buf: &mut Vec<u8>) -> Result<(), ClassfileError> {
+        serialize_list(self, buf, "local variable table entry")
+    }
+
+    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> Result<Vec<LocalVariableTableEntry>, ClassfileError> {
+        deserialize_list(buf, classfile, "local variable table entry")
+    }
+}
+
+impl Serializable for Vec<LocalVariableTypeTableEntry> {
+    fn serialize(self, buf: &mut Vec<u8>) -> Result<(), ClassfileError> {
+        serialize_list(self, buf, "local variable type table entry")
+    }
+
+    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> Result<Vec<LocalVariableTypeTableEntry>, ClassfileError> {
+        deserialize_list(buf, classfile, "local variable type table entry")
+    }
+}
+
+impl Serializable for Vec<Annotation> {
+    fn serialize(self, buf: &mut Vec<u8>) -> Result<(), ClassfileError> {
+        serialize_list(self, buf, "annotation")
     }
 
-    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> Vec<LineNumberTableEntry> {
-        let len = u16::deserialize(buf, classfile);
-        (0..len).into_iter().map(|_| LineNumberTableEntry::deserialize(buf, classfile)).collect()
+    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> Result<Vec<Annotation>, ClassfileError> {
+        deserialize_list(buf, classfile, "annotation")
+    }
+}
+
+impl Serializable for Vec<ElementValuePair> {
+    fn serialize(self, buf: &mut Vec<u8>) -> Result<(), ClassfileError> {
+        serialize_list(self, buf, "element")
+    }
+
+    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> Result<Vec<ElementValuePair>, ClassfileError> {
+        deserialize_list(buf, classfile, "element")
+    }
+}
+
+impl Serializable for Vec<ElementValue> {
+    fn serialize(self, buf: &mut Vec<u8>) -> Result<(), ClassfileError> {
+        serialize_list(self, buf, "array element")
+    }
+
+    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> Result<Vec<ElementValue>, ClassfileError> {
+        deserialize_list(buf, classfile, "array element")
+    }
+}
+
+impl Serializable for Vec<TypeAnnotation> {
+    fn serialize(self, buf: &mut Vec<u8>) -> Result<(), ClassfileError> {
+        serialize_list(self, buf, "type annotation")
+    }
+
+    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> Result<Vec<TypeAnnotation>, ClassfileError> {
+        deserialize_list(buf, classfile, "type annotation")
+    }
+}
+
+impl Serializable for Vec<LocalVariableTarget> {
+    fn serialize(self, buf: &mut Vec<u8>) -> Result<(), ClassfileError> {
+        serialize_list(self, buf, "local variable")
+    }
+
+    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> Result<Vec<LocalVariableTarget>, ClassfileError> {
+        deserialize_list(buf, classfile, "local variable")
     }
 }
 
 impl Serializable for Vec<StackMapFrame> {
-    fn serialize(self, buf: &mut Vec<u8>) {
-        (self.len() as u16).serialize(buf);
-        for constant in self.into_iter() {
-            constant.serialize(buf);
-        }
+    fn serialize(self, buf: &mut Vec<u8>) -> Result<(), ClassfileError> {
+        serialize_list(self, buf, "frame")
     }
 
-    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> Vec<StackMapFrame> {
-        let len = u16::deserialize(buf, classfile);
-        (0..len).into_iter().map(|_| StackMapFrame::deserialize(buf, classfile)).collect()
+    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> Result<Vec<StackMapFrame>, ClassfileError> {
+        deserialize_list(buf, classfile, "frame")
     }
 }
 
 impl Serializable for Vec<VerificationType> {
-    fn serialize(self, buf: &mut Vec<u8>) {
-        (self.len() as u16).serialize(buf);
-        for constant in self.into_iter() {
-            constant.serialize(buf);
-        }
+    fn serialize(self, buf: &mut Vec<u8>) -> Result<(), ClassfileError> {
+        serialize_list(self, buf, "verification type")
     }
 
-    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> Vec<VerificationType> {
-        let len = u16::deserialize(buf, classfile);
-        (0..len).into_iter().map(|_| VerificationType::deserialize(buf, classfile)).collect()
+    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> Result<Vec<VerificationType>, ClassfileError> {
+        deserialize_list(buf, classfile, "verification type")
     }
 }
 
 impl Serializable for Vec<Instruction> {
-    fn serialize(self, buf: &mut Vec<u8>) {
+    fn serialize(self, buf: &mut Vec<u8>) -> Result<(), ClassfileError> {
         let mut code = vec![];
         for inst in self.into_iter() {
-            inst.serialize(&mut code);
+            inst.serialize(&mut code)?;
+        }
+        if code.len() > u16::MAX as usize {
+            return Err(ClassfileError::new(buf.len(), ClassfileErrorCause::CodeTooLarge(code.len())));
         }
-        code.serialize(buf);
+        code.serialize(buf)
     }
 
-    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> Vec<Instruction> {
-        let code: Vec<u8> = Vec::deserialize(buf, classfile);
-        let code_len = code.len() as u32;
-        let mut code_buf = &mut Deserializer::new(Box::new(code.into_iter()));
+    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> Result<Vec<Instruction>, ClassfileError> {
+        let offset = buf.offset();
+        let code: Vec<u8> = Vec::deserialize(buf, classfile)?;
+        if code.len() > u16::MAX as usize {
+            return Err(ClassfileError::new(offset, ClassfileErrorCause::CodeTooLarge(code.len())));
+        }
+        let code_buf = &mut buf.sub_deserializer(code);
         let mut out = vec![];
-        while code_buf.bytes_taken < code_len {
-            out.push(Instruction::deserialize(code_buf, classfile));
+        while code_buf.remaining() > 0 {
+            let pc = code_buf.bytes_taken;
+            out.push(Instruction::deserialize(code_buf, classfile).map_err(|e| e.within(&format!("instruction at pc {}", pc)))?);
         }
-        out
+        Ok(out)
+    }
+}
+
+fn serialize_count(count: usize, buf: &mut Vec<u8>) -> Result<(), ClassfileError> {
+    if count > u16::MAX as usize {
+        return Err(ClassfileError::new(buf.len(), ClassfileErrorCause::TooManyEntries(count)));
+    }
+    (count as u16).serialize(buf)
+}
+
+fn serialize_list<T: Serializable>(list: Vec<T>, buf: &mut Vec<u8>, item_name: &str) -> Result<(), ClassfileError> {
+    serialize_count(list.len(), buf)?;
+    for (i, item) in list.into_iter().enumerate() {
+        item.serialize(buf).map_err(|e| e.within(&format!("{} #{}", item_name, i)))?;
     }
+    Ok(())
+}
+
+// a list with a 1-byte count, like a method's parameter annotations or a type path
+fn serialize_short_list<T: Serializable>(list: Vec<T>, buf: &mut Vec<u8>, item_name: &str) -> Result<(), ClassfileError> {
+    if list.len() > u8::MAX as usize {
+        return Err(ClassfileError::new(buf.len(), ClassfileErrorCause::TooManyEntriesForByteCount(list.len())));
+    }
+    (list.len() as u8).serialize(buf)?;
+    for (i, item) in list.into_iter().enumerate() {
+        item.serialize(buf).map_err(|e| e.within(&format!("{} #{}", item_name, i)))?;
+    }
+    Ok(())
+}
+
+fn deserialize_short_list<T: Serializable>(buf: &mut Deserializer, classfile: &Classfile, item_name: &str) -> Result<Vec<T>, ClassfileError> {
+    let len = u8::deserialize(buf, classfile)?;
+    (0..len).map(|i| T::deserialize(buf, classfile).map_err(|e| e.within(&format!("{} #{}", item_name, i)))).collect()
+}
+
+fn deserialize_list<T: Serializable>(buf: &mut Deserializer, classfile: &Classfile, item_name: &str) -> Result<Vec<T>, ClassfileError> {
+    let len = u16::deserialize(buf, classfile)?;
+    (0..len).map(|i| T::deserialize(buf, classfile).map_err(|e| e.within(&format!("{} #{}", item_name, i)))).collect()
 }
 
 impl Serializable for Constant {
-    fn serialize(self, buf: &mut Vec<u8>) {
+    fn serialize(self, buf: &mut Vec<u8>) -> Result<(), ClassfileError> {
         match self {
             Constant::Utf8(string) => {
-                (1 as u8).serialize(buf);
-                string.serialize(buf);
+                1u8.serialize(buf)?;
+                string.serialize(buf)?;
+            },
+            Constant::Integer(value) => {
+                3u8.serialize(buf)?;
+                (value as u32).serialize(buf)?;
+            },
+            Constant::Float(value) => {
+                4u8.serialize(buf)?;
+                value.to_bits().serialize(buf)?;
+            },
+            Constant::Long(value) => {
+                5u8.serialize(buf)?;
+                ((value >> 32) as u32).serialize(buf)?;
+                (value as u32).serialize(buf)?;
+            },
+            Constant::Double(value) => {
+                6u8.serialize(buf)?;
+                let bits = value.to_bits();
+                ((bits >> 32) as u32).serialize(buf)?;
+                (bits as u32).serialize(buf)?;
             },
             Constant::Class(name_index) => {
-                (7 as u8).serialize(buf);
-                name_index.serialize(buf);
+                7u8.serialize(buf)?;
+                name_index.serialize(buf)?;
             },
             Constant::String(string_index) => {
-                (8 as u8).serialize(buf);
-                string_index.serialize(buf);
+                8u8.serialize(buf)?;
+                string_index.serialize(buf)?;
             },
             Constant::Fieldref(class_index, name_and_type_index) => {
-                (9 as u8).serialize(buf);
-                class_index.serialize(buf);
-                name_and_type_index.serialize(buf);
+                9u8.serialize(buf)?;
+                class_index.serialize(buf)?;
+                name_and_type_index.serialize(buf)?;
             },
             Constant::Methodref(class_index, name_and_type_index) => {
-                (10 as u8).serialize(buf);
-                class_index.serialize(buf);
-                name_and_type_index.serialize(buf);
+                10u8.serialize(buf)?;
+                class_index.serialize(buf)?;
+                name_and_type_index.serialize(buf)?;
+            },
+            Constant::InterfaceMethodref(class_index, name_and_type_index) => {
+                11u8.serialize(buf)?;
+                class_index.serialize(buf)?;
+                name_and_type_index.serialize(buf)?;
             },
             Constant::NameAndType(name_index, descriptor_index) => {
-                (12 as u8).serialize(buf);
-                name_index.serialize(buf);
-                descriptor_index.serialize(buf);
+                12u8.serialize(buf)?;
+                name_index.serialize(buf)?;
+                descriptor_index.serialize(buf)?;
+            },
+            Constant::MethodHandle(reference_kind, reference_index) => {
+                15u8.serialize(buf)?;
+                reference_kind.serialize(buf)?;
+                reference_index.serialize(buf)?;
+            },
+            Constant::MethodType(descriptor_index) => {
+                16u8.serialize(buf)?;
+                descriptor_index.serialize(buf)?;
+            },
+            Constant::Dynamic(bootstrap_method_attr_index, name_and_type_index) => {
+                17u8.serialize(buf)?;
+                bootstrap_method_attr_index.serialize(buf)?;
+                name_and_type_index.serialize(buf)?;
+            },
+            Constant::InvokeDynamic(bootstrap_method_attr_index, name_and_type_index) => {
+                18u8.serialize(buf)?;
+                bootstrap_method_attr_index.serialize(buf)?;
+                name_and_type_index.serialize(buf)?;
+            },
+            Constant::Module(name_index) => {
+                19u8.serialize(buf)?;
+                name_index.serialize(buf)?;
+            },
+            Constant::Package(name_index) => {
+                20u8.serialize(buf)?;
+                name_index.serialize(buf)?;
+            },
+            Constant::Unusable => {
+                // the second slot of a Long or Double isn't written out
             },
         }
+        Ok(())
     }
 
-    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> Constant {
-        let code = u8::deserialize(buf, classfile);
-        match code {
-            1 => Constant::Utf8(String::deserialize(buf, classfile)),
-            7 => Constant::Class(u16::deserialize(buf, classfile)),
-            8 => Constant::String(u16::deserialize(buf, classfile)),
-            9 => Constant::Fieldref(u16::deserialize(buf, classfile), u16::deserialize(buf, classfile)),
-            10 => Constant::Methodref(u16::deserialize(buf, classfile), u16::deserialize(buf, classfile)),
-            12 => Constant::NameAndType(u16::deserialize(buf, classfile), u16::deserialize(buf, classfile)),
-            _ => panic!("Don't know how to deserialize Constant of type: {}", code)
-        }
+    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> Result<Constant, ClassfileError> {
+        let code = u8::deserialize(buf, classfile)?;
+        Ok(match code {
+            1 => Constant::Utf8(JavaString::deserialize(buf, classfile)?),
+            3 => Constant::Integer(u32::deserialize(buf, classfile)? as i32),
+            4 => Constant::Float(f32::from_bits(u32::deserialize(buf, classfile)?)),
+            5 => {
+                let high = u32::deserialize(buf, classfile)? as u64;
+                let low = u32::deserialize(buf, classfile)? as u64;
+                Constant::Long(((high << 32) + low) as i64)
+            },
+            6 => {
+                let high = u32::deserialize(buf, classfile)? as u64;
+                let low = u32::deserialize(buf, classfile)? as u64;
+                Constant::Double(f64::from_bits((high << 32) + low))
+            },
+            7 => Constant::Class(u16::deserialize(buf, classfile)?),
+            8 => Constant::String(u16::deserialize(buf, classfile)?),
+            9 => Constant::Fieldref(u16::deserialize(buf, classfile)?, u16::deserialize(buf, classfile)?),
+            10 => Constant::Methodref(u16::deserialize(buf, classfile)?, u16::deserialize(buf, classfile)?),
+            11 => Constant::InterfaceMethodref(u16::deserialize(buf, classfile)?, u16::deserialize(buf, classfile)?),
+            12 => Constant::NameAndType(u16::deserialize(buf, classfile)?, u16::deserialize(buf, classfile)?),
+            15 => Constant::MethodHandle(u8::deserialize(buf, classfile)?, u16::deserialize(buf, classfile)?),
+            16 => Constant::MethodType(u16::deserialize(buf, classfile)?),
+            17 => Constant::Dynamic(u16::deserialize(buf, classfile)?, u16::deserialize(buf, classfile)?),
+            18 => Constant::InvokeDynamic(u16::deserialize(buf, classfile)?, u16::deserialize(buf, classfile)?),
+            19 => Constant::Module(u16::deserialize(buf, classfile)?),
+            20 => Constant::Package(u16::deserialize(buf, classfile)?),
+            _ => return Err(ClassfileError::new(buf.offset() - 1, ClassfileErrorCause::InvalidConstantTag(code)))
+        })
     }
 }
 
-impl Serializable for Interface {
-    fn serialize(self, _buf: &mut Vec<u8>) {
-        panic!("TODO implement Interface::serialize")
+impl Serializable for Field {
+    fn serialize(self, buf: &mut Vec<u8>) -> Result<(), ClassfileError> {
+        self.access_flags.serialize(buf)?;
+        self.name_index.serialize(buf)?;
+        self.descriptor_index.serialize(buf)?;
+        self.attributes.serialize(buf)?;
+        Ok(())
     }
 
-    fn deserialize(_buf: &mut Deserializer, _classfile: &Classfile) -> Interface {
-        panic!("TODO implement Interface::deserialize")
+    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> Result<Field, ClassfileError> {
+        Ok(Field {
+            access_flags: u16::deserialize(buf, classfile)?,
+            name_index: u16::deserialize(buf, classfile)?,
+            descriptor_index: u16::deserialize(buf, classfile)?,
+            attributes: Vec::deserialize(buf, classfile)?,
+        })
     }
 }
 
-impl Serializable for Field {
-    fn serialize(self, _buf: &mut Vec<u8>) {
-        panic!("TODO implement Field::serialize")
+impl Serializable for Method {
+    fn serialize(self, buf: &mut Vec<u8>) -> Result<(), ClassfileError> {
+        self.access_flags.serialize(buf)?;
+        self.name_index.serialize(buf)?;
+        self.descriptor_index.serialize(buf)?;
+        self.attributes.serialize(buf)?;
+        Ok(())
     }
 
-    fn deserialize(_buf: &mut Deserializer, _classfile: &Classfile) -> Field {
-        panic!("TODO implement Field::deserialize")
+    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> Result<Method, ClassfileError> {
+        Ok(Method {
+            access_flags: u16::deserialize(buf, classfile)?,
+            name_index: u16::deserialize(buf, classfile)?,
+            descriptor_index: u16::deserialize(buf, classfile)?,
+            attributes: Vec::deserialize(buf, classfile)?,
+        })
     }
 }
 
-impl Serializable for Method {
-    fn serialize(self, buf: &mut Vec<u8>) {
-        self.access_flags.serialize(buf);
-        self.name_index.serialize(buf);
-        self.descriptor_index.serialize(buf);
-        self.attributes.serialize(buf);
-    }
-
-    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> Method {
-        Method {
-            access_flags: u16::deserialize(buf, classfile),
-            name_index: u16::deserialize(buf, classfile),
-            descriptor_index: u16::deserialize(buf, classfile),
-            attributes: Vec::deserialize(buf, classfile),
+impl Serializable for Attribute {
+    fn serialize(self, buf: &mut Vec<u8>) -> Result<(), ClassfileError> {
+        self.name_index().serialize(buf)?;
+
+        // write a placeholder for the attribute length, and fill it in once the attribute body has been written
+        let length_offset = buf.len();
+        0u32.serialize(buf)?;
+
+        match self {
+            Attribute::Code(_, max_stack, max_locals, code, exception_table, attributes) => {
+                max_stack.serialize(buf)?;
+                max_locals.serialize(buf)?;
+                code.serialize(buf)?;
+                exception_table.serialize(buf)?;
+                attributes.serialize(buf)?;
+            },
+            Attribute::AnnotationDefault(_, default_value) => {
+                default_value.serialize(buf)?;
+            },
+            Attribute::BootstrapMethods(_, bootstrap_methods) => {
+                bootstrap_methods.serialize(buf)?;
+            },
+            Attribute::ConstantValue(_, constantvalue_index) => {
+                constantvalue_index.serialize(buf)?;
+            },
+            Attribute::Deprecated(_) => {},
+            Attribute::EnclosingMethod(_, class_index, method_index) => {
+                class_index.serialize(buf)?;
+                method_index.serialize(buf)?;
+            },
+            Attribute::Exceptions(_, exception_index_table) => {
+                serialize_list(exception_index_table, buf, "exception")?;
+            },
+            Attribute::InnerClasses(_, classes) => {
+                classes.serialize(buf)?;
+            },
+            Attribute::LineNumberTable(_, entries) => {
+                entries.serialize(buf)?;
+            },
+            Attribute::LocalVariableTable(_, entries) => {
+                entries.serialize(buf)?;
+            },
+            Attribute::LocalVariableTypeTable(_, entries) => {
+                entries.serialize(buf)?;
+            },
+            Attribute::NestHost(_, host_class_index) => {
+                host_class_index.serialize(buf)?;
+            },
+            Attribute::NestMembers(_, classes) => {
+                serialize_list(classes, buf, "nest member")?;
+            },
+            Attribute::RuntimeVisibleAnnotations(_, annotations) |
+            Attribute::RuntimeInvisibleAnnotations(_, annotations) => {
+                annotations.serialize(buf)?;
+            },
+            Attribute::RuntimeVisibleParameterAnnotations(_, parameter_annotations) |
+            Attribute::RuntimeInvisibleParameterAnnotations(_, parameter_annotations) => {
+                serialize_short_list(parameter_annotations, buf, "parameter")?;
+            },
+            Attribute::RuntimeVisibleTypeAnnotations(_, annotations) |
+            Attribute::RuntimeInvisibleTypeAnnotations(_, annotations) => {
+                annotations.serialize(buf)?;
+            },
+            Attribute::Signature(_, signature_index) => {
+                signature_index.serialize(buf)?;
+            },
+            Attribute::SourceDebugExtension(_, debug_extension) => {
+                // the whole body, with no length of its own
+                buf.extend_from_slice(&encode_modified_utf8(&debug_extension));
+            },
+            Attribute::SourceFile(_, sourcefile_index) => {
+                sourcefile_index.serialize(buf)?;
+            },
+            Attribute::StackMapTable(_, entries) => {
+                entries.serialize(buf)?;
+            },
+            Attribute::Synthetic(_) => {},
+            Attribute::Custom { value, .. } => {
+                buf.extend_from_slice(&value.encode());
+            },
+            Attribute::Unknown { bytes, .. } => {
+                buf.extend_from_slice(&bytes);
+            },
         }
+
+        let length = (buf.len() - length_offset - 4) as u32;
+        let mut length_bytes = vec![];
+        length.serialize(&mut length_bytes)?;
+        buf[length_offset..length_offset + 4].copy_from_slice(&length_bytes);
+        Ok(())
+    }
+
+    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> Result<Attribute, ClassfileError> {
+        let attribute_name_index = u16::deserialize(buf, classfile)?;
+        let attribute_name = lookup_utf8(buf, classfile, attribute_name_index).map_err(|e| e.within("attribute name"))?;
+
+        let attribute_body: Vec<u8> = Vec::deserialize(buf, classfile)?;
+        let buf2 = &mut buf.sub_deserializer(attribute_body);
+        deserialize_attribute_body(buf2, classfile, attribute_name, attribute_name_index).map_err(|e| e.within(&format!("attribute {}", attribute_name)))
     }
 }
 
-impl Serializable for Attribute {
-    fn serialize(self, buf: &mut Vec<u8>) {
-        // generate a temporary buffer holding the attribute "body"
-        let mut attribute_body = vec![];
-        let mut attribute_name_index;
-
-        {
-            let mut body_buf = &mut attribute_body;
-            match self {
-                Attribute::Code(name_index, max_stack, max_locals, code, exception_table, attributes) => {
-                    attribute_name_index = name_index;
-
-                    max_stack.serialize(body_buf);
-                    max_locals.serialize(body_buf);
-                    code.serialize(body_buf);
-                    exception_table.serialize(body_buf);
-                    attributes.serialize(body_buf);
-                },
-                Attribute::LineNumberTable(name_index, entries) => {
-                    attribute_name_index = name_index;
-                    entries.serialize(body_buf);
-                },
-                Attribute::SourceFile(name_index, sourcefile_index) => {
-                    attribute_name_index = name_index;
-                    sourcefile_index.serialize(body_buf);
-                },
-                Attribute::StackMapTable(name_index, entries) => {
-                    attribute_name_index = name_index;
-                    entries.serialize(body_buf);
+fn deserialize_attribute_body(buf2: &mut Deserializer, classfile: &Classfile, attribute_name: &str, attribute_name_index: u16) -> Result<Attribute, ClassfileError> {
+    let attribute = match attribute_name {
+        "Code" => {
+            let max_stack = u16::deserialize(buf2, classfile)?;
+            let max_locals = u16::deserialize(buf2, classfile)?;
+            let code = Vec::deserialize(buf2, classfile)?;
+            let exception_table = Vec::deserialize(buf2, classfile)?;
+            let attributes = Vec::deserialize(buf2, classfile)?;
+            Attribute::Code(attribute_name_index, max_stack, max_locals, code, exception_table, attributes)
+        },
+        "AnnotationDefault" => {
+            let default_value = ElementValue::deserialize(buf2, classfile)?;
+            Attribute::AnnotationDefault(attribute_name_index, default_value)
+        },
+        "BootstrapMethods" => {
+            let bootstrap_methods = Vec::deserialize(buf2, classfile)?;
+            Attribute::BootstrapMethods(attribute_name_index, bootstrap_methods)
+        },
+        "ConstantValue" => {
+            let constantvalue_index = u16::deserialize(buf2, classfile)?;
+            Attribute::ConstantValue(attribute_name_index, constantvalue_index)
+        },
+        "Deprecated" => {
+            Attribute::Deprecated(attribute_name_index)
+        },
+        "EnclosingMethod" => {
+            let class_index = u16::deserialize(buf2, classfile)?;
+            let method_index = u16::deserialize(buf2, classfile)?;
+            Attribute::EnclosingMethod(attribute_name_index, class_index, method_index)
+        },
+        "Exceptions" => {
+            let exception_index_table = deserialize_list(buf2, classfile, "exception")?;
+            Attribute::Exceptions(attribute_name_index, exception_index_table)
+        },
+        "InnerClasses" => {
+            let classes = Vec::deserialize(buf2, classfile)?;
+            Attribute::InnerClasses(attribute_name_index, classes)
+        },
+        "LineNumberTable" => {
+            let entries = Vec::deserialize(buf2, classfile)?;
+            Attribute::LineNumberTable(attribute_name_index, entries)
+        },
+        "LocalVariableTable" => {
+            let entries = Vec::deserialize(buf2, classfile)?;
+            Attribute::LocalVariableTable(attribute_name_index, entries)
+        },
+        "LocalVariableTypeTable" => {
+            let entries = Vec::deserialize(buf2, classfile)?;
+            Attribute::LocalVariableTypeTable(attribute_name_index, entries)
+        },
+        "NestHost" => {
+            let host_class_index = u16::deserialize(buf2, classfile)?;
+            Attribute::NestHost(attribute_name_index, host_class_index)
+        },
+        "NestMembers" => {
+            let classes = deserialize_list(buf2, classfile, "nest member")?;
+            Attribute::NestMembers(attribute_name_index, classes)
+        },
+        "RuntimeVisibleAnnotations" => {
+            let annotations = Vec::deserialize(buf2, classfile)?;
+            Attribute::RuntimeVisibleAnnotations(attribute_name_index, annotations)
+        },
+        "RuntimeInvisibleAnnotations" => {
+            let annotations = Vec::deserialize(buf2, classfile)?;
+            Attribute::RuntimeInvisibleAnnotations(attribute_name_index, annotations)
+        },
+        "RuntimeVisibleParameterAnnotations" => {
+            let parameter_annotations = deserialize_short_list(buf2, classfile, "parameter")?;
+            Attribute::RuntimeVisibleParameterAnnotations(attribute_name_index, parameter_annotations)
+        },
+        "RuntimeInvisibleParameterAnnotations" => {
+            let parameter_annotations = deserialize_short_list(buf2, classfile, "parameter")?;
+            Attribute::RuntimeInvisibleParameterAnnotations(attribute_name_index, parameter_annotations)
+        },
+        "RuntimeVisibleTypeAnnotations" => {
+            let annotations = Vec::deserialize(buf2, classfile)?;
+            Attribute::RuntimeVisibleTypeAnnotations(attribute_name_index, annotations)
+        },
+        "RuntimeInvisibleTypeAnnotations" => {
+            let annotations = Vec::deserialize(buf2, classfile)?;
+            Attribute::RuntimeInvisibleTypeAnnotations(attribute_name_index, annotations)
+        },
+        "Signature" => {
+            let signature_index = u16::deserialize(buf2, classfile)?;
+            Attribute::Signature(attribute_name_index, signature_index)
+        },
+        "SourceDebugExtension" => {
+            let bytes = buf2.take_bytes(buf2.remaining())?;
+            let debug_extension = decode_modified_utf8(&bytes).ok_or_else(|| ClassfileError::new(buf2.base_offset, ClassfileErrorCause::InvalidModifiedUtf8))?;
+            Attribute::SourceDebugExtension(attribute_name_index, debug_extension)
+        },
+        "SourceFile" => {
+            let sourcefile_index = u16::deserialize(buf2, classfile)?;
+            Attribute::SourceFile(attribute_name_index, sourcefile_index)
+        },
+        "StackMapTable" => {
+            let entries = Vec::deserialize(buf2, classfile)?;
+            Attribute::StackMapTable(attribute_name_index, entries)
+        },
+        "Synthetic" => {
+            Attribute::Synthetic(attribute_name_index)
+        },
+        _ => {
+            let bytes = buf2.take_bytes(buf2.remaining())?;
+            match buf2.decoders.get(attribute_name) {
+                Some(decoder) => {
+                    let value = decoder(&bytes, &classfile.constant_pool).map_err(|message| ClassfileError::new(buf2.base_offset, ClassfileErrorCause::AttributeDecoderFailed(message)))?;
+                    Attribute::Custom { name_index: attribute_name_index, value }
                 },
+                None => Attribute::Unknown { name_index: attribute_name_index, bytes },
             }
-        }
+        },
+    };
+    buf2.finish()?;
+    Ok(attribute)
+}
+
+// attribute names (and other strings) are referenced by their index in the constant pool
+fn lookup_utf8<'a>(buf: &Deserializer, classfile: &'a Classfile, index: u16) -> Result<&'a str, ClassfileError> {
+    classfile.constant_pool.get_utf8(index).ok_or_else(|| ClassfileError::new(buf.offset() - 2, ClassfileErrorCause::InvalidConstantIndex(index)))
+}
+
+impl Serializable for ExceptionTableEntry {
+    fn serialize(self, buf: &mut Vec<u8>) -> Result<(), ClassfileError> {
+        self.start_pc.serialize(buf)?;
+        self.end_pc.serialize(buf)?;
+        self.handler_pc.serialize(buf)?;
+        self.catch_type.serialize(buf)?;
+        Ok(())
+    }
+
+    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> Result<ExceptionTableEntry, ClassfileError> {
+        Ok(ExceptionTableEntry {
+            start_pc: u16::deserialize(buf, classfile)?,
+            end_pc: u16::deserialize(buf, classfile)?,
+            handler_pc: u16::deserialize(buf, classfile)?,
+            catch_type: u16::deserialize(buf, classfile)?,
+        })
+    }
+}
 
-        // append the attribute body to the real buffer
-        attribute_name_index.serialize(buf);
-        attribute_body.serialize(buf);
+impl Serializable for BootstrapMethod {
+    fn serialize(self, buf: &mut Vec<u8>) -> Result<(), ClassfileError> {
+        self.bootstrap_method_ref.serialize(buf)?;
+        serialize_list(self.bootstrap_arguments, buf, "bootstrap argument")?;
+        Ok(())
     }
 
-    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> Attribute {
-        let attribute_name_index = u16::deserialize(buf, classfile);
-        let attribute_name = classfile.lookup_string(attribute_name_index);
+    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> Result<BootstrapMethod, ClassfileError> {
+        Ok(BootstrapMethod {
+            bootstrap_method_ref: u16::deserialize(buf, classfile)?,
+            bootstrap_arguments: deserialize_list(buf, classfile, "bootstrap argument")?,
+        })
+    }
+}
 
-        let attribute_body: Vec<u8> = Vec::deserialize(buf, classfile);
-        let mut buf2 = &mut Deserializer::new(Box::new(attribute_body.into_iter()));
+impl Serializable for InnerClass {
+    fn serialize(self, buf: &mut Vec<u8>) -> Result<(), ClassfileError> {
+        self.inner_class_info_index.serialize(buf)?;
+        self.outer_class_info_index.serialize(buf)?;
+        self.inner_name_index.serialize(buf)?;
+        self.inner_class_access_flags.serialize(buf)?;
+        Ok(())
+    }
 
-        match attribute_name {
-            "Code" => {
-                let max_stack = u16::deserialize(buf2, classfile);
-                let max_locals = u16::deserialize(buf2, classfile);
-                let code = Vec::deserialize(buf2, classfile);
-                let exception_table = Vec::deserialize(buf2, classfile);
-                let attributes = Vec::deserialize(buf2, classfile);
-                Attribute::Code(attribute_name_index, max_stack, max_locals, code, exception_table, attributes)
+    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> Result<InnerClass, ClassfileError> {
+        Ok(InnerClass {
+            inner_class_info_index: u16::deserialize(buf, classfile)?,
+            outer_class_info_index: u16::deserialize(buf, classfile)?,
+            inner_name_index: u16::deserialize(buf, classfile)?,
+            inner_class_access_flags: u16::deserialize(buf, classfile)?,
+        })
+    }
+}
+
+impl Serializable for LineNumberTableEntry {
+    fn serialize(self, buf: &mut Vec<u8>) -> Result<(), ClassfileError> {
+        self.start_pc.serialize(buf)?;
+        self.line_number.serialize(buf)?;
+        Ok(())
+    }
+
+    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> Result<LineNumberTableEntry, ClassfileError> {
+        Ok(LineNumberTableEntry {
+            start_pc: u16::deserialize(buf, classfile)?,
+            line_number: u16::deserialize(buf, classfile)?,
+        })
+    }
+}
+
+impl Serializable for LocalVariableTableEntry {
+    fn serialize(self, buf: &mut Vec<u8>) -> Result<(), ClassfileError> {
+        self.start_pc.serialize(buf)?;
+        self.length.serialize(buf)?;
+        self.name_index.serialize(buf)?;
+        self.descriptor_index.serialize(buf)?;
+        self.index.serialize(buf)?;
+        Ok(())
+    }
+
+    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> Result<LocalVariableTableEntry, ClassfileError> {
+        Ok(LocalVariableTableEntry {
+            start_pc: u16::deserialize(buf, classfile)?,
+            length: u16::deserialize(buf, classfile)?,
+            name_index: u16::deserialize(buf, classfile)?,
+            descriptor_index: u16::deserialize(buf, classfile)?,
+            index: u16::deserialize(buf, classfile)?,
+        })
+    }
+}
+
+impl Serializable for LocalVariableTypeTableEntry {
+    fn serialize(self, buf: &mut Vec<u8>) -> Result<(), ClassfileError> {
+        self.start_pc.serialize(buf)?;
+        self.length.serialize(buf)?;
+        self.name_index.serialize(buf)?;
+        self.signature_index.serialize(buf)?;
+        self.index.serialize(buf)?;
+        Ok(())
+    }
+
+    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> Result<LocalVariableTypeTableEntry, ClassfileError> {
+        Ok(LocalVariableTypeTableEntry {
+            start_pc: u16::deserialize(buf, classfile)?,
+            length: u16::deserialize(buf, classfile)?,
+            name_index: u16::deserialize(buf, classfile)?,
+            signature_index: u16::deserialize(buf, classfile)?,
+            index: u16::deserialize(buf, classfile)?,
+        })
+    }
+}
+
+impl Serializable for Annotation {
+    fn serialize(self, buf: &mut Vec<u8>) -> Result<(), ClassfileError> {
+        self.type_index.serialize(buf)?;
+        self.element_value_pairs.serialize(buf)?;
+        Ok(())
+    }
+
+    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> Result<Annotation, ClassfileError> {
+        Ok(Annotation {
+            type_index: u16::deserialize(buf, classfile)?,
+            element_value_pairs: Vec::deserialize(buf, classfile)?,
+        })
+    }
+}
+
+impl Serializable for ElementValuePair {
+    fn serialize(self, buf: &mut Vec<u8>) -> Result<(), ClassfileError> {
+        self.element_name_index.serialize(buf)?;
+        self.value.serialize(buf)?;
+        Ok(())
+    }
+
+    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> Result<ElementValuePair, ClassfileError> {
+        Ok(ElementValuePair {
+            element_name_index: u16::deserialize(buf, classfile)?,
+            value: ElementValue::deserialize(buf, classfile)?,
+        })
+    }
+}
+
+impl Serializable for ElementValue {
+    fn serialize(self, buf: &mut Vec<u8>) -> Result<(), ClassfileError> {
+        match self {
+            ElementValue::Byte(const_value_index) => {
+                b'B'.serialize(buf)?;
+                const_value_index.serialize(buf)?;
             },
-            "LineNumberTable" => {
-                let entries = Vec::deserialize(buf2, classfile);
-                Attribute::LineNumberTable(attribute_name_index, entries)
+            ElementValue::Char(const_value_index) => {
+                b'C'.serialize(buf)?;
+                const_value_index.serialize(buf)?;
             },
-            "SourceFile" => {
-                let sourcefile_index = u16::deserialize(buf2, classfile);
-                Attribute::SourceFile(attribute_name_index, sourcefile_index)
+            ElementValue::Double(const_value_index) => {
+                b'D'.serialize(buf)?;
+                const_value_index.serialize(buf)?;
             },
-            "StackMapTable" => {
-                let entries = Vec::deserialize(buf2, classfile);
-                Attribute::StackMapTable(attribute_name_index, entries)
+            ElementValue::Float(const_value_index) => {
+                b'F'.serialize(buf)?;
+                const_value_index.serialize(buf)?;
             },
-            _ => panic!("TODO implement Attribute::deserialize for attribute type: {:?}", attribute_name)
+            ElementValue::Int(const_value_index) => {
+                b'I'.serialize(buf)?;
+                const_value_index.serialize(buf)?;
+            },
+            ElementValue::Long(const_value_index) => {
+                b'J'.serialize(buf)?;
+                const_value_index.serialize(buf)?;
+            },
+            ElementValue::Short(const_value_index) => {
+                b'S'.serialize(buf)?;
+                const_value_index.serialize(buf)?;
+            },
+            ElementValue::Boolean(const_value_index) => {
+                b'Z'.serialize(buf)?;
+                const_value_index.serialize(buf)?;
+            },
+            ElementValue::String(const_value_index) => {
+                b's'.serialize(buf)?;
+                const_value_index.serialize(buf)?;
+            },
+            ElementValue::Enum(type_name_index, const_name_index) => {
+                b'e'.serialize(buf)?;
+                type_name_index.serialize(buf)?;
+                const_name_index.serialize(buf)?;
+            },
+            ElementValue::Class(class_info_index) => {
+                b'c'.serialize(buf)?;
+                class_info_index.serialize(buf)?;
+            },
+            ElementValue::Annotation(annotation) => {
+                b'@'.serialize(buf)?;
+                annotation.serialize(buf)?;
+            },
+            ElementValue::Array(values) => {
+                b'['.serialize(buf)?;
+                values.serialize(buf)?;
+            },
+        }
+        Ok(())
+    }
+
+    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> Result<ElementValue, ClassfileError> {
+        let tag = u8::deserialize(buf, classfile)?;
+        Ok(match tag {
+            b'B' => ElementValue::Byte(u16::deserialize(buf, classfile)?),
+            b'C' => ElementValue::Char(u16::deserialize(buf, classfile)?),
+            b'D' => ElementValue::Double(u16::deserialize(buf, classfile)?),
+            b'F' => ElementValue::Float(u16::deserialize(buf, classfile)?),
+            b'I' => ElementValue::Int(u16::deserialize(buf, classfile)?),
+            b'J' => ElementValue::Long(u16::deserialize(buf, classfile)?),
+            b'S' => ElementValue::Short(u16::deserialize(buf, classfile)?),
+            b'Z' => ElementValue::Boolean(u16::deserialize(buf, classfile)?),
+            b's' => ElementValue::String(u16::deserialize(buf, classfile)?),
+            b'e' => {
+                let type_name_index = u16::deserialize(buf, classfile)?;
+                let const_name_index = u16::deserialize(buf, classfile)?;
+                ElementValue::Enum(type_name_index, const_name_index)
+            },
+            b'c' => ElementValue::Class(u16::deserialize(buf, classfile)?),
+            b'@' => ElementValue::Annotation(Annotation::deserialize(buf, classfile)?),
+            b'[' => ElementValue::Array(Vec::deserialize(buf, classfile)?),
+            _ => return Err(ClassfileError::new(buf.offset() - 1, ClassfileErrorCause::InvalidElementValueTag(tag)))
+        })
+    }
+}
 
+impl Serializable for TypeAnnotation {
+    fn serialize(self, buf: &mut Vec<u8>) -> Result<(), ClassfileError> {
+        self.target_type.serialize(buf)?;
+        match self.target_info {
+            TargetInfo::TypeParameter(type_parameter_index) => {
+                type_parameter_index.serialize(buf)?;
+            },
+            TargetInfo::Supertype(supertype_index) => {
+                supertype_index.serialize(buf)?;
+            },
+            TargetInfo::TypeParameterBound(type_parameter_index, bound_index) => {
+                type_parameter_index.serialize(buf)?;
+                bound_index.serialize(buf)?;
+            },
+            TargetInfo::Empty => {},
+            TargetInfo::FormalParameter(formal_parameter_index) => {
+                formal_parameter_index.serialize(buf)?;
+            },
+            TargetInfo::Throws(throws_type_index) => {
+                throws_type_index.serialize(buf)?;
+            },
+            TargetInfo::LocalVariable(table) => {
+                table.serialize(buf)?;
+            },
+            TargetInfo::Catch(exception_table_index) => {
+                exception_table_index.serialize(buf)?;
+            },
+            TargetInfo::Offset(offset) => {
+                offset.serialize(buf)?;
+            },
+            TargetInfo::TypeArgument(offset, type_argument_index) => {
+                offset.serialize(buf)?;
+                type_argument_index.serialize(buf)?;
+            },
         }
+        serialize_short_list(self.type_path, buf, "type path entry")?;
+        self.annotation.serialize(buf)?;
+        Ok(())
+    }
+
+    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> Result<TypeAnnotation, ClassfileError> {
+        let target_type = u8::deserialize(buf, classfile)?;
+        let target_info = match target_type {
+            0x00 | 0x01 => TargetInfo::TypeParameter(u8::deserialize(buf, classfile)?),
+            0x10 => TargetInfo::Supertype(u16::deserialize(buf, classfile)?),
+            0x11 | 0x12 => {
+                let type_parameter_index = u8::deserialize(buf, classfile)?;
+                let bound_index = u8::deserialize(buf, classfile)?;
+                TargetInfo::TypeParameterBound(type_parameter_index, bound_index)
+            },
+            0x13..=0x15 => TargetInfo::Empty,
+            0x16 => TargetInfo::FormalParameter(u8::deserialize(buf, classfile)?),
+            0x17 => TargetInfo::Throws(u16::deserialize(buf, classfile)?),
+            0x40 | 0x41 => TargetInfo::LocalVariable(Vec::deserialize(buf, classfile)?),
+            0x42 => TargetInfo::Catch(u16::deserialize(buf, classfile)?),
+            0x43..=0x46 => TargetInfo::Offset(u16::deserialize(buf, classfile)?),
+            0x47..=0x4B => {
+                let offset = u16::deserialize(buf, classfile)?;
+                let type_argument_index = u8::deserialize(buf, classfile)?;
+                TargetInfo::TypeArgument(offset, type_argument_index)
+            },
+            _ => return Err(ClassfileError::new(buf.offset() - 1, ClassfileErrorCause::InvalidTargetType(target_type)))
+        };
+        Ok(TypeAnnotation {
+            target_type,
+            target_info,
+            type_path: deserialize_short_list(buf, classfile, "type path entry")?,
+            annotation: Annotation::deserialize(buf, classfile)?,
+        })
     }
 }
 
-impl Serializable for ExceptionTableEntry {
-    fn serialize(self, _buf: &mut Vec<u8>) {
-        panic!("TODO implement ExceptionTableEntry::serialize")
+impl Serializable for LocalVariableTarget {
+    fn serialize(self, buf: &mut Vec<u8>) -> Result<(), ClassfileError> {
+        self.start_pc.serialize(buf)?;
+        self.length.serialize(buf)?;
+        self.index.serialize(buf)?;
+        Ok(())
     }
 
-    fn deserialize(_buf: &mut Deserializer, _classfile: &Classfile) -> ExceptionTableEntry {
-        panic!("TODO implement ExceptionTableEntry::deserialize")
+    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> Result<LocalVariableTarget, ClassfileError> {
+        Ok(LocalVariableTarget {
+            start_pc: u16::deserialize(buf, classfile)?,
+            length: u16::deserialize(buf, classfile)?,
+            index: u16::deserialize(buf, classfile)?,
+        })
     }
 }
 
-impl Serializable for LineNumberTableEntry {
-    fn serialize(self, buf: &mut Vec<u8>) {
-        self.start_pc.serialize(buf);
-        self.line_number.serialize(buf);
+impl Serializable for TypePathEntry {
+    fn serialize(self, buf: &mut Vec<u8>) -> Result<(), ClassfileError> {
+        self.type_path_kind.serialize(buf)?;
+        self.type_argument_index.serialize(buf)?;
+        Ok(())
     }
 
-    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> LineNumberTableEntry {
-        LineNumberTableEntry {
-            start_pc: u16::deserialize(buf, classfile),
-            line_number: u16::deserialize(buf, classfile),
-        }
+    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> Result<TypePathEntry, ClassfileError> {
+        Ok(TypePathEntry {
+            type_path_kind: u8::deserialize(buf, classfile)?,
+            type_argument_index: u8::deserialize(buf, classfile)?,
+        })
     }
 }
 
 impl Serializable for StackMapFrame {
-    fn serialize(self, buf: &mut Vec<u8>) {
+    fn serialize(self, buf: &mut Vec<u8>) -> Result<(), ClassfileError> {
         match self {
             StackMapFrame::SameFrame(offset_delta) => {
                 let frame_type = offset_delta;
-                frame_type.serialize(buf);
+                frame_type.serialize(buf)?;
             },
             StackMapFrame::SameLocals1StackItemFrame(offset_delta, verification_type) => {
                 let frame_type = offset_delta + 64;
-                frame_type.serialize(buf);
-                verification_type.serialize(buf);
+                frame_type.serialize(buf)?;
+                verification_type.serialize(buf)?;
             },
             StackMapFrame::SameLocals1StackItemFrameExtended(offset_delta, verification_type) => {
                 let frame_type: u8 = 247;
-                frame_type.serialize(buf);
-                offset_delta.serialize(buf);
-                verification_type.serialize(buf);
+                frame_type.serialize(buf)?;
+                offset_delta.serialize(buf)?;
+                verification_type.serialize(buf)?;
             },
             StackMapFrame::ChopFrame(k, offset_delta) => {
                 let frame_type = 251 - k;
-                frame_type.serialize(buf);
-                offset_delta.serialize(buf);
+                frame_type.serialize(buf)?;
+                offset_delta.serialize(buf)?;
             },
             StackMapFrame::SameFrameExtended(offset_delta) => {
                 let frame_type: u8 = 251;
-                frame_type.serialize(buf);
-                offset_delta.serialize(buf);
+                frame_type.serialize(buf)?;
+                offset_delta.serialize(buf)?;
             },
             StackMapFrame::AppendFrame(k, offset_delta, locals) => {
                 let frame_type = 251 + k;
-                frame_type.serialize(buf);
-                offset_delta.serialize(buf);
+                frame_type.serialize(buf)?;
+                offset_delta.serialize(buf)?;
                 for local in locals {
-                    local.serialize(buf);
+                    local.serialize(buf)?;
                 }
             },
             StackMapFrame::FullFrame(offset_delta, locals, stack_items) => {
                 let frame_type: u8 = 255;
-                frame_type.serialize(buf);
-                offset_delta.serialize(buf);
-                locals.serialize(buf);
-                stack_items.serialize(buf);
+                frame_type.serialize(buf)?;
+                offset_delta.serialize(buf)?;
+                locals.serialize(buf)?;
+                stack_items.serialize(buf)?;
             },
         }
+        Ok(())
     }
 
-    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> StackMapFrame {
-        let frame_type = u8::deserialize(buf, classfile);
-        match frame_type {
-            0...63 => {
+    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> Result<StackMapFrame, ClassfileError> {
+        let frame_type = u8::deserialize(buf, classfile)?;
+        Ok(match frame_type {
+            0..=63 => {
                 let offset_delta = frame_type;
                 StackMapFrame::SameFrame(offset_delta)
             },
-            64...127 => {
+            64..=127 => {
                 let offset_delta = frame_type - 64;
-                let verification_type = VerificationType::deserialize(buf, classfile);
+                let verification_type = VerificationType::deserialize(buf, classfile)?;
                 StackMapFrame::SameLocals1StackItemFrame(offset_delta, verification_type)
             },
             247 => {
-                let offset_delta = u16::deserialize(buf, classfile);
-                let verification_type = VerificationType::deserialize(buf, classfile);
+                let offset_delta = u16::deserialize(buf, classfile)?;
+                let verification_type = VerificationType::deserialize(buf, classfile)?;
                 StackMapFrame::SameLocals1StackItemFrameExtended(offset_delta, verification_type)
             },
-            248...250 => {
+            248..=250 => {
                 let k = 251 - frame_type;
-                let offset_delta = u16::deserialize(buf, classfile);
+                let offset_delta = u16::deserialize(buf, classfile)?;
                 StackMapFrame::ChopFrame(k, offset_delta)
             },
             251 => {
-                let offset_delta = u16::deserialize(buf, classfile);
+                let offset_delta = u16::deserialize(buf, classfile)?;
                 StackMapFrame::SameFrameExtended(offset_delta)
             },
-            252...254 => {
+            252..=254 => {
                 let k = frame_type - 251;
-                let offset_delta = u16::deserialize(buf, classfile);
-                let locals = (0..k).into_iter().map(|_| VerificationType::deserialize(buf, classfile)).collect();
+                let offset_delta = u16::deserialize(buf, classfile)?;
+                let locals = (0..k).map(|_| VerificationType::deserialize(buf, classfile)).collect::<Result<_, _>>()?;
                 StackMapFrame::AppendFrame(k, offset_delta, locals)
             },
             255 => {
-                let offset_delta = u16::deserialize(buf, classfile);
-                let locals = Vec::deserialize(buf, classfile);
-                let stack_items = Vec::deserialize(buf, classfile);
+                let offset_delta = u16::deserialize(buf, classfile)?;
+                let locals = Vec::deserialize(buf, classfile)?;
+                let stack_items = Vec::deserialize(buf, classfile)?;
                 StackMapFrame::FullFrame(offset_delta, locals, stack_items)
             },
-            _ => panic!("Invalid StackMapFrame type: {}", frame_type)
-        }
+            _ => return Err(ClassfileError::new(buf.offset() - 1, ClassfileErrorCause::InvalidStackMapFrameType(frame_type)))
+        })
     }
 }
 
 impl Serializable for VerificationType {
-    fn serialize(self, buf: &mut Vec<u8>) {
+    fn serialize(self, buf: &mut Vec<u8>) -> Result<(), ClassfileError> {
         match self {
             VerificationType::Top => {
-                (0 as u8).serialize(buf);
+                0u8.serialize(buf)?;
             },
             VerificationType::Integer => {
-                (1 as u8).serialize(buf);
+                1u8.serialize(buf)?;
             },
             VerificationType::Float => {
-                (2 as u8).serialize(buf);
-            },
-            VerificationType::Long => {
-                (3 as u8).serialize(buf);
+                2u8.serialize(buf)?;
             },
             VerificationType::Double => {
-                (4 as u8).serialize(buf);
+                3u8.serialize(buf)?;
+            },
+            VerificationType::Long => {
+                4u8.serialize(buf)?;
             },
             VerificationType::Null => {
-                (5 as u8).serialize(buf);
+                5u8.serialize(buf)?;
             },
             VerificationType::UninitializedThis => {
-                (6 as u8).serialize(buf);
+                6u8.serialize(buf)?;
             },
             VerificationType::Object(cpool_index) => {
-                (7 as u8).serialize(buf);
-                cpool_index.serialize(buf);
+                7u8.serialize(buf)?;
+                cpool_index.serialize(buf)?;
             },
             VerificationType::Uninitialized(offset) => {
-                (8 as u8).serialize(buf);
-                offset.serialize(buf);
+                8u8.serialize(buf)?;
+                offset.serialize(buf)?;
             },
         }
+        Ok(())
     }
 
-    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> VerificationType {
-        let verification_type = u8::deserialize(buf, classfile);
-        match verification_type {
+    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> Result<VerificationType, ClassfileError> {
+        let verification_type = u8::deserialize(buf, classfile)?;
+        Ok(match verification_type {
             0 => {
                 VerificationType::Top
             },
@@ -603,10 +1367,10 @@ impl Serializable for VerificationType {
                 VerificationType::Float
             },
             3 => {
-                VerificationType::Long
+                VerificationType::Double
             },
             4 => {
-                VerificationType::Double
+                VerificationType::Long
             },
             5 => {
                 VerificationType::Null
@@ -615,148 +1379,709 @@ impl Serializable for VerificationType {
                 VerificationType::UninitializedThis
             },
             7 => {
-                let cpool_index = u16::deserialize(buf, classfile);
+                let cpool_index = u16::deserialize(buf, classfile)?;
                 VerificationType::Object(cpool_index)
             },
             8 => {
-                let offset = u16::deserialize(buf, classfile);
+                let offset = u16::deserialize(buf, classfile)?;
                 VerificationType::Uninitialized(offset)
             },
-            _ => panic!("Invalid VerificationType: {}", verification_type)
-        }
+            _ => return Err(ClassfileError::new(buf.offset() - 1, ClassfileErrorCause::InvalidVerificationType(verification_type)))
+        })
     }
 }
 
 impl Serializable for Instruction {
-    fn serialize(self, buf: &mut Vec<u8>) {
+    // NOTE: buf must hold only the code of the current method, since switch padding is relative to the start of the code
+    fn serialize(self, buf: &mut Vec<u8>) -> Result<(), ClassfileError> {
         match self {
+            Instruction::Nop => {
+                0x0u8.serialize(buf)?;
+            },
+            Instruction::AconstNull => {
+                0x1u8.serialize(buf)?;
+            },
             Instruction::IconstM1 => {
-                (0x2 as u8).serialize(buf);
+                0x2u8.serialize(buf)?;
             },
             Instruction::Iconst0 => {
-                (0x3 as u8).serialize(buf);
+                0x3u8.serialize(buf)?;
             },
             Instruction::Iconst1 => {
-                (0x4 as u8).serialize(buf);
+                0x4u8.serialize(buf)?;
             },
             Instruction::Iconst2 => {
-                (0x5 as u8).serialize(buf);
+                0x5u8.serialize(buf)?;
             },
             Instruction::Iconst3 => {
-                (0x6 as u8).serialize(buf);
+                0x6u8.serialize(buf)?;
             },
             Instruction::Iconst4 => {
-                (0x7 as u8).serialize(buf);
+                0x7u8.serialize(buf)?;
             },
             Instruction::Iconst5 => {
-                (0x8 as u8).serialize(buf);
+                0x8u8.serialize(buf)?;
+            },
+            Instruction::Lconst0 => {
+                0x9u8.serialize(buf)?;
+            },
+            Instruction::Lconst1 => {
+                0xAu8.serialize(buf)?;
+            },
+            Instruction::Fconst0 => {
+                0xBu8.serialize(buf)?;
+            },
+            Instruction::Fconst1 => {
+                0xCu8.serialize(buf)?;
+            },
+            Instruction::Fconst2 => {
+                0xDu8.serialize(buf)?;
+            },
+            Instruction::Dconst0 => {
+                0xEu8.serialize(buf)?;
+            },
+            Instruction::Dconst1 => {
+                0xFu8.serialize(buf)?;
             },
             Instruction::Bipush(val) => {
-                (0x10 as u8).serialize(buf);
-                val.serialize(buf);
+                0x10u8.serialize(buf)?;
+                val.serialize(buf)?;
+            },
+            Instruction::Sipush(val) => {
+                0x11u8.serialize(buf)?;
+                val.serialize(buf)?;
             },
             Instruction::LoadConstant(index) => {
-                (0x12 as u8).serialize(buf);
-                index.serialize(buf);
+                0x12u8.serialize(buf)?;
+                index.serialize(buf)?;
+            },
+            Instruction::LoadConstantWide(index) => {
+                0x13u8.serialize(buf)?;
+                index.serialize(buf)?;
+            },
+            Instruction::LoadConstant2Wide(index) => {
+                0x14u8.serialize(buf)?;
+                index.serialize(buf)?;
+            },
+            Instruction::Iload(index) => {
+                0x15u8.serialize(buf)?;
+                index.serialize(buf)?;
+            },
+            Instruction::Lload(index) => {
+                0x16u8.serialize(buf)?;
+                index.serialize(buf)?;
+            },
+            Instruction::Fload(index) => {
+                0x17u8.serialize(buf)?;
+                index.serialize(buf)?;
+            },
+            Instruction::Dload(index) => {
+                0x18u8.serialize(buf)?;
+                index.serialize(buf)?;
+            },
+            Instruction::Aload(index) => {
+                0x19u8.serialize(buf)?;
+                index.serialize(buf)?;
+            },
+            Instruction::Iload0 => {
+                0x1Au8.serialize(buf)?;
+            },
+            Instruction::Iload1 => {
+                0x1Bu8.serialize(buf)?;
+            },
+            Instruction::Iload2 => {
+                0x1Cu8.serialize(buf)?;
+            },
+            Instruction::Iload3 => {
+                0x1Du8.serialize(buf)?;
+            },
+            Instruction::Lload0 => {
+                0x1Eu8.serialize(buf)?;
+            },
+            Instruction::Lload1 => {
+                0x1Fu8.serialize(buf)?;
+            },
+            Instruction::Lload2 => {
+                0x20u8.serialize(buf)?;
+            },
+            Instruction::Lload3 => {
+                0x21u8.serialize(buf)?;
+            },
+            Instruction::Fload0 => {
+                0x22u8.serialize(buf)?;
+            },
+            Instruction::Fload1 => {
+                0x23u8.serialize(buf)?;
+            },
+            Instruction::Fload2 => {
+                0x24u8.serialize(buf)?;
+            },
+            Instruction::Fload3 => {
+                0x25u8.serialize(buf)?;
+            },
+            Instruction::Dload0 => {
+                0x26u8.serialize(buf)?;
+            },
+            Instruction::Dload1 => {
+                0x27u8.serialize(buf)?;
+            },
+            Instruction::Dload2 => {
+                0x28u8.serialize(buf)?;
+            },
+            Instruction::Dload3 => {
+                0x29u8.serialize(buf)?;
             },
             Instruction::Aload0 => {
-                (0x2A as u8).serialize(buf);
+                0x2Au8.serialize(buf)?;
             },
             Instruction::Aload1 => {
-                (0x2B as u8).serialize(buf);
+                0x2Bu8.serialize(buf)?;
             },
             Instruction::Aload2 => {
-                (0x2C as u8).serialize(buf);
+                0x2Cu8.serialize(buf)?;
             },
             Instruction::Aload3 => {
-                (0x2D as u8).serialize(buf);
+                0x2Du8.serialize(buf)?;
+            },
+            Instruction::Iaload => {
+                0x2Eu8.serialize(buf)?;
+            },
+            Instruction::Laload => {
+                0x2Fu8.serialize(buf)?;
+            },
+            Instruction::Faload => {
+                0x30u8.serialize(buf)?;
+            },
+            Instruction::Daload => {
+                0x31u8.serialize(buf)?;
             },
             Instruction::Aaload => {
-                (0x32 as u8).serialize(buf);
+                0x32u8.serialize(buf)?;
+            },
+            Instruction::Baload => {
+                0x33u8.serialize(buf)?;
+            },
+            Instruction::Caload => {
+                0x34u8.serialize(buf)?;
+            },
+            Instruction::Saload => {
+                0x35u8.serialize(buf)?;
+            },
+            Instruction::Istore(index) => {
+                0x36u8.serialize(buf)?;
+                index.serialize(buf)?;
+            },
+            Instruction::Lstore(index) => {
+                0x37u8.serialize(buf)?;
+                index.serialize(buf)?;
+            },
+            Instruction::Fstore(index) => {
+                0x38u8.serialize(buf)?;
+                index.serialize(buf)?;
+            },
+            Instruction::Dstore(index) => {
+                0x39u8.serialize(buf)?;
+                index.serialize(buf)?;
+            },
+            Instruction::Astore(index) => {
+                0x3Au8.serialize(buf)?;
+                index.serialize(buf)?;
+            },
+            Instruction::Istore0 => {
+                0x3Bu8.serialize(buf)?;
             },
-            Instruction::IfEq(index) => {
-                (0x99 as u8).serialize(buf);
-                index.serialize(buf);
+            Instruction::Istore1 => {
+                0x3Cu8.serialize(buf)?;
             },
-            Instruction::IfNe(index) => {
-                (0x9A as u8).serialize(buf);
-                index.serialize(buf);
+            Instruction::Istore2 => {
+                0x3Du8.serialize(buf)?;
             },
-            Instruction::IfLt(index) => {
-                (0x9B as u8).serialize(buf);
-                index.serialize(buf);
+            Instruction::Istore3 => {
+                0x3Eu8.serialize(buf)?;
             },
-            Instruction::IfGe(index) => {
-                (0x9C as u8).serialize(buf);
-                index.serialize(buf);
+            Instruction::Lstore0 => {
+                0x3Fu8.serialize(buf)?;
             },
-            Instruction::IfGt(index) => {
-                (0x9D as u8).serialize(buf);
-                index.serialize(buf);
+            Instruction::Lstore1 => {
+                0x40u8.serialize(buf)?;
             },
-            Instruction::IfLe(index) => {
-                (0x9E as u8).serialize(buf);
-                index.serialize(buf);
+            Instruction::Lstore2 => {
+                0x41u8.serialize(buf)?;
             },
-            Instruction::IfIcmpEq(index) => {
-                (0x9F as u8).serialize(buf);
-                index.serialize(buf);
+            Instruction::Lstore3 => {
+                0x42u8.serialize(buf)?;
             },
-            Instruction::IfIcmpNe(index) => {
-                (0xA0 as u8).serialize(buf);
-                index.serialize(buf);
+            Instruction::Fstore0 => {
+                0x43u8.serialize(buf)?;
             },
-            Instruction::IfIcmpLt(index) => {
-                (0xA1 as u8).serialize(buf);
-                index.serialize(buf);
+            Instruction::Fstore1 => {
+                0x44u8.serialize(buf)?;
             },
-            Instruction::IfIcmpGe(index) => {
-                (0xA2 as u8).serialize(buf);
-                index.serialize(buf);
+            Instruction::Fstore2 => {
+                0x45u8.serialize(buf)?;
             },
-            Instruction::IfIcmpGt(index) => {
-                (0xA3 as u8).serialize(buf);
-                index.serialize(buf);
+            Instruction::Fstore3 => {
+                0x46u8.serialize(buf)?;
             },
-            Instruction::IfIcmpLe(index) => {
-                (0xA4 as u8).serialize(buf);
-                index.serialize(buf);
+            Instruction::Dstore0 => {
+                0x47u8.serialize(buf)?;
             },
-            Instruction::Goto(index) => {
-                (0xA7 as u8).serialize(buf);
-                index.serialize(buf);
+            Instruction::Dstore1 => {
+                0x48u8.serialize(buf)?;
+            },
+            Instruction::Dstore2 => {
+                0x49u8.serialize(buf)?;
+            },
+            Instruction::Dstore3 => {
+                0x4Au8.serialize(buf)?;
+            },
+            Instruction::Astore0 => {
+                0x4Bu8.serialize(buf)?;
+            },
+            Instruction::Astore1 => {
+                0x4Cu8.serialize(buf)?;
+            },
+            Instruction::Astore2 => {
+                0x4Du8.serialize(buf)?;
+            },
+            Instruction::Astore3 => {
+                0x4Eu8.serialize(buf)?;
+            },
+            Instruction::Iastore => {
+                0x4Fu8.serialize(buf)?;
+            },
+            Instruction::Lastore => {
+                0x50u8.serialize(buf)?;
+            },
+            Instruction::Fastore => {
+                0x51u8.serialize(buf)?;
+            },
+            Instruction::Dastore => {
+                0x52u8.serialize(buf)?;
+            },
+            Instruction::Aastore => {
+                0x53u8.serialize(buf)?;
+            },
+            Instruction::Bastore => {
+                0x54u8.serialize(buf)?;
+            },
+            Instruction::Castore => {
+                0x55u8.serialize(buf)?;
+            },
+            Instruction::Sastore => {
+                0x56u8.serialize(buf)?;
+            },
+            Instruction::Pop => {
+                0x57u8.serialize(buf)?;
+            },
+            Instruction::Pop2 => {
+                0x58u8.serialize(buf)?;
+            },
+            Instruction::Dup => {
+                0x59u8.serialize(buf)?;
+            },
+            Instruction::DupX1 => {
+                0x5Au8.serialize(buf)?;
+            },
+            Instruction::DupX2 => {
+                0x5Bu8.serialize(buf)?;
+            },
+            Instruction::Dup2 => {
+                0x5Cu8.serialize(buf)?;
+            },
+            Instruction::Dup2X1 => {
+                0x5Du8.serialize(buf)?;
+            },
+            Instruction::Dup2X2 => {
+                0x5Eu8.serialize(buf)?;
+            },
+            Instruction::Swap => {
+                0x5Fu8.serialize(buf)?;
             },
             Instruction::Iadd => {
-                (0x60 as u8).serialize(buf);
+                0x60u8.serialize(buf)?;
+            },
+            Instruction::Ladd => {
+                0x61u8.serialize(buf)?;
+            },
+            Instruction::Fadd => {
+                0x62u8.serialize(buf)?;
+            },
+            Instruction::Dadd => {
+                0x63u8.serialize(buf)?;
+            },
+            Instruction::Isub => {
+                0x64u8.serialize(buf)?;
+            },
+            Instruction::Lsub => {
+                0x65u8.serialize(buf)?;
+            },
+            Instruction::Fsub => {
+                0x66u8.serialize(buf)?;
+            },
+            Instruction::Dsub => {
+                0x67u8.serialize(buf)?;
+            },
+            Instruction::Imul => {
+                0x68u8.serialize(buf)?;
+            },
+            Instruction::Lmul => {
+                0x69u8.serialize(buf)?;
+            },
+            Instruction::Fmul => {
+                0x6Au8.serialize(buf)?;
+            },
+            Instruction::Dmul => {
+                0x6Bu8.serialize(buf)?;
+            },
+            Instruction::Idiv => {
+                0x6Cu8.serialize(buf)?;
+            },
+            Instruction::Ldiv => {
+                0x6Du8.serialize(buf)?;
+            },
+            Instruction::Fdiv => {
+                0x6Eu8.serialize(buf)?;
+            },
+            Instruction::Ddiv => {
+                0x6Fu8.serialize(buf)?;
+            },
+            Instruction::Irem => {
+                0x70u8.serialize(buf)?;
+            },
+            Instruction::Lrem => {
+                0x71u8.serialize(buf)?;
+            },
+            Instruction::Frem => {
+                0x72u8.serialize(buf)?;
+            },
+            Instruction::Drem => {
+                0x73u8.serialize(buf)?;
+            },
+            Instruction::Ineg => {
+                0x74u8.serialize(buf)?;
+            },
+            Instruction::Lneg => {
+                0x75u8.serialize(buf)?;
+            },
+            Instruction::Fneg => {
+                0x76u8.serialize(buf)?;
+            },
+            Instruction::Dneg => {
+                0x77u8.serialize(buf)?;
+            },
+            Instruction::Ishl => {
+                0x78u8.serialize(buf)?;
+            },
+            Instruction::Lshl => {
+                0x79u8.serialize(buf)?;
+            },
+            Instruction::Ishr => {
+                0x7Au8.serialize(buf)?;
+            },
+            Instruction::Lshr => {
+                0x7Bu8.serialize(buf)?;
+            },
+            Instruction::Iushr => {
+                0x7Cu8.serialize(buf)?;
+            },
+            Instruction::Lushr => {
+                0x7Du8.serialize(buf)?;
+            },
+            Instruction::Iand => {
+                0x7Eu8.serialize(buf)?;
+            },
+            Instruction::Land => {
+                0x7Fu8.serialize(buf)?;
+            },
+            Instruction::Ior => {
+                0x80u8.serialize(buf)?;
+            },
+            Instruction::Lor => {
+                0x81u8.serialize(buf)?;
+            },
+            Instruction::Ixor => {
+                0x82u8.serialize(buf)?;
+            },
+            Instruction::Lxor => {
+                0x83u8.serialize(buf)?;
+            },
+            Instruction::Iinc(index, val) => {
+                0x84u8.serialize(buf)?;
+                index.serialize(buf)?;
+                val.serialize(buf)?;
+            },
+            Instruction::I2l => {
+                0x85u8.serialize(buf)?;
+            },
+            Instruction::I2f => {
+                0x86u8.serialize(buf)?;
+            },
+            Instruction::I2d => {
+                0x87u8.serialize(buf)?;
+            },
+            Instruction::L2i => {
+                0x88u8.serialize(buf)?;
+            },
+            Instruction::L2f => {
+                0x89u8.serialize(buf)?;
+            },
+            Instruction::L2d => {
+                0x8Au8.serialize(buf)?;
+            },
+            Instruction::F2i => {
+                0x8Bu8.serialize(buf)?;
+            },
+            Instruction::F2l => {
+                0x8Cu8.serialize(buf)?;
+            },
+            Instruction::F2d => {
+                0x8Du8.serialize(buf)?;
+            },
+            Instruction::D2i => {
+                0x8Eu8.serialize(buf)?;
+            },
+            Instruction::D2l => {
+                0x8Fu8.serialize(buf)?;
+            },
+            Instruction::D2f => {
+                0x90u8.serialize(buf)?;
+            },
+            Instruction::I2b => {
+                0x91u8.serialize(buf)?;
+            },
+            Instruction::I2c => {
+                0x92u8.serialize(buf)?;
+            },
+            Instruction::I2s => {
+                0x93u8.serialize(buf)?;
+            },
+            Instruction::Lcmp => {
+                0x94u8.serialize(buf)?;
+            },
+            Instruction::Fcmpl => {
+                0x95u8.serialize(buf)?;
+            },
+            Instruction::Fcmpg => {
+                0x96u8.serialize(buf)?;
+            },
+            Instruction::Dcmpl => {
+                0x97u8.serialize(buf)?;
+            },
+            Instruction::Dcmpg => {
+                0x98u8.serialize(buf)?;
+            },
+            Instruction::IfEq(offset) => {
+                0x99u8.serialize(buf)?;
+                (offset as u16).serialize(buf)?;
+            },
+            Instruction::IfNe(offset) => {
+                0x9Au8.serialize(buf)?;
+                (offset as u16).serialize(buf)?;
+            },
+            Instruction::IfLt(offset) => {
+                0x9Bu8.serialize(buf)?;
+                (offset as u16).serialize(buf)?;
+            },
+            Instruction::IfGe(offset) => {
+                0x9Cu8.serialize(buf)?;
+                (offset as u16).serialize(buf)?;
+            },
+            Instruction::IfGt(offset) => {
+                0x9Du8.serialize(buf)?;
+                (offset as u16).serialize(buf)?;
+            },
+            Instruction::IfLe(offset) => {
+                0x9Eu8.serialize(buf)?;
+                (offset as u16).serialize(buf)?;
+            },
+            Instruction::IfIcmpEq(offset) => {
+                0x9Fu8.serialize(buf)?;
+                (offset as u16).serialize(buf)?;
+            },
+            Instruction::IfIcmpNe(offset) => {
+                0xA0u8.serialize(buf)?;
+                (offset as u16).serialize(buf)?;
+            },
+            Instruction::IfIcmpLt(offset) => {
+                0xA1u8.serialize(buf)?;
+                (offset as u16).serialize(buf)?;
+            },
+            Instruction::IfIcmpGe(offset) => {
+                0xA2u8.serialize(buf)?;
+                (offset as u16).serialize(buf)?;
+            },
+            Instruction::IfIcmpGt(offset) => {
+                0xA3u8.serialize(buf)?;
+                (offset as u16).serialize(buf)?;
+            },
+            Instruction::IfIcmpLe(offset) => {
+                0xA4u8.serialize(buf)?;
+                (offset as u16).serialize(buf)?;
+            },
+            Instruction::IfAcmpEq(offset) => {
+                0xA5u8.serialize(buf)?;
+                (offset as u16).serialize(buf)?;
+            },
+            Instruction::IfAcmpNe(offset) => {
+                0xA6u8.serialize(buf)?;
+                (offset as u16).serialize(buf)?;
+            },
+            Instruction::Goto(offset) => {
+                0xA7u8.serialize(buf)?;
+                (offset as u16).serialize(buf)?;
+            },
+            Instruction::Jsr(offset) => {
+                0xA8u8.serialize(buf)?;
+                (offset as u16).serialize(buf)?;
+            },
+            Instruction::Ret(index) => {
+                0xA9u8.serialize(buf)?;
+                index.serialize(buf)?;
+            },
+            Instruction::TableSwitch(default, low, offsets) => {
+                0xAAu8.serialize(buf)?;
+                serialize_switch_padding(buf)?;
+                (default as u32).serialize(buf)?;
+                (low as u32).serialize(buf)?;
+                ((low + offsets.len() as i32 - 1) as u32).serialize(buf)?;
+                for offset in offsets {
+                    (offset as u32).serialize(buf)?;
+                }
+            },
+            Instruction::LookupSwitch(default, pairs) => {
+                0xABu8.serialize(buf)?;
+                serialize_switch_padding(buf)?;
+                (default as u32).serialize(buf)?;
+                (pairs.len() as u32).serialize(buf)?;
+                for (key, offset) in pairs {
+                    (key as u32).serialize(buf)?;
+                    (offset as u32).serialize(buf)?;
+                }
+            },
+            Instruction::Ireturn => {
+                0xACu8.serialize(buf)?;
+            },
+            Instruction::Lreturn => {
+                0xADu8.serialize(buf)?;
+            },
+            Instruction::Freturn => {
+                0xAEu8.serialize(buf)?;
+            },
+            Instruction::Dreturn => {
+                0xAFu8.serialize(buf)?;
+            },
+            Instruction::Areturn => {
+                0xB0u8.serialize(buf)?;
             },
             Instruction::Return => {
-                (0xB1 as u8).serialize(buf);
+                0xB1u8.serialize(buf)?;
             },
             Instruction::GetStatic(index) => {
-                (0xB2 as u8).serialize(buf);
-                index.serialize(buf);
+                0xB2u8.serialize(buf)?;
+                index.serialize(buf)?;
+            },
+            Instruction::PutStatic(index) => {
+                0xB3u8.serialize(buf)?;
+                index.serialize(buf)?;
+            },
+            Instruction::GetField(index) => {
+                0xB4u8.serialize(buf)?;
+                index.serialize(buf)?;
+            },
+            Instruction::PutField(index) => {
+                0xB5u8.serialize(buf)?;
+                index.serialize(buf)?;
             },
             Instruction::InvokeVirtual(index) => {
-                (0xB6 as u8).serialize(buf);
-                index.serialize(buf);
+                0xB6u8.serialize(buf)?;
+                index.serialize(buf)?;
             },
             Instruction::InvokeSpecial(index) => {
-                (0xB7 as u8).serialize(buf);
-                index.serialize(buf);
+                0xB7u8.serialize(buf)?;
+                index.serialize(buf)?;
             },
             Instruction::InvokeStatic(index) => {
-                (0xB8 as u8).serialize(buf);
-                index.serialize(buf);
+                0xB8u8.serialize(buf)?;
+                index.serialize(buf)?;
+            },
+            Instruction::InvokeInterface(index, count) => {
+                0xB9u8.serialize(buf)?;
+                index.serialize(buf)?;
+                count.serialize(buf)?;
+                0u8.serialize(buf)?;
+            },
+            Instruction::InvokeDynamic(index) => {
+                0xBAu8.serialize(buf)?;
+                index.serialize(buf)?;
+                0u16.serialize(buf)?;
+            },
+            Instruction::New(index) => {
+                0xBBu8.serialize(buf)?;
+                index.serialize(buf)?;
+            },
+            Instruction::NewArray(atype) => {
+                0xBCu8.serialize(buf)?;
+                atype.serialize(buf)?;
+            },
+            Instruction::ANewArray(index) => {
+                0xBDu8.serialize(buf)?;
+                index.serialize(buf)?;
             },
             Instruction::ArrayLength => {
-                (0xBE as u8).serialize(buf);
+                0xBEu8.serialize(buf)?;
+            },
+            Instruction::Athrow => {
+                0xBFu8.serialize(buf)?;
+            },
+            Instruction::CheckCast(index) => {
+                0xC0u8.serialize(buf)?;
+                index.serialize(buf)?;
+            },
+            Instruction::InstanceOf(index) => {
+                0xC1u8.serialize(buf)?;
+                index.serialize(buf)?;
+            },
+            Instruction::MonitorEnter => {
+                0xC2u8.serialize(buf)?;
+            },
+            Instruction::MonitorExit => {
+                0xC3u8.serialize(buf)?;
+            },
+            Instruction::Wide(instruction) => {
+                0xC4u8.serialize(buf)?;
+                instruction.serialize(buf)?;
+            },
+            Instruction::MultiANewArray(index, dimensions) => {
+                0xC5u8.serialize(buf)?;
+                index.serialize(buf)?;
+                dimensions.serialize(buf)?;
+            },
+            Instruction::IfNull(offset) => {
+                0xC6u8.serialize(buf)?;
+                (offset as u16).serialize(buf)?;
+            },
+            Instruction::IfNonNull(offset) => {
+                0xC7u8.serialize(buf)?;
+                (offset as u16).serialize(buf)?;
+            },
+            Instruction::GotoW(offset) => {
+                0xC8u8.serialize(buf)?;
+                (offset as u32).serialize(buf)?;
+            },
+            Instruction::JsrW(offset) => {
+                0xC9u8.serialize(buf)?;
+                (offset as u32).serialize(buf)?;
             },
         }
+        Ok(())
     }
 
-    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> Instruction {
-        let code = u8::deserialize(buf, classfile);
-        match code {
+    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> Result<Instruction, ClassfileError> {
+        let code = u8::deserialize(buf, classfile)?;
+        Ok(match code {
+            0x00 => Instruction::Nop,
+            0x01 => Instruction::AconstNull,
             0x02 => Instruction::IconstM1,
             0x03 => Instruction::Iconst0,
             0x04 => Instruction::Iconst1,
@@ -764,35 +2089,391 @@ impl Serializable for Instruction {
             0x06 => Instruction::Iconst3,
             0x07 => Instruction::Iconst4,
             0x08 => Instruction::Iconst5,
-            0x10 => Instruction::Bipush(u8::deserialize(buf, classfile)),
-            0x12 => Instruction::LoadConstant(u8::deserialize(buf, classfile)),
+            0x09 => Instruction::Lconst0,
+            0x0A => Instruction::Lconst1,
+            0x0B => Instruction::Fconst0,
+            0x0C => Instruction::Fconst1,
+            0x0D => Instruction::Fconst2,
+            0x0E => Instruction::Dconst0,
+            0x0F => Instruction::Dconst1,
+            0x10 => Instruction::Bipush(u8::deserialize(buf, classfile)?),
+            0x11 => Instruction::Sipush(u16::deserialize(buf, classfile)?),
+            0x12 => Instruction::LoadConstant(u8::deserialize(buf, classfile)?),
+            0x13 => Instruction::LoadConstantWide(u16::deserialize(buf, classfile)?),
+            0x14 => Instruction::LoadConstant2Wide(u16::deserialize(buf, classfile)?),
+            0x15 => Instruction::Iload(u8::deserialize(buf, classfile)?),
+            0x16 => Instruction::Lload(u8::deserialize(buf, classfile)?),
+            0x17 => Instruction::Fload(u8::deserialize(buf, classfile)?),
+            0x18 => Instruction::Dload(u8::deserialize(buf, classfile)?),
+            0x19 => Instruction::Aload(u8::deserialize(buf, classfile)?),
+            0x1A => Instruction::Iload0,
+            0x1B => Instruction::Iload1,
+            0x1C => Instruction::Iload2,
+            0x1D => Instruction::Iload3,
+            0x1E => Instruction::Lload0,
+            0x1F => Instruction::Lload1,
+            0x20 => Instruction::Lload2,
+            0x21 => Instruction::Lload3,
+            0x22 => Instruction::Fload0,
+            0x23 => Instruction::Fload1,
+            0x24 => Instruction::Fload2,
+            0x25 => Instruction::Fload3,
+            0x26 => Instruction::Dload0,
+            0x27 => Instruction::Dload1,
+            0x28 => Instruction::Dload2,
+            0x29 => Instruction::Dload3,
             0x2A => Instruction::Aload0,
             0x2B => Instruction::Aload1,
             0x2C => Instruction::Aload2,
             0x2D => Instruction::Aload3,
+            0x2E => Instruction::Iaload,
+            0x2F => Instruction::Laload,
+            0x30 => Instruction::Faload,
+            0x31 => Instruction::Daload,
             0x32 => Instruction::Aaload,
-            0x99 => Instruction::IfEq(u16::deserialize(buf, classfile)),
-            0x9A => Instruction::IfNe(u16::deserialize(buf, classfile)),
-            0x9B => Instruction::IfLt(u16::deserialize(buf, classfile)),
-            0x9C => Instruction::IfGe(u16::deserialize(buf, classfile)),
-            0x9D => Instruction::IfGt(u16::deserialize(buf, classfile)),
-            0x9E => Instruction::IfLe(u16::deserialize(buf, classfile)),
-            0x9F => Instruction::IfIcmpEq(u16::deserialize(buf, classfile)),
-            0xA0 => Instruction::IfIcmpNe(u16::deserialize(buf, classfile)),
-            0xA1 => Instruction::IfIcmpLt(u16::deserialize(buf, classfile)),
-            0xA2 => Instruction::IfIcmpGe(u16::deserialize(buf, classfile)),
-            0xA3 => Instruction::IfIcmpGt(u16::deserialize(buf, classfile)),
-            0xA4 => Instruction::IfIcmpLe(u16::deserialize(buf, classfile)),
-            0xA7 => Instruction::Goto(u16::deserialize(buf, classfile)),
+            0x33 => Instruction::Baload,
+            0x34 => Instruction::Caload,
+            0x35 => Instruction::Saload,
+            0x36 => Instruction::Istore(u8::deserialize(buf, classfile)?),
+            0x37 => Instruction::Lstore(u8::deserialize(buf, classfile)?),
+            0x38 => Instruction::Fstore(u8::deserialize(buf, classfile)?),
+            0x39 => Instruction::Dstore(u8::deserialize(buf, classfile)?),
+            0x3A => Instruction::Astore(u8::deserialize(buf, classfile)?),
+            0x3B => Instruction::Istore0,
+            0x3C => Instruction::Istore1,
+            0x3D => Instruction::Istore2,
+            0x3E => Instruction::Istore3,
+            0x3F => Instruction::Lstore0,
+            0x40 => Instruction::Lstore1,
+            0x41 => Instruction::Lstore2,
+            0x42 => Instruction::Lstore3,
+            0x43 => Instruction::Fstore0,
+            0x44 => Instruction::Fstore1,
+            0x45 => Instruction::Fstore2,
+            0x46 => Instruction::Fstore3,
+            0x47 => Instruction::Dstore0,
+            0x48 => Instruction::Dstore1,
+            0x49 => Instruction::Dstore2,
+            0x4A => Instruction::Dstore3,
+            0x4B => Instruction::Astore0,
+            0x4C => Instruction::Astore1,
+            0x4D => Instruction::Astore2,
+            0x4E => Instruction::Astore3,
+            0x4F => Instruction::Iastore,
+            0x50 => Instruction::Lastore,
+            0x51 => Instruction::Fastore,
+            0x52 => Instruction::Dastore,
+            0x53 => Instruction::Aastore,
+            0x54 => Instruction::Bastore,
+            0x55 => Instruction::Castore,
+            0x56 => Instruction::Sastore,
+            0x57 => Instruction::Pop,
+            0x58 => Instruction::Pop2,
+            0x59 => Instruction::Dup,
+            0x5A => Instruction::DupX1,
+            0x5B => Instruction::DupX2,
+            0x5C => Instruction::Dup2,
+            0x5D => Instruction::Dup2X1,
+            0x5E => Instruction::Dup2X2,
+            0x5F => Instruction::Swap,
             0x60 => Instruction::Iadd,
+            0x61 => Instruction::Ladd,
+            0x62 => Instruction::Fadd,
+            0x63 => Instruction::Dadd,
+            0x64 => Instruction::Isub,
+            0x65 => Instruction::Lsub,
+            0x66 => Instruction::Fsub,
+            0x67 => Instruction::Dsub,
+            0x68 => Instruction::Imul,
+            0x69 => Instruction::Lmul,
+            0x6A => Instruction::Fmul,
+            0x6B => Instruction::Dmul,
+            0x6C => Instruction::Idiv,
+            0x6D => Instruction::Ldiv,
+            0x6E => Instruction::Fdiv,
+            0x6F => Instruction::Ddiv,
+            0x70 => Instruction::Irem,
+            0x71 => Instruction::Lrem,
+            0x72 => Instruction::Frem,
+            0x73 => Instruction::Drem,
+            0x74 => Instruction::Ineg,
+            0x75 => Instruction::Lneg,
+            0x76 => Instruction::Fneg,
+            0x77 => Instruction::Dneg,
+            0x78 => Instruction::Ishl,
+            0x79 => Instruction::Lshl,
+            0x7A => Instruction::Ishr,
+            0x7B => Instruction::Lshr,
+            0x7C => Instruction::Iushr,
+            0x7D => Instruction::Lushr,
+            0x7E => Instruction::Iand,
+            0x7F => Instruction::Land,
+            0x80 => Instruction::Ior,
+            0x81 => Instruction::Lor,
+            0x82 => Instruction::Ixor,
+            0x83 => Instruction::Lxor,
+            0x84 => Instruction::Iinc(u8::deserialize(buf, classfile)?, u8::deserialize(buf, classfile)?),
+            0x85 => Instruction::I2l,
+            0x86 => Instruction::I2f,
+            0x87 => Instruction::I2d,
+            0x88 => Instruction::L2i,
+            0x89 => Instruction::L2f,
+            0x8A => Instruction::L2d,
+            0x8B => Instruction::F2i,
+            0x8C => Instruction::F2l,
+            0x8D => Instruction::F2d,
+            0x8E => Instruction::D2i,
+            0x8F => Instruction::D2l,
+            0x90 => Instruction::D2f,
+            0x91 => Instruction::I2b,
+            0x92 => Instruction::I2c,
+            0x93 => Instruction::I2s,
+            0x94 => Instruction::Lcmp,
+            0x95 => Instruction::Fcmpl,
+            0x96 => Instruction::Fcmpg,
+            0x97 => Instruction::Dcmpl,
+            0x98 => Instruction::Dcmpg,
+            0x99 => Instruction::IfEq(u16::deserialize(buf, classfile)? as i16),
+            0x9A => Instruction::IfNe(u16::deserialize(buf, classfile)? as i16),
+            0x9B => Instruction::IfLt(u16::deserialize(buf, classfile)? as i16),
+            0x9C => Instruction::IfGe(u16::deserialize(buf, classfile)? as i16),
+            0x9D => Instruction::IfGt(u16::deserialize(buf, classfile)? as i16),
+            0x9E => Instruction::IfLe(u16::deserialize(buf, classfile)? as i16),
+            0x9F => Instruction::IfIcmpEq(u16::deserialize(buf, classfile)? as i16),
+            0xA0 => Instruction::IfIcmpNe(u16::deserialize(buf, classfile)? as i16),
+            0xA1 => Instruction::IfIcmpLt(u16::deserialize(buf, classfile)? as i16),
+            0xA2 => Instruction::IfIcmpGe(u16::deserialize(buf, classfile)? as i16),
+            0xA3 => Instruction::IfIcmpGt(u16::deserialize(buf, classfile)? as i16),
+            0xA4 => Instruction::IfIcmpLe(u16::deserialize(buf, classfile)? as i16),
+            0xA5 => Instruction::IfAcmpEq(u16::deserialize(buf, classfile)? as i16),
+            0xA6 => Instruction::IfAcmpNe(u16::deserialize(buf, classfile)? as i16),
+            0xA7 => Instruction::Goto(u16::deserialize(buf, classfile)? as i16),
+            0xA8 => Instruction::Jsr(u16::deserialize(buf, classfile)? as i16),
+            0xA9 => Instruction::Ret(u8::deserialize(buf, classfile)?),
+            0xAA => {
+                deserialize_switch_padding(buf)?;
+                let default = u32::deserialize(buf, classfile)? as i32;
+                let low = u32::deserialize(buf, classfile)? as i32;
+                let high = u32::deserialize(buf, classfile)? as i32;
+                let offsets = (low..=high).map(|_| u32::deserialize(buf, classfile).map(|v| v as i32)).collect::<Result<_, _>>()?;
+                Instruction::TableSwitch(default, low, offsets)
+            },
+            0xAB => {
+                deserialize_switch_padding(buf)?;
+                let default = u32::deserialize(buf, classfile)? as i32;
+                let npairs = u32::deserialize(buf, classfile)?;
+                let mut pairs = vec![];
+                for _ in 0..npairs {
+                    let key = u32::deserialize(buf, classfile)? as i32;
+                    let offset = u32::deserialize(buf, classfile)? as i32;
+                    pairs.push((key, offset));
+                }
+                Instruction::LookupSwitch(default, pairs)
+            },
+            0xAC => Instruction::Ireturn,
+            0xAD => Instruction::Lreturn,
+            0xAE => Instruction::Freturn,
+            0xAF => Instruction::Dreturn,
+            0xB0 => Instruction::Areturn,
             0xB1 => Instruction::Return,
-            0xB2 => Instruction::GetStatic(u16::deserialize(buf, classfile)),
-            0xB6 => Instruction::InvokeVirtual(u16::deserialize(buf, classfile)),
-            0xB7 => Instruction::InvokeSpecial(u16::deserialize(buf, classfile)),
-            0xB8 => Instruction::InvokeStatic(u16::deserialize(buf, classfile)),
+            0xB2 => Instruction::GetStatic(u16::deserialize(buf, classfile)?),
+            0xB3 => Instruction::PutStatic(u16::deserialize(buf, classfile)?),
+            0xB4 => Instruction::GetField(u16::deserialize(buf, classfile)?),
+            0xB5 => Instruction::PutField(u16::deserialize(buf, classfile)?),
+            0xB6 => Instruction::InvokeVirtual(u16::deserialize(buf, classfile)?),
+            0xB7 => Instruction::InvokeSpecial(u16::deserialize(buf, classfile)?),
+            0xB8 => Instruction::InvokeStatic(u16::deserialize(buf, classfile)?),
+            0xB9 => {
+                let index = u16::deserialize(buf, classfile)?;
+                let count = u8::deserialize(buf, classfile)?;
+                u8::deserialize(buf, classfile)?; // always zero
+                Instruction::InvokeInterface(index, count)
+            },
+            0xBA => {
+                let index = u16::deserialize(buf, classfile)?;
+                u16::deserialize(buf, classfile)?; // always zero
+                Instruction::InvokeDynamic(index)
+            },
+            0xBB => Instruction::New(u16::deserialize(buf, classfile)?),
+            0xBC => Instruction::NewArray(u8::deserialize(buf, classfile)?),
+            0xBD => Instruction::ANewArray(u16::deserialize(buf, classfile)?),
             0xBE => Instruction::ArrayLength,
-            _ => panic!("Don't know how to deserialize Instruction of type: 0x{:X}", code)
+            0xBF => Instruction::Athrow,
+            0xC0 => Instruction::CheckCast(u16::deserialize(buf, classfile)?),
+            0xC1 => Instruction::InstanceOf(u16::deserialize(buf, classfile)?),
+            0xC2 => Instruction::MonitorEnter,
+            0xC3 => Instruction::MonitorExit,
+            0xC4 => Instruction::Wide(WideInstruction::deserialize(buf, classfile)?),
+            0xC5 => Instruction::MultiANewArray(u16::deserialize(buf, classfile)?, u8::deserialize(buf, classfile)?),
+            0xC6 => Instruction::IfNull(u16::deserialize(buf, classfile)? as i16),
+            0xC7 => Instruction::IfNonNull(u16::deserialize(buf, classfile)? as i16),
+            0xC8 => Instruction::GotoW(u32::deserialize(buf, classfile)? as i32),
+            0xC9 => Instruction::JsrW(u32::deserialize(buf, classfile)? as i32),
+            _ => return Err(ClassfileError::new(buf.offset() - 1, ClassfileErrorCause::InvalidOpcode(code)))
+        })
+    }
+}
+
+impl Serializable for WideInstruction {
+    fn serialize(self, buf: &mut Vec<u8>) -> Result<(), ClassfileError> {
+        match self {
+            WideInstruction::Iload(index) => {
+                0x15u8.serialize(buf)?;
+                index.serialize(buf)?;
+            },
+            WideInstruction::Lload(index) => {
+                0x16u8.serialize(buf)?;
+                index.serialize(buf)?;
+            },
+            WideInstruction::Fload(index) => {
+                0x17u8.serialize(buf)?;
+                index.serialize(buf)?;
+            },
+            WideInstruction::Dload(index) => {
+                0x18u8.serialize(buf)?;
+                index.serialize(buf)?;
+            },
+            WideInstruction::Aload(index) => {
+                0x19u8.serialize(buf)?;
+                index.serialize(buf)?;
+            },
+            WideInstruction::Istore(index) => {
+                0x36u8.serialize(buf)?;
+                index.serialize(buf)?;
+            },
+            WideInstruction::Lstore(index) => {
+                0x37u8.serialize(buf)?;
+                index.serialize(buf)?;
+            },
+            WideInstruction::Fstore(index) => {
+                0x38u8.serialize(buf)?;
+                index.serialize(buf)?;
+            },
+            WideInstruction::Dstore(index) => {
+                0x39u8.serialize(buf)?;
+                index.serialize(buf)?;
+            },
+            WideInstruction::Astore(index) => {
+                0x3Au8.serialize(buf)?;
+                index.serialize(buf)?;
+            },
+            WideInstruction::Iinc(index, val) => {
+                0x84u8.serialize(buf)?;
+                index.serialize(buf)?;
+                val.serialize(buf)?;
+            },
+            WideInstruction::Ret(index) => {
+                0xA9u8.serialize(buf)?;
+                index.serialize(buf)?;
+            },
+        }
+        Ok(())
+    }
+
+    fn deserialize(buf: &mut Deserializer, classfile: &Classfile) -> Result<WideInstruction, ClassfileError> {
+        let code = u8::deserialize(buf, classfile)?;
+        Ok(match code {
+            0x15 => WideInstruction::Iload(u16::deserialize(buf, classfile)?),
+            0x16 => WideInstruction::Lload(u16::deserialize(buf, classfile)?),
+            0x17 => WideInstruction::Fload(u16::deserialize(buf, classfile)?),
+            0x18 => WideInstruction::Dload(u16::deserialize(buf, classfile)?),
+            0x19 => WideInstruction::Aload(u16::deserialize(buf, classfile)?),
+            0x36 => WideInstruction::Istore(u16::deserialize(buf, classfile)?),
+            0x37 => WideInstruction::Lstore(u16::deserialize(buf, classfile)?),
+            0x38 => WideInstruction::Fstore(u16::deserialize(buf, classfile)?),
+            0x39 => WideInstruction::Dstore(u16::deserialize(buf, classfile)?),
+            0x3A => WideInstruction::Astore(u16::deserialize(buf, classfile)?),
+            0x84 => WideInstruction::Iinc(u16::deserialize(buf, classfile)?, u16::deserialize(buf, classfile)?),
+            0xA9 => WideInstruction::Ret(u16::deserialize(buf, classfile)?),
+            _ => return Err(ClassfileError::new(buf.offset() - 1, ClassfileErrorCause::InvalidWideOpcode(code)))
+        })
+    }
+}
+
+// the padding is relative to the start of the method's code, which is where the code buffer starts
+fn serialize_switch_padding(buf: &mut Vec<u8>) -> Result<(), ClassfileError> {
+    while !buf.len().is_multiple_of(4) {
+        0u8.serialize(buf)?;
+    }
+    Ok(())
+}
+
+fn deserialize_switch_padding(buf: &mut Deserializer) -> Result<(), ClassfileError> {
+    while !buf.bytes_taken.is_multiple_of(4) {
+        buf.take_byte()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use class_builder::*;
+
+    #[test]
+    fn nul_is_written_as_two_bytes() {
+        assert_eq!(encode_modified_utf8("a\0b"), vec![b'a', 0xC0, 0x80, b'b']);
+        assert_eq!(decode_modified_utf8(&[b'a', 0xC0, 0x80, b'b']).as_deref(), Some("a\0b"));
+    }
+
+    #[test]
+    fn plain_nul_byte_is_malformed() {
+        assert_eq!(decode_modified_utf8(&[b'a', 0, b'b']), None);
+    }
+
+    #[test]
+    fn supplementary_characters_are_written_as_surrogate_pairs() {
+        // U+1F600 is the surrogate pair D83D DE00, each written as its own 3-byte sequence
+        let bytes = vec![0xED, 0xA0, 0xBD, 0xED, 0xB8, 0x80];
+        assert_eq!(encode_modified_utf8("\u{1F600}"), bytes);
+        assert_eq!(decode_modified_utf8(&bytes).as_deref(), Some("\u{1F600}"));
+    }
+
+    #[test]
+    fn truncated_sequences_are_malformed() {
+        assert_eq!(decode_modified_utf8(&[0xE2, 0x82]), None);
+        assert_eq!(decode_modified_utf8(&[0xC3, b'a']), None);
+        assert_eq!(decode_modified_utf8(&[0xF0, 0x9F, 0x98, 0x80]), None); // standard UTF-8 for U+1F600
+    }
+
+    #[test]
+    fn unpaired_surrogates_are_kept() {
+        let bytes = [b'x', 0xED, 0xA0, 0xBD];
+        assert_eq!(decode_modified_utf8(&bytes), None);
+        let string = JavaString::from_modified_utf8(&bytes).unwrap();
+        assert_eq!(string.as_str(), "x\u{FFFD}");
+        assert_eq!(string.to_modified_utf8(), bytes);
+        assert!(string.keeps_original_bytes());
+        assert!(!JavaString::from_modified_utf8(b"x").unwrap().keeps_original_bytes());
+    }
+
+    #[test]
+    fn strings_up_to_65535_bytes_can_be_written() {
+        let mut buf = vec![];
+        JavaString::new(&"a".repeat(65535)).serialize(&mut buf).unwrap();
+        assert_eq!(buf.len(), 2 + 65535);
+        assert_eq!(&buf[..2], &[0xFF, 0xFF]);
+    }
+
+    #[test]
+    fn longer_strings_are_an_error() {
+        // each é takes two bytes, so this is one byte over the limit
+        match JavaString::new(&"é".repeat(32768)).serialize(&mut vec![]) {
+            Err(ClassfileError { cause: ClassfileErrorCause::StringTooLong(65536), .. }) => {},
+            result => panic!("expected StringTooLong(65536), got {:?}", result),
         }
+    }
 
+    #[test]
+    fn code_over_65535_bytes_is_an_error() {
+        let classfile = ClassBuilder::new(ACC_PUBLIC, "Test", "java/lang/Object").done().unwrap();
+        let decoders = AttributeDecoders::new();
+        let mut bytes = vec![];
+        70001u32.serialize(&mut bytes).unwrap();
+        bytes.resize(4 + 70001, 0); // nops
+        match Vec::<Instruction>::deserialize(&mut Deserializer::new(bytes, 0, &decoders), &classfile) {
+            Err(ClassfileError { cause: ClassfileErrorCause::CodeTooLarge(70001), .. }) => {},
+            result => panic!("expected CodeTooLarge(70001), got {:?}", result.map(|code| code.len())),
+        }
     }
 }
@@ -0,0 +1,63 @@
+use std::fmt;
+
+// A JSR-45 source map (SMAP), which tells debuggers how the line numbers in a class's LineNumberTable map back to
+// lines in the files it was generated from, e.g. a template or a program in another language. Attach it to a class
+// with ClassBuilder::source_map.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SourceMap {
+    generated_file: String, // the file named by the SourceFile attribute
+    stratum: String,        // the name debuggers show for the original language, e.g. JSP
+    files: Vec<(String, Option<String>)>, // name, and optionally path
+    lines: Vec<LineMapping>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct LineMapping {
+    file_id: usize,
+    input_line: u32,
+    output_line: u32,
+}
+
+impl SourceMap {
+    pub fn new(generated_file: &str, stratum: &str) -> SourceMap {
+        SourceMap {
+            generated_file: generated_file.to_owned(),
+            stratum: stratum.to_owned(),
+            files: vec![],
+            lines: vec![],
+        }
+    }
+
+    // Adds an original source file, with the path to find it at if that's different from its name. Returns the id to
+    // pass to map_line.
+    pub fn add_file(&mut self, name: &str, path: Option<&str>) -> usize {
+        self.files.push((name.to_owned(), path.map(|path| path.to_owned())));
+        self.files.len()
+    }
+
+    // Maps a line number in the class file (as given to MethodBuilder::line) to a line of an original source file.
+    pub fn map_line(&mut self, file_id: usize, input_line: u32, output_line: u32) {
+        self.lines.push(LineMapping { file_id, input_line, output_line });
+    }
+}
+
+impl fmt::Display for SourceMap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "SMAP")?;
+        writeln!(f, "{}", self.generated_file)?;
+        writeln!(f, "{}", self.stratum)?;
+        writeln!(f, "*S {}", self.stratum)?;
+        writeln!(f, "*F")?;
+        for (i, (name, path)) in self.files.iter().enumerate() {
+            match path {
+                Some(path) => writeln!(f, "+ {} {}\n{}", i + 1, name, path)?,
+                None => writeln!(f, "{} {}", i + 1, name)?,
+            }
+        }
+        writeln!(f, "*L")?;
+        for line in &self.lines {
+            writeln!(f, "{}#{}:{}", line.input_line, line.file_id, line.output_line)?;
+        }
+        writeln!(f, "*E")
+    }
+}
@@ -0,0 +1,863 @@
+use std::collections::{BTreeSet, HashMap};
+
+use classfile::*;
+use constant_pool::*;
+use java_type_signatures::*;
+
+// The type of a single local variable slot or stack entry, as inferred by analyzing a method's code.
+// Unlike VerificationType, classes are referred to by name rather than by constant pool index.
+#[derive(Clone, Debug, PartialEq)]
+pub enum InferredType {
+    Top,
+    Integer,
+    Float,
+    Long,
+    Double,
+    Null,
+    UninitializedThis,
+    Object(String),     // internal class name (java/lang/String), or an array descriptor ([I)
+    Uninitialized(u16), // result of the `new` instruction at this pc, before its constructor has been called
+}
+
+// Locals have one entry per slot, so a Long or Double is followed by a Top. The stack has one entry per value.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    pub locals: Vec<InferredType>,
+    pub stack: Vec<InferredType>,
+}
+
+// Finds the closest common superclass of two different, non-array classes
+pub type CommonSuperclass = dyn Fn(&str, &str) -> String;
+
+// What the analysis needs to know about the method being analyzed.
+pub struct MethodContext<'a> {
+    pub constants: &'a ConstantPool,
+    pub this_class: &'a str,
+    pub name: &'a str,
+    pub descriptor: &'a str,
+    pub is_static: bool,
+    pub common_superclass: &'a CommonSuperclass,
+    pub frame_hints: &'a HashMap<u16, Frame>, // by pc, the types to merge different classes to there
+}
+
+pub struct FrameAnalysis {
+    pub instructions: Vec<Instruction>,
+    pub exception_table: Vec<ExceptionTableEntry>,
+    pub initial_frame: Frame,
+    pub frames: Vec<(u16, Frame)>,
+}
+
+impl Frame {
+    pub fn stack_slots(&self) -> u16 {
+        self.stack.iter().map(|t| if t.is_wide() { 2 } else { 1 }).sum()
+    }
+}
+
+impl InferredType {
+    fn is_wide(&self) -> bool {
+        matches!(*self, InferredType::Long | InferredType::Double)
+    }
+
+    fn is_reference(&self) -> bool {
+        matches!(*self, InferredType::Null | InferredType::Object(_))
+    }
+}
+
+// Works out the types of every local variable and stack entry at each branch target, exception handler and
+// instruction following an unconditional jump, which is where the verifier expects a StackMapTable frame.
+// Code that can never be reached is replaced with nops followed by athrow (like ASM does), so that it verifies
+// against a trivial frame, and exception handler ranges are trimmed to exclude it.
+pub fn analyze(context: &MethodContext, instructions: Vec<Instruction>, exception_table: Vec<ExceptionTableEntry>) -> Result<FrameAnalysis, String> {
+    let mut pcs = vec![];
+    let mut pc = 0;
+    for instruction in &instructions {
+        pcs.push(pc);
+        pc += instruction.size(pc);
+    }
+    let code_length = pc;
+    let index_of_pc: HashMap<u16, usize> = pcs.iter().enumerate().map(|(i, &pc)| (pc, i)).collect();
+    let index_of = |pc: u16| -> Result<usize, String> {
+        if pc == code_length {
+            Ok(instructions.len())
+        } else {
+            index_of_pc.get(&pc).cloned().ok_or_else(|| format!("No instruction starts at pc {}", pc))
+        }
+    };
+
+    // the handlers that cover each instruction, as (handler index, exception class)
+    let mut handlers: Vec<Vec<(usize, String)>> = vec![vec![]; instructions.len()];
+    for entry in &exception_table {
+        let handler_index = index_of(entry.handler_pc)?;
+        let exception_class = if entry.catch_type == 0 {
+            "java/lang/Throwable".to_owned()
+        } else {
+            class_name(context.constants, entry.catch_type)?.to_owned()
+        };
+        for covered in handlers.iter_mut().take(index_of(entry.end_pc)?).skip(index_of(entry.start_pc)?) {
+            covered.push((handler_index, exception_class.clone()));
+        }
+    }
+
+    // a method has to end in a return, throw or jump, so there's at least one instruction
+    if instructions.is_empty() {
+        return Err("Execution falls off the end of the code at pc 0".to_owned());
+    }
+
+    let initial_frame = initial_frame(context)?;
+    let mut states: Vec<Option<Frame>> = vec![None; instructions.len()];
+    let mut frame_indices = BTreeSet::new();
+    let mut worklist = vec![0];
+    states[0] = Some(initial_frame.clone());
+
+    while let Some(index) = worklist.pop() {
+        let pc = pcs[index];
+        let before = states[index].clone().unwrap();
+        let mut interpreter = Interpreter { context, instructions: &instructions, index_of_pc: &index_of_pc, pc, frame: before.clone() };
+        interpreter.execute(&instructions[index]).map_err(|e| format!("{} at pc {}", e, pc))?;
+        let after = interpreter.frame;
+
+        let (targets, falls_through) = branch_targets(&instructions[index], pc);
+        let mut successors = vec![];
+        for target in targets {
+            if target < 0 || target >= code_length as i32 {
+                return Err(format!("Branch target {} is outside the code at pc {}", target, pc));
+            }
+            let target_index = index_of(target as u16)?;
+            frame_indices.insert(target_index);
+            successors.push(target_index);
+        }
+        if falls_through {
+            successors.push(index + 1);
+        } else if index + 1 < instructions.len() {
+            frame_indices.insert(index + 1);
+        }
+
+        for successor in successors {
+            if successor >= instructions.len() {
+                return Err(format!("Execution falls off the end of the code at pc {}", pc));
+            }
+            if merge_into(context, &mut states[successor], &after, context.frame_hints.get(&pcs[successor])).map_err(|e| format!("{} at pc {}", e, pcs[successor]))? {
+                worklist.push(successor);
+            }
+        }
+
+        // handlers see the locals from both before and after the instruction, with just the exception on the stack
+        for &(handler_index, ref exception_class) in &handlers[index] {
+            frame_indices.insert(handler_index);
+            let stack = vec![InferredType::Object(exception_class.clone())];
+            for locals in &[&before.locals, &after.locals] {
+                let frame = Frame { locals: locals.to_vec(), stack: stack.clone() };
+                if merge_into(context, &mut states[handler_index], &frame, context.frame_hints.get(&pcs[handler_index])).map_err(|e| format!("{} at pc {}", e, pcs[handler_index]))? {
+                    worklist.push(handler_index);
+                }
+            }
+        }
+    }
+
+    // replace each run of unreachable instructions with nop, ..., nop, athrow
+    let mut live_instructions = vec![];
+    let mut frames = vec![];
+    let mut dead_start = None;
+    for (index, instruction) in instructions.into_iter().enumerate() {
+        match states[index] {
+            Some(ref frame) => {
+                if let Some(start_pc) = dead_start.take() {
+                    replace_dead_code(start_pc, pcs[index], &mut live_instructions, &mut frames);
+                }
+                if frame_indices.contains(&index) {
+                    frames.push((pcs[index], frame.clone()));
+                }
+                live_instructions.push(instruction);
+            },
+            None => {
+                if dead_start.is_none() {
+                    dead_start = Some(pcs[index]);
+                }
+            },
+        }
+    }
+    if let Some(start_pc) = dead_start {
+        replace_dead_code(start_pc, code_length, &mut live_instructions, &mut frames);
+    }
+
+    // split exception handler ranges around unreachable code
+    let mut live_exception_table = vec![];
+    for entry in exception_table {
+        let mut range_start = None;
+        for (i, &pc) in pcs.iter().enumerate() {
+            let live = pc >= entry.start_pc && pc < entry.end_pc && states[i].is_some();
+            match (live, range_start) {
+                (true, None) => range_start = Some(pc),
+                (false, Some(start_pc)) => {
+                    live_exception_table.push(ExceptionTableEntry { start_pc, end_pc: pc, ..entry.clone() });
+                    range_start = None;
+                },
+                _ => {},
+            }
+        }
+        if let Some(start_pc) = range_start {
+            live_exception_table.push(ExceptionTableEntry { start_pc, end_pc: code_length, ..entry.clone() });
+        }
+    }
+
+    Ok(FrameAnalysis {
+        instructions: live_instructions,
+        exception_table: live_exception_table,
+        initial_frame,
+        frames,
+    })
+}
+
+// The largest number of operand stack slots in use at any point of the method, following each path through the
+// code from its start and from each exception handler. Unreachable code is not counted.
+pub fn max_stack(constants: &ConstantPool, instructions: &[Instruction], exception_table: &[ExceptionTableEntry]) -> Result<u16, String> {
+    let mut pcs = vec![];
+    let mut pc = 0;
+    for instruction in instructions {
+        pcs.push(pc);
+        pc += instruction.size(pc);
+    }
+    let index_of_pc: HashMap<u16, usize> = pcs.iter().enumerate().map(|(i, &pc)| (pc, i)).collect();
+    let index_of = |pc: i32| -> Result<usize, String> {
+        if pc < 0 || pc > u16::MAX as i32 {
+            return Err(format!("No instruction starts at pc {}", pc));
+        }
+        index_of_pc.get(&(pc as u16)).cloned().ok_or_else(|| format!("No instruction starts at pc {}", pc))
+    };
+
+    let mut depths: Vec<Option<u16>> = vec![None; instructions.len()];
+    let mut max = 0;
+    let mut worklist = vec![(0, 0)];
+    while let Some((index, depth)) = worklist.pop() {
+        match depths[index] {
+            Some(existing) if existing == depth => continue,
+            Some(existing) => return Err(format!("Inconsistent stack depths {} and {} at pc {}", existing, depth, pcs[index])),
+            None => depths[index] = Some(depth),
+        }
+
+        let (pops, pushes) = instructions[index].stack_effect(constants).map_err(|e| format!("{} at pc {}", e, pcs[index]))?;
+        if pops > depth {
+            return Err(format!("Instruction pops {} stack slots but only {} are in use at pc {}", pops, depth, pcs[index]));
+        }
+        let after = depth - pops + pushes;
+        max = max.max(depth).max(after);
+
+        let (targets, falls_through) = branch_targets(&instructions[index], pcs[index]);
+        for target in targets {
+            worklist.push((index_of(target)?, after));
+        }
+        if falls_through {
+            if index + 1 >= instructions.len() {
+                return Err(format!("Execution falls off the end of the code at pc {}", pcs[index]));
+            }
+            worklist.push((index + 1, after));
+        }
+        for entry in exception_table {
+            if pcs[index] >= entry.start_pc && pcs[index] < entry.end_pc {
+                worklist.push((index_of(entry.handler_pc as i32)?, 1));
+            }
+        }
+    }
+    Ok(max)
+}
+
+fn replace_dead_code(start_pc: u16, end_pc: u16, instructions: &mut Vec<Instruction>, frames: &mut Vec<(u16, Frame)>) {
+    for _ in start_pc..end_pc - 1 {
+        instructions.push(Instruction::Nop);
+    }
+    instructions.push(Instruction::Athrow);
+    let throwable = InferredType::Object("java/lang/Throwable".to_owned());
+    frames.push((start_pc, Frame { locals: vec![], stack: vec![throwable] }));
+}
+
+// Compresses a list of frames (ordered by pc) into StackMapTable entries, each relative to the frame before it.
+pub fn encode_frames<F: FnMut(&str) -> u16>(initial_frame: &Frame, frames: &[(u16, Frame)], mut class_index: F) -> Vec<StackMapFrame> {
+    let mut encoded = vec![];
+    let mut previous_locals = compress_locals(&initial_frame.locals);
+    let mut previous_pc: Option<u16> = None;
+
+    for &(pc, ref frame) in frames {
+        let offset_delta = match previous_pc {
+            Some(previous) => pc - previous - 1,
+            None => pc,
+        };
+        let locals = compress_locals(&frame.locals);
+        let mut to_verification_types = |types: &[InferredType]| -> Vec<VerificationType> {
+            types.iter().map(|t| verification_type(t, &mut class_index)).collect()
+        };
+
+        let stack_frame = if locals == previous_locals && frame.stack.is_empty() {
+            if offset_delta <= 63 {
+                StackMapFrame::SameFrame(offset_delta as u8)
+            } else {
+                StackMapFrame::SameFrameExtended(offset_delta)
+            }
+        } else if locals == previous_locals && frame.stack.len() == 1 {
+            let stack_item = to_verification_types(&frame.stack).remove(0);
+            if offset_delta <= 63 {
+                StackMapFrame::SameLocals1StackItemFrame(offset_delta as u8, stack_item)
+            } else {
+                StackMapFrame::SameLocals1StackItemFrameExtended(offset_delta, stack_item)
+            }
+        } else if frame.stack.is_empty() && locals.len() > previous_locals.len() && locals.len() - previous_locals.len() <= 3 && locals.starts_with(&previous_locals) {
+            let appended = to_verification_types(&locals[previous_locals.len()..]);
+            StackMapFrame::AppendFrame(appended.len() as u8, offset_delta, appended)
+        } else if frame.stack.is_empty() && locals.len() < previous_locals.len() && previous_locals.len() - locals.len() <= 3 && previous_locals.starts_with(&locals) {
+            StackMapFrame::ChopFrame((previous_locals.len() - locals.len()) as u8, offset_delta)
+        } else {
+            StackMapFrame::FullFrame(offset_delta, to_verification_types(&locals), to_verification_types(&frame.stack))
+        };
+
+        encoded.push(stack_frame);
+        previous_locals = locals;
+        previous_pc = Some(pc);
+    }
+    encoded
+}
+
+// one entry per value (so Long and Double aren't followed by Top), without trailing Tops
+fn compress_locals(locals: &[InferredType]) -> Vec<InferredType> {
+    let mut compressed = vec![];
+    let mut i = 0;
+    while i < locals.len() {
+        compressed.push(locals[i].clone());
+        i += if locals[i].is_wide() { 2 } else { 1 };
+    }
+    while compressed.last() == Some(&InferredType::Top) {
+        compressed.pop();
+    }
+    compressed
+}
+
+fn verification_type<F: FnMut(&str) -> u16>(t: &InferredType, class_index: &mut F) -> VerificationType {
+    match *t {
+        InferredType::Top => VerificationType::Top,
+        InferredType::Integer => VerificationType::Integer,
+        InferredType::Float => VerificationType::Float,
+        InferredType::Long => VerificationType::Long,
+        InferredType::Double => VerificationType::Double,
+        InferredType::Null => VerificationType::Null,
+        InferredType::UninitializedThis => VerificationType::UninitializedThis,
+        InferredType::Object(ref class) => VerificationType::Object(class_index(class)),
+        InferredType::Uninitialized(pc) => VerificationType::Uninitialized(pc),
+    }
+}
+
+fn initial_frame(context: &MethodContext) -> Result<Frame, String> {
+    let mut locals = vec![];
+    if !context.is_static {
+        if context.name == "<init>" && context.this_class != "java/lang/Object" {
+            locals.push(InferredType::UninitializedThis);
+        } else {
+            locals.push(InferredType::Object(context.this_class.to_owned()));
+        }
+    }
+    let descriptor = MethodDescriptor::parse(context.descriptor).map_err(|e| e.to_string())?;
+    for argument_type in &descriptor.args {
+        let t = java_type(argument_type);
+        let wide = t.is_wide();
+        locals.push(t);
+        if wide {
+            locals.push(InferredType::Top);
+        }
+    }
+    Ok(Frame { locals, stack: vec![] })
+}
+
+// Returns whether the frame changed, in which case the instruction needs to be (re-)analyzed.
+fn merge_into(context: &MethodContext, existing: &mut Option<Frame>, incoming: &Frame, hint: Option<&Frame>) -> Result<bool, String> {
+    let frame = match *existing {
+        None => {
+            *existing = Some(incoming.clone());
+            return Ok(true);
+        },
+        Some(ref mut frame) => frame,
+    };
+
+    if frame.stack.len() != incoming.stack.len() {
+        return Err(format!("Inconsistent stack heights ({} and {})", frame.stack.len(), incoming.stack.len()));
+    }
+
+    let mut changed = false;
+    for (i, (slot, t)) in frame.stack.iter_mut().zip(incoming.stack.iter()).enumerate() {
+        let merged = merge_types(context, slot, t, hint.and_then(|hint| hint.stack.get(i)));
+        if merged == InferredType::Top {
+            return Err(format!("Incompatible stack types ({:?} and {:?})", slot, t));
+        }
+        if merged != *slot {
+            *slot = merged;
+            changed = true;
+        }
+    }
+
+    // locals that aren't set on every path become unusable
+    let len = frame.locals.len().max(incoming.locals.len());
+    for i in 0..len {
+        let merged = match (frame.locals.get(i), incoming.locals.get(i)) {
+            (Some(a), Some(b)) => merge_types(context, a, b, hint.and_then(|hint| hint.locals.get(i))),
+            _ => InferredType::Top,
+        };
+        if i >= frame.locals.len() {
+            frame.locals.push(merged);
+        } else if merged != frame.locals[i] {
+            frame.locals[i] = merged;
+            changed = true;
+        }
+    }
+    Ok(changed)
+}
+
+// Two different classes merge to the class hinted at for the slot, if any (trusting that it's a superclass of both)
+fn merge_types(context: &MethodContext, a: &InferredType, b: &InferredType, hint: Option<&InferredType>) -> InferredType {
+    match (a, b) {
+        _ if a == b => a.clone(),
+        (&InferredType::Null, t) | (t, &InferredType::Null) if t.is_reference() => t.clone(),
+        (InferredType::Object(a), InferredType::Object(b)) => match hint {
+            Some(InferredType::Object(class)) => InferredType::Object(class.clone()),
+            _ => InferredType::Object(merge_classes(context, a, b)),
+        },
+        _ => InferredType::Top,
+    }
+}
+
+// Without loading classes we can't find the real common superclass, so that's left to the context (which merges
+// to Object unless it's told about the class hierarchy). Arrays merge to an array of their components' merge.
+fn merge_classes(context: &MethodContext, a: &str, b: &str) -> String {
+    if a == b {
+        return a.to_owned();
+    }
+    if let (Some(component_a), Some(component_b)) = (array_component_class(a), array_component_class(b)) {
+        return array_of(&merge_classes(context, &component_a, &component_b));
+    }
+    if a.starts_with('[') || b.starts_with('[') {
+        return "java/lang/Object".to_owned();
+    }
+    (context.common_superclass)(a, b)
+}
+
+// the class name of an array's elements, if they are objects (or arrays)
+fn array_component_class(array: &str) -> Option<String> {
+    if !array.starts_with('[') {
+        return None;
+    }
+    match descriptor_type(&array[1..]) {
+        Some(InferredType::Object(class)) => Some(class),
+        _ => None,
+    }
+}
+
+fn array_of(class: &str) -> String {
+    if class.starts_with('[') {
+        format!("[{}", class)
+    } else {
+        format!("[L{};", class)
+    }
+}
+
+// the targets of a jump, and whether execution can also continue with the next instruction
+fn branch_targets(instruction: &Instruction, pc: u16) -> (Vec<i32>, bool) {
+    let target = |offset: i16| pc as i32 + offset as i32;
+    let wide_target = |offset: i32| pc as i32 + offset;
+    match *instruction {
+        Instruction::IfEq(offset) | Instruction::IfNe(offset) | Instruction::IfLt(offset) |
+        Instruction::IfGe(offset) | Instruction::IfGt(offset) | Instruction::IfLe(offset) |
+        Instruction::IfIcmpEq(offset) | Instruction::IfIcmpNe(offset) | Instruction::IfIcmpLt(offset) |
+        Instruction::IfIcmpGe(offset) | Instruction::IfIcmpGt(offset) | Instruction::IfIcmpLe(offset) |
+        Instruction::IfAcmpEq(offset) | Instruction::IfAcmpNe(offset) |
+        Instruction::IfNull(offset) | Instruction::IfNonNull(offset) => (vec![target(offset)], true),
+        Instruction::Goto(offset) => (vec![target(offset)], false),
+        Instruction::GotoW(offset) => (vec![wide_target(offset)], false),
+        Instruction::TableSwitch(default, _, ref offsets) => {
+            let mut targets = vec![wide_target(default)];
+            targets.extend(offsets.iter().map(|&offset| wide_target(offset)));
+            (targets, false)
+        },
+        Instruction::LookupSwitch(default, ref pairs) => {
+            let mut targets = vec![wide_target(default)];
+            targets.extend(pairs.iter().map(|&(_, offset)| wide_target(offset)));
+            (targets, false)
+        },
+        Instruction::Ireturn | Instruction::Lreturn | Instruction::Freturn | Instruction::Dreturn |
+        Instruction::Areturn | Instruction::Return | Instruction::Athrow => (vec![], false),
+        _ => (vec![], true),
+    }
+}
+
+struct Interpreter<'a> {
+    context: &'a MethodContext<'a>,
+    instructions: &'a [Instruction],
+    index_of_pc: &'a HashMap<u16, usize>,
+    pc: u16,
+    frame: Frame,
+}
+
+impl<'a> Interpreter<'a> {
+    fn push(&mut self, t: InferredType) {
+        self.frame.stack.push(t);
+    }
+
+    fn pop(&mut self) -> Result<InferredType, String> {
+        self.frame.stack.pop().ok_or_else(|| "Stack underflow".to_owned())
+    }
+
+    fn pop_n(&mut self, n: usize) -> Result<Vec<InferredType>, String> {
+        if n > self.frame.stack.len() {
+            return Err("Stack underflow".to_owned());
+        }
+        let at = self.frame.stack.len() - n;
+        Ok(self.frame.stack.split_off(at))
+    }
+
+    // pops values totalling the given number of slots, for the pop2 and dup* family, which work on raw slots
+    fn pop_slots(&mut self, slots: usize) -> Result<Vec<InferredType>, String> {
+        let mut values = vec![];
+        let mut taken = 0;
+        while taken < slots {
+            let value = self.pop()?;
+            taken += if value.is_wide() { 2 } else { 1 };
+            values.insert(0, value);
+        }
+        if taken != slots {
+            return Err("Instruction would split a long or double on the stack".to_owned());
+        }
+        Ok(values)
+    }
+
+    fn pop_and_push(&mut self, pops: usize, t: InferredType) -> Result<(), String> {
+        self.pop_n(pops)?;
+        self.push(t);
+        Ok(())
+    }
+
+    fn load(&mut self, index: u16) -> Result<(), String> {
+        let t = self.frame.locals.get(index as usize).cloned().unwrap_or(InferredType::Top);
+        if t == InferredType::Top {
+            return Err(format!("Local variable {} is not set", index));
+        }
+        self.push(t);
+        Ok(())
+    }
+
+    fn store(&mut self, index: u16) -> Result<(), String> {
+        let t = self.pop()?;
+        let index = index as usize;
+        let wide = t.is_wide();
+        let needed = index + if wide { 2 } else { 1 };
+        if self.frame.locals.len() < needed {
+            self.frame.locals.resize(needed, InferredType::Top);
+        }
+        // overwriting the second half of a long or double makes the whole thing unusable
+        if index > 0 && self.frame.locals[index - 1].is_wide() {
+            self.frame.locals[index - 1] = InferredType::Top;
+        }
+        self.frame.locals[index] = t;
+        if wide {
+            self.frame.locals[index + 1] = InferredType::Top;
+        }
+        Ok(())
+    }
+
+    fn array_load(&mut self) -> Result<(), String> {
+        self.pop()?;
+        let array = self.pop()?;
+        let component = match array {
+            InferredType::Null => InferredType::Null,
+            InferredType::Object(ref array) if array.starts_with('[') => {
+                descriptor_type(&array[1..]).ok_or_else(|| format!("Invalid array type {}", array))?
+            },
+            _ => return Err(format!("Expected an array, found {:?}", array)),
+        };
+        self.push(component);
+        Ok(())
+    }
+
+    fn invoke(&mut self, descriptor: &str, has_receiver: bool, is_constructor: bool) -> Result<(), String> {
+        let descriptor = MethodDescriptor::parse(descriptor).map_err(|e| e.to_string())?;
+        self.pop_n(descriptor.args.len())?;
+        if has_receiver {
+            let receiver = self.pop()?;
+            if is_constructor {
+                let initialized = match receiver {
+                    InferredType::UninitializedThis => InferredType::Object(self.context.this_class.to_owned()),
+                    InferredType::Uninitialized(new_pc) => {
+                        let new_index = self.index_of_pc.get(&new_pc).ok_or_else(|| format!("No new instruction at pc {}", new_pc))?;
+                        match self.instructions[*new_index] {
+                            Instruction::New(class_index) => InferredType::Object(class_name(self.context.constants, class_index)?.to_owned()),
+                            _ => return Err(format!("No new instruction at pc {}", new_pc)),
+                        }
+                    },
+                    _ => return Err(format!("Calling a constructor on an initialized object: {:?}", receiver)),
+                };
+                for t in self.frame.locals.iter_mut().chain(self.frame.stack.iter_mut()) {
+                    if *t == receiver {
+                        *t = initialized.clone();
+                    }
+                }
+            }
+        }
+        if descriptor.ret != Java::Void {
+            self.push(java_type(&descriptor.ret));
+        }
+        Ok(())
+    }
+
+    fn execute(&mut self, instruction: &Instruction) -> Result<(), String> {
+        use self::InferredType::*;
+
+        let constants = self.context.constants;
+        match *instruction {
+            Instruction::Nop => {},
+            Instruction::AconstNull => self.push(Null),
+            Instruction::IconstM1 | Instruction::Iconst0 | Instruction::Iconst1 | Instruction::Iconst2 |
+            Instruction::Iconst3 | Instruction::Iconst4 | Instruction::Iconst5 |
+            Instruction::Bipush(_) | Instruction::Sipush(_) => self.push(Integer),
+            Instruction::Lconst0 | Instruction::Lconst1 => self.push(Long),
+            Instruction::Fconst0 | Instruction::Fconst1 | Instruction::Fconst2 => self.push(Float),
+            Instruction::Dconst0 | Instruction::Dconst1 => self.push(Double),
+            Instruction::LoadConstant(index) => self.push(constant_type(constants, index as u16)?),
+            Instruction::LoadConstantWide(index) | Instruction::LoadConstant2Wide(index) => self.push(constant_type(constants, index)?),
+
+            Instruction::Iload(index) | Instruction::Lload(index) | Instruction::Fload(index) |
+            Instruction::Dload(index) | Instruction::Aload(index) => self.load(index as u16)?,
+            Instruction::Iload0 | Instruction::Lload0 | Instruction::Fload0 | Instruction::Dload0 | Instruction::Aload0 => self.load(0)?,
+            Instruction::Iload1 | Instruction::Lload1 | Instruction::Fload1 | Instruction::Dload1 | Instruction::Aload1 => self.load(1)?,
+            Instruction::Iload2 | Instruction::Lload2 | Instruction::Fload2 | Instruction::Dload2 | Instruction::Aload2 => self.load(2)?,
+            Instruction::Iload3 | Instruction::Lload3 | Instruction::Fload3 | Instruction::Dload3 | Instruction::Aload3 => self.load(3)?,
+
+            Instruction::Iaload | Instruction::Baload | Instruction::Caload | Instruction::Saload => self.pop_and_push(2, Integer)?,
+            Instruction::Laload => self.pop_and_push(2, Long)?,
+            Instruction::Faload => self.pop_and_push(2, Float)?,
+            Instruction::Daload => self.pop_and_push(2, Double)?,
+            Instruction::Aaload => self.array_load()?,
+
+            Instruction::Istore(index) | Instruction::Lstore(index) | Instruction::Fstore(index) |
+            Instruction::Dstore(index) | Instruction::Astore(index) => self.store(index as u16)?,
+            Instruction::Istore0 | Instruction::Lstore0 | Instruction::Fstore0 | Instruction::Dstore0 | Instruction::Astore0 => self.store(0)?,
+            Instruction::Istore1 | Instruction::Lstore1 | Instruction::Fstore1 | Instruction::Dstore1 | Instruction::Astore1 => self.store(1)?,
+            Instruction::Istore2 | Instruction::Lstore2 | Instruction::Fstore2 | Instruction::Dstore2 | Instruction::Astore2 => self.store(2)?,
+            Instruction::Istore3 | Instruction::Lstore3 | Instruction::Fstore3 | Instruction::Dstore3 | Instruction::Astore3 => self.store(3)?,
+
+            Instruction::Iastore | Instruction::Lastore | Instruction::Fastore | Instruction::Dastore |
+            Instruction::Aastore | Instruction::Bastore | Instruction::Castore | Instruction::Sastore => {
+                self.pop_n(3)?;
+            },
+
+            Instruction::Pop => {
+                self.pop_slots(1)?;
+            },
+            Instruction::Pop2 => {
+                self.pop_slots(2)?;
+            },
+            Instruction::Dup => {
+                let a = self.pop_slots(1)?;
+                self.frame.stack.extend(a.clone());
+                self.frame.stack.extend(a);
+            },
+            Instruction::DupX1 => {
+                let a = self.pop_slots(1)?;
+                let b = self.pop_slots(1)?;
+                self.frame.stack.extend(a.clone());
+                self.frame.stack.extend(b);
+                self.frame.stack.extend(a);
+            },
+            Instruction::DupX2 => {
+                let a = self.pop_slots(1)?;
+                let b = self.pop_slots(2)?;
+                self.frame.stack.extend(a.clone());
+                self.frame.stack.extend(b);
+                self.frame.stack.extend(a);
+            },
+            Instruction::Dup2 => {
+                let a = self.pop_slots(2)?;
+                self.frame.stack.extend(a.clone());
+                self.frame.stack.extend(a);
+            },
+            Instruction::Dup2X1 => {
+                let a = self.pop_slots(2)?;
+                let b = self.pop_slots(1)?;
+                self.frame.stack.extend(a.clone());
+                self.frame.stack.extend(b);
+                self.frame.stack.extend(a);
+            },
+            Instruction::Dup2X2 => {
+                let a = self.pop_slots(2)?;
+                let b = self.pop_slots(2)?;
+                self.frame.stack.extend(a.clone());
+                self.frame.stack.extend(b);
+                self.frame.stack.extend(a);
+            },
+            Instruction::Swap => {
+                let a = self.pop_slots(1)?;
+                let b = self.pop_slots(1)?;
+                self.frame.stack.extend(a);
+                self.frame.stack.extend(b);
+            },
+
+            Instruction::Iadd | Instruction::Isub | Instruction::Imul | Instruction::Idiv | Instruction::Irem |
+            Instruction::Ishl | Instruction::Ishr | Instruction::Iushr | Instruction::Iand | Instruction::Ior | Instruction::Ixor |
+            Instruction::Lcmp | Instruction::Fcmpl | Instruction::Fcmpg | Instruction::Dcmpl | Instruction::Dcmpg => self.pop_and_push(2, Integer)?,
+            Instruction::Ladd | Instruction::Lsub | Instruction::Lmul | Instruction::Ldiv | Instruction::Lrem |
+            Instruction::Lshl | Instruction::Lshr | Instruction::Lushr | Instruction::Land | Instruction::Lor | Instruction::Lxor => self.pop_and_push(2, Long)?,
+            Instruction::Fadd | Instruction::Fsub | Instruction::Fmul | Instruction::Fdiv | Instruction::Frem => self.pop_and_push(2, Float)?,
+            Instruction::Dadd | Instruction::Dsub | Instruction::Dmul | Instruction::Ddiv | Instruction::Drem => self.pop_and_push(2, Double)?,
+
+            Instruction::Ineg | Instruction::L2i | Instruction::F2i | Instruction::D2i |
+            Instruction::I2b | Instruction::I2c | Instruction::I2s |
+            Instruction::ArrayLength | Instruction::InstanceOf(_) => self.pop_and_push(1, Integer)?,
+            Instruction::Lneg | Instruction::I2l | Instruction::F2l | Instruction::D2l => self.pop_and_push(1, Long)?,
+            Instruction::Fneg | Instruction::I2f | Instruction::L2f | Instruction::D2f => self.pop_and_push(1, Float)?,
+            Instruction::Dneg | Instruction::I2d | Instruction::L2d | Instruction::F2d => self.pop_and_push(1, Double)?,
+            Instruction::Iinc(_, _) => {},
+
+            Instruction::IfEq(_) | Instruction::IfNe(_) | Instruction::IfLt(_) | Instruction::IfGe(_) |
+            Instruction::IfGt(_) | Instruction::IfLe(_) | Instruction::IfNull(_) | Instruction::IfNonNull(_) |
+            Instruction::TableSwitch(_, _, _) | Instruction::LookupSwitch(_, _) |
+            Instruction::Ireturn | Instruction::Lreturn | Instruction::Freturn | Instruction::Dreturn | Instruction::Areturn |
+            Instruction::Athrow | Instruction::MonitorEnter | Instruction::MonitorExit => {
+                self.pop()?;
+            },
+            Instruction::IfIcmpEq(_) | Instruction::IfIcmpNe(_) | Instruction::IfIcmpLt(_) | Instruction::IfIcmpGe(_) |
+            Instruction::IfIcmpGt(_) | Instruction::IfIcmpLe(_) | Instruction::IfAcmpEq(_) | Instruction::IfAcmpNe(_) => {
+                self.pop_n(2)?;
+            },
+            Instruction::Goto(_) | Instruction::GotoW(_) | Instruction::Return => {},
+            Instruction::Jsr(_) | Instruction::JsrW(_) | Instruction::Ret(_) | Instruction::Wide(WideInstruction::Ret(_)) => {
+                return Err("jsr and ret can't be used in classes with stack map frames".to_owned());
+            },
+
+            Instruction::GetStatic(index) => {
+                let t = member_type(constants, index)?;
+                self.push(t);
+            },
+            Instruction::PutStatic(_) => {
+                self.pop()?;
+            },
+            Instruction::GetField(index) => {
+                let t = member_type(constants, index)?;
+                self.pop_and_push(1, t)?;
+            },
+            Instruction::PutField(_) => {
+                self.pop_n(2)?;
+            },
+            Instruction::InvokeVirtual(index) | Instruction::InvokeInterface(index, _) => {
+                let (_, descriptor) = member_name_and_descriptor(constants, index)?;
+                self.invoke(descriptor, true, false)?;
+            },
+            Instruction::InvokeSpecial(index) => {
+                let (name, descriptor) = member_name_and_descriptor(constants, index)?;
+                self.invoke(descriptor, true, name == "<init>")?;
+            },
+            Instruction::InvokeStatic(index) | Instruction::InvokeDynamic(index) => {
+                let (_, descriptor) = member_name_and_descriptor(constants, index)?;
+                self.invoke(descriptor, false, false)?;
+            },
+
+            Instruction::New(_) => {
+                let pc = self.pc;
+                self.push(Uninitialized(pc));
+            },
+            Instruction::NewArray(atype) => {
+                let array = match atype {
+                    4 => "[Z",
+                    5 => "[C",
+                    6 => "[F",
+                    7 => "[D",
+                    8 => "[B",
+                    9 => "[S",
+                    10 => "[I",
+                    11 => "[J",
+                    _ => return Err(format!("Invalid newarray type {}", atype)),
+                };
+                self.pop_and_push(1, Object(array.to_owned()))?;
+            },
+            Instruction::ANewArray(index) => {
+                let array = array_of(class_name(constants, index)?);
+                self.pop_and_push(1, Object(array))?;
+            },
+            Instruction::MultiANewArray(index, dimensions) => {
+                let array = class_name(constants, index)?.to_owned();
+                self.pop_and_push(dimensions as usize, Object(array))?;
+            },
+            Instruction::CheckCast(index) => {
+                let class = class_name(constants, index)?.to_owned();
+                self.pop_and_push(1, Object(class))?;
+            },
+
+            Instruction::Wide(ref wide) => match *wide {
+                WideInstruction::Iload(index) | WideInstruction::Lload(index) | WideInstruction::Fload(index) |
+                WideInstruction::Dload(index) | WideInstruction::Aload(index) => self.load(index)?,
+                WideInstruction::Istore(index) | WideInstruction::Lstore(index) | WideInstruction::Fstore(index) |
+                WideInstruction::Dstore(index) | WideInstruction::Astore(index) => self.store(index)?,
+                WideInstruction::Iinc(_, _) | WideInstruction::Ret(_) => {},
+            },
+        }
+        Ok(())
+    }
+}
+
+fn lookup(constants: &ConstantPool, index: u16) -> Result<&Constant, String> {
+    constants.get(index).ok_or_else(|| format!("Invalid constant pool index {}", index))
+}
+
+fn utf8(constants: &ConstantPool, index: u16) -> Result<&str, String> {
+    constants.get_utf8(index).ok_or_else(|| format!("Expected a Utf8 constant at index {}", index))
+}
+
+fn class_name(constants: &ConstantPool, index: u16) -> Result<&str, String> {
+    constants.get_class_name(index).ok_or_else(|| format!("Expected a Class constant at index {}", index))
+}
+
+// the name and descriptor of a field, method or dynamically-computed call site or constant
+fn member_name_and_descriptor(constants: &ConstantPool, index: u16) -> Result<(&str, &str), String> {
+    let name_and_type_index = match *lookup(constants, index)? {
+        Constant::Fieldref(_, name_and_type_index) |
+        Constant::Methodref(_, name_and_type_index) |
+        Constant::InterfaceMethodref(_, name_and_type_index) |
+        Constant::Dynamic(_, name_and_type_index) |
+        Constant::InvokeDynamic(_, name_and_type_index) => name_and_type_index,
+        ref other => return Err(format!("Expected a member reference at index {}, found {:?}", index, other)),
+    };
+    match *lookup(constants, name_and_type_index)? {
+        Constant::NameAndType(name_index, descriptor_index) => Ok((utf8(constants, name_index)?, utf8(constants, descriptor_index)?)),
+        ref other => Err(format!("Expected a NameAndType constant at index {}, found {:?}", name_and_type_index, other)),
+    }
+}
+
+fn member_type(constants: &ConstantPool, index: u16) -> Result<InferredType, String> {
+    let (_, descriptor) = member_name_and_descriptor(constants, index)?;
+    descriptor_type(descriptor).ok_or_else(|| format!("Invalid field descriptor: {}", descriptor))
+}
+
+// the type of value pushed by ldc, ldc_w and ldc2_w
+fn constant_type(constants: &ConstantPool, index: u16) -> Result<InferredType, String> {
+    let t = match *lookup(constants, index)? {
+        Constant::Integer(_) => InferredType::Integer,
+        Constant::Float(_) => InferredType::Float,
+        Constant::Long(_) => InferredType::Long,
+        Constant::Double(_) => InferredType::Double,
+        Constant::String(_) => InferredType::Object("java/lang/String".to_owned()),
+        Constant::Class(_) => InferredType::Object("java/lang/Class".to_owned()),
+        Constant::MethodType(_) => InferredType::Object("java/lang/invoke/MethodType".to_owned()),
+        Constant::MethodHandle(_, _) => InferredType::Object("java/lang/invoke/MethodHandle".to_owned()),
+        Constant::Dynamic(_, _) => member_type(constants, index)?,
+        ref other => return Err(format!("Constant at index {} can't be loaded: {:?}", index, other)),
+    };
+    Ok(t)
+}
+
+// the type of a value with the given field descriptor, or None for a malformed descriptor
+fn descriptor_type(descriptor: &str) -> Option<InferredType> {
+    Java::parse(descriptor).ok().map(|t| java_type(&t))
+}
+
+fn java_type(t: &Java) -> InferredType {
+    match *t {
+        Java::Boolean | Java::Byte | Java::Char | Java::Short | Java::Int => InferredType::Integer,
+        Java::Float => InferredType::Float,
+        Java::Long => InferredType::Long,
+        Java::Double => InferredType::Double,
+        Java::Class(ref class) => InferredType::Object(class.to_string()),
+        Java::Array(_) => InferredType::Object(t.to_string()),
+        Java::Void => InferredType::Top, // not the type of any value
+    }
+}
@@ -0,0 +1,14 @@
+// compiled with javac 17 into Merge.class, for the disassembler's round trip test
+public class Merge {
+    static int number(boolean b) {
+        Number n;
+        if (b) n = Integer.valueOf(1); else n = Long.valueOf(2);
+        return n.intValue();
+    }
+
+    static void exception(boolean b) throws Exception {
+        Exception e;
+        if (b) e = new java.io.IOException(); else e = new java.sql.SQLException();
+        throw e;
+    }
+}